@@ -15,6 +15,7 @@
 use anyhow::Context as _;
 use buck2_audit::AuditCommand;
 use buck2_client::args::expand_argfiles_with_context;
+use buck2_client::commands::affected::AffectedCommand;
 use buck2_client::commands::build::BuildCommand;
 use buck2_client::commands::bxl::BxlCommand;
 use buck2_client::commands::clean::CleanCommand;
@@ -261,6 +262,7 @@ pub(crate) enum CommandKind {
     InternalTestRunner(InternalTestRunnerCommand),
     #[clap(subcommand)]
     Audit(AuditCommand),
+    Affected(AffectedCommand),
     Aquery(AqueryCommand),
     Build(BuildCommand),
     Bxl(BxlCommand),
@@ -369,6 +371,7 @@ impl CommandKind {
                 .exec(matches, command_ctx, process.log_reload_handle.dupe())
                 .into(),
             CommandKind::InternalTestRunner(cmd) => cmd.exec(matches, command_ctx).into(),
+            CommandKind::Affected(cmd) => cmd.exec(matches, command_ctx),
             CommandKind::Aquery(cmd) => cmd.exec(matches, command_ctx),
             CommandKind::Build(cmd) => cmd.exec(matches, command_ctx),
             CommandKind::Bxl(cmd) => cmd.exec(matches, command_ctx),
@@ -31,6 +31,14 @@ pub struct AuditOutputCommand {
     #[clap(long)]
     pub json: bool,
 
+    /// If the configuration hash of the output path does not match the current platform
+    /// configuration, look for the action in the configurations known to the daemon (e.g. exec
+    /// platforms or transitions), and then in the current platform configuration by the path
+    /// after the target name (e.g. for content-based output paths), warning that such a match
+    /// may not be the action which produced the output.
+    #[clap(long)]
+    pub search_known_configurations: bool,
+
     #[clap(flatten)]
     pub query_attributes: CommonAttributeArgs,
 }
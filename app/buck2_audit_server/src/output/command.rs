@@ -20,7 +20,9 @@ use buck2_cli_proto::ClientContext;
 use buck2_common::dice::cells::HasCellResolver;
 use buck2_common::global_cfg_options::GlobalCfgOptions;
 use buck2_core::cells::CellResolver;
+use buck2_core::configuration::data::ConfigurationData;
 use buck2_core::fs::project_rel_path::ProjectRelativePath;
+use buck2_events::dispatch::console_warning;
 use buck2_node::target_calculation::ConfiguredTargetCalculation;
 use buck2_server_ctx::ctx::ServerCommandContextTrait;
 use buck2_server_ctx::ctx::ServerCommandDiceContext;
@@ -46,6 +48,7 @@ async fn audit_output<'v>(
     cell_resolver: &'v CellResolver,
     dice_ctx: &'v mut DiceComputations<'_>,
     global_cfg_options: &'v GlobalCfgOptions,
+    search_known_configurations: bool,
 ) -> anyhow::Result<Option<AuditOutputResult>> {
     let buck_out_parser = BuckOutPathParser::new(cell_resolver);
     let parsed = buck_out_parser.parse(output_path)?;
@@ -72,37 +75,86 @@ async fn audit_output<'v>(
         .get_configured_target(&target_label, global_cfg_options)
         .await?;
 
-    let command_config = configured_target_label.cfg();
-    let command_config_hash = command_config.output_hash();
-    if command_config_hash.as_str() != config_hash {
-        return Ok(Some(AuditOutputResult::MaybeRelevant(target_label)));
-    }
+    // When searching, the output may have been produced in another configuration (e.g. an exec
+    // platform or after a transition) which this daemon has seen. Failing that (e.g. content-based
+    // output paths, or outputs built by another daemon) try the current configuration, matching
+    // on the path after the target name only.
+    let known_cfgs = search_known_configurations.then(ConfigurationData::iter_existing);
+    let (configured_target_label, exact_cfg) =
+        match find_output_configuration(configured_target_label.cfg(), &config_hash, known_cfgs) {
+            OutputConfiguration::Current => (configured_target_label, true),
+            OutputConfiguration::Known(cfg) => (target_label.configure(cfg), true),
+            OutputConfiguration::Unknown if search_known_configurations => {
+                (configured_target_label, false)
+            }
+            OutputConfiguration::Unknown => {
+                return Ok(Some(AuditOutputResult::MaybeRelevant(target_label)));
+            }
+        };
 
     let analysis = dice_ctx
         .get_analysis_result(&configured_target_label)
         .await?
         .require_compatible()?;
 
-    Ok(FIND_MATCHING_ACTION.get()?(
+    let action = FIND_MATCHING_ACTION.get()?(
         dice_ctx,
         working_dir,
         global_cfg_options,
         &analysis,
         path_after_target_name,
     )
-    .await?
-    .map(AuditOutputResult::Match))
+    .await?;
+
+    Ok(match action {
+        Some(action) if exact_cfg => Some(AuditOutputResult::Match(action)),
+        Some(action) => Some(AuditOutputResult::ApproximateMatch(action)),
+        None if !exact_cfg => Some(AuditOutputResult::MaybeRelevant(target_label)),
+        None => None,
+    })
+}
+
+/// The configuration an output path was produced in.
+#[derive(Debug, PartialEq)]
+enum OutputConfiguration {
+    /// The configuration the target has for the current command.
+    Current,
+    /// Another configuration known to the daemon.
+    Known(ConfigurationData),
+    Unknown,
+}
+
+/// Find the configuration with the hash of an output path, among the known configurations
+/// if they are given.
+fn find_output_configuration(
+    current: &ConfigurationData,
+    config_hash: &str,
+    known: Option<impl Iterator<Item = ConfigurationData>>,
+) -> OutputConfiguration {
+    if current.output_hash().as_str() == config_hash {
+        return OutputConfiguration::Current;
+    }
+    match known.and_then(|mut known| known.find(|cfg| cfg.output_hash().as_str() == config_hash)) {
+        Some(cfg) => OutputConfiguration::Known(cfg),
+        None => OutputConfiguration::Unknown,
+    }
 }
 
 pub(crate) fn init_audit_output() {
     AUDIT_OUTPUT.init(
-        |output_path, working_dir, cell_resolver, dice_ctx, global_cfg_options| {
+        |output_path,
+         working_dir,
+         cell_resolver,
+         dice_ctx,
+         global_cfg_options,
+         search_known_configurations| {
             Box::pin(audit_output(
                 output_path,
                 working_dir,
                 cell_resolver,
                 dice_ctx,
                 global_cfg_options,
+                search_known_configurations,
             ))
         },
     );
@@ -135,14 +187,18 @@ impl AuditSubcommand for AuditOutputCommand {
                 )
                 .await?;
 
-                let result = audit_output(&self.output_path, working_dir, &cell_resolver, &mut dice_ctx, &global_cfg_options).await?;
+                let result = audit_output(&self.output_path, working_dir, &cell_resolver, &mut dice_ctx, &global_cfg_options, self.search_known_configurations).await?;
 
                 let mut stdout = stdout.as_writer();
 
+                if let Some(AuditOutputResult::ApproximateMatch(_)) = &result {
+                    console_warning(AuditOutputResult::approximate_match_warning(&self.output_path));
+                }
+
                 match result {
                     Some(result) => {
                         match result {
-                            AuditOutputResult::Match(action) => {
+                            AuditOutputResult::Match(action) | AuditOutputResult::ApproximateMatch(action) => {
                                 (PRINT_ACTION_NODE.get()?)(&mut stdout, action, self.json, &self.query_attributes.get()?, &cell_resolver).await?
                             },
                             AuditOutputResult::MaybeRelevant(label) => {
@@ -168,3 +224,42 @@ impl AuditSubcommand for AuditOutputCommand {
             .await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use buck2_core::configuration::data::ConfigurationData;
+
+    use crate::output::command::find_output_configuration;
+    use crate::output::command::OutputConfiguration;
+
+    #[test]
+    fn test_find_output_configuration() {
+        let current = ConfigurationData::testing_new();
+        let other = ConfigurationData::unspecified();
+        let known = || Some(vec![ConfigurationData::unbound(), other.clone()].into_iter());
+        let no_known = None::<std::vec::IntoIter<ConfigurationData>>;
+
+        assert_eq!(
+            OutputConfiguration::Current,
+            find_output_configuration(&current, current.output_hash().as_str(), known())
+        );
+        assert_eq!(
+            OutputConfiguration::Current,
+            find_output_configuration(&current, current.output_hash().as_str(), no_known.clone())
+        );
+
+        // Without searching, only the current configuration matches.
+        assert_eq!(
+            OutputConfiguration::Unknown,
+            find_output_configuration(&current, other.output_hash().as_str(), no_known)
+        );
+        assert_eq!(
+            OutputConfiguration::Known(other.clone()),
+            find_output_configuration(&current, other.output_hash().as_str(), known())
+        );
+        assert_eq!(
+            OutputConfiguration::Unknown,
+            find_output_configuration(&current, "0123456789abcdef", known())
+        );
+    }
+}
@@ -23,11 +23,24 @@ use crate::actions::query::ActionQueryNode;
 pub enum AuditOutputResult {
     /// The exact action that matched the buck-out path.
     Match(ActionQueryNode),
+    /// The configuration of the buck-out path is unknown, but the action of the target in the
+    /// current configuration produces the same path after the configuration hash. It may not be
+    /// the action which produced the buck-out path.
+    ApproximateMatch(ActionQueryNode),
     /// If the platform configuration of the buck-out path doesn't match the platform used when calling
     /// audit output, then we return the unconfigured target label.
     MaybeRelevant(TargetLabel),
 }
 
+impl AuditOutputResult {
+    /// Warning to show along with an [`AuditOutputResult::ApproximateMatch`].
+    pub fn approximate_match_warning(output_path: &str) -> String {
+        format!(
+            "The configuration of `{output_path}` is not known to the daemon. Returning the action which produces the same path in the configuration used by this command, which may not be the one that produced it"
+        )
+    }
+}
+
 pub static AUDIT_OUTPUT: LateBinding<
     for<'v> fn(
         &'v str,
@@ -35,8 +48,10 @@ pub static AUDIT_OUTPUT: LateBinding<
         &'v CellResolver,
         &'v mut DiceComputations,
         &'v GlobalCfgOptions,
-    )
-        -> Pin<Box<dyn Future<Output = anyhow::Result<Option<AuditOutputResult>>> + 'v>>,
+        bool,
+    ) -> Pin<
+        Box<dyn Future<Output = anyhow::Result<Option<AuditOutputResult>>> + Send + 'v>,
+    >,
 > = LateBinding::new("AUDIT_OUTPUT");
 
 pub async fn audit_output<'v>(
@@ -45,6 +60,7 @@ pub async fn audit_output<'v>(
     cell_resolver: &'v CellResolver,
     dice_ctx: &'v mut DiceComputations<'_>,
     global_cfg_options: &'v GlobalCfgOptions,
+    search_known_configurations: bool,
 ) -> anyhow::Result<Option<AuditOutputResult>> {
     (AUDIT_OUTPUT.get()?)(
         output_path,
@@ -52,6 +68,7 @@ pub async fn audit_output<'v>(
         cell_resolver,
         dice_ctx,
         global_cfg_options,
+        search_known_configurations,
     )
     .await
 }
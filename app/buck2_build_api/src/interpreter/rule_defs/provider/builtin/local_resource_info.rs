@@ -21,6 +21,7 @@ use starlark::values::none::NoneOr;
 use starlark::values::type_repr::DictType;
 use starlark::values::Coerce;
 use starlark::values::Freeze;
+use starlark::values::FrozenValue;
 use starlark::values::Trace;
 use starlark::values::Value;
 
@@ -52,7 +53,8 @@ pub struct LocalResourceInfoGen<V> {
     /// will be reserved from the pool, for example `{"socket_address": "bar:2"}` and environment variable with
     /// name resolved using mapping in `resource_env_vars` field and `"socket_address"` key will be added to
     /// execution command.
-    #[provider(field_type = StarlarkCmdArgs<'v>)]
+    /// Either this or `ports` must be set.
+    #[provider(field_type = NoneOr<StarlarkCmdArgs<'v>>)]
     setup: V,
     /// Mapping from environment variable (appended to an execution command which is dependent on this local resource)
     /// to keys in setup command JSON output.
@@ -61,23 +63,57 @@ pub struct LocalResourceInfoGen<V> {
     /// Timeout in seconds for `setup` command.
     #[provider(field_type = NoneOr<f64>)]
     setup_timeout_seconds: V,
+    /// Instead of running `setup`, Buck2 creates a pool of this many resources itself, where every
+    /// key in `resource_env_vars` maps to a distinct free local TCP port.
+    #[provider(field_type = NoneOr<i32>)]
+    ports: V,
+    /// Command run with a resource's environment variables before it is handed to a test.
+    /// If it fails, the resource is taken out of the pool.
+    #[provider(field_type = NoneOr<StarlarkCmdArgs<'v>>)]
+    health_check: V,
+    /// Command run once for every resource of the pool, with its environment variables, when
+    /// resources are released. Runs before the `pid` from `setup` is sent SIGTERM.
+    #[provider(field_type = NoneOr<StarlarkCmdArgs<'v>>)]
+    teardown: V,
+    /// How many tests can use a single resource before it is taken out of the pool.
+    #[provider(field_type = NoneOr<i32>)]
+    max_uses: V,
 }
 
 fn validate_local_resource_info<'v, V>(info: &LocalResourceInfoGen<V>) -> anyhow::Result<()>
 where
     V: ValueLike<'v>,
 {
-    let setup = StarlarkCmdArgs::try_from_value(info.setup.to_value()).with_context(|| {
-        format!(
-            "Value for `setup` field is not a command line: `{}`",
-            info.setup
-        )
-    })?;
-    if setup.is_empty() {
-        return Err(anyhow::anyhow!(
-            "Value for `setup` field is an empty command line: `{}`",
-            info.setup
-        ));
+    let ports = NoneOr::<i32>::unpack_value(info.ports.to_value())
+        .context("`ports` must be an int if provided")?
+        .into_option();
+    match (info.setup.to_value().is_none(), ports) {
+        (true, None) => {
+            return Err(anyhow::anyhow!(
+                "One of `setup` or `ports` fields must be provided"
+            ));
+        }
+        (false, Some(_)) => {
+            return Err(anyhow::anyhow!(
+                "Only one of `setup` or `ports` fields can be provided"
+            ));
+        }
+        (true, Some(ports)) if ports <= 0 => {
+            return Err(anyhow::anyhow!(
+                "Value for `ports` field must be positive: `{}`",
+                ports
+            ));
+        }
+        (true, Some(_)) => {}
+        (false, None) => validate_command_line("setup", info.setup.to_value())?,
+    }
+    for (name, value) in [
+        ("health_check", info.health_check.to_value()),
+        ("teardown", info.teardown.to_value()),
+    ] {
+        if !value.is_none() {
+            validate_command_line(name, value)?;
+        }
     }
 
     let env_vars = DictRef::from_value(info.resource_env_vars.to_value()).with_context(|| {
@@ -119,6 +155,34 @@ where
     NoneOr::<f64>::unpack_value(info.setup_timeout_seconds.to_value())
         .context("`setup_timeout_seconds` must be a number if provided")?;
 
+    let max_uses = NoneOr::<i32>::unpack_value(info.max_uses.to_value())
+        .context("`max_uses` must be an int if provided")?;
+    if let NoneOr::Other(max_uses) = max_uses {
+        if max_uses <= 0 {
+            return Err(anyhow::anyhow!(
+                "Value for `max_uses` field must be positive: `{}`",
+                max_uses
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_command_line(name: &str, value: Value) -> anyhow::Result<()> {
+    let cmd = StarlarkCmdArgs::try_from_value(value).with_context(|| {
+        format!(
+            "Value for `{}` field is not a command line: `{}`",
+            name, value
+        )
+    })?;
+    if cmd.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Value for `{}` field is an empty command line: `{}`",
+            name,
+            value
+        ));
+    }
     Ok(())
 }
 
@@ -126,17 +190,26 @@ where
 fn local_resource_info_creator(globals: &mut GlobalsBuilder) {
     #[starlark(as_type = FrozenLocalResourceInfo)]
     fn LocalResourceInfo<'v>(
-        #[starlark(require = named)] setup: Value<'v>,
+        #[starlark(require = named, default = NoneOr::None)] setup: NoneOr<Value<'v>>,
         #[starlark(require = named)] resource_env_vars: Value<'v>,
         #[starlark(require = named, default = NoneOr::None)] setup_timeout_seconds: NoneOr<
             Value<'v>,
         >,
+        #[starlark(require = named, default = NoneOr::None)] ports: NoneOr<Value<'v>>,
+        #[starlark(require = named, default = NoneOr::None)] health_check: NoneOr<Value<'v>>,
+        #[starlark(require = named, default = NoneOr::None)] teardown: NoneOr<Value<'v>>,
+        #[starlark(require = named, default = NoneOr::None)] max_uses: NoneOr<Value<'v>>,
         eval: &mut Evaluator<'v, '_>,
     ) -> anyhow::Result<LocalResourceInfo<'v>> {
+        let heap = eval.heap();
         let result = LocalResourceInfo {
-            setup,
+            setup: heap.alloc(setup),
             resource_env_vars,
-            setup_timeout_seconds: eval.heap().alloc(setup_timeout_seconds),
+            setup_timeout_seconds: heap.alloc(setup_timeout_seconds),
+            ports: heap.alloc(ports),
+            health_check: heap.alloc(health_check),
+            teardown: heap.alloc(teardown),
+            max_uses: heap.alloc(max_uses),
         };
         validate_local_resource_info(&result)?;
         Ok(result)
@@ -159,10 +232,44 @@ impl FrozenLocalResourceInfo {
             .collect()
     }
 
-    pub fn setup_command_line(&self) -> &dyn CommandLineArgLike {
-        ValueAsCommandLineLike::unpack_value_err(self.setup.to_value())
+    /// `None` for port pools, which Buck2 sets up itself.
+    pub fn setup_command_line(&self) -> Option<&dyn CommandLineArgLike> {
+        Self::optional_command_line(&self.setup)
+    }
+
+    pub fn health_check_command_line(&self) -> Option<&dyn CommandLineArgLike> {
+        Self::optional_command_line(&self.health_check)
+    }
+
+    pub fn teardown_command_line(&self) -> Option<&dyn CommandLineArgLike> {
+        Self::optional_command_line(&self.teardown)
+    }
+
+    fn optional_command_line(value: &FrozenValue) -> Option<&dyn CommandLineArgLike> {
+        if value.is_none() {
+            None
+        } else {
+            Some(
+                ValueAsCommandLineLike::unpack_value_err(value.to_value())
+                    .unwrap()
+                    .0,
+            )
+        }
+    }
+
+    /// Number of resources in a pool of local ports allocated by Buck2.
+    pub fn port_pool_size(&self) -> Option<usize> {
+        NoneOr::<i32>::unpack_value(self.ports.to_value())
             .unwrap()
-            .0
+            .into_option()
+            .map(|ports| ports as usize)
+    }
+
+    pub fn max_uses(&self) -> Option<u64> {
+        NoneOr::<i32>::unpack_value(self.max_uses.to_value())
+            .unwrap()
+            .into_option()
+            .map(|max_uses| max_uses as u64)
     }
 
     pub fn setup_timeout(&self) -> Option<Duration> {
@@ -13,6 +13,7 @@ use buck2_core::fs::project_rel_path::ProjectRelativePath;
 use buck2_node::configured_universe::CqueryUniverse;
 use buck2_node::nodes::configured::ConfiguredTargetNode;
 use buck2_node::nodes::unconfigured::TargetNode;
+use buck2_query::query::syntax::simple::eval::profile::QueryProfile;
 use buck2_query::query::syntax::simple::eval::values::QueryEvaluationResult;
 use buck2_util::late_binding::LateBinding;
 use dice::DiceComputations;
@@ -47,6 +48,7 @@ pub trait QueryFrontend: Send + Sync + 'static {
         query_args: &[String],
         global_cfg_options: GlobalCfgOptions,
         target_universe: Option<&[String]>,
+        profile: Option<&QueryProfile>,
     ) -> anyhow::Result<QueryEvaluationResult<ConfiguredTargetNode>>;
 
     async fn eval_aquery(
@@ -31,6 +31,8 @@ fn test_construction() -> anyhow::Result<()> {
             LocalResourceInfo(setup=cmd_args(["/foo", "--resource"]), resource_env_vars={"RESOURCE_ENV_VAR": "json_key"})
             LocalResourceInfo(setup=cmd_args(["/foo", "--resource"]), resource_env_vars={"RESOURCE_ENV_VAR": "json_key"}, setup_timeout_seconds=10)
             LocalResourceInfo(setup=cmd_args(["/foo", "--resource"]), resource_env_vars={"RESOURCE_ENV_VAR": "json_key"}, setup_timeout_seconds=10.5)
+            LocalResourceInfo(setup=["/foo"], resource_env_vars={"RESOURCE_ENV_VAR": "json_key"}, health_check=["/foo", "--check"], teardown=cmd_args(["/foo", "--teardown"]), max_uses=3)
+            LocalResourceInfo(ports=4, resource_env_vars={"DB_PORT": "db", "ADMIN_PORT": "admin"})
         "#
     );
     tester.run_starlark_bzl_test(test)?;
@@ -170,6 +172,58 @@ fn test_validation() -> anyhow::Result<()> {
             "`setup_timeout_seconds` must be a number if provided",
         );
     }
+    {
+        let test = indoc!(
+            r#"
+            def test():
+                LocalResourceInfo(setup=["/foo"], ports=2, resource_env_vars={"PORT": "port"})
+            "#
+        );
+        expect_error(
+            tester.run_starlark_bzl_test(test),
+            test,
+            "Only one of `setup` or `ports` fields can be provided",
+        );
+    }
+    {
+        let test = indoc!(
+            r#"
+            def test():
+                LocalResourceInfo(ports=0, resource_env_vars={"PORT": "port"})
+            "#
+        );
+        expect_error(
+            tester.run_starlark_bzl_test(test),
+            test,
+            "Value for `ports` field must be positive",
+        );
+    }
+    {
+        let test = indoc!(
+            r#"
+            def test():
+                LocalResourceInfo(setup=["/foo"], resource_env_vars={"PORT": "port"}, health_check=[])
+            "#
+        );
+        expect_error(
+            tester.run_starlark_bzl_test(test),
+            test,
+            "Value for `health_check` field is an empty command line",
+        );
+    }
+    {
+        let test = indoc!(
+            r#"
+            def test():
+                LocalResourceInfo(setup=["/foo"], resource_env_vars={"PORT": "port"}, max_uses=0)
+            "#
+        );
+        expect_error(
+            tester.run_starlark_bzl_test(test),
+            test,
+            "Value for `max_uses` field must be positive",
+        );
+    }
     Ok(())
 }
 
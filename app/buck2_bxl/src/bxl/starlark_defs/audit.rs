@@ -138,16 +138,21 @@ fn audit_methods(builder: &mut MethodsBuilder) {
                         target_platform,
                         cli_modifiers: vec![].into(),
                     },
+                    false,
                 )
                 .await?
                 .map(|result| {
                     anyhow::Ok(match result {
-                        AuditOutputResult::Match(action) => heap.alloc(StarlarkAction(
-                            action
-                                .action()
-                                .context("audit_output did not return an action")?
-                                .dupe(),
-                        )),
+                        // Approximate matches only occur when searching known configurations.
+                        AuditOutputResult::Match(action)
+                        | AuditOutputResult::ApproximateMatch(action) => {
+                            heap.alloc(StarlarkAction(
+                                action
+                                    .action()
+                                    .context("audit_output did not return an action")?
+                                    .dupe(),
+                            ))
+                        }
                         AuditOutputResult::MaybeRelevant(label) => {
                             heap.alloc(StarlarkTargetLabel::new(label))
                         }
@@ -12,21 +12,25 @@ use std::time::Instant;
 use buck2_build_api::interpreter::rule_defs::artifact::starlark_artifact_like::ValueAsArtifactLikeUnpack;
 use buck2_build_api::interpreter::rule_defs::cmd_args::value_as::ValueAsCommandLineLike;
 use buck2_node::nodes::configured::ConfiguredTargetNode;
+use buck2_node::nodes::configured_diff;
 use buck2_node::nodes::unconfigured::TargetNode;
 use buck2_query::query::syntax::simple::eval::file_set::FileSet;
 use buck2_query::query::syntax::simple::eval::set::TargetSet;
 use dupe::Dupe;
+use dupe::IterDupedExt;
 use futures::FutureExt;
 use indexmap::IndexSet;
 use starlark::environment::GlobalsBuilder;
 use starlark::starlark_module;
 use starlark::values::none::NoneType;
+use starlark::values::structs::AllocStruct;
 use starlark::values::tuple::UnpackTuple;
 use starlark::values::Heap;
 use starlark::values::StringValue;
 use starlark::values::Value;
 use starlark::values::ValueLike;
 
+use super::alloc_node::AllocNode;
 use super::artifacts::visit_artifact_path_without_associated_deduped;
 use super::context::output::get_artifact_path_display;
 use super::context::output::get_cmd_line_inputs;
@@ -63,6 +67,64 @@ pub(crate) fn register_target_function(builder: &mut GlobalsBuilder) {
     fn utarget_set() -> anyhow::Result<StarlarkTargetSet<TargetNode>> {
         Ok(StarlarkTargetSet::from(TargetSet::new()))
     }
+
+    /// Computes the difference between two configured target sets, e.g. the results of the
+    /// same query evaluated in two different target universes or configurations.
+    ///
+    /// Returns a struct with fields:
+    /// - `added`: targets only in `new`
+    /// - `removed`: targets only in `old`
+    /// - `changed`: list of structs with fields `old`, `new` (the target nodes),
+    ///   `changed_attrs` (names of attributes whose values differ) and
+    ///   `configuration_changed`
+    ///
+    /// Targets are matched by label and configuration first, then targets left over on both
+    /// sides are matched by label alone and reported as a configuration change.
+    ///
+    /// Sample usage:
+    /// ```text
+    /// def _impl_diff_targets(ctx):
+    ///     old = ctx.cquery().deps("//foo:bar", target_universe = ["//foo:bar"])
+    ///     new = ctx.cquery().deps("//foo:bar", target_universe = ["//foo:baz"])
+    ///     diff = diff_targets(old, new)
+    ///     for c in diff.changed:
+    ///         ctx.output.print(c.new.label, c.changed_attrs)
+    /// ```
+    fn diff_targets<'v>(
+        #[starlark(require = pos)] old: &StarlarkTargetSet<ConfiguredTargetNode>,
+        #[starlark(require = pos)] new: &StarlarkTargetSet<ConfiguredTargetNode>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Value<'v>> {
+        let diff = configured_diff::diff_targets(old.0.iter().duped(), new.0.iter().duped());
+        let changed: Vec<Value<'v>> = diff
+            .changed
+            .into_iter()
+            .map(|c| {
+                let configuration_changed = c.configuration_changed();
+                heap.alloc(AllocStruct([
+                    ("old", c.old.alloc(heap)),
+                    ("new", c.new.alloc(heap)),
+                    ("changed_attrs", heap.alloc(c.changed_attrs)),
+                    ("configuration_changed", heap.alloc(configuration_changed)),
+                ]))
+            })
+            .collect();
+        Ok(heap.alloc(AllocStruct([
+            (
+                "added",
+                heap.alloc(StarlarkTargetSet::from(
+                    diff.added.into_iter().collect::<TargetSet<_>>(),
+                )),
+            ),
+            (
+                "removed",
+                heap.alloc(StarlarkTargetSet::from(
+                    diff.removed.into_iter().collect::<TargetSet<_>>(),
+                )),
+            ),
+            ("changed", heap.alloc(changed)),
+        ])))
+    }
 }
 
 /// Global methods on the file set.
@@ -237,3 +299,83 @@ pub(crate) fn register_error_handling_function(builder: &mut GlobalsBuilder) {
         Err(BxlErrorWithoutStacktrace(s).into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use buck2_core::configuration::data::ConfigurationData;
+    use buck2_core::target::configured_target_label::ConfiguredTargetLabel;
+    use buck2_node::nodes::configured::ConfiguredTargetNode;
+    use buck2_query::query::syntax::simple::eval::set::TargetSet;
+    use dupe::Dupe;
+    use starlark::environment::GlobalsBuilder;
+    use starlark::environment::Module;
+    use starlark::eval::Evaluator;
+    use starlark::syntax::AstModule;
+    use starlark::syntax::Dialect;
+
+    use crate::bxl::starlark_defs::functions::register_target_function;
+    use crate::bxl::starlark_defs::targetset::StarlarkTargetSet;
+
+    fn target_set(
+        targets: &[(&str, &ConfigurationData)],
+    ) -> StarlarkTargetSet<ConfiguredTargetNode> {
+        StarlarkTargetSet::from(
+            targets
+                .iter()
+                .map(|(label, cfg)| {
+                    ConfiguredTargetNode::testing_new(
+                        ConfiguredTargetLabel::testing_parse(label, (*cfg).dupe()),
+                        "foo_lib",
+                    )
+                })
+                .collect::<TargetSet<_>>(),
+        )
+    }
+
+    #[test]
+    fn test_diff_targets() {
+        let cfg = ConfigurationData::testing_new();
+        let other_cfg = ConfigurationData::unspecified();
+        let module = Module::new();
+        module.set(
+            "old",
+            module.heap().alloc(target_set(&[
+                ("root//pkg:kept", &cfg),
+                ("root//pkg:removed", &cfg),
+                ("root//pkg:moved", &other_cfg),
+            ])),
+        );
+        module.set(
+            "new",
+            module.heap().alloc(target_set(&[
+                ("root//pkg:kept", &cfg),
+                ("root//pkg:moved", &cfg),
+                ("root//pkg:added", &cfg),
+            ])),
+        );
+        let content = r#"
+def labels(targets):
+    return [str(t.label.raw_target()) for t in targets]
+
+diff = diff_targets(old, new)
+(
+    labels(diff.added),
+    labels(diff.removed),
+    [
+        (str(c.old.label.raw_target()), str(c.new.label.raw_target()), c.changed_attrs, c.configuration_changed)
+        for c in diff.changed
+    ],
+    len(diff_targets(new, new).changed),
+)
+"#;
+        let ast = AstModule::parse("diff.bxl", content.to_owned(), &Dialect::Extended).unwrap();
+        let globals = GlobalsBuilder::standard()
+            .with(register_target_function)
+            .build();
+        let res = Evaluator::new(&module).eval_module(ast, &globals).unwrap();
+        assert_eq!(
+            r#"(["root//pkg:added"], ["root//pkg:removed"], [("root//pkg:moved", "root//pkg:moved", [], True)], 0)"#,
+            res.to_repr()
+        );
+    }
+}
@@ -0,0 +1,214 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use async_trait::async_trait;
+use buck2_cli_proto::CqueryRequest;
+use buck2_cli_proto::CqueryResponse;
+use buck2_cli_proto::QueryOutputFormat;
+use buck2_client_ctx::client_ctx::ClientCommandContext;
+use buck2_client_ctx::common::CommonBuildConfigurationOptions;
+use buck2_client_ctx::common::CommonCommandOptions;
+use buck2_client_ctx::common::CommonConsoleOptions;
+use buck2_client_ctx::common::CommonDaemonCommandOptions;
+use buck2_client_ctx::daemon::client::BuckdClientConnector;
+use buck2_client_ctx::daemon::client::StdoutPartialResultHandler;
+use buck2_client_ctx::exit_result::ExitResult;
+use buck2_client_ctx::path_arg::PathArg;
+use buck2_client_ctx::streaming::StreamingCommand;
+use buck2_core::fs::fs_util;
+
+#[derive(Debug, buck2_error::Error)]
+enum AffectedCommandError {
+    #[error("No changed files were given, pass `--changed-files` or `--changed-files-file`")]
+    NoChangedFiles,
+    #[error("Queries cannot quote `{0}`, which contains both single and double quotes")]
+    UnsupportedQuotes(String),
+}
+
+/// Print the targets and tests affected by changes to the given files.
+///
+/// Changed source files are mapped to their owning targets, changed `BUCK`, `.bzl` and `PACKAGE`
+/// files to the targets they (transitively) define, and then all reverse dependencies of those
+/// targets within the configured target universe are printed. A change to a buckconfig file of a
+/// cell (`.buckconfig`, `.buckconfig.local` or a file in `.buckconfig.d`) affects every target in
+/// the universe. Other changed files which no target owns are an error.
+///
+/// Examples:
+///
+/// `buck2 affected --changed-files=foo/bar.cpp,foo/defs.bzl -u //foo/...`
+///
+/// `git diff --name-only HEAD~ > changed.txt && buck2 affected --changed-files-file=changed.txt`
+#[derive(Debug, clap::Parser)]
+#[clap(name = "affected", verbatim_doc_comment)]
+pub struct AffectedCommand {
+    #[clap(flatten)]
+    common_opts: CommonCommandOptions,
+
+    /// Comma separated list of changed files, relative to the current directory.
+    #[clap(long, use_delimiter = true, value_name = "FILES")]
+    changed_files: Vec<String>,
+
+    /// File containing a newline separated list of changed files, relative to the current directory.
+    #[clap(long, value_name = "PATH")]
+    changed_files_file: Option<PathArg>,
+
+    /// Comma separated list of target patterns at which to root the universe of targets which
+    /// can be affected.
+    #[clap(
+        long,
+        short = 'u',
+        use_delimiter = true,
+        default_value = "//...",
+        value_name = "PATTERNS"
+    )]
+    target_universe: Vec<String>,
+
+    /// Only print affected tests: tests of the affected targets (via their `tests` attribute)
+    /// and affected targets whose rule type ends with `_test`.
+    #[clap(long)]
+    tests_only: bool,
+
+    /// Output in JSON format.
+    #[clap(long)]
+    json: bool,
+}
+
+/// Quote a word for the query, with the quote type it does not contain.
+fn quote(word: &str) -> anyhow::Result<String> {
+    match (word.contains('"'), word.contains('\'')) {
+        (false, _) => Ok(format!("\"{}\"", word)),
+        (true, false) => Ok(format!("'{}'", word)),
+        (true, true) => Err(AffectedCommandError::UnsupportedQuotes(word.to_owned()).into()),
+    }
+}
+
+fn quote_all<'a>(words: impl IntoIterator<Item = &'a str>) -> anyhow::Result<String> {
+    Ok(words
+        .into_iter()
+        .map(quote)
+        .collect::<anyhow::Result<Vec<_>>>()?
+        .join(" "))
+}
+
+impl AffectedCommand {
+    fn changed_files(&self, ctx: &ClientCommandContext<'_>) -> anyhow::Result<Vec<String>> {
+        let mut files = self.changed_files.clone();
+        if let Some(path) = &self.changed_files_file {
+            let contents = fs_util::read_to_string(path.resolve(&ctx.working_dir))?;
+            files.extend(
+                contents
+                    .lines()
+                    .map(str::trim)
+                    .filter(|l| !l.is_empty())
+                    .map(str::to_owned),
+            );
+        }
+        if files.is_empty() {
+            return Err(AffectedCommandError::NoChangedFiles.into());
+        }
+        Ok(files)
+    }
+
+    fn query(&self, changed_files: &[String]) -> anyhow::Result<String> {
+        // The tests are added by `affected()` itself, so the affected targets are computed once.
+        Ok(format!(
+            "affected(set({}), fileset({}), '{}')",
+            quote_all(self.target_universe.iter().map(|s| s.as_str()))?,
+            quote_all(changed_files.iter().map(|s| s.as_str()))?,
+            if self.tests_only {
+                "tests_only"
+            } else {
+                "with_tests"
+            },
+        ))
+    }
+}
+
+#[async_trait]
+impl StreamingCommand for AffectedCommand {
+    const COMMAND_NAME: &'static str = "affected";
+
+    async fn exec_impl(
+        self,
+        buckd: &mut BuckdClientConnector,
+        matches: &clap::ArgMatches,
+        ctx: &mut ClientCommandContext<'_>,
+    ) -> ExitResult {
+        let changed_files = self.changed_files(ctx)?;
+        let query = self.query(&changed_files)?;
+        let context = ctx.client_context(matches, &self)?;
+        let unstable_output_format = if self.json {
+            QueryOutputFormat::Json
+        } else {
+            QueryOutputFormat::Default
+        } as i32;
+
+        let CqueryResponse {} = buckd
+            .with_flushing()
+            .cquery(
+                CqueryRequest {
+                    query,
+                    query_args: Vec::new(),
+                    context: Some(context),
+                    output_attributes: Vec::new(),
+                    target_universe: self.target_universe,
+                    show_providers: false,
+                    unstable_output_format,
+                    correct_owner: true,
+                    diff_snapshot: false,
+                    diff_against: None,
+                    profile: false,
+                },
+                ctx.stdin()
+                    .console_interaction_stream(&self.common_opts.console_opts),
+                &mut StdoutPartialResultHandler,
+            )
+            .await??;
+
+        ExitResult::success()
+    }
+
+    fn console_opts(&self) -> &CommonConsoleOptions {
+        &self.common_opts.console_opts
+    }
+
+    fn event_log_opts(&self) -> &CommonDaemonCommandOptions {
+        &self.common_opts.event_log_opts
+    }
+
+    fn common_opts(&self) -> &CommonBuildConfigurationOptions {
+        &self.common_opts.config_opts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use crate::commands::affected::AffectedCommand;
+
+    #[test]
+    fn test_query() {
+        let cmd = AffectedCommand::parse_from(["affected", "-u", "//foo/...", "--tests-only"]);
+        assert_eq!(
+            "affected(set(\"//foo/...\"), fileset(\"a.c\"), 'tests_only')",
+            cmd.query(&["a.c".to_owned()]).unwrap()
+        );
+        assert_eq!(
+            "affected(set(\"//foo/...\"), fileset('a\".c'), 'tests_only')",
+            cmd.query(&["a\".c".to_owned()]).unwrap()
+        );
+        let cmd = AffectedCommand::parse_from(["affected", "-u", "//foo/..."]);
+        assert_eq!(
+            "affected(set(\"//foo/...\"), fileset(\"a.c\"), 'with_tests')",
+            cmd.query(&["a.c".to_owned()]).unwrap()
+        );
+        assert!(cmd.query(&["a\"'.c".to_owned()]).is_err());
+    }
+}
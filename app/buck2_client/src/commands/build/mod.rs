@@ -9,6 +9,7 @@
 
 use std::io::Write;
 use std::path::PathBuf;
+use std::time::Instant;
 
 use anyhow::Context;
 use async_trait::async_trait;
@@ -38,6 +39,7 @@ use gazebo::prelude::*;
 
 use crate::commands::build::out::copy_to_out;
 use crate::print::PrintOutputs;
+use crate::watch;
 
 mod out;
 
@@ -136,6 +138,12 @@ pub struct BuildCommand {
         help = "Experimental: Path to a file where the Buck2 daemon should write a list of produced artifacts in json format"
     )]
     output_hashes_file: Option<PathArg>,
+
+    #[clap(
+        long,
+        help = "Build again whenever source files change, until interrupted. Requires `buck2.file_watcher = notify` or `watchman`"
+    )]
+    watch: bool,
 }
 
 impl BuildCommand {
@@ -159,54 +167,15 @@ impl BuildCommand {
         }
         build_providers::Action::Skip
     }
-}
 
-#[derive(Debug, Clone, Dupe, clap::ArgEnum)]
-#[clap(rename_all = "snake_case")]
-pub enum FinalArtifactMaterializations {
-    All,
-    None,
-}
-
-pub trait MaterializationsToProto {
-    fn to_proto(&self) -> buck2_cli_proto::build_request::Materializations;
-}
-impl MaterializationsToProto for Option<FinalArtifactMaterializations> {
-    fn to_proto(&self) -> buck2_cli_proto::build_request::Materializations {
-        match self {
-            Some(FinalArtifactMaterializations::All) => {
-                buck2_cli_proto::build_request::Materializations::Materialize
-            }
-            Some(FinalArtifactMaterializations::None) => {
-                buck2_cli_proto::build_request::Materializations::Skip
-            }
-            None => buck2_cli_proto::build_request::Materializations::Default,
-        }
-    }
-}
-
-pub fn print_build_result(
-    console: &FinalConsole,
-    errors: &[buck2_data::ErrorReport],
-) -> anyhow::Result<()> {
-    for error in errors {
-        console.print_error(&error.message)?;
-    }
-    Ok(())
-}
-
-#[async_trait]
-impl StreamingCommand for BuildCommand {
-    const COMMAND_NAME: &'static str = "build";
-
-    async fn exec_impl(
-        self,
-        buckd: &mut BuckdClientConnector,
+    async fn run_build(
+        &self,
+        buckd: &mut BuckdClientConnector<'_>,
         matches: &clap::ArgMatches,
         ctx: &mut ClientCommandContext<'_>,
     ) -> ExitResult {
         let show_default_other_outputs = false;
-        let context = ctx.client_context(matches, &self)?;
+        let context = ctx.client_context(matches, self)?;
 
         let result = buckd
             .with_flushing()
@@ -228,9 +197,10 @@ impl StreamingCommand for BuildCommand {
                     }),
                     build_opts: Some(self.build_opts.to_proto()),
                     final_artifact_materializations: self.materializations.to_proto() as i32,
-                    target_universe: self.target_universe,
+                    target_universe: self.target_universe.clone(),
                     output_hashes_file: self
                         .output_hashes_file
+                        .as_ref()
                         .map(|p| {
                             p.resolve(&ctx.working_dir).into_string().with_context(|| {
                                 format!(
@@ -306,6 +276,69 @@ impl StreamingCommand for BuildCommand {
 
         res.with_stdout(stdout)
     }
+}
+
+#[derive(Debug, Clone, Dupe, clap::ArgEnum)]
+#[clap(rename_all = "snake_case")]
+pub enum FinalArtifactMaterializations {
+    All,
+    None,
+}
+
+pub trait MaterializationsToProto {
+    fn to_proto(&self) -> buck2_cli_proto::build_request::Materializations;
+}
+impl MaterializationsToProto for Option<FinalArtifactMaterializations> {
+    fn to_proto(&self) -> buck2_cli_proto::build_request::Materializations {
+        match self {
+            Some(FinalArtifactMaterializations::All) => {
+                buck2_cli_proto::build_request::Materializations::Materialize
+            }
+            Some(FinalArtifactMaterializations::None) => {
+                buck2_cli_proto::build_request::Materializations::Skip
+            }
+            None => buck2_cli_proto::build_request::Materializations::Default,
+        }
+    }
+}
+
+pub fn print_build_result(
+    console: &FinalConsole,
+    errors: &[buck2_data::ErrorReport],
+) -> anyhow::Result<()> {
+    for error in errors {
+        console.print_error(&error.message)?;
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl StreamingCommand for BuildCommand {
+    const COMMAND_NAME: &'static str = "build";
+
+    async fn exec_impl(
+        self,
+        buckd: &mut BuckdClientConnector,
+        matches: &clap::ArgMatches,
+        ctx: &mut ClientCommandContext<'_>,
+    ) -> ExitResult {
+        if !self.watch {
+            return self.run_build(buckd, matches, ctx).await;
+        }
+
+        let console = self.common_opts.console_opts.final_console();
+        let mut iteration = 1;
+        loop {
+            let start = Instant::now();
+            let result = self.run_build(buckd, matches, ctx).await;
+            if let Some(result) =
+                watch::finish_iteration(buckd, &console, iteration, start, result).await
+            {
+                return result;
+            }
+            iteration += 1;
+        }
+    }
 
     fn console_opts(&self) -> &CommonConsoleOptions {
         &self.common_opts.console_opts
@@ -7,6 +7,7 @@
  * of this source tree.
  */
 
+pub mod affected;
 pub mod build;
 pub mod bxl;
 pub mod clean;
@@ -103,6 +103,7 @@ enum BuckProfileMode {
     Bytecode,
     BytecodePairs,
     Typecheck,
+    Coverage,
 }
 
 #[derive(Debug, clap::Parser)]
@@ -142,6 +143,8 @@ pub struct ProfileCommonOptions {
     /// Output file path for profile data.
     ///
     /// File will be created if it does not exist, and overwritten if it does.
+    /// Flame graph and coverage modes write a directory instead, coverage as
+    /// `coverage.lcov` and Cobertura `coverage.xml`.
     #[clap(long, short = 'o', value_name = "PATH")]
     output: PathArg,
 
@@ -175,6 +178,7 @@ fn profile_mode_to_profile(mode: &BuckProfileMode) -> Profiler {
         BuckProfileMode::Bytecode => Profiler::Bytecode,
         BuckProfileMode::BytecodePairs => Profiler::BytecodePairs,
         BuckProfileMode::Typecheck => Profiler::Typecheck,
+        BuckProfileMode::Coverage => Profiler::Coverage,
     }
 }
 
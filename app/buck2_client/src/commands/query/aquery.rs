@@ -18,6 +18,7 @@ use buck2_client_ctx::common::CommonDaemonCommandOptions;
 use buck2_client_ctx::daemon::client::BuckdClientConnector;
 use buck2_client_ctx::daemon::client::StdoutPartialResultHandler;
 use buck2_client_ctx::exit_result::ExitResult;
+use buck2_client_ctx::path_arg::PathArg;
 use buck2_client_ctx::streaming::StreamingCommand;
 use buck2_core::if_else_opensource;
 
@@ -51,6 +52,10 @@ List all the commands for run actions for building a target
 
 `buck2 aquery 'kind(run, deps("//java/com/example/app:amazing+more"))' --output-attribute=cmd`
 
+Print the action, its category, identifier and command line, which produced an output
+
+`buck2 aquery --owner-of-output buck-out/v2/gen/root/<cfg_hash>/foo/__bar__/out.txt`
+
 Dynamic outputs (`ctx.actions.dynamic_output`):
 
 Currently, aquery interacts poorly with dynamic outputs. It may
@@ -72,6 +77,27 @@ pub struct AqueryCommand {
 
     #[clap(flatten)]
     query_common: CommonQueryOptions,
+
+    /// Find the action which produced the given buck-out path instead of evaluating a query.
+    /// Shorthand for `buck2 aquery 'owner_of_output(PATH)'`, which also prints the category,
+    /// identifier and command line of the action unless output attributes are given.
+    #[clap(long, value_name = "PATH", conflicts_with = "QUERY")]
+    owner_of_output: Option<PathArg>,
+}
+
+#[derive(Debug, buck2_error::Error)]
+enum AqueryCommandError {
+    #[error("Double quotes are not supported in `--owner-of-output` path `{0}`")]
+    UnsupportedQuote(String),
+}
+
+impl AqueryCommand {
+    fn owner_of_output_query(path: &str) -> anyhow::Result<String> {
+        if path.contains('"') {
+            return Err(AqueryCommandError::UnsupportedQuote(path.to_owned()).into());
+        }
+        Ok(format!("owner_of_output(\"{}\")", path))
+    }
 }
 
 #[async_trait]
@@ -84,9 +110,21 @@ impl StreamingCommand for AqueryCommand {
         matches: &clap::ArgMatches,
         ctx: &mut ClientCommandContext<'_>,
     ) -> ExitResult {
-        let (query, query_args) = self.query_common.get_query();
+        let (query, query_args) = match &self.owner_of_output {
+            Some(path) => {
+                let path = path.resolve(&ctx.working_dir);
+                (
+                    Self::owner_of_output_query(&path.to_string_lossy())?,
+                    vec![],
+                )
+            }
+            None => self.query_common.get_query()?,
+        };
         let unstable_output_format = self.query_common.output_format() as i32;
-        let output_attributes = self.query_common.attributes.get()?;
+        let mut output_attributes = self.query_common.attributes.get()?;
+        if self.owner_of_output.is_some() && output_attributes.is_empty() {
+            output_attributes = vec!["^(category|identifier|cmd)$".to_owned()];
+        }
         let context = ctx.client_context(matches, &self)?;
 
         let AqueryResponse {} = buckd
@@ -120,3 +158,24 @@ impl StreamingCommand for AqueryCommand {
         &self.common_opts.config_opts
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::query::aquery::AqueryCommand;
+
+    #[test]
+    fn test_owner_of_output_query() {
+        assert_eq!(
+            "owner_of_output(\"/repo/buck-out/v2/gen/root/abc/foo/__bar__/out.txt\")",
+            AqueryCommand::owner_of_output_query(
+                "/repo/buck-out/v2/gen/root/abc/foo/__bar__/out.txt"
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            "owner_of_output(\"/repo/buck-out/it's here.txt\")",
+            AqueryCommand::owner_of_output_query("/repo/buck-out/it's here.txt").unwrap()
+        );
+        assert!(AqueryCommand::owner_of_output_query("/repo/buck-out/\"out\".txt").is_err());
+    }
+}
@@ -27,12 +27,18 @@ enum QueryOutputFormatArg {
     DotCompact,
 }
 
+#[derive(Debug, buck2_error::Error)]
+enum CommonQueryOptionsError {
+    #[error("The following required arguments were not provided: <QUERY>")]
+    MissingQuery,
+}
+
 /// Args common to all the query commands
 #[derive(Debug, clap::Parser, serde::Serialize, serde::Deserialize)]
 #[clap(group = clap::ArgGroup::new("output_attribute_flags").multiple(false))]
 pub(crate) struct CommonQueryOptions {
     #[clap(name = "QUERY", help = "the query to evaluate")]
-    query: String,
+    query: Option<String>,
 
     #[clap(flatten)]
     pub attributes: CommonAttributeArgs,
@@ -101,16 +107,19 @@ impl CommonQueryOptions {
         }
     }
 
-    pub fn get_query(&self) -> (String, Vec<String>) {
-        if self.query.contains(QUERY_PERCENT_SS_PLACEHOLDER) {
+    pub fn get_query(&self) -> anyhow::Result<(String, Vec<String>)> {
+        let query = self
+            .query
+            .as_deref()
+            .ok_or(CommonQueryOptionsError::MissingQuery)?;
+        Ok(if query.contains(QUERY_PERCENT_SS_PLACEHOLDER) {
             let replacement = Self::args_as_set(&self.query_args);
             (
-                self.query
-                    .replace(QUERY_PERCENT_SS_PLACEHOLDER, &replacement),
+                query.replace(QUERY_PERCENT_SS_PLACEHOLDER, &replacement),
                 vec![],
             )
         } else {
-            (self.query.clone(), self.query_args.clone())
-        }
+            (query.to_owned(), self.query_args.clone())
+        })
     }
 }
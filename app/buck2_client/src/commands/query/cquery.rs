@@ -10,6 +10,7 @@
 use async_trait::async_trait;
 use buck2_cli_proto::CqueryRequest;
 use buck2_cli_proto::CqueryResponse;
+use buck2_cli_proto::QueryOutputFormat;
 use buck2_client_ctx::client_ctx::ClientCommandContext;
 use buck2_client_ctx::common::CommonBuildConfigurationOptions;
 use buck2_client_ctx::common::CommonCommandOptions;
@@ -18,7 +19,9 @@ use buck2_client_ctx::common::CommonDaemonCommandOptions;
 use buck2_client_ctx::daemon::client::BuckdClientConnector;
 use buck2_client_ctx::daemon::client::StdoutPartialResultHandler;
 use buck2_client_ctx::exit_result::ExitResult;
+use buck2_client_ctx::path_arg::PathArg;
 use buck2_client_ctx::streaming::StreamingCommand;
+use buck2_core::fs::fs_util;
 use buck2_core::if_else_opensource;
 
 use crate::commands::query::common::CommonQueryOptions;
@@ -60,6 +63,12 @@ List the deps of a target (special characters in a target will
 require quotes):
 
 `buck2 cquery 'deps("//java/com/example/app:amazing+more")'`
+
+Save the result of a query, and later print what changed:
+
+`buck2 cquery 'deps(//foo:bar)' --diff-snapshot > before.json`
+
+`buck2 cquery 'deps(//foo:bar)' --diff-against before.json`
 "#
     )
 }
@@ -109,6 +118,57 @@ pub struct CqueryCommand {
     /// See this post https://fburl.com/1mf2d2xj for details.
     #[clap(long)]
     correct_owner: bool,
+
+    /// Print a snapshot of the query result instead of the result itself.
+    ///
+    /// The snapshot records each target's configuration, target hash and attributes,
+    /// and can be saved to a file and passed to `--diff-against` later.
+    #[clap(
+        long,
+        conflicts_with_all = &["diff-against", "show-providers", "output_attribute_flags"]
+    )]
+    diff_snapshot: bool,
+
+    /// Print the difference between the query result and a snapshot previously
+    /// produced with `--diff-snapshot`: added and removed targets, and targets with
+    /// changed attributes or configurations.
+    #[clap(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = &["show-providers", "output_attribute_flags"]
+    )]
+    diff_against: Option<PathArg>,
+
+    /// Record the time spent resolving target literals and configuring the universe, and
+    /// the evaluation time and result size of each subexpression of the query, and print
+    /// them with the annotated expression tree to stderr.
+    ///
+    /// Not supported for queries with `%s` or `%Ss`.
+    #[clap(long)]
+    profile: bool,
+}
+
+#[derive(Debug, buck2_error::Error)]
+enum CqueryCommandError {
+    #[error(
+        "`--diff-snapshot` and `--diff-against` don't support the `dot` and `dot_compact` output formats"
+    )]
+    DiffDotOutputFormat,
+}
+
+impl CqueryCommand {
+    /// Snapshots and their diffs are lists of targets, they can't be printed as a graph.
+    fn check_diff_output_format(&self) -> anyhow::Result<()> {
+        if (self.diff_snapshot || self.diff_against.is_some())
+            && matches!(
+                self.query_common.output_format(),
+                QueryOutputFormat::Dot | QueryOutputFormat::DotCompact
+            )
+        {
+            return Err(CqueryCommandError::DiffDotOutputFormat.into());
+        }
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -121,7 +181,8 @@ impl StreamingCommand for CqueryCommand {
         matches: &clap::ArgMatches,
         ctx: &mut ClientCommandContext<'_>,
     ) -> ExitResult {
-        let (query, query_args) = self.query_common.get_query();
+        self.check_diff_output_format()?;
+        let (query, query_args) = self.query_common.get_query()?;
         let unstable_output_format = self.query_common.output_format() as i32;
         let output_attributes = self.query_common.attributes.get()?;
         let context = ctx.client_context(matches, &self)?;
@@ -137,6 +198,11 @@ impl StreamingCommand for CqueryCommand {
             }
         };
 
+        let diff_against = match &self.diff_against {
+            Some(path) => Some(fs_util::read_to_string(path.resolve(&ctx.working_dir))?),
+            None => None,
+        };
+
         let CqueryResponse {} = buckd
             .with_flushing()
             .cquery(
@@ -149,6 +215,9 @@ impl StreamingCommand for CqueryCommand {
                     show_providers: self.show_providers,
                     unstable_output_format,
                     correct_owner,
+                    diff_snapshot: self.diff_snapshot,
+                    diff_against,
+                    profile: self.profile,
                 },
                 ctx.stdin()
                     .console_interaction_stream(&self.common_opts.console_opts),
@@ -171,3 +240,51 @@ impl StreamingCommand for CqueryCommand {
         &self.common_opts.config_opts
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use crate::commands::query::cquery::CqueryCommand;
+
+    fn parse(args: &[&str]) -> clap::Result<CqueryCommand> {
+        CqueryCommand::try_parse_from(["cquery", "deps(//foo:bar)"].iter().chain(args))
+    }
+
+    #[test]
+    fn test_diff_conflicts() {
+        for diff in [&["--diff-snapshot"][..], &["--diff-against", "before.json"]] {
+            assert!(parse(diff).is_ok());
+            for other in [
+                &["--show-providers"][..],
+                &["--output-attribute", "srcs"],
+                &["-A"],
+                &["-B"],
+            ] {
+                assert!(parse(&[diff, other].concat()).is_err());
+            }
+        }
+        assert!(parse(&["--diff-snapshot", "--diff-against", "before.json"]).is_err());
+    }
+
+    #[test]
+    fn test_diff_output_format() {
+        for diff in [&["--diff-snapshot"][..], &["--diff-against", "before.json"]] {
+            for format in [&["--json"][..], &["--output-format", "json"]] {
+                let cmd = parse(&[diff, format].concat()).unwrap();
+                assert!(cmd.check_diff_output_format().is_ok());
+            }
+            for format in [
+                &["--dot"][..],
+                &["--dot-compact"],
+                &["--output-format", "dot"],
+                &["--output-format", "dot_compact"],
+            ] {
+                let cmd = parse(&[diff, format].concat()).unwrap();
+                assert!(cmd.check_diff_output_format().is_err());
+            }
+        }
+        let cmd = parse(&["--output-format", "dot"]).unwrap();
+        assert!(cmd.check_diff_output_format().is_ok());
+    }
+}
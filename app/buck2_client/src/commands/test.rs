@@ -7,9 +7,12 @@
  * of this source tree.
  */
 
+use std::time::Instant;
+
 use anyhow::Context;
 use async_trait::async_trait;
 use buck2_cli_proto::CounterWithExamples;
+use buck2_cli_proto::LocalTestExecution;
 use buck2_cli_proto::TestRequest;
 use buck2_cli_proto::TestSessionOptions;
 use buck2_client_ctx::client_ctx::ClientCommandContext;
@@ -29,12 +32,16 @@ use buck2_client_ctx::streaming::StreamingCommand;
 use buck2_client_ctx::subscribers::superconsole::test::span_from_build_failure_count;
 use buck2_client_ctx::subscribers::superconsole::test::TestCounterColumn;
 use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_path::AbsPathBuf;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
 use buck2_core::fs::working_dir::WorkingDir;
 use gazebo::prelude::*;
 use superconsole::Line;
 use superconsole::Span;
+use thiserror::Error;
 
 use crate::commands::build::print_build_result;
+use crate::watch;
 
 fn forward_output_to_path(
     output: &str,
@@ -45,6 +52,71 @@ fn forward_output_to_path(
         .context("Failed to write test executor output to path")
 }
 
+#[derive(Error, Debug)]
+enum TestCommandError {
+    #[error("No test to run locally was selected")]
+    NoLocalTest,
+    #[error(
+        "Only a single test can be run locally, but multiple were selected: {}",
+        .0.join(", ")
+    )]
+    MultipleLocalTests(Vec<String>),
+}
+
+/// The command line, working directory and environment to run a test prepared for local
+/// execution with, optionally under `wrapper`.
+#[derive(Debug, PartialEq)]
+struct LocalTestCommand {
+    argv: Vec<String>,
+    cwd: AbsPathBuf,
+    env: Vec<(String, String)>,
+}
+
+impl LocalTestCommand {
+    fn new(
+        wrapper: Option<&str>,
+        mut local_executions: Vec<LocalTestExecution>,
+    ) -> anyhow::Result<Self> {
+        let execution = match local_executions.len() {
+            0 => return Err(TestCommandError::NoLocalTest.into()),
+            1 => local_executions.pop().unwrap(),
+            _ => {
+                return Err(TestCommandError::MultipleLocalTests(
+                    local_executions.into_iter().map(|e| e.target).collect(),
+                )
+                .into());
+            }
+        };
+        let mut argv = match wrapper {
+            Some(wrapper) => shlex::split(wrapper)
+                .with_context(|| format!("Invalid `--wrapper`: `{}`", wrapper))?,
+            None => Vec::new(),
+        };
+        argv.extend(execution.cmd);
+        if argv.is_empty() {
+            return Err(anyhow::anyhow!("Empty test command"));
+        }
+        Ok(Self {
+            argv,
+            cwd: AbsPathBuf::new(execution.cwd)?,
+            env: execution
+                .env
+                .into_iter()
+                .map(|var| (var.key, var.value))
+                .collect(),
+        })
+    }
+}
+
+/// Replace this process with a test prepared for local execution, optionally under `wrapper`.
+fn exec_local_test(
+    wrapper: Option<&str>,
+    local_executions: Vec<LocalTestExecution>,
+) -> anyhow::Result<ExitResult> {
+    let LocalTestCommand { argv, cwd, env } = LocalTestCommand::new(wrapper, local_executions)?;
+    Ok(ExitResult::exec(argv[0].clone(), argv, Some(cwd), env))
+}
+
 fn print_error_counter(
     console: &FinalConsole,
     counter: &CounterWithExamples,
@@ -123,6 +195,11 @@ If include patterns are present, regardless of whether exclude patterns are pres
     #[clap(long, group = "re_options", alias = "unstable-force-tests-on-re")]
     unstable_allow_all_tests_on_re: bool,
 
+    /// Run all the tests, even when `test.cache_results` is set and a test already passed with
+    /// the same command, environment and inputs.
+    #[clap(long)]
+    no_cache_tests: bool,
+
     // NOTE: the field below is given a different name from the test runner's `timeout` to avoid
     // confusion between the two parameters.
     /// How long to execute tests for. If the timeout is exceeded, Buck2 will exit
@@ -142,6 +219,58 @@ If include patterns are present, regardless of whether exclude patterns are pres
     #[clap(long = "overall-timeout")]
     timeout: Option<humantime::Duration>,
 
+    /// Writes a JUnit XML report of the test results to the provided path, with one test suite
+    /// per target.
+    #[clap(long, value_name = "PATH")]
+    junit_xml: Option<PathArg>,
+
+    /// Writes a JSON report of the test results to the provided path. Each result has its
+    /// target, name, status, duration, retries and (truncated) output.
+    #[clap(long, value_name = "PATH")]
+    test_report_json: Option<PathArg>,
+
+    /// Only run the tests of this shard, numbered from 0. Every test target is assigned to one
+    /// of `--shard-count` shards, so running all the shards runs all the tests once.
+    ///
+    /// Test targets with the `shard_testcases` label are run on every shard instead, with
+    /// their testcases split across shards, when using the internal test runner.
+    #[clap(long, requires = "shard-count", value_name = "N")]
+    shard_index: Option<u32>,
+
+    /// The number of shards to split the tests in.
+    #[clap(long, requires = "shard-index", value_name = "M")]
+    shard_count: Option<u32>,
+
+    /// A report written by `--test-report-json` in an earlier run, whose test durations are
+    /// used to balance the shards. Tests missing from it are assigned to a shard by a hash of
+    /// their name.
+    #[clap(long, requires = "shard-count", value_name = "PATH")]
+    shard_durations: Option<PathArg>,
+
+    /// Collect the code coverage of the tests, and write it as an lcov report to the provided
+    /// path, or to `coverage.lcov` in buck-out when `--coverage` is passed without one.
+    /// Tests are run with `LLVM_PROFILE_FILE` and `GCOV_PREFIX` pointing to an output
+    /// directory, and their profiles are merged with the tools configured in
+    /// `test.llvm_profdata`, `test.llvm_cov` and `test.gcov`.
+    #[clap(long, value_name = "PATH", require_equals = true)]
+    coverage: Option<Option<PathArg>>,
+
+    /// Run the selected test in the foreground, with the terminal attached, instead of running
+    /// it through the test runner. Its inputs are materialized and it gets the command and
+    /// environment the test runner would use. Exactly one test target must be selected.
+    #[clap(long)]
+    interactive: bool,
+
+    /// Like `--interactive`, but run the test under this command, e.g. `--wrapper='gdb --args'`.
+    #[clap(long, value_name = "COMMAND")]
+    wrapper: Option<String>,
+
+    /// Test again whenever source files change, until interrupted. Only the tests affected by
+    /// the changes are rebuilt, and tests cached by `test.cache_results` are not run again.
+    /// Requires `buck2.file_watcher = notify` or `watchman`.
+    #[clap(long, conflicts_with_all = &["interactive", "wrapper"])]
+    watch: bool,
+
     #[clap(name = "TARGET_PATTERNS", help = "Patterns to test")]
     patterns: Vec<String>,
 
@@ -177,17 +306,24 @@ If include patterns are present, regardless of whether exclude patterns are pres
     test_executor_args: Vec<String>,
 }
 
-#[async_trait]
-impl StreamingCommand for TestCommand {
-    const COMMAND_NAME: &'static str = "test";
-
-    async fn exec_impl(
-        self,
-        buckd: &mut BuckdClientConnector,
+impl TestCommand {
+    async fn run_tests(
+        &self,
+        buckd: &mut BuckdClientConnector<'_>,
         matches: &clap::ArgMatches,
         ctx: &mut ClientCommandContext<'_>,
     ) -> ExitResult {
-        let context = ctx.client_context(matches, &self)?;
+        let context = ctx.client_context(matches, self)?;
+        let coverage_report_path = match &self.coverage {
+            Some(Some(path)) => Some(path.resolve(&ctx.working_dir)),
+            Some(None) => Some(
+                ctx.paths()?
+                    .buck_out_path()
+                    .join(ForwardRelativePath::unchecked_new("coverage.lcov"))
+                    .into_abs_path_buf(),
+            ),
+            None => None,
+        };
         let response = buckd
             .with_flushing()
             .test(
@@ -196,9 +332,9 @@ impl StreamingCommand for TestCommand {
                     target_patterns: self
                         .patterns
                         .map(|pat| buck2_data::TargetPattern { value: pat.clone() }),
-                    test_executor_args: self.test_executor_args,
-                    excluded_labels: self.exclude,
-                    included_labels: self.include,
+                    test_executor_args: self.test_executor_args.clone(),
+                    excluded_labels: self.exclude.clone(),
+                    included_labels: self.include.clone(),
                     always_exclude: self.always_exclude,
                     build_filtered_targets: self.build_filtered_targets,
                     // we don't currently have a different flag for this, so just use the build one.
@@ -209,6 +345,7 @@ impl StreamingCommand for TestCommand {
                             || self.unstable_allow_all_tests_on_re,
                         force_use_project_relative_paths: self.unstable_allow_all_tests_on_re,
                         force_run_from_project_root: self.unstable_allow_all_tests_on_re,
+                        no_cache_tests: self.no_cache_tests,
                     }),
                     timeout: self
                         .timeout
@@ -218,6 +355,32 @@ impl StreamingCommand for TestCommand {
                         })
                         .transpose()
                         .context("Invalid `timeout`")?,
+                    junit_xml_path: self
+                        .junit_xml
+                        .as_ref()
+                        .map(|p| p.resolve(&ctx.working_dir).into_string())
+                        .transpose()?
+                        .unwrap_or_default(),
+                    test_report_json_path: self
+                        .test_report_json
+                        .as_ref()
+                        .map(|p| p.resolve(&ctx.working_dir).into_string())
+                        .transpose()?
+                        .unwrap_or_default(),
+                    shard_index: self.shard_index.unwrap_or_default(),
+                    shard_count: self.shard_count.unwrap_or_default(),
+                    shard_durations_path: self
+                        .shard_durations
+                        .as_ref()
+                        .map(|p| p.resolve(&ctx.working_dir).into_string())
+                        .transpose()?
+                        .unwrap_or_default(),
+                    coverage_report_path: coverage_report_path
+                        .clone()
+                        .map(|p| p.into_string())
+                        .transpose()?
+                        .unwrap_or_default(),
+                    prepare_for_local_execution: self.interactive || self.wrapper.is_some(),
                 },
                 ctx.stdin()
                     .console_interaction_stream(&self.common_opts.console_opts),
@@ -238,6 +401,8 @@ impl StreamingCommand for TestCommand {
         let failed = statuses.failed.as_ref().context("Missing `failed`")?;
         let fatals = statuses.fatals.as_ref().context("Missing `fatals`")?;
         let skipped = statuses.skipped.as_ref().context("Missing `skipped`")?;
+        // Only reported when tests were retried.
+        let flaky = statuses.flaky.as_ref().filter(|flaky| flaky.count > 0);
 
         let console = self.common_opts.console_opts.final_console();
         print_build_result(&console, &response.errors)?;
@@ -256,6 +421,13 @@ impl StreamingCommand for TestCommand {
             console.print_error(&format!("{} BUILDS FAILED", build_errors.len()))?;
         }
 
+        if self.interactive || self.wrapper.is_some() {
+            if !build_errors.is_empty() {
+                return ExitResult::from_errors(build_errors.iter().copied());
+            }
+            return exec_local_test(self.wrapper.as_deref(), response.local_executions)?;
+        }
+
         // TODO(nmj): Might make sense for us to expose the event ctx, and use its
         //            handle_stdout method, instead of raw buck2_client::println!s here.
         // TODO: also remove the duplicate information when the above is done.
@@ -276,24 +448,36 @@ impl StreamingCommand for TestCommand {
             line.push(column.to_span_from_test_statuses(statuses)?);
             line.push(Span::new_unstyled_lossy(". "));
         }
+        if flaky.is_some() {
+            line.push(TestCounterColumn::FLAKY.to_span_from_test_statuses(statuses)?);
+            line.push(Span::new_unstyled_lossy(". "));
+        }
         line.push(span_from_build_failure_count(build_errors.len())?);
         eprint_line(&line)?;
 
         print_error_counter(&console, listing_failed, "LISTINGS FAILED", "⚠")?;
         print_error_counter(&console, failed, "TESTS FAILED", "✗")?;
         print_error_counter(&console, fatals, "TESTS FATALS", "⚠")?;
-        if passed.count + failed.count + fatals.count + skipped.count == 0 {
+        if let Some(flaky) = flaky {
+            print_error_counter(&console, flaky, "TESTS FLAKY", "≈")?;
+        }
+        let flaky_count = flaky.map_or(0, |flaky| flaky.count);
+        if passed.count + failed.count + fatals.count + skipped.count + flaky_count == 0 {
             console.print_warning("NO TESTS RAN")?;
         }
 
+        if let Some(path) = &coverage_report_path {
+            console.print_stderr(&format!("Coverage report written to {}", path.display()))?;
+        }
+
         let info_messages = response.executor_info_messages;
         for message in info_messages {
             console.print_stderr(message.as_str())?;
         }
 
-        match self.test_executor_stderr {
+        match &self.test_executor_stderr {
             Some(OutputDestinationArg::Path(path)) => {
-                forward_output_to_path(&response.executor_stderr, &path, &ctx.working_dir)?;
+                forward_output_to_path(&response.executor_stderr, path, &ctx.working_dir)?;
             }
             Some(OutputDestinationArg::Stream) => {
                 console.print_error(&response.executor_stderr)?;
@@ -313,9 +497,9 @@ impl StreamingCommand for TestCommand {
             ExitResult::bail("Test executor did not provide an exit code")
         };
 
-        match self.test_executor_stdout {
+        match &self.test_executor_stdout {
             Some(OutputDestinationArg::Path(path)) => {
-                forward_output_to_path(&response.executor_stdout, &path, &ctx.working_dir)?;
+                forward_output_to_path(&response.executor_stdout, path, &ctx.working_dir)?;
                 exit_result
             }
             Some(OutputDestinationArg::Stream) => {
@@ -324,6 +508,35 @@ impl StreamingCommand for TestCommand {
             _ => exit_result,
         }
     }
+}
+
+#[async_trait]
+impl StreamingCommand for TestCommand {
+    const COMMAND_NAME: &'static str = "test";
+
+    async fn exec_impl(
+        self,
+        buckd: &mut BuckdClientConnector,
+        matches: &clap::ArgMatches,
+        ctx: &mut ClientCommandContext<'_>,
+    ) -> ExitResult {
+        if !self.watch {
+            return self.run_tests(buckd, matches, ctx).await;
+        }
+
+        let console = self.common_opts.console_opts.final_console();
+        let mut iteration = 1;
+        loop {
+            let start = Instant::now();
+            let result = self.run_tests(buckd, matches, ctx).await;
+            if let Some(result) =
+                watch::finish_iteration(buckd, &console, iteration, start, result).await
+            {
+                return result;
+            }
+            iteration += 1;
+        }
+    }
 
     fn console_opts(&self) -> &CommonConsoleOptions {
         &self.common_opts.console_opts
@@ -337,3 +550,81 @@ impl StreamingCommand for TestCommand {
         &self.common_opts.config_opts
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use buck2_cli_proto::local_test_execution::EnvironmentVariable;
+    use buck2_cli_proto::LocalTestExecution;
+    use buck2_core::fs::paths::abs_path::AbsPathBuf;
+
+    use crate::commands::test::LocalTestCommand;
+    use crate::commands::test::TestCommandError;
+
+    fn cwd() -> &'static str {
+        if cfg!(windows) { "C:\\work" } else { "/work" }
+    }
+
+    fn execution(target: &str) -> LocalTestExecution {
+        LocalTestExecution {
+            target: target.to_owned(),
+            cmd: vec!["test_bin".to_owned(), "--flag".to_owned()],
+            env: vec![EnvironmentVariable {
+                key: "KEY".to_owned(),
+                value: "value".to_owned(),
+            }],
+            cwd: cwd().to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_local_test_command() {
+        assert_eq!(
+            LocalTestCommand {
+                argv: vec!["test_bin".to_owned(), "--flag".to_owned()],
+                cwd: AbsPathBuf::new(cwd()).unwrap(),
+                env: vec![("KEY".to_owned(), "value".to_owned())],
+            },
+            LocalTestCommand::new(None, vec![execution("//:a")]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_local_test_command_wrapper() {
+        let command = LocalTestCommand::new(
+            Some("gdb --args -ex 'set pagination off'"),
+            vec![execution("//:a")],
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                "gdb",
+                "--args",
+                "-ex",
+                "set pagination off",
+                "test_bin",
+                "--flag"
+            ],
+            command.argv
+        );
+
+        assert!(LocalTestCommand::new(Some("gdb 'unterminated"), vec![execution("//:a")]).is_err());
+    }
+
+    #[test]
+    fn test_local_test_command_not_one_test() {
+        let err = LocalTestCommand::new(None, Vec::new()).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TestCommandError>(),
+            Some(TestCommandError::NoLocalTest)
+        ));
+
+        let err =
+            LocalTestCommand::new(None, vec![execution("//:a"), execution("//:b")]).unwrap_err();
+        match err.downcast_ref::<TestCommandError>() {
+            Some(TestCommandError::MultipleLocalTests(targets)) => {
+                assert_eq!(&vec!["//:a".to_owned(), "//:b".to_owned()], targets)
+            }
+            _ => panic!("Unexpected error: {:#}", err),
+        }
+    }
+}
@@ -16,3 +16,4 @@
 pub mod args;
 pub mod commands;
 pub mod print;
+pub(crate) mod watch;
@@ -0,0 +1,61 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! `--watch`: run a command again whenever the file watcher sees source changes.
+//!
+//! Every iteration is a new daemon command, so it syncs the file watcher into a new DICE
+//! transaction and only recomputes what the changes invalidated.
+
+use std::time::Instant;
+
+use buck2_cli_proto::WaitForFileChangesRequest;
+use buck2_client_ctx::command_outcome::CommandOutcome;
+use buck2_client_ctx::daemon::client::BuckdClientConnector;
+use buck2_client_ctx::exit_result::ExitResult;
+use buck2_client_ctx::final_console::FinalConsole;
+
+/// Reports how one iteration went and blocks until files change. Returns the result to exit with
+/// if we should stop watching instead.
+pub(crate) async fn finish_iteration(
+    buckd: &mut BuckdClientConnector<'_>,
+    console: &FinalConsole,
+    iteration: u64,
+    start: Instant,
+    result: ExitResult,
+) -> Option<ExitResult> {
+    let success = match result.into_iteration_success() {
+        Ok(success) => success,
+        Err(result) => return Some(result),
+    };
+
+    let summary = format!(
+        "Watch iteration {} {} in {:.1}s. Waiting for file changes...",
+        iteration,
+        if success { "succeeded" } else { "failed" },
+        start.elapsed().as_secs_f64(),
+    );
+    let printed = if success {
+        console.print_success(&summary)
+    } else {
+        console.print_error(&summary)
+    };
+    if let Err(e) = printed {
+        return Some(ExitResult::err(e));
+    }
+
+    match buckd
+        .with_flushing()
+        .wait_for_file_changes(WaitForFileChangesRequest {})
+        .await
+    {
+        Ok(CommandOutcome::Success(_)) => None,
+        Ok(CommandOutcome::Failure(result)) => Some(result),
+        Err(e) => Some(ExitResult::err(e)),
+    }
+}
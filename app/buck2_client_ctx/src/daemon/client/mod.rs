@@ -565,6 +565,11 @@ impl<'a, 'b> FlushingBuckdClient<'a, 'b> {
     );
 
     oneshot_method!(flush_dep_files, FlushDepFilesRequest, GenericResponse);
+    oneshot_method!(
+        wait_for_file_changes,
+        WaitForFileChangesRequest,
+        GenericResponse
+    );
 
     oneshot_method!(unstable_crash, UnstableCrashRequest, GenericResponse);
     debug_method!(segfault, SegfaultRequest, SegfaultResponse);
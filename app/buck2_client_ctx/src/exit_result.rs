@@ -135,6 +135,22 @@ impl ExitResult {
         self
     }
 
+    /// For a command that runs repeatedly (`--watch`), emit the stdout of this run now and return
+    /// whether it succeeded. A result that is not a plain exit status (e.g. an error, which should
+    /// be reported rather than running the command again) is returned as-is in `Err`.
+    pub fn into_iteration_success(self) -> Result<bool, Self> {
+        let success = match &self.variant {
+            ExitResultVariant::Status(status) => {
+                matches!(status, ExitCode::Success | ExitCode::Explicit(0))
+            }
+            _ => return Err(self),
+        };
+        match crate::stdio::print_bytes(&self.stdout) {
+            Ok(()) => Ok(success),
+            Err(e) => Err(Self::err(e)),
+        }
+    }
+
     pub fn report(self) -> ! {
         match crate::stdio::print_bytes(&self.stdout) {
             Ok(()) => self.variant.report(),
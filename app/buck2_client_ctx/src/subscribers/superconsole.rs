@@ -83,6 +83,7 @@ pub struct StatefulSuperConsole {
     state: SuperConsoleState,
     super_console: Option<SuperConsole>,
     verbosity: Verbosity,
+    expect_spans: bool,
 }
 
 #[derive(Copy, Clone, Dupe, Debug)]
@@ -289,9 +290,26 @@ impl StatefulSuperConsole {
             state: SuperConsoleState::new(replay_speed, trace_id, verbosity, expect_spans, config)?,
             super_console: Some(super_console),
             verbosity,
+            expect_spans,
         })
     }
 
+    /// The console is finalized when a command finishes. A client can run several commands on
+    /// one connection (e.g. `--watch`), so start a fresh console when the next one starts.
+    fn restart(&mut self, trace_id: TraceId) -> anyhow::Result<()> {
+        if let Some(super_console) = Self::console_builder().build()? {
+            self.state = SuperConsoleState::new(
+                Some(self.state.time_speed.speed()),
+                trace_id,
+                self.verbosity,
+                self.expect_spans,
+                self.state.config.clone(),
+            )?;
+            self.super_console = Some(super_console);
+        }
+        Ok(())
+    }
+
     /// Construct a console suitable for use by the Buck2 CLI. We use non-blocking output here
     /// because we do all our event processing on a single thread, so that if stderr is blocked
     /// (e.g.  because the client is using a resumable remote terminal and they've temporarily
@@ -374,6 +392,10 @@ impl StatefulSuperConsole {
 #[async_trait]
 impl UnpackingEventSubscriber for StatefulSuperConsole {
     async fn handle_event(&mut self, event: &Arc<BuckEvent>) -> anyhow::Result<()> {
+        if self.super_console.is_none() && event.command_start()?.is_some() {
+            self.restart(event.trace_id()?)?;
+        }
+
         match &mut self.super_console {
             Some(_) => {
                 self.handle_inner_event(event)
@@ -7,96 +7,256 @@
  * of this source tree.
  */
 
+use std::collections::BTreeMap;
 use std::sync::Arc;
+use std::time::Duration;
 
+use anyhow::Context;
+use async_trait::async_trait;
 use buck2_core::target::configured_target_label::ConfiguredTargetLabel;
 use derivative::Derivative;
+use dupe::Dupe;
 use tokio::sync::mpsc;
 use tokio::sync::mpsc::UnboundedReceiver;
 use tokio::sync::mpsc::UnboundedSender;
 use tokio::sync::Mutex;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, buck2_error::Error)]
+enum LocalResourceError {
+    #[error("Setup of local resource `{0}` created no resources")]
+    #[buck2(user)]
+    NoResources(ConfiguredTargetLabel),
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct EnvironmentVariable {
     pub key: String,
     pub value: String,
 }
 
 /// Resource represented by a list of environment variable key-value pairs.
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct LocalResource(pub Vec<EnvironmentVariable>);
 
+/// Resources created by one run of the setup of a pool.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LocalResourceBatch {
+    /// ID of process which is holding the resources.
+    /// SIGTERM is sent to this process to free the resources.
+    pub owning_pid: Option<i32>,
+    pub resources: Vec<LocalResource>,
+}
+
+/// A command run against a single resource, with that resource's environment variables set.
+#[derive(Clone, Debug)]
+pub struct LocalResourceCommand {
+    pub argv: Vec<String>,
+    pub timeout: Duration,
+}
+
+/// Runs the commands managing the resources of a pool, on the executor which set up the pool.
+#[async_trait]
+pub trait LocalResourceRunner: Send + Sync {
+    /// Runs the setup again, for resources replacing the ones retired from the pool.
+    async fn setup(&self) -> anyhow::Result<LocalResourceBatch>;
+
+    /// Runs `command` with the environment variables of `resource` set.
+    async fn run(
+        &self,
+        command: &LocalResourceCommand,
+        resource: &LocalResource,
+    ) -> anyhow::Result<()>;
+
+    /// Called once every resource of `batch` was retired and torn down, or the pool was
+    /// released, to free whatever the runner holds for the batch.
+    fn release(&self, _batch: &LocalResourceBatch) {}
+}
+
+/// How a pool manages the resources in it beyond handing them out.
+#[derive(Clone, Debug, Default)]
+pub struct LocalResourcePoolOptions {
+    /// Run before a resource is handed out. Resources that fail it are retired.
+    pub health_check: Option<LocalResourceCommand>,
+    /// Run for every resource when it is retired or the pool is released.
+    pub teardown: Option<LocalResourceCommand>,
+    /// Number of times a resource can be handed out before it is retired.
+    pub max_uses: Option<u64>,
+}
+
+struct PooledResource {
+    resource: LocalResource,
+    uses: u64,
+    /// Key in `PoolShared::batches` of the setup run which created the resource.
+    batch: u64,
+}
+
+/// A setup run with resources which were not torn down yet.
+struct PoolBatch {
+    batch: LocalResourceBatch,
+    /// Number of resources of the batch which were not torn down yet.
+    remaining: usize,
+}
+
+enum PoolEntry {
+    Resource(PooledResource),
+    /// A resource which reached `max_uses`, for whoever receives it to tear it down.
+    Retired(PooledResource),
+    /// Sent once the last resource of the pool is retired, for whoever receives it to set up
+    /// new ones. Sent again if that fails, so that the next one waiting tries again.
+    Replenish,
+}
+
+/// Parts of the pool shared with the holders.
+struct PoolShared {
+    options: LocalResourcePoolOptions,
+    runner: Arc<dyn LocalResourceRunner>,
+    /// Resources which were set up and not retired.
+    live: parking_lot::Mutex<Vec<LocalResource>>,
+    /// Processes holding the resources of every setup run, including the retired ones.
+    owning_pids: parking_lot::Mutex<Vec<i32>>,
+    /// Setup runs which still have resources to tear down, and the key of the next one.
+    batches: parking_lot::Mutex<(u64, BTreeMap<u64, PoolBatch>)>,
+    sender: UnboundedSender<PoolEntry>,
+}
+
+impl PoolShared {
+    fn add(&self, batch: LocalResourceBatch) {
+        if let Some(pid) = batch.owning_pid {
+            self.owning_pids.lock().push(pid);
+        }
+        let id = {
+            let mut batches = self.batches.lock();
+            let id = batches.0;
+            batches.0 += 1;
+            batches.1.insert(
+                id,
+                PoolBatch {
+                    batch: batch.clone(),
+                    remaining: batch.resources.len(),
+                },
+            );
+            id
+        };
+        let mut live = self.live.lock();
+        for resource in batch.resources {
+            live.push(resource.clone());
+            let _ignored = self.sender.send(PoolEntry::Resource(PooledResource {
+                resource,
+                uses: 0,
+                batch: id,
+            }));
+        }
+        if live.is_empty() {
+            let _ignored = self.sender.send(PoolEntry::Replenish);
+        }
+    }
+
+    /// Records that a resource of the batch `id` was torn down, releasing the batch if it was
+    /// the last one.
+    fn torn_down(&self, id: u64) {
+        let released = {
+            let mut batches = self.batches.lock();
+            match batches.1.get_mut(&id) {
+                Some(batch) if batch.remaining > 1 => {
+                    batch.remaining -= 1;
+                    None
+                }
+                _ => batches.1.remove(&id),
+            }
+        };
+        if let Some(released) = released {
+            self.runner.release(&released.batch);
+        }
+    }
+}
+
 /// RAII handle for resource spec, returns spec to the pool on drop.
 pub struct LocalResourceHolder {
     // Optionality is only needed so we can move out the spec on drop.
-    spec: Option<LocalResource>,
-    sender: UnboundedSender<LocalResource>,
+    spec: Option<PooledResource>,
+    pool: Arc<PoolShared>,
 }
 
 impl Drop for LocalResourceHolder {
     fn drop(&mut self) {
-        let _ignored = self.sender.send(
-            self.spec
-                .take()
-                .expect("Should only be absent in already dropped object."),
-        );
+        let mut spec = self
+            .spec
+            .take()
+            .expect("Should only be absent in already dropped object.");
+        spec.uses += 1;
+        let entry = if self
+            .pool
+            .options
+            .max_uses
+            .map_or(false, |max_uses| spec.uses >= max_uses)
+        {
+            // Tearing down is async, so it is left to the next one acquiring a resource.
+            PoolEntry::Retired(spec)
+        } else {
+            PoolEntry::Resource(spec)
+        };
+        let _ignored = self.pool.sender.send(entry);
     }
 }
 
 impl AsRef<LocalResource> for LocalResourceHolder {
     fn as_ref(&self) -> &LocalResource {
-        self.spec
+        &self
+            .spec
             .as_ref()
             .expect("Should only be absent in already dropped object.")
+            .resource
     }
 }
 
 /// Blocking resource pool to manage access to prepared local resources.
-#[derive(Clone, Debug, Derivative)]
-#[derivative(PartialEq, Eq, PartialOrd, Ord, Hash)]
+///
+/// Resources which fail the health check or reach `max_uses` are torn down, and once none
+/// are left the setup is run again to replace them, so acquiring a resource only waits.
+#[derive(Clone, Derivative)]
+#[derivative(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct LocalResourceState {
     // Set of resources of same type should be uniquely identified by configured target label providing `LocalResourceInfo`.
     // This is the assumption for equiality, ordering and hash implementations.
     source_target: ConfiguredTargetLabel,
     #[derivative(
+        Debug = "ignore",
         Hash = "ignore",
         PartialEq = "ignore",
         PartialOrd = "ignore",
         Ord = "ignore"
     )]
-    owning_pid: Option<i32>,
-    #[derivative(
-        Hash = "ignore",
-        PartialEq = "ignore",
-        PartialOrd = "ignore",
-        Ord = "ignore"
-    )]
-    sender: UnboundedSender<LocalResource>,
+    pool: Arc<PoolShared>,
     #[derivative(
+        Debug = "ignore",
         Hash = "ignore",
         PartialEq = "ignore",
         PartialOrd = "ignore",
         Ord = "ignore"
     )]
-    receiver: Arc<Mutex<UnboundedReceiver<LocalResource>>>,
+    receiver: Arc<Mutex<UnboundedReceiver<PoolEntry>>>,
 }
 
 impl LocalResourceState {
     pub fn new(
         source_target: ConfiguredTargetLabel,
-        owning_pid: Option<i32>,
-        specs: Vec<LocalResource>,
+        batch: LocalResourceBatch,
+        options: LocalResourcePoolOptions,
+        runner: Arc<dyn LocalResourceRunner>,
     ) -> Self {
         let (sender, receiver) = mpsc::unbounded_channel();
-        for spec in specs {
-            sender.send(spec).expect(
-                "Not expected send to fail when channel is not closed and receiver is not dropped.",
-            );
-        }
+        let pool = Arc::new(PoolShared {
+            options,
+            runner,
+            live: parking_lot::Mutex::new(Vec::new()),
+            owning_pids: parking_lot::Mutex::new(Vec::new()),
+            batches: parking_lot::Mutex::new((0, BTreeMap::new())),
+            sender,
+        });
+        pool.add(batch);
         LocalResourceState {
             source_target,
-            owning_pid,
-            sender,
+            pool,
             receiver: Arc::new(Mutex::new(receiver)),
         }
     }
@@ -105,59 +265,361 @@ impl LocalResourceState {
         &self.source_target
     }
 
-    /// ID of process which actually is holding a pool of resources.
-    /// SIGTERM is sent to this process to free the resources.
-    pub fn owning_pid(&self) -> Option<i32> {
-        self.owning_pid
+    /// IDs of processes which actually are holding the resources, one per setup run.
+    /// SIGTERM is sent to these processes to free the resources.
+    pub fn owning_pids(&self) -> Vec<i32> {
+        self.pool.owning_pids.lock().clone()
+    }
+
+    /// Waits for a resource that passes the health check.
+    pub async fn acquire_resource(&self) -> anyhow::Result<LocalResourceHolder> {
+        loop {
+            let entry = {
+                let mut guard = self.receiver.lock().await;
+                guard.recv().await.unwrap()
+            };
+            let spec = match entry {
+                PoolEntry::Resource(spec) => spec,
+                PoolEntry::Retired(spec) => {
+                    self.retire(spec).await;
+                    continue;
+                }
+                PoolEntry::Replenish => {
+                    self.replenish().await?;
+                    continue;
+                }
+            };
+
+            if let Some(health_check) = &self.pool.options.health_check {
+                if let Err(e) = self.pool.runner.run(health_check, &spec.resource).await {
+                    tracing::warn!(
+                        "Retiring a resource of local resource `{}` after a failed health check: {:#}",
+                        self.source_target,
+                        e
+                    );
+                    self.retire(spec).await;
+                    continue;
+                }
+            }
+
+            return Ok(LocalResourceHolder {
+                spec: Some(spec),
+                pool: self.pool.dupe(),
+            });
+        }
+    }
+
+    /// Removes a resource from the pool and tears it down. Asks for new resources once the
+    /// last one is retired, and releases its batch once all of them are torn down.
+    async fn retire(&self, spec: PooledResource) {
+        let resource = spec.resource;
+        {
+            let mut live = self.pool.live.lock();
+            if let Some(index) = live.iter().position(|r| r == &resource) {
+                live.remove(index);
+            }
+            if live.is_empty() {
+                let _ignored = self.pool.sender.send(PoolEntry::Replenish);
+            }
+        }
+        if let Some(teardown) = &self.pool.options.teardown {
+            if let Err(e) = self.pool.runner.run(teardown, &resource).await {
+                tracing::warn!(
+                    "Failed to tear down a retired resource of local resource `{}`: {:#}",
+                    self.source_target,
+                    e
+                );
+            }
+        }
+        self.pool.torn_down(spec.batch);
     }
 
-    pub async fn acquire_resource(&self) -> LocalResourceHolder {
-        let spec = {
-            let mut guard = self.receiver.lock().await;
-            Some(guard.recv().await.unwrap())
+    async fn replenish(&self) -> anyhow::Result<()> {
+        let batch = match self.pool.runner.setup().await {
+            Ok(batch) if batch.resources.is_empty() => {
+                Err(LocalResourceError::NoResources(self.source_target.dupe()).into())
+            }
+            res => res,
         };
-        LocalResourceHolder {
-            spec,
-            sender: self.sender.clone(),
+        match batch {
+            Ok(batch) => {
+                self.pool.add(batch);
+                Ok(())
+            }
+            Err(e) => {
+                let _ignored = self.pool.sender.send(PoolEntry::Replenish);
+                Err(e.context(format!(
+                    "Failed to replace the retired resources of local resource `{}`",
+                    self.source_target
+                )))
+            }
         }
     }
+
+    /// Runs the teardown command for every resource which was not retired yet, then releases
+    /// every batch.
+    pub async fn teardown(&self) -> anyhow::Result<()> {
+        let res = match &self.pool.options.teardown {
+            Some(teardown) => {
+                let resources = std::mem::take(&mut *self.pool.live.lock());
+                let futs = resources
+                    .iter()
+                    .map(|resource| self.pool.runner.run(teardown, resource));
+                futures::future::join_all(futs)
+                    .await
+                    .into_iter()
+                    .collect::<anyhow::Result<Vec<_>>>()
+                    .with_context(|| {
+                        format!(
+                            "Failed to tear down local resource `{}`",
+                            self.source_target
+                        )
+                    })
+            }
+            None => Ok(Vec::new()),
+        };
+        let batches = std::mem::take(&mut self.pool.batches.lock().1);
+        for (_, batch) in batches {
+            self.pool.runner.release(&batch.batch);
+        }
+        res?;
+        Ok(())
+    }
+
+    /// Resources which were set up and not retired yet.
+    pub fn resources(&self) -> Vec<LocalResource> {
+        self.pool.live.lock().clone()
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use async_trait::async_trait;
     use buck2_core::configuration::data::ConfigurationData;
     use buck2_core::target::configured_target_label::ConfiguredTargetLabel;
+    use parking_lot::Mutex;
 
     use super::EnvironmentVariable;
     use crate::local_resource_state::LocalResource;
+    use crate::local_resource_state::LocalResourceBatch;
+    use crate::local_resource_state::LocalResourceCommand;
+    use crate::local_resource_state::LocalResourcePoolOptions;
+    use crate::local_resource_state::LocalResourceRunner;
     use crate::local_resource_state::LocalResourceState;
 
-    #[tokio::test]
-    async fn test_canary() -> anyhow::Result<()> {
+    fn resource(value: &str) -> LocalResource {
+        LocalResource(vec![EnvironmentVariable {
+            key: "RESOURCE".to_owned(),
+            value: value.to_owned(),
+        }])
+    }
+
+    fn command(name: &str) -> LocalResourceCommand {
+        LocalResourceCommand {
+            argv: vec![name.to_owned()],
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Records the commands run, and fails the health check of resources named `unhealthy*`.
+    #[derive(Default)]
+    struct TestRunner {
+        fail_setup: bool,
+        setups: Mutex<u32>,
+        runs: Mutex<Vec<(String, String)>>,
+        releases: Mutex<Vec<Vec<String>>>,
+    }
+
+    impl TestRunner {
+        fn runs(&self, command: &str) -> Vec<String> {
+            self.runs
+                .lock()
+                .iter()
+                .filter(|(c, _)| c == command)
+                .map(|(_, r)| r.clone())
+                .collect()
+        }
+    }
+
+    #[async_trait]
+    impl LocalResourceRunner for TestRunner {
+        async fn setup(&self) -> anyhow::Result<LocalResourceBatch> {
+            if self.fail_setup {
+                return Err(anyhow::anyhow!("setup failed"));
+            }
+            let mut setups = self.setups.lock();
+            *setups += 1;
+            Ok(LocalResourceBatch {
+                owning_pid: Some(*setups as i32),
+                resources: vec![
+                    resource(&format!("setup{}-a", *setups)),
+                    resource(&format!("setup{}-b", *setups)),
+                ],
+            })
+        }
+
+        async fn run(
+            &self,
+            command: &LocalResourceCommand,
+            resource: &LocalResource,
+        ) -> anyhow::Result<()> {
+            let value = resource.0[0].value.clone();
+            self.runs
+                .lock()
+                .push((command.argv[0].clone(), value.clone()));
+            if command.argv[0] == "health_check" && value.starts_with("unhealthy") {
+                return Err(anyhow::anyhow!("unhealthy"));
+            }
+            Ok(())
+        }
+
+        fn release(&self, batch: &LocalResourceBatch) {
+            self.releases.lock().push(
+                batch
+                    .resources
+                    .iter()
+                    .map(|r| r.0[0].value.clone())
+                    .collect(),
+            );
+        }
+    }
+
+    fn pool(
+        resources: &[&str],
+        options: LocalResourcePoolOptions,
+        runner: &Arc<TestRunner>,
+    ) -> LocalResourceState {
         let target =
             ConfiguredTargetLabel::testing_parse("foo//bar:baz", ConfigurationData::testing_new());
-        let specs = vec![
-            LocalResource(vec![EnvironmentVariable {
-                key: "FOO".to_owned(),
-                value: "foo".to_owned(),
-            }]),
-            LocalResource(vec![EnvironmentVariable {
-                key: "BAR".to_owned(),
-                value: "bar".to_owned(),
-            }]),
-        ];
-
-        let state = LocalResourceState::new(target, Some(0), specs);
+        let batch = LocalResourceBatch {
+            owning_pid: Some(0),
+            resources: resources.iter().copied().map(resource).collect(),
+        };
+        LocalResourceState::new(target, batch, options, runner.clone())
+    }
+
+    #[tokio::test]
+    async fn test_canary() -> anyhow::Result<()> {
+        let runner = Arc::new(TestRunner::default());
+        let state = pool(&["foo", "bar"], Default::default(), &runner);
         let handle = tokio::spawn(async move {
             {
-                let _holder1 = state.acquire_resource().await;
-                let _holder2 = state.acquire_resource().await;
+                let _holder1 = state.acquire_resource().await?;
+                let _holder2 = state.acquire_resource().await?;
             }
             for _ in 0..10 {
-                let _x = state.acquire_resource().await;
+                let _x = state.acquire_resource().await?;
             }
+            anyhow::Ok(())
+        });
+        handle.await??;
+        assert_eq!(*runner.setups.lock(), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_max_uses() -> anyhow::Result<()> {
+        let runner = Arc::new(TestRunner::default());
+        let options = LocalResourcePoolOptions {
+            teardown: Some(command("teardown")),
+            max_uses: Some(2),
+            ..Default::default()
+        };
+        let state = pool(&["foo", "bar"], options, &runner);
+        // More than 2 resources × 2 uses, so the retired resources are replaced twice.
+        let mut acquired = Vec::new();
+        for _ in 0..10 {
+            let holder = state.acquire_resource().await?;
+            acquired.push(holder.as_ref().0[0].value.clone());
+        }
+        assert_eq!(
+            acquired,
+            vec![
+                "foo", "bar", "foo", "bar", "setup1-a", "setup1-b", "setup1-a", "setup1-b",
+                "setup2-a", "setup2-b"
+            ]
+        );
+        assert_eq!(*runner.setups.lock(), 2);
+        assert_eq!(
+            runner.runs("teardown"),
+            vec!["foo", "bar", "setup1-a", "setup1-b"]
+        );
+        assert_eq!(state.owning_pids(), vec![0, 1, 2]);
+        assert_eq!(
+            state.resources(),
+            vec![resource("setup2-a"), resource("setup2-b")]
+        );
+        // Batches are released once all their resources are torn down.
+        assert_eq!(
+            *runner.releases.lock(),
+            vec![vec!["foo", "bar"], vec!["setup1-a", "setup1-b"]]
+        );
+
+        // Releasing the pool tears down what was not torn down yet.
+        state.teardown().await?;
+        assert_eq!(
+            runner.runs("teardown"),
+            vec!["foo", "bar", "setup1-a", "setup1-b", "setup2-a", "setup2-b"]
+        );
+        assert_eq!(runner.releases.lock().len(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failed_health_check() -> anyhow::Result<()> {
+        let runner = Arc::new(TestRunner::default());
+        let options = LocalResourcePoolOptions {
+            health_check: Some(command("health_check")),
+            teardown: Some(command("teardown")),
+            ..Default::default()
+        };
+        let state = pool(&["unhealthy", "foo"], options, &runner);
+        for _ in 0..3 {
+            let holder = state.acquire_resource().await?;
+            assert_eq!(holder.as_ref(), &resource("foo"));
+        }
+        assert_eq!(runner.runs("teardown"), vec!["unhealthy"]);
+        assert_eq!(state.resources(), vec![resource("foo")]);
+        assert_eq!(*runner.setups.lock(), 0);
+        // The batch still has a resource which was not torn down.
+        assert!(runner.releases.lock().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_all_health_checks_failed() -> anyhow::Result<()> {
+        let runner = Arc::new(TestRunner::default());
+        let options = LocalResourcePoolOptions {
+            health_check: Some(command("health_check")),
+            ..Default::default()
+        };
+        let state = pool(&["unhealthy1", "unhealthy2"], options, &runner);
+        let holder = state.acquire_resource().await?;
+        assert_eq!(holder.as_ref(), &resource("setup1-a"));
+        assert_eq!(*runner.setups.lock(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failed_setup() -> anyhow::Result<()> {
+        let runner = Arc::new(TestRunner {
+            fail_setup: true,
+            ..Default::default()
         });
-        handle.await?;
+        let options = LocalResourcePoolOptions {
+            max_uses: Some(1),
+            ..Default::default()
+        };
+        let state = pool(&["foo"], options, &runner);
+        drop(state.acquire_resource().await?);
+        let error = format!("{:#}", state.acquire_resource().await.err().unwrap());
+        assert!(error.contains("Failed to replace"), "{}", error);
+        assert!(error.contains("setup failed"), "{}", error);
+        // Everybody waiting for a resource gets the error, not just the first one.
+        assert!(state.acquire_resource().await.is_err());
         Ok(())
     }
 }
@@ -10,3 +10,4 @@
 pub mod execution;
 pub mod execution_platforms;
 pub mod executor_config;
+pub mod timeout_escalation;
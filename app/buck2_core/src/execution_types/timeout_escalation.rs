@@ -0,0 +1,77 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! What to do with a command which exceeds its timeout before it is killed, shared by the
+//! test runners, the executors and the forkserver.
+
+use std::time::Duration;
+
+use crate::fs::paths::abs_norm_path::AbsNormPathBuf;
+
+/// How to collect the stacks of a command that is still running after its timeout grace period.
+///
+/// `P` is the file `gdb` writes the stacks to. Test runners ask for a `gdb` stack dump with
+/// `()`, and the orchestrator picks the file.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum StackDump<P = AbsNormPathBuf> {
+    /// Send SIGQUIT, on which e.g. the JVM and Go print their stacks to the command's output.
+    Sigquit,
+    /// Attach gdb and write the stacks of all threads to a file.
+    Gdb(P),
+}
+
+/// What to do with a command that exceeds its timeout before it is killed.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct TimeoutEscalation<P = AbsNormPathBuf> {
+    /// How long to wait for the command to exit after SIGTERM.
+    pub grace_period: Duration,
+    pub stack_dump: Option<StackDump<P>>,
+}
+
+impl<P> TimeoutEscalation<P> {
+    /// Change the file of a `gdb` stack dump. Returning `None` skips the stack dump.
+    pub fn map_gdb_output<Q>(self, f: impl FnOnce(P) -> Option<Q>) -> TimeoutEscalation<Q> {
+        TimeoutEscalation {
+            grace_period: self.grace_period,
+            stack_dump: match self.stack_dump {
+                None => None,
+                Some(StackDump::Sigquit) => Some(StackDump::Sigquit),
+                Some(StackDump::Gdb(output)) => f(output).map(StackDump::Gdb),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::execution_types::timeout_escalation::StackDump;
+    use crate::execution_types::timeout_escalation::TimeoutEscalation;
+
+    #[test]
+    fn test_map_gdb_output() {
+        let escalation = |stack_dump| TimeoutEscalation {
+            grace_period: Duration::from_secs(1),
+            stack_dump,
+        };
+        assert_eq!(
+            escalation(Some(StackDump::Gdb("out"))),
+            escalation(Some(StackDump::Gdb(()))).map_gdb_output(|()| Some("out"))
+        );
+        assert_eq!(
+            escalation(None::<StackDump<&str>>),
+            escalation(Some(StackDump::Gdb(()))).map_gdb_output(|()| None)
+        );
+        assert_eq!(
+            escalation(Some(StackDump::Sigquit)),
+            escalation(Some(StackDump::<()>::Sigquit)).map_gdb_output(|()| Some("out"))
+        );
+    }
+}
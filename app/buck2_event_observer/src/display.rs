@@ -510,6 +510,7 @@ pub fn format_test_result(test_result: &buck2_data::TestResult) -> anyhow::Resul
         status,
         duration,
         details,
+        cached,
         ..
     } = test_result;
     let status = TestStatus::try_from(*status)?;
@@ -532,6 +533,7 @@ pub fn format_test_result(test_result: &buck2_data::TestResult) -> anyhow::Resul
         TestStatus::UNKNOWN => Span::new_styled("? Unknown".to_owned().cyan()),
         TestStatus::RERUN => Span::new_styled("↻ Rerun".to_owned().cyan()),
         TestStatus::LISTING_FAILED => Span::new_styled("⚠ Listing failed".to_owned().red()),
+        TestStatus::FLAKY => Span::new_styled("≈ Flaky".to_owned().yellow()),
     }?;
     let mut base = Line::from_iter([prefix, Span::new_unstyled(format!(": {}", name,))?]);
     if let Some(duration) = duration {
@@ -544,6 +546,9 @@ pub fn format_test_result(test_result: &buck2_data::TestResult) -> anyhow::Resul
             ))?);
         }
     }
+    if *cached {
+        base.push(Span::new_styled(" [cached]".to_owned().dark_grey())?);
+    }
     // If a test has details, we always show them. It's the test runner's
     // responsibility to withhold details when these are not relevant.
     // For instance, tpx will always withhold details of passing tests
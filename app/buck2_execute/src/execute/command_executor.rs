@@ -311,3 +311,68 @@ fn re_create_action(
             .expect("We did put a platform a few lines up"),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execute::action_digest::ActionDigest;
+
+    fn action_digest(
+        args: &[&str],
+        env: &[(&str, &str)],
+        inputs: &str,
+        timeout: Option<Duration>,
+    ) -> ActionDigest {
+        let digest_config = DigestConfig::testing_default();
+        re_create_action(
+            args.iter().map(|a| (*a).to_owned()).collect(),
+            &[],
+            None,
+            &env.iter()
+                .map(|(k, v)| ((*k).to_owned(), (*v).to_owned()))
+                .collect::<SortedVectorMap<_, _>>(),
+            &TrackedFileDigest::from_content(inputs.as_bytes(), digest_config.cas_digest_config()),
+            [],
+            timeout,
+            RE::Platform::default(),
+            false,
+            digest_config,
+            OutputPathsBehavior::default(),
+            false,
+        )
+        .unwrap()
+        .digest()
+    }
+
+    /// Test results are cached by action digest, so it must change with anything that can
+    /// change the result of the command.
+    #[test]
+    fn test_action_digest_covers_command_env_and_inputs() {
+        let digest = action_digest(&["test"], &[("A", "1")], "inputs", None);
+        assert_eq!(
+            digest,
+            action_digest(&["test"], &[("A", "1")], "inputs", None)
+        );
+        assert_ne!(
+            digest,
+            action_digest(&["test", "--flag"], &[("A", "1")], "inputs", None)
+        );
+        assert_ne!(
+            digest,
+            action_digest(&["test"], &[("A", "2")], "inputs", None)
+        );
+        assert_ne!(
+            digest,
+            action_digest(&["test"], &[("A", "1")], "changed inputs", None)
+        );
+        assert_ne!(
+            digest,
+            action_digest(
+                &["test"],
+                &[("A", "1")],
+                "inputs",
+                Some(Duration::from_secs(1))
+            )
+        );
+    }
+}
@@ -17,6 +17,7 @@ use buck2_common::local_resource_state::LocalResourceState;
 use buck2_core::directory::DirectoryEntry;
 use buck2_core::directory::DirectoryIterator;
 use buck2_core::directory::FingerprintedDirectory;
+use buck2_core::execution_types::timeout_escalation::TimeoutEscalation;
 use buck2_core::fs::artifact_path_resolver::ArtifactFs;
 use buck2_core::fs::buck_out_path::BuckOutPath;
 use buck2_core::fs::buck_out_path::BuckOutScratchPath;
@@ -293,6 +294,9 @@ pub struct CommandExecutionRequest {
     paths: CommandExecutionPaths,
     env: SortedVectorMap<String, String>,
     timeout: Option<Duration>,
+    /// Only supported for local execution with the forkserver. Otherwise, commands that time out
+    /// are killed right away.
+    timeout_escalation: Option<TimeoutEscalation>,
     executor_preference: ExecutorPreference,
     host_sharing_requirements: HostSharingRequirements,
     // Used to disable the low pass filter for concurrent local actions. Enabled by default
@@ -336,6 +340,7 @@ impl CommandExecutionRequest {
             paths,
             env,
             timeout: None,
+            timeout_escalation: None,
             executor_preference: ExecutorPreference::Default,
             host_sharing_requirements: HostSharingRequirements::default(),
             low_pass_filter: true,
@@ -361,6 +366,11 @@ impl CommandExecutionRequest {
         self
     }
 
+    pub fn with_timeout_escalation(mut self, timeout_escalation: TimeoutEscalation) -> Self {
+        self.timeout_escalation = Some(timeout_escalation);
+        self
+    }
+
     pub fn with_executor_preference(mut self, executor_preference: ExecutorPreference) -> Self {
         self.executor_preference = executor_preference;
         self
@@ -456,6 +466,10 @@ impl CommandExecutionRequest {
         self.timeout
     }
 
+    pub fn timeout_escalation(&self) -> Option<&TimeoutEscalation> {
+        self.timeout_escalation.as_ref()
+    }
+
     pub fn executor_preference(&self) -> ExecutorPreference {
         self.executor_preference
     }
@@ -23,6 +23,7 @@ use buck2_common::file_ops::FileDigestConfig;
 use buck2_common::liveliness_observer::LivelinessObserver;
 use buck2_common::liveliness_observer::LivelinessObserverExt;
 use buck2_common::local_resource_state::LocalResourceHolder;
+use buck2_core::execution_types::timeout_escalation::TimeoutEscalation;
 use buck2_core::fs::artifact_path_resolver::ArtifactFs;
 use buck2_core::fs::fs_util;
 use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
@@ -63,7 +64,7 @@ use buck2_execute::knobs::ExecutorGlobalKnobs;
 use buck2_execute::materialize::materializer::MaterializationError;
 use buck2_execute::materialize::materializer::Materializer;
 use buck2_forkserver::client::ForkserverClient;
-use buck2_forkserver::run::gather_output;
+use buck2_forkserver::run::gather_output_with_timeout_escalation;
 use buck2_forkserver::run::maybe_absolutize_exe;
 use buck2_forkserver::run::timeout_into_cancellation;
 use buck2_forkserver::run::GatherOutputStatus;
@@ -142,6 +143,7 @@ impl LocalExecutor {
         env: impl IntoIterator<Item = (impl AsRef<OsStr> + Send, impl AsRef<OsStr> + Send)> + Send + 'a,
         working_directory: Option<&'a ProjectRelativePath>,
         timeout: Option<Duration>,
+        timeout_escalation: Option<&'a TimeoutEscalation>,
         env_inheritance: Option<&'a EnvironmentInheritance>,
         liveliness_observer: impl LivelinessObserver + 'static,
         disable_miniperf: bool,
@@ -166,6 +168,7 @@ impl LocalExecutor {
                             env,
                             &working_directory,
                             timeout,
+                            timeout_escalation,
                             env_inheritance,
                             liveliness_observer,
                             self.knobs.enable_miniperf && !disable_miniperf,
@@ -175,7 +178,7 @@ impl LocalExecutor {
 
                     #[cfg(not(unix))]
                     {
-                        let _unused = (forkserver, disable_miniperf);
+                        let _unused = (forkserver, disable_miniperf, timeout_escalation);
                         Err(anyhow::anyhow!("Forkserver is not supported off-UNIX"))
                     }
                 }
@@ -200,7 +203,12 @@ impl LocalExecutor {
                     let cancellation =
                         select(timeout.boxed(), alive.boxed()).map(|r| r.factor_first().0);
 
-                    gather_output(cmd, cancellation).await
+                    gather_output_with_timeout_escalation(
+                        cmd,
+                        cancellation,
+                        timeout_escalation.cloned(),
+                    )
+                    .await
                 }
                 .with_context(|| format!("Failed to gather output from command: {}", exe)),
             }
@@ -433,6 +441,7 @@ impl LocalExecutor {
                         env,
                         request.working_directory(),
                         request.timeout(),
+                        request.timeout_escalation(),
                         request.local_environment_inheritance(),
                         liveliness_observer,
                         request.disable_miniperf(),
@@ -744,12 +753,16 @@ impl PreparedCommandExecutor for LocalExecutor {
                 // Test 1 acquires resource B and test 2 acquires resource A.
                 // Now test 1 is waiting on resource B and test 2 is waiting on resource A.
                 for r in request.required_local_resources() {
-                    holders.push(r.acquire_resource().await);
+                    holders.push(r.acquire_resource().await?);
                 }
-                holders
+                anyhow::Ok(holders)
             },
         )
         .await;
+        let local_resource_holders = match local_resource_holders {
+            Ok(holders) => holders,
+            Err(e) => return manager.error("acquire_local_resource", e),
+        };
 
         let _worker_permit = self.acquire_worker_permit(request).await;
 
@@ -1075,6 +1088,7 @@ impl EnvironmentBuilder for Command {
 mod unix {
     use std::os::unix::ffi::OsStrExt;
 
+    use buck2_core::execution_types::timeout_escalation::StackDump;
     use buck2_execute::execute::environment_inheritance::EnvironmentInheritance;
 
     use super::*;
@@ -1086,6 +1100,7 @@ mod unix {
         env: impl IntoIterator<Item = (impl AsRef<OsStr>, impl AsRef<OsStr>)>,
         working_directory: &AbsPath,
         command_timeout: Option<Duration>,
+        timeout_escalation: Option<&TimeoutEscalation>,
         env_inheritance: Option<&EnvironmentInheritance>,
         liveliness_observer: impl LivelinessObserver + 'static,
         enable_miniperf: bool,
@@ -1106,6 +1121,9 @@ mod unix {
             enable_miniperf,
             std_redirects: None,
             graceful_shutdown_timeout_s: None,
+            timeout_escalation: timeout_escalation
+                .map(timeout_escalation_to_proto)
+                .transpose()?,
         };
         apply_local_execution_environment(&mut req, working_directory, env, env_inheritance);
         forkserver
@@ -1113,6 +1131,26 @@ mod unix {
             .await
     }
 
+    fn timeout_escalation_to_proto(
+        escalation: &TimeoutEscalation,
+    ) -> anyhow::Result<buck2_forkserver_proto::TimeoutEscalation> {
+        use buck2_forkserver_proto::timeout_escalation;
+
+        Ok(buck2_forkserver_proto::TimeoutEscalation {
+            grace_period: Some(escalation.grace_period.try_into()?),
+            stack_dump: escalation.stack_dump.as_ref().map(|d| match d {
+                StackDump::Sigquit => {
+                    timeout_escalation::StackDump::Sigquit(timeout_escalation::Sigquit {})
+                }
+                StackDump::Gdb(output) => {
+                    timeout_escalation::StackDump::Gdb(timeout_escalation::Gdb {
+                        output: output.as_os_str().as_bytes().to_vec(),
+                    })
+                }
+            }),
+        })
+    }
+
     trait CommandRequestExt {
         fn push_env_directive<D>(&mut self, directive: D)
         where
@@ -1175,6 +1213,7 @@ mod tests {
     use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
     use buck2_execute::execute::blocking::testing::DummyBlockingExecutor;
     use buck2_execute::materialize::nodisk::NoDiskMaterializer;
+    use buck2_forkserver::run::gather_output;
     use host_sharing::HostSharingStrategy;
 
     use super::*;
@@ -1308,6 +1347,7 @@ mod tests {
                 None,
                 None,
                 None,
+                None,
                 NoopLivelinessObserver::create(),
                 false,
             )
@@ -1343,6 +1383,7 @@ mod tests {
                 &HashMap::<String, String>::default(),
                 None,
                 None,
+                None,
                 Some(&EnvironmentInheritance::empty()),
                 NoopLivelinessObserver::create(),
                 false,
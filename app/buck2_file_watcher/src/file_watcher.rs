@@ -32,6 +32,13 @@ pub trait FileWatcher: Allocative + Send + Sync + 'static {
         &self,
         dice: DiceTransactionUpdater,
     ) -> anyhow::Result<(DiceTransactionUpdater, Mergebase)>;
+
+    /// Wait until files have changed since the last `sync`. Used by `--watch`.
+    async fn wait_for_changes(&self) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!(
+            "`--watch` requires `buck2.file_watcher = notify` or `watchman`"
+        ))
+    }
 }
 
 impl dyn FileWatcher {
@@ -33,6 +33,7 @@ use notify::EventKind;
 use notify::RecommendedWatcher;
 use notify::Watcher;
 use starlark_map::ordered_set::OrderedSet;
+use tokio::sync::Notify;
 use tracing::info;
 
 use crate::file_watcher::FileWatcher;
@@ -181,6 +182,9 @@ pub struct NotifyFileWatcher {
     #[allocative(skip)]
     watcher: RecommendedWatcher,
     data: Arc<Mutex<anyhow::Result<NotifyFileData>>>,
+    /// Signalled whenever `data` gains events (or an error) that the next `sync` will pick up.
+    #[allocative(skip)]
+    changed: Arc<Notify>,
 }
 
 impl NotifyFileWatcher {
@@ -191,6 +195,8 @@ impl NotifyFileWatcher {
     ) -> anyhow::Result<Self> {
         let data = Arc::new(Mutex::new(Ok(NotifyFileData::new())));
         let data2 = data.dupe();
+        let changed = Arc::new(Notify::new());
+        let changed2 = changed.dupe();
         let root2 = root.dupe();
         let mut watcher = notify::recommended_watcher(move |event| {
             let mut guard = data2.lock().unwrap();
@@ -199,9 +205,24 @@ impl NotifyFileWatcher {
                     *guard = Err(e);
                 }
             }
+            if Self::has_changes(&guard) {
+                changed2.notify_one();
+            }
         })?;
         watcher.watch(root.root().as_path(), notify::RecursiveMode::Recursive)?;
-        Ok(Self { watcher, data })
+        Ok(Self {
+            watcher,
+            data,
+            changed,
+        })
+    }
+
+    fn has_changes(data: &anyhow::Result<NotifyFileData>) -> bool {
+        match data {
+            Ok(data) => !data.events.is_empty(),
+            // Report errors on the next sync.
+            Err(_) => true,
+        }
     }
 
     fn sync2(
@@ -239,4 +260,73 @@ impl FileWatcher for NotifyFileWatcher {
         )
         .await
     }
+
+    async fn wait_for_changes(&self) -> anyhow::Result<()> {
+        loop {
+            if Self::has_changes(&self.data.lock().unwrap()) {
+                return Ok(());
+            }
+            // `notify_one` stores a permit if nobody is waiting, so a change between the check
+            // above and this wait is not lost.
+            self.changed.notified().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use buck2_common::ignores::ignore_set::IgnoreSet;
+    use buck2_core::cells::cell_root_path::CellRootPathBuf;
+    use buck2_core::cells::name::CellName;
+    use buck2_core::cells::CellResolver;
+    use buck2_core::fs::fs_util;
+    use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+    use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+    use buck2_core::fs::project::ProjectRoot;
+
+    use crate::file_watcher::FileWatcher;
+    use crate::notify::NotifyFileWatcher;
+
+    #[tokio::test]
+    async fn test_wait_for_changes() -> anyhow::Result<()> {
+        let cell = CellName::testing_new("root");
+        let cell_resolver =
+            CellResolver::testing_with_name_and_path(cell, CellRootPathBuf::testing_new(""));
+        let tempdir = tempfile::tempdir()?;
+        let root_path = fs_util::canonicalize(AbsNormPathBuf::new(tempdir.path().to_owned())?)?;
+        let proj_root = ProjectRoot::new(root_path)?;
+        let root = proj_root.root().to_owned();
+        fs_util::create_dir_all(root.join(ForwardRelativePath::unchecked_new("buck-out")))?;
+        fs_util::create_dir_all(root.join(ForwardRelativePath::unchecked_new("ignored")))?;
+
+        let watcher = NotifyFileWatcher::new(
+            &proj_root,
+            cell_resolver,
+            HashMap::from([(cell, IgnoreSet::from_ignore_spec("ignored", true)?)]),
+        )?;
+        let wait = || tokio::time::timeout(Duration::from_secs(1), watcher.wait_for_changes());
+
+        // Nothing changed yet.
+        assert!(wait().await.is_err());
+
+        // Neither our own outputs nor ignored files count as changes.
+        fs_util::write(
+            root.join(ForwardRelativePath::unchecked_new("buck-out/file")),
+            "",
+        )?;
+        fs_util::write(
+            root.join(ForwardRelativePath::unchecked_new("ignored/file")),
+            "",
+        )?;
+        assert!(wait().await.is_err());
+
+        fs_util::write(root.join(ForwardRelativePath::unchecked_new("file")), "")?;
+        wait().await??;
+        // The change stays pending until the next sync, so waiting again returns at once.
+        wait().await??;
+        Ok(())
+    }
 }
@@ -212,9 +212,16 @@ pub trait SyncableQueryProcessor: Send + Sync {
     ) -> anyhow::Result<(Self::Output, Self::Payload)>;
 }
 
+/// How often `wait_for_changes` asks watchman for new changes. Watchman queries are cheap, but
+/// this runs for as long as a `--watch` command is idle, so don't poll more often than a user
+/// would notice.
+const WAIT_FOR_CHANGES_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
 /// commands to be sent to the SyncableQueryHandler.
 enum SyncableQueryCommand<T, P> {
     Sync(P, oneshot::Sender<anyhow::Result<(T, P)>>),
+    /// Reply with the client and a query for the changes since the last sync.
+    SinceLastSync(oneshot::Sender<anyhow::Result<(WatchmanClient, QueryRequestCommon)>>),
 }
 
 /// A SyncableQuery is similar to a subscription. When created, it accepts a query expression
@@ -284,6 +291,10 @@ where
                     // job. That's fine.
                     let _ignore = sync_tx.send(res);
                 }
+                Some(SyncableQueryCommand::SinceLastSync(tx)) => {
+                    let res = self.since_last_sync(&mut client).await;
+                    let _ignore = tx.send(res);
+                }
                 None => {
                     // This indicates the controlling SyncableQuery has been dropped.
                     return;
@@ -292,6 +303,21 @@ where
         }
     }
 
+    /// A query for the changes since the last sync. If there is no connection, this reconnects,
+    /// which makes the query (like the next sync) return a fresh instance.
+    async fn since_last_sync(
+        &mut self,
+        client: &mut Option<WatchmanClient>,
+    ) -> anyhow::Result<(WatchmanClient, QueryRequestCommon)> {
+        if client.is_none() {
+            self.reconnect(client).await?;
+        }
+        let client = client.as_ref().context("No Watchman connection")?;
+        let mut query = self.query.clone();
+        query.since = Some(Clock::Spec(self.last_clock.clone()));
+        Ok((client.dupe(), query))
+    }
+
     /// sync() will send a since query to watchman and invoke the processor
     /// with either the received events or a fresh instance call.
     async fn sync(
@@ -474,6 +500,45 @@ where
         }
     }
 
+    /// Wait until watchman has seen a change since the last `sync()` for which `filter` returns
+    /// true, or until the next `sync()` would be a fresh instance.
+    ///
+    /// This polls outside of the handler's run loop, so that it does not hold up `sync()`.
+    pub async fn wait_for_changes(
+        &self,
+        filter: impl Fn(&WatchmanEvent) -> bool + Send,
+    ) -> anyhow::Result<()> {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.control_tx
+            .send(SyncableQueryCommand::SinceLastSync(tx))
+            .ok()
+            .context("SyncableQueryHandler has exited")?;
+        let (client, mut query) = rx
+            .await
+            .context("SyncableQueryHandler did not return a response for wait request")??;
+
+        loop {
+            let QueryResult {
+                is_fresh_instance,
+                files,
+                clock,
+                ..
+            } = client.query::<BuckQueryResult>(query.clone()).await?;
+            if is_fresh_instance
+                || files
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|f| f.into_event())
+                    .any(|event| filter(&event))
+            {
+                return Ok(());
+            }
+            // Only filtered out changes so far, which we don't need to see again.
+            query.since = Some(Clock::Spec(unpack_clock(clock).1));
+            tokio::time::sleep(WAIT_FOR_CHANGES_POLL_INTERVAL).await;
+        }
+    }
+
     pub fn new(
         connector: Connector,
         path: impl AsRef<Path>,
@@ -17,6 +17,7 @@ use async_trait::async_trait;
 use buck2_common::dice::file_ops::FileChangeTracker;
 use buck2_common::ignores::ignore_set::IgnoreSet;
 use buck2_common::legacy_configs::LegacyBuckConfig;
+use buck2_core::cells::cell_path::CellPath;
 use buck2_core::cells::name::CellName;
 use buck2_core::cells::CellResolver;
 use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
@@ -25,6 +26,7 @@ use buck2_core::rollout_percentage::RolloutPercentage;
 use buck2_events::dispatch::span_async;
 use buck2_util::process::async_background_command;
 use dice::DiceTransactionUpdater;
+use dupe::Dupe;
 use tracing::info;
 use tracing::warn;
 use watchman_client::expr::Expr;
@@ -42,7 +44,7 @@ use crate::watchman::core::WatchmanKind;
 
 struct WatchmanQueryProcessor {
     cells: CellResolver,
-    ignore_specs: HashMap<CellName, IgnoreSet>,
+    ignore_specs: Arc<HashMap<CellName, IgnoreSet>>,
     retain_dep_files_on_watchman_fresh_instance: bool,
     report_global_rev: bool,
     last_mergebase: Option<String>,
@@ -109,11 +111,7 @@ impl WatchmanQueryProcessor {
     ) -> anyhow::Result<()> {
         let cell_path = self.cells.get_cell_path(path)?;
 
-        let ignore = self
-            .ignore_specs
-            .get(&cell_path.cell())
-            .expect("unexpected cell name mismatch")
-            .is_match(cell_path.path());
+        let ignore = is_ignored(&self.ignore_specs, &cell_path);
 
         info!("Watchman: {:?} (ignore = {})", ev, ignore);
 
@@ -200,6 +198,13 @@ impl WatchmanQueryProcessor {
     }
 }
 
+fn is_ignored(ignore_specs: &HashMap<CellName, IgnoreSet>, cell_path: &CellPath) -> bool {
+    ignore_specs
+        .get(&cell_path.cell())
+        .expect("unexpected cell name mismatch")
+        .is_match(cell_path.path())
+}
+
 fn find_first_valid_parent(mut path: &Path) -> Option<&ProjectRelativePath> {
     loop {
         path = path.parent()?;
@@ -309,6 +314,8 @@ impl SyncableQueryProcessor for WatchmanQueryProcessor {
 pub(crate) struct WatchmanFileWatcher {
     #[allocative(skip)]
     query: SyncableQuery<buck2_data::FileWatcherStats, DiceTransactionUpdater>,
+    cells: CellResolver,
+    ignore_specs: Arc<HashMap<CellName, IgnoreSet>>,
 }
 
 /// The watchman query is constructed once on daemon startup. It is an unfiltered watchman query
@@ -335,6 +342,7 @@ impl WatchmanFileWatcher {
             .parse::<bool>("buck2", "watchman_report_global_rev")?
             .unwrap_or(false);
 
+        let ignore_specs = Arc::new(ignore_specs);
         let query = SyncableQuery::new(
             Connector::new(),
             project_root,
@@ -344,8 +352,8 @@ impl WatchmanFileWatcher {
                 Expr::FileType(FileType::Symlink),
             ]),
             Box::new(WatchmanQueryProcessor {
-                cells,
-                ignore_specs,
+                cells: cells.dupe(),
+                ignore_specs: ignore_specs.dupe(),
                 retain_dep_files_on_watchman_fresh_instance,
                 report_global_rev,
                 last_mergebase: None,
@@ -354,7 +362,34 @@ impl WatchmanFileWatcher {
             watchman_merge_base,
         )?;
 
-        Ok(Self { query })
+        Ok(Self {
+            query,
+            cells,
+            ignore_specs,
+        })
+    }
+
+    /// Whether the next `sync` would process this event, rather than skip or ignore it.
+    fn is_relevant(&self, event: &WatchmanEvent) -> bool {
+        let path = match ProjectRelativePath::new(&event.path) {
+            Ok(_)
+                if matches!(
+                    (&event.kind, &event.event),
+                    (WatchmanKind::Directory, WatchmanEventType::Modify)
+                ) =>
+            {
+                // Skipped by `process_one_change`.
+                return false;
+            }
+            Ok(path) => Some(path),
+            // Invalid paths invalidate their first valid parent directory.
+            Err(_) => find_first_valid_parent(&event.path),
+        };
+        match path.map(|path| self.cells.get_cell_path(path)) {
+            Some(Ok(cell_path)) => !is_ignored(&self.ignore_specs, &cell_path),
+            // Let the next `sync` report the error.
+            _ => true,
+        }
     }
 }
 
@@ -381,4 +416,10 @@ impl FileWatcher for WatchmanFileWatcher {
         )
         .await
     }
+
+    async fn wait_for_changes(&self) -> anyhow::Result<()> {
+        self.query
+            .wait_for_changes(|event| self.is_relevant(event))
+            .await
+    }
 }
@@ -223,3 +223,65 @@ async fn test_syncable_query() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_wait_for_changes() -> anyhow::Result<()> {
+    // This test doesn't work unless Watchman is working, so let's
+    // over-approximate that as fbcode_build for now.
+    if !cfg!(fbcode_build) {
+        return Ok(());
+    }
+
+    let tempdir = tempfile::tempdir()?;
+
+    let root = tempdir.path().join("root");
+    let watchman_dir = tempdir.path().join("watchman");
+    fs::create_dir(&watchman_dir)?;
+    fs::create_dir(&root)?;
+
+    let mut watchman_instance = spawn_watchman(&watchman_dir).await?;
+
+    let connector = Connector::default().unix_domain_socket(&watchman_instance.sock);
+
+    let watchman_query = SyncableQuery::new(
+        connector,
+        &root,
+        Expr::Any(vec![Expr::FileType(FileType::Regular)]),
+        Box::new(TestQueryProcessor),
+        None,
+    )?;
+
+    let wait = || {
+        tokio::time::timeout(
+            Duration::from_secs(1),
+            watchman_query.wait_for_changes(|e| !e.path.starts_with("ignored")),
+        )
+    };
+
+    // Before the first sync, the next sync is a fresh instance.
+    wait().await??;
+    assert_eq!(watchman_query.sync(()).await?.0, Out::FreshInstance);
+
+    // No changes since the last sync.
+    assert_matches!(wait().await, Err(..));
+
+    // Changes the filter rejects don't count.
+    File::create(root.join("ignored"))?;
+    assert_matches!(wait().await, Err(..));
+
+    File::create(root.join("test"))?;
+    wait().await??;
+    // The filter only decides when to stop waiting: the sync still sees every change.
+    let mut files = match watchman_query.sync(()).await?.0 {
+        Out::Files(files) => files,
+        out => panic!("Expected files, got {:?}", out),
+    };
+    files.sort();
+    assert_eq!(files, vec!["ignored".to_owned(), "test".to_owned()]);
+    assert_matches!(wait().await, Err(..));
+
+    // Clean up
+    watchman_instance.shutdown().await?;
+
+    Ok(())
+}
@@ -22,6 +22,7 @@ use std::time::Duration;
 
 use anyhow::Context as _;
 use async_trait::async_trait;
+use buck2_core::execution_types::timeout_escalation::TimeoutEscalation;
 use buck2_core::fs::fs_util;
 use buck2_core::fs::paths::abs_path::AbsPath;
 use bytes::Bytes;
@@ -243,7 +244,7 @@ where
             Outcome::Finished(status) => decoder.decode_status(status).await?.into(),
             Outcome::Cancelled(res) => {
                 kill_process
-                    .kill(&mut process_group)
+                    .kill(&mut process_group, &res)
                     .await
                     .context("Failed to terminate child after timeout")?;
 
@@ -295,6 +296,19 @@ pub async fn gather_output<T>(
     cmd: Command,
     cancellation: T,
 ) -> anyhow::Result<(GatherOutputStatus, Vec<u8>, Vec<u8>)>
+where
+    T: Future<Output = anyhow::Result<GatherOutputStatus>> + Send,
+{
+    gather_output_with_timeout_escalation(cmd, cancellation, None).await
+}
+
+/// Like `gather_output`, but a command which times out is wound down with `timeout_escalation`
+/// instead of being killed outright.
+pub async fn gather_output_with_timeout_escalation<T>(
+    cmd: Command,
+    cancellation: T,
+    timeout_escalation: Option<TimeoutEscalation>,
+) -> anyhow::Result<(GatherOutputStatus, Vec<u8>, Vec<u8>)>
 where
     T: Future<Output = anyhow::Result<GatherOutputStatus>> + Send,
 {
@@ -307,7 +321,10 @@ where
         process_details,
         cancellation,
         DefaultStatusDecoder,
-        DefaultKillProcess::default(),
+        DefaultKillProcess {
+            graceful_shutdown_timeout_s: None,
+            timeout_escalation,
+        },
         true,
     )?;
     decode_command_event_stream(stream).await
@@ -316,17 +333,27 @@ where
 /// Dependency injection for kill. We use this in testing.
 #[async_trait]
 pub(crate) trait KillProcess {
-    async fn kill(self, process: &mut ProcessGroup) -> anyhow::Result<()>;
+    async fn kill(
+        self,
+        process: &mut ProcessGroup,
+        reason: &GatherOutputStatus,
+    ) -> anyhow::Result<()>;
 }
 
 #[derive(Default)]
 pub(crate) struct DefaultKillProcess {
     pub graceful_shutdown_timeout_s: Option<u32>,
+    /// Used instead of a plain kill when the process timed out.
+    pub timeout_escalation: Option<TimeoutEscalation>,
 }
 
 #[async_trait]
 impl KillProcess for DefaultKillProcess {
-    async fn kill(self, process_group: &mut ProcessGroup) -> anyhow::Result<()> {
+    async fn kill(
+        self,
+        process_group: &mut ProcessGroup,
+        reason: &GatherOutputStatus,
+    ) -> anyhow::Result<()> {
         let pid = match process_group.id() {
             Some(pid) => pid,
             None => {
@@ -334,6 +361,12 @@ impl KillProcess for DefaultKillProcess {
                 return Ok(());
             }
         };
+        if let (GatherOutputStatus::TimedOut(..), Some(escalation)) =
+            (reason, &self.timeout_escalation)
+        {
+            tracing::info!("Terminating timed out process {}", pid);
+            return process_group.kill_after_timeout(escalation).await;
+        }
         tracing::info!("Killing process {}", pid);
         process_group.kill(self.graceful_shutdown_timeout_s).await
     }
@@ -494,6 +527,59 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_gather_output_timeout_escalation() -> anyhow::Result<()> {
+        let mut cmd = background_command("sh");
+        cmd.args([
+            "-c",
+            "trap '' TERM; echo hello; while true; do sleep 0.1; done",
+        ]);
+
+        let grace_period = Duration::from_secs(1);
+        let now = Instant::now();
+        let (status, stdout, _stderr) = gather_output_with_timeout_escalation(
+            cmd,
+            timeout_into_cancellation(Some(Duration::from_secs(1))),
+            Some(TimeoutEscalation {
+                grace_period,
+                stack_dump: None,
+            }),
+        )
+        .await?;
+        assert_matches!(status, GatherOutputStatus::TimedOut(..));
+        assert_eq!(str::from_utf8(&stdout)?.trim(), "hello");
+        // SIGTERM is ignored, so the command is only killed after the grace period.
+        assert!(now.elapsed() >= Duration::from_secs(1) + grace_period);
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_gather_output_cancelled_does_not_escalate() -> anyhow::Result<()> {
+        let mut cmd = background_command("sh");
+        cmd.args(["-c", "trap '' TERM; while true; do sleep 0.1; done"]);
+
+        let now = Instant::now();
+        let (status, _stdout, _stderr) = gather_output_with_timeout_escalation(
+            cmd,
+            async {
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                Ok(GatherOutputStatus::Cancelled)
+            },
+            Some(TimeoutEscalation {
+                grace_period: Duration::from_secs(60),
+                stack_dump: None,
+            }),
+        )
+        .await?;
+        assert_matches!(status, GatherOutputStatus::Cancelled);
+        assert!(now.elapsed() < Duration::from_secs(30));
+
+        Ok(())
+    }
+
     #[cfg(unix)]
     #[tokio::test]
     async fn test_spawn_retry_txt_busy() -> anyhow::Result<()> {
@@ -639,12 +725,18 @@ mod tests {
 
         #[async_trait]
         impl KillProcess for Kill {
-            async fn kill(self, process_group: &mut ProcessGroup) -> anyhow::Result<()> {
+            async fn kill(
+                self,
+                process_group: &mut ProcessGroup,
+                reason: &GatherOutputStatus,
+            ) -> anyhow::Result<()> {
                 *self.killed.lock().unwrap() = true;
 
                 // We still need to kill the process. On Windows in particular our test will hang
                 // if we do not.
-                DefaultKillProcess::default().kill(process_group).await
+                DefaultKillProcess::default()
+                    .kill(process_group, reason)
+                    .await
             }
         }
 
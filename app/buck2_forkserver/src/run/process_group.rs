@@ -90,6 +90,15 @@ impl ProcessGroup {
     ) -> anyhow::Result<()> {
         self.inner.kill(graceful_shutdown_timeout_s).await
     }
+
+    /// Terminates a process that timed out: SIGTERM, a grace period, an optional stack dump,
+    /// and finally a kill of the whole group.
+    pub(crate) async fn kill_after_timeout(
+        &mut self,
+        escalation: &TimeoutEscalation,
+    ) -> anyhow::Result<()> {
+        self.inner.kill_after_timeout(escalation).await
+    }
 }
 
 #[cfg(test)]
@@ -8,6 +8,7 @@
  */
 
 use std::os::unix::process::CommandExt;
+use std::path::Path;
 use std::process::Command as StdCommand;
 use std::process::ExitStatus;
 use std::process::Stdio;
@@ -15,6 +16,9 @@ use std::time::Duration;
 
 use anyhow::Context;
 use buck2_common::kill_util::try_terminate_process_gracefully;
+use buck2_core::execution_types::timeout_escalation::StackDump;
+use buck2_core::execution_types::timeout_escalation::TimeoutEscalation;
+use buck2_util::process::background_command;
 use nix::sys::signal;
 use nix::sys::signal::Signal;
 use nix::unistd::Pid;
@@ -24,6 +28,12 @@ use tokio::process::ChildStderr;
 use tokio::process::ChildStdout;
 use tokio::process::Command;
 
+/// How long the runtime gets to print its stacks after SIGQUIT.
+const SIGQUIT_DUMP_WAIT: Duration = Duration::from_secs(2);
+
+/// How long we let `gdb` spend attaching and collecting backtraces.
+const GDB_DUMP_TIMEOUT: Duration = Duration::from_secs(30);
+
 pub(crate) struct ProcessCommandImpl {
     inner: Command,
 }
@@ -95,4 +105,188 @@ impl ProcessGroupImpl {
                 .with_context(|| format!("Failed to kill process {}", pid))
         }
     }
+
+    pub(crate) async fn kill_after_timeout(
+        &mut self,
+        escalation: &TimeoutEscalation,
+    ) -> anyhow::Result<()> {
+        let pid: i32 = self
+            .inner
+            .id()
+            .and_then(|id| id.try_into().ok())
+            .context("PID does not fit a i32")?;
+        let pgid = Pid::from_raw(pid);
+
+        killpg_if_exists(pgid, Signal::SIGTERM)?;
+
+        let exited = tokio::time::timeout(escalation.grace_period, self.inner.wait())
+            .await
+            .is_ok();
+
+        if !exited {
+            match &escalation.stack_dump {
+                Some(StackDump::Sigquit) => {
+                    killpg_if_exists(pgid, Signal::SIGQUIT)?;
+                    let _ignored = tokio::time::timeout(SIGQUIT_DUMP_WAIT, self.inner.wait()).await;
+                }
+                Some(StackDump::Gdb(output)) => {
+                    if let Err(e) = gdb_stack_dump(pid, output.as_path()).await {
+                        tracing::warn!("Failed to collect a stack dump of {}: {:#}", pid, e);
+                    }
+                }
+                None => {}
+            }
+        }
+
+        // Even if the leader exited, anything it left behind in the group goes too.
+        killpg_if_exists(pgid, Signal::SIGKILL)
+    }
+}
+
+fn killpg_if_exists(pgid: Pid, signal: Signal) -> anyhow::Result<()> {
+    match signal::killpg(pgid, signal) {
+        Ok(()) | Err(nix::errno::Errno::ESRCH) => Ok(()),
+        Err(e) => Err(e).with_context(|| format!("Failed to send {} to process {}", signal, pgid)),
+    }
+}
+
+async fn gdb_stack_dump(pid: i32, output: &Path) -> anyhow::Result<()> {
+    let stdout = std::fs::File::create(output)
+        .with_context(|| format!("Failed to create `{}`", output.display()))?;
+    let stderr = stdout.try_clone()?;
+
+    let mut cmd = background_command("gdb");
+    cmd.args(["-batch", "-nx", "-ex", "thread apply all bt", "-p"])
+        .arg(pid.to_string())
+        .stdin(Stdio::null())
+        .stdout(stdout)
+        .stderr(stderr);
+    let mut cmd: Command = cmd.into();
+    cmd.kill_on_drop(true);
+
+    let status = tokio::time::timeout(GDB_DUMP_TIMEOUT, cmd.status())
+        .await
+        .context("Timed out running `gdb`")?
+        .context("Failed to run `gdb`")?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("`gdb` exited with {}", status));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::process::ExitStatusExt;
+    use std::path::Path;
+    use std::time::Duration;
+    use std::time::Instant;
+
+    use buck2_core::execution_types::timeout_escalation::StackDump;
+    use buck2_core::execution_types::timeout_escalation::TimeoutEscalation;
+    use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+    use buck2_util::process::background_command;
+    use nix::sys::signal::Signal;
+
+    use crate::run::process_group::ProcessCommand;
+    use crate::run::process_group::ProcessGroup;
+
+    /// Spawns a shell which runs `setup` (e.g. to install traps), and then keeps running.
+    /// Returns once `setup` has run, so signals do not race with it.
+    async fn spawn_after_setup(dir: &Path, setup: &str) -> anyhow::Result<ProcessGroup> {
+        let mut cmd = background_command("sh");
+        cmd.current_dir(dir).arg("-c").arg(format!(
+            "{}; touch ready; while true; do sleep 0.1; done",
+            setup
+        ));
+        let process = ProcessCommand::new(cmd).spawn()?;
+        while !dir.join("ready").exists() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        Ok(process)
+    }
+
+    fn escalation(grace_period: Duration, stack_dump: Option<StackDump>) -> TimeoutEscalation {
+        TimeoutEscalation {
+            grace_period,
+            stack_dump,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_kill_after_timeout_exits_on_sigterm() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let mut process = spawn_after_setup(tempdir.path(), "true").await?;
+
+        let now = Instant::now();
+        process
+            .kill_after_timeout(&escalation(Duration::from_secs(60), None))
+            .await?;
+        assert!(now.elapsed() < Duration::from_secs(30));
+
+        let status = process.wait().await?;
+        assert_eq!(status.signal(), Some(Signal::SIGTERM as i32));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_kill_after_timeout_kills_after_grace_period() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let mut process = spawn_after_setup(tempdir.path(), "trap '' TERM").await?;
+
+        let grace_period = Duration::from_millis(500);
+        let now = Instant::now();
+        process
+            .kill_after_timeout(&escalation(grace_period, None))
+            .await?;
+        assert!(now.elapsed() >= grace_period);
+
+        let status = process.wait().await?;
+        assert_eq!(status.signal(), Some(Signal::SIGKILL as i32));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_kill_after_timeout_sigquit_stack_dump() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let mut process = spawn_after_setup(
+            tempdir.path(),
+            "trap '' TERM; trap 'echo stacks > stacks' QUIT",
+        )
+        .await?;
+
+        process
+            .kill_after_timeout(&escalation(
+                Duration::from_millis(100),
+                Some(StackDump::Sigquit),
+            ))
+            .await?;
+
+        let status = process.wait().await?;
+        assert_eq!(status.signal(), Some(Signal::SIGKILL as i32));
+        assert_eq!(
+            std::fs::read_to_string(tempdir.path().join("stacks"))?,
+            "stacks\n"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_kill_after_timeout_gdb_stack_dump() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let mut process = spawn_after_setup(tempdir.path(), "trap '' TERM").await?;
+
+        // Whether or not `gdb` is installed and allowed to attach, the process is killed.
+        let output = AbsNormPathBuf::new(tempdir.path().join("stacks"))?;
+        process
+            .kill_after_timeout(&escalation(
+                Duration::from_millis(100),
+                Some(StackDump::Gdb(output.clone())),
+            ))
+            .await?;
+
+        let status = process.wait().await?;
+        assert_eq!(status.signal(), Some(Signal::SIGKILL as i32));
+        assert!(output.as_path().exists());
+        Ok(())
+    }
 }
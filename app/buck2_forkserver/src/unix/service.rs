@@ -18,6 +18,8 @@ use std::sync::Arc;
 
 use anyhow::Context as _;
 use buck2_common::convert::ProstDurationExt;
+use buck2_core::execution_types::timeout_escalation::StackDump;
+use buck2_core::execution_types::timeout_escalation::TimeoutEscalation;
 use buck2_core::fs::fs_util;
 use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
 use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
@@ -115,6 +117,7 @@ impl Forkserver for UnixForkserverService {
                 enable_miniperf,
                 std_redirects,
                 graceful_shutdown_timeout_s,
+                timeout_escalation,
             } = msg;
 
             let exe = OsStr::from_bytes(&exe);
@@ -125,6 +128,10 @@ impl Forkserver for UnixForkserverService {
                 .map(|t| t.try_into_duration())
                 .transpose()
                 .context("Invalid timeout")?;
+            let timeout_escalation = timeout_escalation
+                .map(convert_timeout_escalation)
+                .transpose()
+                .context("Invalid timeout escalation")?;
 
             let exe = maybe_absolutize_exe(exe, cwd)?;
 
@@ -180,6 +187,7 @@ impl Forkserver for UnixForkserverService {
                     MiniperfStatusDecoder::new(out),
                     DefaultKillProcess {
                         graceful_shutdown_timeout_s,
+                        timeout_escalation,
                     },
                     stream_stdio,
                 )?
@@ -190,6 +198,7 @@ impl Forkserver for UnixForkserverService {
                     DefaultStatusDecoder,
                     DefaultKillProcess {
                         graceful_shutdown_timeout_s,
+                        timeout_escalation,
                     },
                     stream_stdio,
                 )?
@@ -215,6 +224,29 @@ impl Forkserver for UnixForkserverService {
     }
 }
 
+fn convert_timeout_escalation(
+    escalation: buck2_forkserver_proto::TimeoutEscalation,
+) -> anyhow::Result<TimeoutEscalation> {
+    use buck2_forkserver_proto::timeout_escalation;
+
+    let grace_period = escalation
+        .grace_period
+        .context("Missing grace_period")?
+        .try_into_duration()?;
+    let stack_dump = match escalation.stack_dump {
+        None => None,
+        Some(timeout_escalation::StackDump::Sigquit(..)) => Some(StackDump::Sigquit),
+        Some(timeout_escalation::StackDump::Gdb(gdb)) => Some(StackDump::Gdb(
+            AbsNormPathBuf::new(Path::new(OsStr::from_bytes(&gdb.output)).to_owned())
+                .context("Invalid gdb stack dump output")?,
+        )),
+    };
+    Ok(TimeoutEscalation {
+        grace_period,
+        stack_dump,
+    })
+}
+
 struct MiniperfContainer {
     /// The Miniperf binary
     miniperf: AbsNormPathBuf,
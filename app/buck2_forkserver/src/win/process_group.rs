@@ -15,6 +15,7 @@ use std::process::Command;
 use std::process::ExitStatus;
 use std::process::Stdio;
 
+use buck2_core::execution_types::timeout_escalation::TimeoutEscalation;
 use buck2_error::Context;
 use tokio::io;
 use tokio::process::ChildStderr;
@@ -144,6 +145,14 @@ impl ProcessGroupImpl {
         self.job.terminate(0)
     }
 
+    // There are no signals to escalate through on Windows, so this is a plain kill.
+    pub(crate) async fn kill_after_timeout(
+        &mut self,
+        _escalation: &TimeoutEscalation,
+    ) -> anyhow::Result<()> {
+        self.kill(None).await
+    }
+
     fn resume(&self) -> anyhow::Result<()> {
         let handle = self
             .child
@@ -21,6 +21,7 @@ use buck2_common::package_boundary::HasPackageBoundaryExceptions;
 use buck2_common::package_listing::dice::DicePackageListingResolver;
 use buck2_common::package_listing::listing::PackageListing;
 use buck2_core::build_file_path::BuildFilePath;
+use buck2_core::bzl::ImportPath;
 use buck2_core::cells::build_file_cell::BuildFileCell;
 use buck2_core::cells::name::CellName;
 use buck2_core::package::PackageLabel;
@@ -48,6 +49,8 @@ use dupe::Dupe;
 use futures::future;
 use futures::FutureExt;
 use indoc::indoc;
+use starlark::assert::assert_module;
+use starlark::assert::TestResult;
 use starlark::codemap::FileSpan;
 use starlark::environment::Globals;
 use starlark::environment::Module;
@@ -237,6 +240,7 @@ impl<'c, 'd: 'c> DiceCalculationDelegate<'c, 'd> {
                         &root_buckconfig,
                         ast,
                         loaded_modules.clone(),
+                        None,
                         provider,
                     )
                     .with_context(|| {
@@ -253,6 +257,49 @@ impl<'c, 'd: 'c> DiceCalculationDelegate<'c, 'd> {
         .await
     }
 
+    /// Evaluates a `.bzl` file with the `asserts` of [`assert_module`] available, then runs
+    /// the tests it defines with evaluators set up as for the evaluation of the file.
+    /// The module is not cached, nor can other modules load it.
+    /// An error means the file failed to load.
+    pub async fn run_test_module(&self, bzl: &ImportPath) -> anyhow::Result<Vec<TestResult>> {
+        let starlark_file = StarlarkModulePath::LoadFile(bzl);
+        let (ast, deps) = self.prepare_eval(starlark_file.into()).await?;
+        let loaded_modules = deps.get_loaded_modules();
+        let buckconfig = self.get_legacy_buck_config_for_starlark().await?;
+        let root_buckconfig = self.ctx.get_legacy_root_config_on_dice().await?;
+
+        with_starlark_eval_provider(
+            &mut self.ctx.bad_dice(),
+            &mut StarlarkProfilerOrInstrumentation::disabled(),
+            format!("test:{}", &starlark_file),
+            move |provider, _| {
+                let module = self
+                    .configs
+                    .eval_module(
+                        starlark_file,
+                        &buckconfig,
+                        &root_buckconfig,
+                        ast,
+                        loaded_modules.clone(),
+                        Some(&assert_module()),
+                        provider,
+                    )
+                    .with_context(|| {
+                        DiceCalculationDelegateError::EvalModuleError(starlark_file.to_string())
+                    })?;
+                self.configs.run_tests(
+                    bzl,
+                    &module,
+                    &buckconfig,
+                    &root_buckconfig,
+                    loaded_modules,
+                    provider,
+                )
+            },
+        )
+        .await
+    }
+
     /// Eval parent `PACKAGE` file for given `PACKAGE` file.
     async fn eval_parent_package_file(
         &self,
@@ -48,6 +48,9 @@ use buck2_node::nodes::eval_result::EvaluationResultWithStats;
 use buck2_node::super_package::SuperPackage;
 use dupe::Dupe;
 use gazebo::prelude::*;
+use starlark::assert::run_test;
+use starlark::assert::test_names;
+use starlark::assert::TestResult;
 use starlark::codemap::FileSpan;
 use starlark::environment::FrozenModule;
 use starlark::environment::Module;
@@ -530,6 +533,7 @@ impl InterpreterForCell {
 
     /// Evaluates the AST for a parsed module. Loaded modules must contain the loaded
     /// environment for all (transitive) required imports.
+    /// The public symbols of `extra_symbols` are made available to the module too.
     /// Returns the FrozenModule for the module.
     pub(crate) fn eval_module(
         self: &Arc<Self>,
@@ -538,9 +542,13 @@ impl InterpreterForCell {
         root_buckconfig: &dyn LegacyBuckConfigView,
         ast: AstModule,
         loaded_modules: LoadedModules,
+        extra_symbols: Option<&FrozenModule>,
         eval_provider: &mut dyn StarlarkEvaluatorProvider,
     ) -> anyhow::Result<FrozenModule> {
         let env = self.create_env(starlark_path.into(), &loaded_modules)?;
+        if let Some(extra_symbols) = extra_symbols {
+            env.import_public_symbols(extra_symbols);
+        }
         let extra_context = match starlark_path {
             StarlarkModulePath::LoadFile(bzl) => PerFileTypeContext::Bzl(BzlEvalCtx {
                 bzl_path: bzl.clone(),
@@ -568,6 +576,47 @@ impl InterpreterForCell {
         env.freeze()
     }
 
+    /// Runs the tests of a module returned by [`eval_module`](Self::eval_module) for `bzl`.
+    /// Each test gets a module of its own, and an evaluator with the same globals, loader,
+    /// print handler and build context the module was evaluated with.
+    pub(crate) fn run_tests(
+        self: &Arc<Self>,
+        bzl: &ImportPath,
+        module: &FrozenModule,
+        buckconfig: &dyn LegacyBuckConfigView,
+        root_buckconfig: &dyn LegacyBuckConfigView,
+        loaded_modules: LoadedModules,
+        eval_provider: &mut dyn StarlarkEvaluatorProvider,
+    ) -> anyhow::Result<Vec<TestResult>> {
+        let import = StarlarkPath::LoadFile(bzl);
+        let file_loader =
+            InterpreterFileLoader::new(loaded_modules, Arc::new(self.load_resolver(import)));
+        let cell_info = self.get_cell_config(import.build_file_cell());
+        let host_info = self.global_state.configuror.host_info();
+        let print = EventDispatcherPrintHandler(get_dispatcher());
+        let mut results = Vec::new();
+        for name in test_names(module) {
+            let env = Module::new();
+            let extra = BuildContext::new_for_module(
+                &env,
+                cell_info,
+                buckconfig,
+                root_buckconfig,
+                host_info,
+                PerFileTypeContext::Bzl(BzlEvalCtx {
+                    bzl_path: bzl.clone(),
+                }),
+                self.ignore_attrs_for_profiling,
+            );
+            let (mut eval, _) = eval_provider.make(&env)?;
+            eval.set_print_handler(&print);
+            eval.set_loader(&file_loader);
+            eval.extra = Some(&extra);
+            results.push(run_test(module, &name, &mut eval));
+        }
+        Ok(results)
+    }
+
     pub(crate) fn eval_package_file(
         self: &Arc<Self>,
         package_file_path: &PackageFilePath,
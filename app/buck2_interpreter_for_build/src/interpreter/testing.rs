@@ -266,6 +266,7 @@ impl Tester {
             root_buckconfig,
             ast,
             loaded_modules.clone(),
+            None,
             &mut provider,
         )?;
         Ok(LoadedModule::new(
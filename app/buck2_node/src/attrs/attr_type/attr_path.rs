@@ -0,0 +1,490 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Evaluation of `attrfilter` style predicates on nested attribute values.
+
+use buck2_query::query::environment::AttrPredicate;
+
+use crate::attrs::attr_type::any_matches::AnyMatches;
+use crate::attrs::coerced_attr::CoercedAttr;
+use crate::attrs::configured_attr::ConfiguredAttr;
+use crate::metadata::key::MetadataKeyRef;
+use crate::metadata::map::MetadataMap;
+
+/// Check if the value at a path inside this attribute matches the predicate.
+///
+/// Each path element is a dict key or a list or tuple index. Metadata keys contain a dot
+/// themselves, so they consume two path elements.
+pub trait AttrPathMatches {
+    fn path_matches(&self, path: &[&str], predicate: &AttrPredicate) -> anyhow::Result<bool>;
+}
+
+fn is_equal(value: &impl AnyMatches, expected: &str) -> anyhow::Result<bool> {
+    value.any_matches(&|s| Ok(s == expected))
+}
+
+fn list_path_matches<A: AttrPathMatches>(
+    items: &[A],
+    index: &str,
+    rest: &[&str],
+    predicate: &AttrPredicate,
+) -> anyhow::Result<bool> {
+    match index.parse::<usize>().ok().and_then(|i| items.get(i)) {
+        Some(item) => item.path_matches(rest, predicate),
+        None => Ok(false),
+    }
+}
+
+fn dict_path_matches<A: AttrPathMatches + AnyMatches>(
+    entries: &[(A, A)],
+    key: &str,
+    rest: &[&str],
+    predicate: &AttrPredicate,
+) -> anyhow::Result<bool> {
+    for (k, v) in entries {
+        if is_equal(k, key)? {
+            return v.path_matches(rest, predicate);
+        }
+    }
+    Ok(false)
+}
+
+fn list_contains<A: AnyMatches>(items: &[A], value: &str) -> anyhow::Result<bool> {
+    for item in items {
+        if is_equal(item, value)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn dict_contains<A: AnyMatches>(entries: &[(A, A)], value: &str) -> anyhow::Result<bool> {
+    for (k, _) in entries {
+        if is_equal(k, value)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn metadata_path_matches(
+    metadata: &MetadataMap,
+    path: &[&str],
+    predicate: &AttrPredicate,
+) -> anyhow::Result<bool> {
+    match path {
+        [] => match predicate {
+            AttrPredicate::AnyMatches(filter) => metadata.any_matches(filter),
+            AttrPredicate::GreaterThan(_) | AttrPredicate::Contains(_) => {
+                metadata.to_value().path_matches(&[], predicate)
+            }
+        },
+        [namespace, key, rest @ ..] => {
+            match metadata.get(MetadataKeyRef::unchecked_new(&format!(
+                "{}.{}",
+                namespace, key
+            ))) {
+                Some(v) => v.as_json().path_matches(rest, predicate),
+                None => Ok(false),
+            }
+        }
+        [_] => Ok(false),
+    }
+}
+
+impl AttrPathMatches for ConfiguredAttr {
+    fn path_matches(&self, path: &[&str], predicate: &AttrPredicate) -> anyhow::Result<bool> {
+        match (self, path) {
+            (ConfiguredAttr::OneOf(l, _), _) => l.path_matches(path, predicate),
+            (ConfiguredAttr::Metadata(m), _) => metadata_path_matches(m, path, predicate),
+            (_, []) => match predicate {
+                AttrPredicate::AnyMatches(filter) => self.any_matches(filter),
+                AttrPredicate::GreaterThan(n) => match self {
+                    ConfiguredAttr::Int(i) => Ok(i > n),
+                    _ => Ok(false),
+                },
+                AttrPredicate::Contains(value) => match self {
+                    ConfiguredAttr::List(items) => list_contains(items, value),
+                    ConfiguredAttr::Tuple(items) => list_contains(items, value),
+                    ConfiguredAttr::Dict(entries) => dict_contains(entries, value),
+                    ConfiguredAttr::String(s) | ConfiguredAttr::EnumVariant(s) => {
+                        Ok(s.contains(value))
+                    }
+                    ConfiguredAttr::Arg(a) => Ok(a.to_string().contains(value)),
+                    _ => Ok(false),
+                },
+            },
+            (ConfiguredAttr::List(items), [index, rest @ ..]) => {
+                list_path_matches(items, index, rest, predicate)
+            }
+            (ConfiguredAttr::Tuple(items), [index, rest @ ..]) => {
+                list_path_matches(items, index, rest, predicate)
+            }
+            (ConfiguredAttr::Dict(entries), [key, rest @ ..]) => {
+                dict_path_matches(entries, key, rest, predicate)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+/// Unconfigured values match if any branch of a `select()` or any part of a concatenation
+/// matches, the same way [`AnyMatches`] treats them.
+impl AttrPathMatches for CoercedAttr {
+    fn path_matches(&self, path: &[&str], predicate: &AttrPredicate) -> anyhow::Result<bool> {
+        match (self, path) {
+            (CoercedAttr::Selector(s), _) => {
+                for value in s.all_values() {
+                    if value.path_matches(path, predicate)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            (CoercedAttr::Concat(items), _) => {
+                for item in &**items {
+                    if item.path_matches(path, predicate)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            (CoercedAttr::OneOf(l, _), _) => l.path_matches(path, predicate),
+            (CoercedAttr::Metadata(m), _) => metadata_path_matches(m, path, predicate),
+            (_, []) => match predicate {
+                AttrPredicate::AnyMatches(filter) => self.any_matches(filter),
+                AttrPredicate::GreaterThan(n) => match self {
+                    CoercedAttr::Int(i) => Ok(i > n),
+                    _ => Ok(false),
+                },
+                AttrPredicate::Contains(value) => match self {
+                    CoercedAttr::List(items) => list_contains(items, value),
+                    CoercedAttr::Tuple(items) => list_contains(items, value),
+                    CoercedAttr::Dict(entries) => dict_contains(entries, value),
+                    CoercedAttr::String(s) | CoercedAttr::EnumVariant(s) => Ok(s.contains(value)),
+                    CoercedAttr::Arg(a) => Ok(a.to_string().contains(value)),
+                    _ => Ok(false),
+                },
+            },
+            (CoercedAttr::List(items), [index, rest @ ..]) => {
+                list_path_matches(items, index, rest, predicate)
+            }
+            (CoercedAttr::Tuple(items), [index, rest @ ..]) => {
+                list_path_matches(items, index, rest, predicate)
+            }
+            (CoercedAttr::Dict(entries), [key, rest @ ..]) => {
+                dict_path_matches(entries, key, rest, predicate)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+impl AttrPathMatches for serde_json::Value {
+    fn path_matches(&self, path: &[&str], predicate: &AttrPredicate) -> anyhow::Result<bool> {
+        match (self, path) {
+            (_, []) => match predicate {
+                AttrPredicate::AnyMatches(filter) => self.any_matches(filter),
+                AttrPredicate::GreaterThan(n) => Ok(match self {
+                    serde_json::Value::Number(v) => match v.as_i64() {
+                        Some(v) => v > *n,
+                        None => v.as_f64().map_or(false, |v| v > *n as f64),
+                    },
+                    _ => false,
+                }),
+                AttrPredicate::Contains(value) => Ok(match self {
+                    serde_json::Value::Array(items) => items.iter().any(|v| match v {
+                        serde_json::Value::String(s) => s == value,
+                        serde_json::Value::Number(n) => n.to_string() == *value,
+                        _ => false,
+                    }),
+                    serde_json::Value::Object(entries) => entries.contains_key(*value),
+                    serde_json::Value::String(s) => s.contains(value),
+                    _ => false,
+                }),
+            },
+            (serde_json::Value::Array(items), [index, rest @ ..]) => {
+                list_path_matches(items, index, rest, predicate)
+            }
+            (serde_json::Value::Object(entries), [key, rest @ ..]) => match entries.get(*key) {
+                Some(v) => v.path_matches(rest, predicate),
+                None => Ok(false),
+            },
+            _ => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_core::target::label::TargetLabel;
+    use buck2_util::arc_str::ArcSlice;
+    use buck2_util::arc_str::ArcStr;
+    use starlark_map::small_map::SmallMap;
+
+    use super::*;
+    use crate::attrs::attr_type::string::StringLiteral;
+    use crate::attrs::coerced_attr::CoercedSelector;
+    use crate::metadata::value::MetadataValue;
+
+    fn eq(expected: &'static str) -> impl Fn(&str) -> anyhow::Result<bool> {
+        move |s| Ok(s == expected)
+    }
+
+    #[test]
+    fn test_serde_json_path_matches() {
+        let v = serde_json::json!({
+            "timeout": 120,
+            "list": ["list1", "list2"],
+            "object": {"key": "value"}
+        });
+        assert!(
+            v.path_matches(&["object", "key"], &AttrPredicate::AnyMatches(&eq("value")))
+                .unwrap()
+        );
+        assert!(
+            !v.path_matches(&["object", "key"], &AttrPredicate::AnyMatches(&eq("key")))
+                .unwrap()
+        );
+        assert!(
+            v.path_matches(&["list", "1"], &AttrPredicate::AnyMatches(&eq("list2")))
+                .unwrap()
+        );
+        assert!(
+            !v.path_matches(&["list", "2"], &AttrPredicate::AnyMatches(&|_| Ok(true)))
+                .unwrap()
+        );
+        assert!(
+            v.path_matches(&["timeout"], &AttrPredicate::GreaterThan(60))
+                .unwrap()
+        );
+        assert!(
+            !v.path_matches(&["timeout"], &AttrPredicate::GreaterThan(120))
+                .unwrap()
+        );
+        assert!(
+            v.path_matches(&["list"], &AttrPredicate::Contains("list1"))
+                .unwrap()
+        );
+        assert!(
+            v.path_matches(&["object"], &AttrPredicate::Contains("key"))
+                .unwrap()
+        );
+        assert!(
+            !v.path_matches(&["object"], &AttrPredicate::Contains("value"))
+                .unwrap()
+        );
+    }
+
+    fn configured_string(s: &str) -> ConfiguredAttr {
+        ConfiguredAttr::String(StringLiteral(ArcStr::from(s)))
+    }
+
+    fn coerced_string(s: &str) -> CoercedAttr {
+        CoercedAttr::String(StringLiteral(ArcStr::from(s)))
+    }
+
+    /// `{"env": {"FOO": "bar"}, "srcs": ["a.c", "b.c"], "shard": (3, -2)}`
+    fn configured_attr() -> ConfiguredAttr {
+        ConfiguredAttr::Dict(
+            [
+                (
+                    configured_string("env"),
+                    ConfiguredAttr::Dict(
+                        [(configured_string("FOO"), configured_string("bar"))]
+                            .into_iter()
+                            .collect(),
+                    ),
+                ),
+                (
+                    configured_string("srcs"),
+                    ConfiguredAttr::List(
+                        [configured_string("a.c"), configured_string("b.c")]
+                            .into_iter()
+                            .collect(),
+                    ),
+                ),
+                (
+                    configured_string("shard"),
+                    ConfiguredAttr::Tuple(
+                        [ConfiguredAttr::Int(3), ConfiguredAttr::Int(-2)]
+                            .into_iter()
+                            .collect(),
+                    ),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        )
+    }
+
+    #[test]
+    fn test_configured_attr_path_matches() {
+        let v = configured_attr();
+        assert!(
+            v.path_matches(&["env", "FOO"], &AttrPredicate::AnyMatches(&eq("bar")))
+                .unwrap()
+        );
+        assert!(
+            !v.path_matches(&["env", "BAR"], &AttrPredicate::AnyMatches(&|_| Ok(true)))
+                .unwrap()
+        );
+        assert!(
+            v.path_matches(&["srcs", "1"], &AttrPredicate::AnyMatches(&eq("b.c")))
+                .unwrap()
+        );
+        assert!(
+            !v.path_matches(&["srcs", "2"], &AttrPredicate::AnyMatches(&|_| Ok(true)))
+                .unwrap()
+        );
+        assert!(
+            !v.path_matches(&["srcs", "x"], &AttrPredicate::AnyMatches(&|_| Ok(true)))
+                .unwrap()
+        );
+        assert!(
+            v.path_matches(&["shard", "0"], &AttrPredicate::GreaterThan(2))
+                .unwrap()
+        );
+        assert!(
+            v.path_matches(&["shard", "1"], &AttrPredicate::GreaterThan(-3))
+                .unwrap()
+        );
+        assert!(
+            !v.path_matches(&["shard", "1"], &AttrPredicate::GreaterThan(-2))
+                .unwrap()
+        );
+        // Only integers are compared.
+        assert!(
+            !v.path_matches(&["env", "FOO"], &AttrPredicate::GreaterThan(i64::MIN))
+                .unwrap()
+        );
+        assert!(
+            v.path_matches(&["env"], &AttrPredicate::Contains("FOO"))
+                .unwrap()
+        );
+        assert!(
+            !v.path_matches(&["env"], &AttrPredicate::Contains("bar"))
+                .unwrap()
+        );
+        assert!(
+            v.path_matches(&["srcs"], &AttrPredicate::Contains("a.c"))
+                .unwrap()
+        );
+        assert!(
+            v.path_matches(&["env", "FOO"], &AttrPredicate::Contains("ba"))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_metadata_path_matches() {
+        let mut map = SmallMap::new();
+        map.insert(
+            "test.config".to_owned().try_into().unwrap(),
+            MetadataValue::new(serde_json::json!({"timeout": 120, "tags": ["slow"]})),
+        );
+        let v = ConfiguredAttr::Metadata(MetadataMap::new(map));
+        assert!(
+            v.path_matches(
+                &["test", "config", "timeout"],
+                &AttrPredicate::GreaterThan(60)
+            )
+            .unwrap()
+        );
+        assert!(
+            v.path_matches(
+                &["test", "config", "tags"],
+                &AttrPredicate::Contains("slow")
+            )
+            .unwrap()
+        );
+        assert!(
+            v.path_matches(&[], &AttrPredicate::Contains("test.config"))
+                .unwrap()
+        );
+        // The key is only complete with both of its parts.
+        assert!(
+            !v.path_matches(&["test"], &AttrPredicate::AnyMatches(&|_| Ok(true)))
+                .unwrap()
+        );
+        assert!(
+            !v.path_matches(
+                &["test", "other", "timeout"],
+                &AttrPredicate::AnyMatches(&|_| Ok(true))
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_coerced_attr_path_matches() {
+        let env = |value: &str| {
+            CoercedAttr::Dict(
+                [(coerced_string("FOO"), coerced_string(value))]
+                    .into_iter()
+                    .collect(),
+            )
+        };
+        // `select({"//:linux": {"FOO": "linux"}, "DEFAULT": {"FOO": "default"}})`
+        let v = CoercedAttr::Selector(Box::new(
+            CoercedSelector::new(
+                ArcSlice::from_iter([(TargetLabel::testing_parse("root//:linux"), env("linux"))]),
+                Some(env("default")),
+            )
+            .unwrap(),
+        ));
+        assert!(
+            v.path_matches(&["FOO"], &AttrPredicate::AnyMatches(&eq("linux")))
+                .unwrap()
+        );
+        assert!(
+            v.path_matches(&["FOO"], &AttrPredicate::AnyMatches(&eq("default")))
+                .unwrap()
+        );
+        assert!(
+            !v.path_matches(&["FOO"], &AttrPredicate::AnyMatches(&eq("mac")))
+                .unwrap()
+        );
+        assert!(
+            v.path_matches(&[], &AttrPredicate::Contains("FOO"))
+                .unwrap()
+        );
+
+        // `[1, 2] + select({"//:linux": [-5]})`
+        let v = CoercedAttr::Concat(Box::new([
+            CoercedAttr::List(
+                [CoercedAttr::Int(1), CoercedAttr::Int(2)]
+                    .into_iter()
+                    .collect(),
+            ),
+            CoercedAttr::Selector(Box::new(
+                CoercedSelector::new(
+                    ArcSlice::from_iter([(
+                        TargetLabel::testing_parse("root//:linux"),
+                        CoercedAttr::List([CoercedAttr::Int(-5)].into_iter().collect()),
+                    )]),
+                    None,
+                )
+                .unwrap(),
+            )),
+        ]));
+        assert!(
+            v.path_matches(&["1"], &AttrPredicate::GreaterThan(1))
+                .unwrap()
+        );
+        assert!(
+            v.path_matches(&["0"], &AttrPredicate::GreaterThan(-6))
+                .unwrap()
+        );
+        assert!(
+            !v.path_matches(&["0"], &AttrPredicate::GreaterThan(1))
+                .unwrap()
+        );
+    }
+}
@@ -46,6 +46,7 @@ pub mod any;
 pub mod any_matches;
 pub mod arg;
 pub mod attr_config;
+pub mod attr_path;
 pub mod attr_like;
 pub mod bool;
 pub mod configuration_dep;
@@ -152,7 +152,7 @@ impl CoercedSelector {
             )
     }
 
-    fn all_values(&self) -> impl Iterator<Item = &'_ CoercedAttr> {
+    pub(crate) fn all_values(&self) -> impl Iterator<Item = &'_ CoercedAttr> {
         self.all_entries().map(|(_, v)| v)
     }
 }
@@ -0,0 +1,303 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Structural diff between two sets of configured targets.
+//!
+//! Used by `buck2 cquery --diff-against` (comparing against a saved snapshot)
+//! and by the BXL `diff_targets()` function (comparing two live target sets).
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+
+use dupe::Dupe;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::attrs::display::AttrDisplayWithContextExt;
+use crate::attrs::fmt_context::AttrFmtContext;
+use crate::attrs::inspect_options::AttrInspectOptions;
+use crate::nodes::configured::ConfiguredTargetNode;
+
+/// Something that can be matched and compared by [`diff_targets`].
+pub trait DiffableTarget {
+    /// Unconfigured label of the target, used to pair targets across configurations.
+    fn diff_label(&self) -> String;
+
+    /// Configuration of the target.
+    fn diff_configuration(&self) -> String;
+
+    /// Hash of the target, if known. When both sides have a hash and the hashes are equal,
+    /// attributes are not compared.
+    fn diff_hash(&self) -> Option<&str>;
+
+    /// Attribute values rendered as strings.
+    fn diff_attrs(&self) -> BTreeMap<String, String>;
+}
+
+impl DiffableTarget for ConfiguredTargetNode {
+    fn diff_label(&self) -> String {
+        self.label().unconfigured().to_string()
+    }
+
+    fn diff_configuration(&self) -> String {
+        self.label().cfg().to_string()
+    }
+
+    fn diff_hash(&self) -> Option<&str> {
+        None
+    }
+
+    fn diff_attrs(&self) -> BTreeMap<String, String> {
+        let ctx = AttrFmtContext {
+            package: Some(self.label().pkg().dupe()),
+        };
+        self.attrs(AttrInspectOptions::All)
+            .map(|a| (a.name.to_owned(), format!("{:#}", a.value.as_display(&ctx))))
+            .collect()
+    }
+}
+
+/// Serialized form of a configured target, as stored in a diff snapshot.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TargetDiffEntry {
+    pub label: String,
+    pub configuration: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    #[serde(default)]
+    pub attrs: BTreeMap<String, String>,
+}
+
+impl TargetDiffEntry {
+    pub fn new(target: &impl DiffableTarget, hash: Option<String>) -> TargetDiffEntry {
+        TargetDiffEntry {
+            label: target.diff_label(),
+            configuration: target.diff_configuration(),
+            hash: hash.or_else(|| target.diff_hash().map(str::to_owned)),
+            attrs: target.diff_attrs(),
+        }
+    }
+}
+
+impl DiffableTarget for TargetDiffEntry {
+    fn diff_label(&self) -> String {
+        self.label.clone()
+    }
+
+    fn diff_configuration(&self) -> String {
+        self.configuration.clone()
+    }
+
+    fn diff_hash(&self) -> Option<&str> {
+        self.hash.as_deref()
+    }
+
+    fn diff_attrs(&self) -> BTreeMap<String, String> {
+        self.attrs.clone()
+    }
+}
+
+/// A saved query result which can later be diffed against.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct TargetDiffSnapshot {
+    pub targets: Vec<TargetDiffEntry>,
+}
+
+/// A target present on both sides of the diff which differs.
+#[derive(Debug)]
+pub struct ChangedTarget<T> {
+    pub old: T,
+    pub new: T,
+    /// Attributes whose values differ, including attributes present only on one side.
+    pub changed_attrs: Vec<String>,
+}
+
+impl<T: DiffableTarget> ChangedTarget<T> {
+    pub fn configuration_changed(&self) -> bool {
+        self.old.diff_configuration() != self.new.diff_configuration()
+    }
+}
+
+#[derive(Debug)]
+pub struct TargetDiff<T> {
+    pub added: Vec<T>,
+    pub removed: Vec<T>,
+    pub changed: Vec<ChangedTarget<T>>,
+}
+
+impl<T> TargetDiff<T> {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+fn changed_attrs(old: &impl DiffableTarget, new: &impl DiffableTarget) -> Vec<String> {
+    if let (Some(old), Some(new)) = (old.diff_hash(), new.diff_hash()) {
+        if old == new {
+            return Vec::new();
+        }
+    }
+    let old = old.diff_attrs();
+    let new = new.diff_attrs();
+    old.keys()
+        .chain(new.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter(|k| old.get(*k) != new.get(*k))
+        .cloned()
+        .collect()
+}
+
+/// Compute the difference between two sets of targets.
+///
+/// Targets are first matched by label and configuration. Targets left over on both sides
+/// with the same label are then matched to each other and reported as changed with a
+/// configuration change. Whatever remains is added or removed.
+pub fn diff_targets<T: DiffableTarget>(
+    old: impl IntoIterator<Item = T>,
+    new: impl IntoIterator<Item = T>,
+) -> TargetDiff<T> {
+    let mut old_by_key: BTreeMap<(String, String), T> = old
+        .into_iter()
+        .map(|t| ((t.diff_label(), t.diff_configuration()), t))
+        .collect();
+
+    let mut changed = Vec::new();
+    let mut new_unmatched: BTreeMap<(String, String), T> = BTreeMap::new();
+    for t in new {
+        let key = (t.diff_label(), t.diff_configuration());
+        match old_by_key.remove(&key) {
+            Some(o) => {
+                let attrs = changed_attrs(&o, &t);
+                if !attrs.is_empty() {
+                    changed.push(ChangedTarget {
+                        old: o,
+                        new: t,
+                        changed_attrs: attrs,
+                    });
+                }
+            }
+            None => {
+                new_unmatched.insert(key, t);
+            }
+        }
+    }
+
+    let mut old_by_label: HashMap<String, Vec<T>> = HashMap::new();
+    for ((label, _), t) in old_by_key {
+        old_by_label.entry(label).or_default().push(t);
+    }
+
+    let mut added = Vec::new();
+    for ((label, _), t) in new_unmatched {
+        let candidate = old_by_label.get_mut(&label).and_then(|v| {
+            if v.is_empty() {
+                None
+            } else {
+                Some(v.remove(0))
+            }
+        });
+        match candidate {
+            Some(o) => {
+                let changed_attrs = changed_attrs(&o, &t);
+                changed.push(ChangedTarget {
+                    old: o,
+                    new: t,
+                    changed_attrs,
+                });
+            }
+            None => added.push(t),
+        }
+    }
+
+    let mut removed: Vec<T> = old_by_label.into_values().flatten().collect();
+    removed.sort_by_key(|t| (t.diff_label(), t.diff_configuration()));
+    changed.sort_by_key(|c| (c.new.diff_label(), c.new.diff_configuration()));
+
+    TargetDiff {
+        added,
+        removed,
+        changed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::nodes::configured_diff::diff_targets;
+    use crate::nodes::configured_diff::TargetDiffEntry;
+
+    fn entry(label: &str, cfg: &str, attrs: &[(&str, &str)]) -> TargetDiffEntry {
+        TargetDiffEntry {
+            label: label.to_owned(),
+            configuration: cfg.to_owned(),
+            hash: None,
+            attrs: attrs
+                .iter()
+                .map(|(k, v)| ((*k).to_owned(), (*v).to_owned()))
+                .collect::<BTreeMap<_, _>>(),
+        }
+    }
+
+    #[test]
+    fn test_diff_added_removed() {
+        let diff = diff_targets(
+            vec![entry("r//:a", "c1", &[]), entry("r//:b", "c1", &[])],
+            vec![entry("r//:b", "c1", &[]), entry("r//:c", "c1", &[])],
+        );
+        assert_eq!(
+            vec!["r//:c"],
+            diff.added.iter().map(|t| &t.label).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec!["r//:a"],
+            diff.removed.iter().map(|t| &t.label).collect::<Vec<_>>()
+        );
+        assert!(diff.changed.is_empty());
+    }
+
+    #[test]
+    fn test_diff_changed_attrs() {
+        let diff = diff_targets(
+            vec![entry("r//:a", "c1", &[("srcs", "[a.c]"), ("name", "a")])],
+            vec![entry(
+                "r//:a",
+                "c1",
+                &[("srcs", "[b.c]"), ("name", "a"), ("x", "1")],
+            )],
+        );
+        assert_eq!(1, diff.changed.len());
+        assert_eq!(vec!["srcs", "x"], diff.changed[0].changed_attrs);
+        assert!(!diff.changed[0].configuration_changed());
+    }
+
+    #[test]
+    fn test_diff_configuration_changed() {
+        let diff = diff_targets(
+            vec![entry("r//:a", "c1", &[("name", "a")])],
+            vec![entry("r//:a", "c2", &[("name", "a")])],
+        );
+        assert!(diff.added.is_empty());
+        assert!(diff.removed.is_empty());
+        assert_eq!(1, diff.changed.len());
+        assert!(diff.changed[0].configuration_changed());
+        assert!(diff.changed[0].changed_attrs.is_empty());
+    }
+
+    #[test]
+    fn test_diff_equal_hash_skips_attrs() {
+        let mut old = entry("r//:a", "c1", &[("name", "a")]);
+        let mut new = entry("r//:a", "c1", &[("name", "b")]);
+        old.hash = Some("1".to_owned());
+        new.hash = Some("1".to_owned());
+        assert!(diff_targets(vec![old], vec![new]).is_empty());
+    }
+}
@@ -8,6 +8,7 @@
  */
 
 pub mod configured;
+pub mod configured_diff;
 pub mod configured_frontend;
 pub mod configured_node_ref;
 pub mod configured_node_visit_all_deps;
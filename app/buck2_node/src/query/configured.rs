@@ -12,12 +12,14 @@ use std::borrow::Cow;
 use buck2_core::build_file_path::BuildFilePath;
 use buck2_core::cells::cell_path::CellPath;
 use buck2_core::target::configured_target_label::ConfiguredTargetLabel;
+use buck2_query::query::environment::AttrPredicate;
 use buck2_query::query::environment::QueryTarget;
 use buck2_query::query::graph::node::LabeledNode;
 use dupe::Dupe;
 use starlark_map::Hashed;
 
 use crate::attrs::attr_type::any_matches::AnyMatches;
+use crate::attrs::attr_type::attr_path::AttrPathMatches;
 use crate::attrs::configured_attr::ConfiguredAttr;
 use crate::attrs::inspect_options::AttrInspectOptions;
 use crate::nodes::configured::ConfiguredTargetNode;
@@ -83,6 +85,14 @@ impl QueryTarget for ConfiguredTargetNode {
         attr.any_matches(filter)
     }
 
+    fn attr_path_matches(
+        attr: &Self::Attr<'_>,
+        path: &[&str],
+        predicate: &AttrPredicate,
+    ) -> anyhow::Result<bool> {
+        attr.path_matches(path, predicate)
+    }
+
     fn attrs_for_each<E, F: FnMut(&str, &Self::Attr<'_>) -> Result<(), E>>(
         &self,
         mut func: F,
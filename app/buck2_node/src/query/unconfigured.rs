@@ -12,10 +12,12 @@ use std::borrow::Cow;
 use buck2_core::build_file_path::BuildFilePath;
 use buck2_core::cells::cell_path::CellPath;
 use buck2_core::target::label::TargetLabel;
+use buck2_query::query::environment::AttrPredicate;
 use buck2_query::query::environment::QueryTarget;
 use buck2_query::query::graph::node::LabeledNode;
 use dupe::Dupe;
 
+use crate::attrs::attr_type::attr_path::AttrPathMatches;
 use crate::attrs::coerced_attr::CoercedAttr;
 use crate::attrs::inspect_options::AttrInspectOptions;
 use crate::nodes::unconfigured::TargetNode;
@@ -63,6 +65,14 @@ impl QueryTarget for TargetNode {
         attr.any_matches(filter)
     }
 
+    fn attr_path_matches(
+        attr: &Self::Attr<'_>,
+        path: &[&str],
+        predicate: &AttrPredicate,
+    ) -> anyhow::Result<bool> {
+        attr.path_matches(path, predicate)
+    }
+
     fn special_attrs_for_each<E, F: FnMut(&str, &Self::Attr<'_>) -> Result<(), E>>(
         &self,
         mut func: F,
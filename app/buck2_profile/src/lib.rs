@@ -36,6 +36,7 @@ pub fn starlark_profiler_configuration_from_request(
         Profiler::Bytecode => ProfileMode::Bytecode,
         Profiler::BytecodePairs => ProfileMode::BytecodePairs,
         Profiler::Typecheck => ProfileMode::Typecheck,
+        Profiler::Coverage => ProfileMode::Coverage,
     };
 
     match req.profile_opts.as_ref().expect("Missing profile opts") {
@@ -92,6 +93,20 @@ pub fn get_profile_response(
                 .context("Failed to write profile")?;
             fs_util::write(output.join("flame.svg"), &svg).context("Failed to write profile")?;
         }
+        Profiler::Coverage => {
+            fs_util::create_dir_if_not_exists(output)?;
+
+            fs_util::write(
+                output.join("coverage.lcov"),
+                profile_data.profile_data.gen()?,
+            )
+            .context("Failed to write profile")?;
+            fs_util::write(
+                output.join("coverage.xml"),
+                profile_data.profile_data.gen_cobertura()?,
+            )
+            .context("Failed to write profile")?;
+        }
         _ => {
             let profile = profile_data.profile_data.gen()?;
             fs_util::write(output, profile).context("Failed to write profile")?;
@@ -72,6 +72,30 @@ impl QueryTargets {
     }
 }
 
+/// Predicate applied to the value found at an attribute path by `attrfilter` and friends.
+#[derive(Clone, Copy)]
+pub enum AttrPredicate<'a> {
+    /// Any string in the value (list elements, dict keys and values, ...) matches the filter.
+    /// This is what plain `attrfilter` and `attrregexfilter` check.
+    AnyMatches(&'a dyn Fn(&str) -> anyhow::Result<bool>),
+    /// The value is an integer greater than the given one.
+    GreaterThan(i64),
+    /// The value is a list or tuple with an element equal to the given string, a dict with
+    /// such a key, or a string containing it as a substring.
+    Contains(&'a str),
+}
+
+impl<'a> AttrPredicate<'a> {
+    /// Evaluate the predicate against a value which is only available as a string.
+    pub fn matches_str(&self, value: &str) -> anyhow::Result<bool> {
+        match self {
+            AttrPredicate::AnyMatches(filter) => filter(value),
+            AttrPredicate::GreaterThan(n) => Ok(value.parse::<i64>().map_or(false, |v| v > *n)),
+            AttrPredicate::Contains(s) => Ok(value.contains(s)),
+        }
+    }
+}
+
 pub trait QueryTarget: LabeledNode + Dupe + Send + Sync + 'static {
     type Attr<'a>: ?Sized + Debug + 'a;
 
@@ -103,6 +127,20 @@ pub trait QueryTarget: LabeledNode + Dupe + Send + Sync + 'static {
         filter: &dyn Fn(&str) -> anyhow::Result<bool>,
     ) -> anyhow::Result<bool>;
 
+    /// Evaluate `predicate` against the value at `path` inside `attr`, where each path element
+    /// is a dict key or a list index. An empty path refers to `attr` itself.
+    /// Returns `false` if the path does not exist.
+    fn attr_path_matches(
+        attr: &Self::Attr<'_>,
+        path: &[&str],
+        predicate: &AttrPredicate,
+    ) -> anyhow::Result<bool> {
+        if !path.is_empty() {
+            return Ok(false);
+        }
+        Self::attr_any_matches(attr, &|s| predicate.matches_str(s))
+    }
+
     fn special_attrs_for_each<E, F: FnMut(&str, &Self::Attr<'_>) -> Result<(), E>>(
         &self,
         func: F,
@@ -22,6 +22,12 @@ use dupe::OptionDupedExt;
 use indexmap::IndexSet;
 
 use super::*;
+use crate::query::syntax::simple::eval::file_set::FileNode;
+use crate::query::syntax::simple::eval::values::QueryValue;
+use crate::query::syntax::simple::functions::helpers::QueryFunctionArg;
+use crate::query::syntax::simple::functions::AffectedTests;
+use crate::query::syntax::simple::functions::DefaultQueryFunctions;
+use crate::query::syntax::simple::functions::DefaultQueryFunctionsModule;
 use crate::query::traversal::AsyncNodeLookup;
 
 #[derive(Debug, Copy, Clone, Dupe, Eq, PartialEq, Hash, Display, From)]
@@ -29,13 +35,39 @@ struct TestTargetId(u64);
 
 impl NodeKey for TestTargetId {}
 
-#[derive(Debug, Copy, Clone, Dupe, Eq, PartialEq, Hash, Display)]
-struct TestTargetAttr;
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum TestTargetAttr {
+    Int(i64),
+    String(String),
+    Dict(Vec<(String, TestTargetAttr)>),
+}
+
+impl TestTargetAttr {
+    fn any_matches(&self, filter: &dyn Fn(&str) -> anyhow::Result<bool>) -> anyhow::Result<bool> {
+        match self {
+            TestTargetAttr::Int(i) => filter(&i.to_string()),
+            TestTargetAttr::String(s) => filter(s),
+            TestTargetAttr::Dict(entries) => {
+                for (k, v) in entries {
+                    if filter(k)? || v.any_matches(filter)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+        }
+    }
+}
 
 #[derive(Clone, Dupe, Eq, PartialEq)]
 struct TestTarget {
     id: TestTargetId,
     deps: Arc<IndexSet<TestTargetId>>,
+    buildfile: Arc<BuildFilePath>,
+    sources: Arc<IndexSet<CellPath>>,
+    attrs: Arc<Vec<(String, TestTargetAttr)>>,
+    rule_type: Arc<str>,
+    tests: Arc<IndexSet<TestTargetId>>,
 }
 
 /// Custom debug to make the test output more readable
@@ -61,11 +93,11 @@ impl QueryTarget for TestTarget {
     }
 
     fn rule_type(&self) -> Cow<str> {
-        unimplemented!()
+        Cow::Borrowed(&self.rule_type)
     }
 
     fn buildfile_path(&self) -> &BuildFilePath {
-        unimplemented!()
+        &self.buildfile
     }
 
     fn deps<'a>(&'a self) -> Box<dyn Iterator<Item = &'a Self::Key> + Send + 'a> {
@@ -80,11 +112,42 @@ impl QueryTarget for TestTarget {
         Box::new(std::iter::empty())
     }
 
+    fn tests<'a>(&'a self) -> Option<impl Iterator<Item = Self::Key> + Send + 'a> {
+        Some(self.tests.iter().copied())
+    }
+
     fn attr_any_matches(
-        _attr: &Self::Attr<'_>,
-        _filter: &dyn Fn(&str) -> anyhow::Result<bool>,
+        attr: &Self::Attr<'_>,
+        filter: &dyn Fn(&str) -> anyhow::Result<bool>,
     ) -> anyhow::Result<bool> {
-        unimplemented!()
+        attr.any_matches(filter)
+    }
+
+    fn attr_path_matches(
+        attr: &Self::Attr<'_>,
+        path: &[&str],
+        predicate: &AttrPredicate,
+    ) -> anyhow::Result<bool> {
+        match (attr, path) {
+            (_, []) => match (attr, predicate) {
+                (_, AttrPredicate::AnyMatches(filter)) => attr.any_matches(filter),
+                (TestTargetAttr::Int(i), AttrPredicate::GreaterThan(n)) => Ok(i > n),
+                (TestTargetAttr::String(s), AttrPredicate::Contains(value)) => {
+                    Ok(s.contains(value))
+                }
+                (TestTargetAttr::Dict(entries), AttrPredicate::Contains(value)) => {
+                    Ok(entries.iter().any(|(k, _)| k == value))
+                }
+                _ => Ok(false),
+            },
+            (TestTargetAttr::Dict(entries), [key, rest @ ..]) => {
+                match entries.iter().find(|(k, _)| k == key) {
+                    Some((_, v)) => Self::attr_path_matches(v, rest, predicate),
+                    None => Ok(false),
+                }
+            }
+            _ => Ok(false),
+        }
     }
 
     fn special_attrs_for_each<E, F: FnMut(&str, &Self::Attr<'_>) -> Result<(), E>>(
@@ -101,13 +164,19 @@ impl QueryTarget for TestTarget {
         unimplemented!()
     }
 
-    fn map_attr<R, F: FnMut(Option<&Self::Attr<'_>>) -> R>(&self, _key: &str, _func: F) -> R {
-        unimplemented!()
+    fn map_attr<R, F: FnMut(Option<&Self::Attr<'_>>) -> R>(&self, key: &str, mut func: F) -> R {
+        func(
+            self.attrs
+                .iter()
+                .find_map(|(k, v)| if k == key { Some(v) } else { None }),
+        )
     }
 }
 
 struct TestEnv {
     graph: HashMap<TestTargetId, TestTarget>,
+    /// The files each build or `.bzl` file loads.
+    loads: HashMap<CellPath, IndexSet<CellPath>>,
 }
 
 impl NodeLookup<TestTarget> for TestEnv {
@@ -175,8 +244,43 @@ impl QueryEnvironment for TestEnv {
         async_depth_limited_traversal(self, root.iter_names(), delegate, visit, depth).await
     }
 
-    async fn owner(&self, _paths: &FileSet) -> anyhow::Result<TargetSet<Self::Target>> {
-        unimplemented!()
+    async fn owner(&self, paths: &FileSet) -> anyhow::Result<TargetSet<Self::Target>> {
+        Ok(self
+            .graph
+            .values()
+            .filter(|t| paths.iter().any(|path| t.sources.contains(path)))
+            .cloned()
+            .collect())
+    }
+
+    async fn allbuildfiles(&self, universe: &TargetSet<Self::Target>) -> anyhow::Result<FileSet> {
+        let mut files: IndexSet<FileNode> = universe
+            .iter()
+            .map(|t| FileNode(t.buildfile.path()))
+            .collect();
+        let mut i = 0;
+        while let Some(file) = files.get_index(i) {
+            let loads = self.loads.get(&file.0).cloned().unwrap_or_default();
+            files.extend(loads.into_iter().map(FileNode));
+            i += 1;
+        }
+        Ok(FileSet::new(files))
+    }
+
+    async fn rbuildfiles(&self, universe: &FileSet, argset: &FileSet) -> anyhow::Result<FileSet> {
+        fn loads_any(env: &TestEnv, file: &CellPath, argset: &FileSet) -> bool {
+            argset.contains(&FileNode(file.clone()))
+                || env.loads.get(file).map_or(false, |loads| {
+                    loads.iter().any(|load| loads_any(env, load, argset))
+                })
+        }
+        Ok(FileSet::new(
+            universe
+                .iter()
+                .filter(|file| loads_any(self, file, argset))
+                .map(|file| FileNode(file.clone()))
+                .collect(),
+        ))
     }
 }
 
@@ -195,6 +299,12 @@ impl TestEnv {
 #[derive(Default)]
 pub struct TestEnvBuilder {
     graph: HashMap<u64, IndexSet<u64>>,
+    buildfiles: HashMap<u64, String>,
+    sources: HashMap<u64, IndexSet<CellPath>>,
+    loads: HashMap<CellPath, IndexSet<CellPath>>,
+    attrs: HashMap<u64, Vec<(String, TestTargetAttr)>>,
+    rule_types: HashMap<u64, String>,
+    tests: HashMap<u64, IndexSet<u64>>,
 }
 
 impl TestEnvBuilder {
@@ -203,17 +313,86 @@ impl TestEnvBuilder {
         self.graph.entry(to).or_default();
     }
 
+    /// Define the target in the given build file, e.g. `root//foo:BUCK`, rather than
+    /// in `root//<id>:BUCK`.
+    fn buildfile(&mut self, id: u64, buildfile: &str) {
+        self.graph.entry(id).or_default();
+        self.buildfiles.insert(id, buildfile.to_owned());
+    }
+
+    fn source(&mut self, id: u64, path: &str) {
+        self.graph.entry(id).or_default();
+        self.sources
+            .entry(id)
+            .or_default()
+            .insert(CellPath::testing_new(path));
+    }
+
+    fn load(&mut self, file: &str, loaded: &str) {
+        self.loads
+            .entry(CellPath::testing_new(file))
+            .or_default()
+            .insert(CellPath::testing_new(loaded));
+    }
+
+    fn attr(&mut self, id: u64, name: &str, value: TestTargetAttr) {
+        self.graph.entry(id).or_default();
+        self.attrs
+            .entry(id)
+            .or_default()
+            .push((name.to_owned(), value));
+    }
+
+    fn rule_type(&mut self, id: u64, rule_type: &str) {
+        self.graph.entry(id).or_default();
+        self.rule_types.insert(id, rule_type.to_owned());
+    }
+
+    fn test(&mut self, id: u64, test: u64) {
+        self.graph.entry(id).or_default();
+        self.graph.entry(test).or_default();
+        self.tests.entry(id).or_default().insert(test);
+    }
+
     fn build(&self) -> TestEnv {
         TestEnv {
             graph: self
                 .graph
                 .iter()
                 .map(|(id, vs)| {
+                    let buildfile = match self.buildfiles.get(id) {
+                        Some(buildfile) => BuildFilePath::testing_new(buildfile),
+                        None => BuildFilePath::testing_new(&format!("root//{}:BUCK", id)),
+                    };
+                    let sources = self.sources.get(id).cloned().unwrap_or_default();
+                    let attrs = self.attrs.get(id).cloned().unwrap_or_default();
+                    let rule_type = self
+                        .rule_types
+                        .get(id)
+                        .map_or("rule", |t| t.as_str())
+                        .into();
+                    let tests = self
+                        .tests
+                        .get(id)
+                        .map(|tests| tests.iter().map(|t| TestTargetId(*t)).collect())
+                        .unwrap_or_default();
                     let id = TestTargetId(*id);
                     let deps = Arc::new(vs.iter().map(|v| TestTargetId(*v)).collect());
-                    (id, TestTarget { id, deps })
+                    (
+                        id,
+                        TestTarget {
+                            id,
+                            deps,
+                            buildfile: Arc::new(buildfile),
+                            sources: Arc::new(sources),
+                            attrs: Arc::new(attrs),
+                            rule_type,
+                            tests: Arc::new(tests),
+                        },
+                    )
                 })
                 .collect(),
+            loads: self.loads.clone(),
         }
     }
 }
@@ -379,3 +558,280 @@ async fn test_rdeps() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Targets `1 -> 2 -> 3` and `4`, with sources and `.bzl` files loaded by their build files.
+/// Target `2` is a test itself, and `5` (outside the universe) is the test of `3`.
+fn affected_test_env() -> TestEnv {
+    let mut env = TestEnvBuilder::default();
+    env.edge(1, 2);
+    env.edge(2, 3);
+    env.rule_type(2, "sh_test");
+    env.rule_type(5, "cxx_test");
+    env.test(3, 5);
+    env.buildfile(1, "root//app:BUCK");
+    env.buildfile(2, "root//lib:BUCK");
+    env.buildfile(3, "root//lib/base:BUCK");
+    env.buildfile(4, "root//other:BUCK");
+    env.source(3, "root//lib/base/base.c");
+    env.source(4, "root//other/other.c");
+    env.load("root//lib/BUCK", "root//defs/lib.bzl");
+    env.load("root//defs/lib.bzl", "root//defs/common.bzl");
+    env.load("root//other/BUCK", "root//defs/common.bzl");
+    env.build()
+}
+
+async fn affected(env: &TestEnv, changed_files: &[&str]) -> anyhow::Result<Vec<u64>> {
+    affected_with_tests(env, changed_files, None).await
+}
+
+async fn affected_with_tests(
+    env: &TestEnv,
+    changed_files: &[&str],
+    tests: Option<AffectedTests>,
+) -> anyhow::Result<Vec<u64>> {
+    let changed_files = FileSet::new(
+        changed_files
+            .iter()
+            .map(|path| FileNode(CellPath::testing_new(path)))
+            .collect(),
+    );
+    let affected = DefaultQueryFunctions::new()
+        .affected(
+            env,
+            &DefaultQueryFunctionsModule::new(),
+            &env.set("1,4")?,
+            &changed_files,
+            tests,
+        )
+        .await?;
+    let mut ids: Vec<_> = affected.iter().map(|t| t.id.0).collect();
+    ids.sort();
+    Ok(ids)
+}
+
+#[tokio::test]
+async fn test_affected_sources() -> anyhow::Result<()> {
+    let env = affected_test_env();
+    assert_eq!(
+        vec![1, 2, 3],
+        affected(&env, &["root//lib/base/base.c"]).await?
+    );
+    assert_eq!(vec![4], affected(&env, &["root//other/other.c"]).await?);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_affected_build_files() -> anyhow::Result<()> {
+    let env = affected_test_env();
+    assert_eq!(vec![1, 2], affected(&env, &["root//lib/BUCK"]).await?);
+    assert_eq!(
+        vec![1, 2, 3],
+        affected(&env, &["root//lib/base/BUCK"]).await?
+    );
+    assert_eq!(vec![1, 2, 3], affected(&env, &["root//lib/PACKAGE"]).await?);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_affected_bzl_files() -> anyhow::Result<()> {
+    let env = affected_test_env();
+    assert_eq!(vec![1, 2], affected(&env, &["root//defs/lib.bzl"]).await?);
+    assert_eq!(
+        vec![1, 2, 4],
+        affected(&env, &["root//defs/common.bzl"]).await?
+    );
+    // Not loaded by the build files of the targets.
+    assert_eq!(
+        Vec::<u64>::new(),
+        affected(&env, &["root//defs/unused.bzl"]).await?
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_affected_buckconfig_files() -> anyhow::Result<()> {
+    let env = affected_test_env();
+    assert_eq!(
+        vec![1, 2, 3, 4],
+        affected(&env, &["root//.buckconfig"]).await?
+    );
+    assert_eq!(
+        vec![1, 2, 3, 4],
+        affected(&env, &["root//.buckconfig.d/tools.bcfg"]).await?
+    );
+    assert_eq!(
+        vec![1, 2, 3, 4],
+        affected(&env, &["other//.buckconfig.local"]).await?
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_affected_unowned_files() -> anyhow::Result<()> {
+    let env = affected_test_env();
+    // A deleted source is no longer owned by any target, and neither are files which are not
+    // inputs of targets. Only the buckconfig files at the root of a cell are known to be
+    // buckconfig files.
+    for path in [
+        "root//lib/base/deleted.c",
+        "root//README.md",
+        "root//.gitignore",
+        "root//lib/.buckconfig",
+        "root//tools.bcfg",
+    ] {
+        assert_eq!(Vec::<u64>::new(), affected(&env, &[path]).await?);
+        assert_eq!(
+            vec![1, 2, 3],
+            affected(&env, &[path, "root//lib/base/base.c"]).await?
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_affected_tests() -> anyhow::Result<()> {
+    let env = affected_test_env();
+    let with_tests = Some(AffectedTests::parse("with_tests")?);
+    let tests_only = Some(AffectedTests::parse("tests_only")?);
+    assert_eq!(
+        vec![1, 2, 3, 5],
+        affected_with_tests(&env, &["root//lib/base/base.c"], with_tests).await?
+    );
+    assert_eq!(
+        vec![2, 5],
+        affected_with_tests(&env, &["root//lib/base/base.c"], tests_only).await?
+    );
+    assert_eq!(
+        vec![1, 2],
+        affected_with_tests(&env, &["root//lib/BUCK"], with_tests).await?
+    );
+    assert_eq!(
+        vec![2],
+        affected_with_tests(&env, &["root//lib/BUCK"], tests_only).await?
+    );
+    // The tests are added to every target when a buckconfig file changed, too.
+    assert_eq!(
+        vec![1, 2, 3, 4, 5],
+        affected_with_tests(&env, &["root//.buckconfig"], with_tests).await?
+    );
+    assert_eq!(
+        Vec::<u64>::new(),
+        affected_with_tests(&env, &["root//other/other.c"], tests_only).await?
+    );
+    assert!(AffectedTests::parse("all").is_err());
+    Ok(())
+}
+
+/// Targets with a `timeout` and an `env` attribute, and target `3` with neither.
+fn attrfilter_test_env() -> TestEnv {
+    let mut env = TestEnvBuilder::default();
+    env.attr(1, "timeout", TestTargetAttr::Int(120));
+    env.attr(
+        1,
+        "env",
+        TestTargetAttr::Dict(vec![(
+            "FOO".to_owned(),
+            TestTargetAttr::String("bar".to_owned()),
+        )]),
+    );
+    env.attr(2, "timeout", TestTargetAttr::Int(-5));
+    env.attr(
+        2,
+        "env",
+        TestTargetAttr::Dict(vec![(
+            "BAZ".to_owned(),
+            TestTargetAttr::String("FOO".to_owned()),
+        )]),
+    );
+    env.attr(3, "name", TestTargetAttr::String("three".to_owned()));
+    env.build()
+}
+
+#[test]
+fn test_attrfilter_path() -> anyhow::Result<()> {
+    let env = attrfilter_test_env();
+    let targets = env.set("1,2,3")?;
+    let functions = DefaultQueryFunctions::<TestEnv>::new();
+
+    assert_eq!(
+        functions.attrfilter("env", "FOO", &targets)?,
+        env.set("1,2")?
+    );
+    assert_eq!(
+        functions.attrfilter("env.FOO", "bar", &targets)?,
+        env.set("1")?
+    );
+    assert_eq!(
+        functions.attrfilter("env.FOO.bar", "bar", &targets)?,
+        TargetSet::new()
+    );
+    assert_eq!(
+        functions.attrfilter("missing.FOO", "bar", &targets)?,
+        TargetSet::new()
+    );
+    // Targets with the attribute but without the path do not match it.
+    assert_eq!(
+        functions.nattrfilter("env.FOO", "bar", &targets)?,
+        env.set("2")?
+    );
+    Ok(())
+}
+
+#[test]
+fn test_attrfilter_gt() -> anyhow::Result<()> {
+    let env = attrfilter_test_env();
+    let targets = env.set("1,2,3")?;
+    let functions = DefaultQueryFunctions::<TestEnv>::new();
+
+    assert_eq!(
+        functions.attrfilter_gt("timeout", 60, &targets)?,
+        env.set("1")?
+    );
+    assert_eq!(
+        functions.attrfilter_gt("timeout", 120, &targets)?,
+        TargetSet::new()
+    );
+    assert_eq!(
+        functions.attrfilter_gt("timeout", -10, &targets)?,
+        env.set("1,2")?
+    );
+    assert_eq!(
+        functions.attrfilter_gt("env", -10, &targets)?,
+        TargetSet::new()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_attrfilter_gt_value() -> anyhow::Result<()> {
+    let env = attrfilter_test_env();
+    let accept = |value| <i64 as QueryFunctionArg<'_, TestEnv>>::accept(&env, value);
+
+    assert_eq!(60, accept(QueryValue::Integer(60)).await?);
+    assert_eq!(-10, accept(QueryValue::String("-10".to_owned())).await?);
+    assert!(accept(QueryValue::String("ten".to_owned())).await.is_err());
+    assert!(accept(QueryValue::Integer(u64::MAX)).await.is_err());
+    Ok(())
+}
+
+#[test]
+fn test_attr_contains() -> anyhow::Result<()> {
+    let env = attrfilter_test_env();
+    let targets = env.set("1,2,3")?;
+    let functions = DefaultQueryFunctions::<TestEnv>::new();
+
+    // Only dict keys are checked, not nested values.
+    assert_eq!(
+        functions.attr_contains("env", "FOO", &targets)?,
+        env.set("1")?
+    );
+    assert_eq!(
+        functions.attr_contains("env.FOO", "ba", &targets)?,
+        env.set("1")?
+    );
+    assert_eq!(
+        functions.attr_contains("name", "hre", &targets)?,
+        env.set("3")?
+    );
+    Ok(())
+}
@@ -43,10 +43,14 @@ pub enum QueryError {
     FunctionUnimplemented(&'static str),
     #[error("Argument `{1}` to `{0}` is not yet supported in buck2")]
     ArgNotYetSupported(String, String),
+    #[error("Invalid integer `{0}`, expected a value that fits in an `i64`")]
+    InvalidInteger(String),
     #[error("Invalid traversal depth `{0}`")]
     InvalidDepth(i32),
     #[error("File literal `{1}` not within the project root `{}`", .0)]
     FileLiteralNotInProject(ProjectRoot, String),
+    #[error("Invalid tests argument `{0}` to `affected`, expected `with_tests` or `tests_only`")]
+    InvalidAffectedTests(String),
     #[error("query function {0} not available in this context")]
     NotAvailableInContext(&'static str),
     #[error(
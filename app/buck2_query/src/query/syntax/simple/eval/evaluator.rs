@@ -9,18 +9,25 @@
 
 //! Implementation of the cli and query_* attr query language.
 
+use std::future::Future;
+use std::time::Instant;
+
 use buck2_query_parser::parse_expr;
 use buck2_query_parser::spanned::Spanned;
 use buck2_query_parser::Expr;
 use futures::FutureExt;
 use gazebo::prelude::*;
 use gazebo::variants::VariantName;
+use starlark_map::small_set::SmallSet;
 
 use crate::__derive_refs::indexmap::IndexSet;
 use crate::query::environment::QueryEnvironment;
 use crate::query::syntax::simple::eval::error::QueryError;
 use crate::query::syntax::simple::eval::file_set::FileNode;
 use crate::query::syntax::simple::eval::file_set::FileSet;
+use crate::query::syntax::simple::eval::literals::extract_target_literals;
+use crate::query::syntax::simple::eval::profile::profile_phase;
+use crate::query::syntax::simple::eval::profile::QueryProfile;
 use crate::query::syntax::simple::eval::set::TargetSet;
 use crate::query::syntax::simple::eval::values::QueryEvaluationValue;
 use crate::query::syntax::simple::eval::values::QueryResult;
@@ -29,11 +36,22 @@ use crate::query::syntax::simple::functions::QueryFunctions;
 pub struct QueryEvaluator<'e, Env: QueryEnvironment> {
     env: &'e Env,
     functions: &'e dyn QueryFunctions<Env = Env>,
+    profile: Option<&'e QueryProfile>,
 }
 
 impl<'e, Env: QueryEnvironment> QueryEvaluator<'e, Env> {
     pub fn new(env: &'e Env, functions: &'e dyn QueryFunctions<Env = Env>) -> Self {
-        Self { env, functions }
+        Self {
+            env,
+            functions,
+            profile: None,
+        }
+    }
+
+    /// Record evaluation time of each subexpression in the given profile.
+    pub fn with_profile(mut self, profile: Option<&'e QueryProfile>) -> Self {
+        self.profile = profile;
+        self
     }
 
     pub fn env(&self) -> &Env {
@@ -114,7 +132,15 @@ impl<'e, Env: QueryEnvironment> QueryEvaluator<'e, Env> {
     ) -> std::pin::Pin<
         Box<dyn std::future::Future<Output = QueryResult<QueryValue<Env::Target>>> + Send + 'a>,
     > {
-        async move { expr.span(self.eval_internal(&expr.value).await) }.boxed()
+        async move {
+            let start = Instant::now();
+            let result = self.eval_internal(&expr.value).await;
+            if let Some(profile) = self.profile {
+                profile.record(&expr.position, start.elapsed(), result.as_ref().ok());
+            }
+            expr.span(result)
+        }
+        .boxed()
     }
 
     pub async fn eval_query<'a>(
@@ -152,3 +178,37 @@ impl<'e, Env: QueryEnvironment> QueryEvaluator<'e, Env> {
             .await
     }
 }
+
+/// Evaluate `query` in the environment `environment` creates for the target literals of
+/// the query, recording the phases of the evaluation in `profile`.
+pub async fn eval_query_in_environment<F, Env, Fut>(
+    functions: &F,
+    query: &str,
+    profile: Option<&QueryProfile>,
+    environment: impl FnOnce(Vec<String>) -> Fut,
+) -> anyhow::Result<QueryEvaluationValue<Env::Target>>
+where
+    F: QueryFunctions<Env = Env>,
+    Env: QueryEnvironment,
+    Fut: Future<Output = anyhow::Result<Env>>,
+{
+    let mut literals = SmallSet::new();
+    profile_phase(profile, "extract literals", async {
+        extract_target_literals(functions, query, &mut literals)
+    })
+    .await?;
+    let env = profile_phase(
+        profile,
+        "create environment",
+        environment(literals.into_iter().collect()),
+    )
+    .await?;
+    profile_phase(
+        profile,
+        "evaluate",
+        QueryEvaluator::new(&env, functions)
+            .with_profile(profile)
+            .eval_query(query),
+    )
+    .await
+}
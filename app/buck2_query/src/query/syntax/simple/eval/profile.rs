@@ -0,0 +1,154 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Per-expression profiling of query evaluation (`cquery --profile`).
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::future::Future;
+use std::ops::Range;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use buck2_query_parser::parse_expr;
+use buck2_query_parser::Expr;
+use buck2_query_parser::SpannedExpr;
+use buck2_util::truncate::truncate;
+
+use crate::query::environment::QueryTarget;
+use crate::query::syntax::simple::eval::values::QueryValue;
+
+#[derive(Default, Clone, Copy, Debug)]
+struct QueryProfileEntry {
+    /// Number of times the expression was evaluated.
+    calls: u64,
+    /// Total time spent evaluating the expression, including subexpressions.
+    total: Duration,
+    /// Size of the result of the last evaluation, if the result is a set.
+    result_size: Option<usize>,
+}
+
+/// Records evaluation time and result sizes of each subexpression of a query, and the time
+/// of the phases around the evaluation, like resolving the target literals of the query.
+///
+/// Subexpressions are identified by their position in the query string, so one profile
+/// must only be used to evaluate one query.
+#[derive(Default, Debug)]
+pub struct QueryProfile {
+    entries: Mutex<HashMap<Range<usize>, QueryProfileEntry>>,
+    /// Phases in the order they started, with their time once they finished.
+    phases: Mutex<Vec<(&'static str, Option<Duration>)>>,
+}
+
+impl QueryProfile {
+    pub fn new() -> QueryProfile {
+        QueryProfile::default()
+    }
+
+    pub(crate) fn record<T: QueryTarget>(
+        &self,
+        position: &Range<usize>,
+        elapsed: Duration,
+        result: Option<&QueryValue<T>>,
+    ) {
+        let result_size = match result {
+            Some(QueryValue::TargetSet(targets)) => Some(targets.len()),
+            Some(QueryValue::FileSet(files)) => Some(files.len()),
+            Some(QueryValue::String(_) | QueryValue::Integer(_)) | None => None,
+        };
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.entry(position.clone()).or_default();
+        entry.calls += 1;
+        entry.total += elapsed;
+        entry.result_size = result_size;
+    }
+
+    async fn phase<T>(&self, phase: &'static str, fut: impl Future<Output = T>) -> T {
+        let index = {
+            let mut phases = self.phases.lock().unwrap();
+            phases.push((phase, None));
+            phases.len() - 1
+        };
+        let start = Instant::now();
+        let result = fut.await;
+        self.phases.lock().unwrap()[index].1 = Some(start.elapsed());
+        result
+    }
+
+    /// Render the recorded phases, then the expression tree of `query` annotated with
+    /// the recorded timings, similar to SQL `EXPLAIN ANALYZE`.
+    pub fn render(&self, query: &str) -> anyhow::Result<String> {
+        let expr = parse_expr(query)?;
+        let mut out = String::new();
+        for (phase, time) in self.phases.lock().unwrap().iter() {
+            match time {
+                Some(time) => writeln!(out, "phase: {}  [time: {:.3?}]", phase, time).unwrap(),
+                None => writeln!(out, "phase: {}  [not finished]", phase).unwrap(),
+            }
+        }
+        let entries = self.entries.lock().unwrap();
+        render_expr(&mut out, query, &expr, &entries, 0);
+        Ok(out)
+    }
+}
+
+/// Time `fut` as the phase `phase` of the query if it is profiled.
+pub async fn profile_phase<T>(
+    profile: Option<&QueryProfile>,
+    phase: &'static str,
+    fut: impl Future<Output = T>,
+) -> T {
+    match profile {
+        Some(profile) => profile.phase(phase, fut).await,
+        None => fut.await,
+    }
+}
+
+fn render_expr(
+    out: &mut String,
+    query: &str,
+    expr: &SpannedExpr,
+    entries: &HashMap<Range<usize>, QueryProfileEntry>,
+    depth: usize,
+) {
+    let label = match &expr.value {
+        Expr::Function { function_name, .. } => format!("{}()", function_name.fragment()),
+        Expr::BinaryOpSequence(..) => "operators".to_owned(),
+        _ => truncate(query.get(expr.position.clone()).unwrap_or_default(), 80),
+    };
+    write!(out, "{:indent$}{}", "", label, indent = depth * 2).unwrap();
+    match entries.get(&expr.position) {
+        Some(entry) => {
+            write!(out, "  [time: {:.3?}, calls: {}", entry.total, entry.calls).unwrap();
+            if let Some(size) = entry.result_size {
+                write!(out, ", size: {}", size).unwrap();
+            }
+            out.push(']');
+        }
+        None => out.push_str("  [not evaluated]"),
+    }
+    out.push('\n');
+
+    match &expr.value {
+        Expr::Function { args, .. } => {
+            for arg in args {
+                render_expr(out, query, arg, entries, depth + 1);
+            }
+        }
+        Expr::BinaryOpSequence(left, rights) => {
+            render_expr(out, query, left, entries, depth + 1);
+            for (op, right) in rights {
+                writeln!(out, "{:indent$}{}", "", op, indent = (depth + 1) * 2).unwrap();
+                render_expr(out, query, right, entries, depth + 1);
+            }
+        }
+        Expr::String(..) | Expr::Integer(..) | Expr::Set(..) | Expr::FileSet(..) => {}
+    }
+}
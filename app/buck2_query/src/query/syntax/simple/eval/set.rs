@@ -17,6 +17,7 @@ use fancy_regex::Regex;
 use fancy_regex::RegexBuilder;
 use indexmap::IndexSet;
 
+use crate::query::environment::AttrPredicate;
 use crate::query::environment::QueryTarget;
 use crate::query::syntax::simple::eval::file_set::FileNode;
 use crate::query::syntax::simple::eval::file_set::FileSet;
@@ -169,29 +170,57 @@ impl<T: QueryTarget> FromIterator<T> for TargetSet<T> {
 /// This contains additional TargetSet functions implemented via the core
 /// functions on TargetSet itself.
 impl<T: QueryTarget> TargetSet<T> {
-    pub fn attrfilter(
+    /// Evaluate `predicate` against `attribute` of `node`. The attribute may be a dotted path
+    /// into a nested value, e.g. `env.FOO` or `srcs.0`. Returns `None` if `node` has no
+    /// such attribute.
+    fn attr_path_matches(
+        node: &T,
+        attribute: &str,
+        predicate: &AttrPredicate,
+    ) -> anyhow::Result<Option<bool>> {
+        if let Some(res) = node.map_attr(attribute, |val| {
+            val.map(|v| T::attr_path_matches(v, &[], predicate))
+        }) {
+            return Ok(Some(res?));
+        }
+        match attribute.split_once('.') {
+            Some((name, path)) => {
+                let path: Vec<&str> = path.split('.').collect();
+                node.map_attr(name, |val| {
+                    val.map(|v| T::attr_path_matches(v, &path, predicate))
+                        .transpose()
+                })
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn attr_path_filter(
         &self,
         attribute: &str,
-        filter: &dyn Fn(&str) -> anyhow::Result<bool>,
+        predicate: &AttrPredicate,
     ) -> anyhow::Result<TargetSet<T>> {
         self.filter(move |node| {
-            node.map_attr(attribute, |val| match val {
-                None => Ok(false),
-                Some(v) => T::attr_any_matches(v, &filter),
-            })
+            Ok(Self::attr_path_matches(node, attribute, predicate)?.unwrap_or(false))
         })
     }
 
+    pub fn attrfilter(
+        &self,
+        attribute: &str,
+        filter: &dyn Fn(&str) -> anyhow::Result<bool>,
+    ) -> anyhow::Result<TargetSet<T>> {
+        self.attr_path_filter(attribute, &AttrPredicate::AnyMatches(filter))
+    }
+
     pub(crate) fn nattrfilter(
         &self,
         attribute: &str,
         filter: &dyn Fn(&str) -> anyhow::Result<bool>,
     ) -> anyhow::Result<TargetSet<T>> {
+        let predicate = AttrPredicate::AnyMatches(filter);
         self.filter(move |node| {
-            node.map_attr(attribute, |val| match val {
-                None => Ok(false),
-                Some(v) => Ok(!T::attr_any_matches(v, &filter)?),
-            })
+            Ok(Self::attr_path_matches(node, attribute, &predicate)?.map_or(false, |m| !m))
         })
     }
 
@@ -12,6 +12,7 @@
 #![cfg(test)]
 
 use std::borrow::Cow;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use buck2_core::build_file_path::BuildFilePath;
@@ -27,9 +28,13 @@ use crate::query::graph::node::LabeledNode;
 use crate::query::graph::node::NodeKey;
 use crate::query::graph::successors::AsyncChildVisitor;
 use crate::query::syntax::simple::eval::error::QueryError;
+use crate::query::syntax::simple::eval::evaluator::eval_query_in_environment;
 use crate::query::syntax::simple::eval::evaluator::QueryEvaluator;
 use crate::query::syntax::simple::eval::file_set::FileSet;
+use crate::query::syntax::simple::eval::profile::profile_phase;
+use crate::query::syntax::simple::eval::profile::QueryProfile;
 use crate::query::syntax::simple::eval::set::TargetSet;
+use crate::query::syntax::simple::eval::values::QueryValue;
 use crate::query::syntax::simple::functions::DefaultQueryFunctionsModule;
 
 #[derive(Clone, Hash, PartialEq, Eq, Debug, Display)]
@@ -121,7 +126,7 @@ impl QueryEnvironment for Env {
     }
 
     async fn eval_literals(&self, _literal: &[&str]) -> anyhow::Result<TargetSet<Self::Target>> {
-        unimplemented!()
+        Ok(TargetSet::new())
     }
 
     async fn eval_file_literal(&self, _literal: &str) -> anyhow::Result<FileSet> {
@@ -172,3 +177,67 @@ pub async fn test_missing_arg() -> anyhow::Result<()> {
     }
     Ok(())
 }
+
+#[test]
+fn test_profile_render() -> anyhow::Result<()> {
+    let input = "kind(lib, deps(//foo:bar)) + //baz:qux";
+    let parsed = parse_expr(input)?;
+    let profile = QueryProfile::new();
+    profile.record::<Target>(
+        &parsed.position,
+        Duration::from_millis(3),
+        Some(&QueryValue::Integer(1)),
+    );
+    assert_eq!(
+        "operators  [time: 3.000ms, calls: 1]\n\
+         \x20 kind()  [not evaluated]\n\
+         \x20   lib  [not evaluated]\n\
+         \x20   deps()  [not evaluated]\n\
+         \x20     //foo:bar  [not evaluated]\n\
+         \x20 +\n\
+         \x20 //baz:qux  [not evaluated]\n",
+        profile.render(input)?
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_profile_phases() -> anyhow::Result<()> {
+    let query = "kind(lib, //foo:bar) + //baz:qux";
+    let profile = QueryProfile::new();
+    let result = eval_query_in_environment(
+        &DefaultQueryFunctionsModule::new(),
+        query,
+        Some(&profile),
+        |literals| {
+            let profile = &profile;
+            async move {
+                assert_eq!(vec!["//foo:bar", "//baz:qux"], literals);
+                profile_phase(Some(profile), "resolve literals", async { Ok(Env) }).await
+            }
+        },
+    )
+    .await?;
+    assert_eq!(0, result.try_into_targets()?.len());
+
+    let rendered = profile.render(query)?;
+    let lines: Vec<_> = rendered.lines().collect();
+    let phases: Vec<_> = lines
+        .iter()
+        .filter_map(|line| line.strip_prefix("phase: "))
+        .map(|line| line.split("  [time: ").next().unwrap())
+        .collect();
+    assert_eq!(
+        vec![
+            "extract literals",
+            "create environment",
+            "resolve literals",
+            "evaluate"
+        ],
+        phases
+    );
+    assert!(lines[4].starts_with("operators  [time: "), "{}", rendered);
+    assert!(lines[5].starts_with("  kind()  [time: "), "{}", rendered);
+    assert!(lines[5].ends_with(", calls: 1, size: 0]"), "{}", rendered);
+    Ok(())
+}
@@ -274,6 +274,26 @@ impl<'a, Env: QueryEnvironment> QueryFunctionArg<'a, Env> for u64 {
     }
 }
 
+/// Signed integers. Query integer literals are unsigned, so negative values are written as
+/// words, e.g. `-1`, and parsed here.
+#[async_trait]
+impl<'a, Env: QueryEnvironment> QueryFunctionArg<'a, Env> for i64 {
+    const ARG_TYPE: QueryArgType = QueryArgType::Integer;
+
+    async fn accept(_env: &Env, val: QueryValue<Env::Target>) -> Result<Self, QueryError> {
+        match val {
+            QueryValue::Integer(v) => {
+                i64::try_from(v).map_err(|_| QueryError::InvalidInteger(v.to_string()))
+            }
+            QueryValue::String(v) => v.parse().map_err(|_| QueryError::InvalidInteger(v)),
+            _ => Err(QueryError::InvalidType {
+                expected: "int",
+                actual: val.variant_name(),
+            }),
+        }
+    }
+}
+
 /// `Option<T>` is used for optional args and so this is the (only?) implementation that supports a "none" arg.
 #[async_trait]
 impl<'a, Env: QueryEnvironment, A: QueryFunctionArg<'a, Env>> QueryFunctionArg<'a, Env>
@@ -13,16 +13,20 @@ use std::marker::PhantomData;
 
 use allocative::Allocative;
 use async_trait::async_trait;
+use buck2_core::cells::cell_path::CellPath;
 use buck2_core::configuration::compatibility::MaybeCompatible;
 use buck2_query_derive::query_module;
 use buck2_query_parser::spanned::Spanned;
 use buck2_query_parser::BinaryOp;
 use buck2_query_parser::Expr;
 use gazebo::variants::VariantName;
+use indexmap::IndexSet;
 
+use crate::query::environment::AttrPredicate;
 use crate::query::environment::QueryEnvironment;
 use crate::query::syntax::simple::eval::error::QueryError;
 use crate::query::syntax::simple::eval::evaluator::QueryEvaluator;
+use crate::query::syntax::simple::eval::file_set::FileNode;
 use crate::query::syntax::simple::eval::file_set::FileSet;
 use crate::query::syntax::simple::eval::set::TargetSet;
 use crate::query::syntax::simple::eval::values::QueryResult;
@@ -236,6 +240,9 @@ impl<Env: QueryEnvironment> DefaultQueryFunctionsModule<Env> {
     ///
     /// For example:
     /// `buck2 query "attrfilter(deps, '//foo:bar', '//...')"` returns the build targets in the repository that depend on `//foo:bar`, or more precisely: those build targets that include `//foo:bar` in their deps argument list.
+    ///
+    /// The attribute may be a dotted path into a nested value, where each element is a dict key or a list index.
+    /// For example, `attrfilter(env.FOO, bar, '//...')` returns the targets whose `env` dictionary maps `FOO` to `bar`.
     async fn attrfilter(
         &self,
         attr: String,
@@ -272,6 +279,43 @@ impl<Env: QueryEnvironment> DefaultQueryFunctionsModule<Env> {
             .into())
     }
 
+    /// The `attrfilter_gt(attribute, value, targets)` operator filters targets to those where the specified attribute
+    /// (or dotted attribute path, as in `attrfilter`) is an integer greater than the specified value.
+    ///
+    /// For example:
+    /// `buck2 cquery "attrfilter_gt(labels_meta.timeout, 60, '//...')"`
+    ///
+    /// The value may be negative, e.g. `attrfilter_gt(priority, -1, '//...')`.
+    async fn attrfilter_gt(
+        &self,
+        attr: String,
+        value: i64,
+        targets: TargetSet<Env::Target>,
+    ) -> QueryFuncResult<Env> {
+        Ok(self
+            .implementation
+            .attrfilter_gt(&attr, value, &targets)?
+            .into())
+    }
+
+    /// The `attr_contains(attribute, value, targets)` operator filters targets to those where the specified attribute
+    /// (or dotted attribute path, as in `attrfilter`) contains the specified value: a list or tuple with an element
+    /// equal to the value, a dict with the value as a key, or a string with the value as a substring.
+    ///
+    /// Unlike `attrfilter`, nested values are not searched, so `attr_contains(env, FOO, '//...')` only matches targets
+    /// whose `env` has a `FOO` key, not a `FOO` value.
+    async fn attr_contains(
+        &self,
+        attr: String,
+        value: String,
+        targets: TargetSet<Env::Target>,
+    ) -> QueryFuncResult<Env> {
+        Ok(self
+            .implementation
+            .attr_contains(&attr, &value, &targets)?
+            .into())
+    }
+
     async fn buildfile(&self, targets: TargetSet<Env::Target>) -> QueryFuncResult<Env> {
         Ok(self.implementation.buildfile(&targets).into())
     }
@@ -388,6 +432,48 @@ impl<Env: QueryEnvironment> DefaultQueryFunctionsModule<Env> {
         Ok(self.implementation.testsof(env, &targets).await?.into())
     }
 
+    /// The `affected(universe, changed_files)` function returns the targets in the transitive
+    /// closure of `universe` which are affected by changes to `changed_files`.
+    ///
+    /// A target is affected if it (transitively) depends on a target which:
+    /// - owns one of the changed source files (as with `owner()`),
+    /// - is defined in a build file which is changed or (transitively) loads a changed `.bzl`
+    ///   file (as with `rbuildfiles()`),
+    /// - is in a package covered by a changed `PACKAGE` file.
+    ///
+    /// If a buckconfig file of a cell (`.buckconfig`, `.buckconfig.local` or a file in
+    /// `.buckconfig.d`) changed, every target in the transitive closure of `universe` is
+    /// considered affected.
+    ///
+    /// Other changed files, such as a `.bzl` file the build files of the targets don't load, a
+    /// deleted source or a `README.md`, affect no target.
+    ///
+    /// The optional third argument adds the tests of the affected targets (as with `testsof()`):
+    /// `'with_tests'` returns the affected targets and their tests, `'tests_only'` returns their
+    /// tests and the affected targets whose rule type ends with `_test`.
+    ///
+    /// Example: `buck2 cquery "affected(//..., fileset(foo/bar.cpp foo/defs.bzl), 'with_tests')"`
+    async fn affected(
+        &self,
+        evaluator: &QueryEvaluator<'_, Env>,
+        universe: TargetSet<Env::Target>,
+        changed_files: FileSet,
+        tests: Option<String>,
+    ) -> QueryFuncResult<Env> {
+        let tests = tests.as_deref().map(AffectedTests::parse).transpose()?;
+        Ok(self
+            .implementation
+            .affected(
+                evaluator.env(),
+                evaluator.functions(),
+                &universe,
+                &changed_files,
+                tests,
+            )
+            .await?
+            .into())
+    }
+
     // These three functions are intentionally implemented as errors. They are only available within the context
     // of a deps functions 3rd parameter expr. When used in that context, the QueryFunctions will be augmented to
     // have non-erroring implementations.
@@ -484,6 +570,25 @@ impl<Env: QueryEnvironment> DefaultQueryFunctionsModule<Env> {
     }
 }
 
+/// Which tests `affected()` adds to the affected targets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AffectedTests {
+    /// The affected targets and their tests.
+    WithTests,
+    /// The tests of the affected targets and the affected targets which are tests themselves.
+    TestsOnly,
+}
+
+impl AffectedTests {
+    pub(crate) fn parse(value: &str) -> Result<Self, QueryError> {
+        match value {
+            "with_tests" => Ok(AffectedTests::WithTests),
+            "tests_only" => Ok(AffectedTests::TestsOnly),
+            _ => Err(QueryError::InvalidAffectedTests(value.to_owned())),
+        }
+    }
+}
+
 #[derive(Allocative)]
 #[allocative(bound = "")]
 pub struct DefaultQueryFunctions<Env: QueryEnvironment> {
@@ -564,6 +669,24 @@ impl<Env: QueryEnvironment> DefaultQueryFunctions<Env> {
         targets.attrregexfilter(attr, value)
     }
 
+    pub fn attrfilter_gt(
+        &self,
+        attr: &str,
+        value: i64,
+        targets: &TargetSet<Env::Target>,
+    ) -> anyhow::Result<TargetSet<Env::Target>> {
+        targets.attr_path_filter(attr, &AttrPredicate::GreaterThan(value))
+    }
+
+    pub fn attr_contains(
+        &self,
+        attr: &str,
+        value: &str,
+        targets: &TargetSet<Env::Target>,
+    ) -> anyhow::Result<TargetSet<Env::Target>> {
+        targets.attr_path_filter(attr, &AttrPredicate::Contains(value))
+    }
+
     pub fn buildfile(&self, targets: &TargetSet<Env::Target>) -> FileSet {
         targets.buildfile()
     }
@@ -651,6 +774,78 @@ impl<Env: QueryEnvironment> DefaultQueryFunctions<Env> {
         env.testsof(targets).await
     }
 
+    pub async fn affected(
+        &self,
+        env: &Env,
+        functions: &dyn QueryFunctions<Env = Env>,
+        universe: &TargetSet<Env::Target>,
+        changed_files: &FileSet,
+        tests: Option<AffectedTests>,
+    ) -> anyhow::Result<TargetSet<Env::Target>> {
+        let affected = self
+            .affected_targets(env, functions, universe, changed_files)
+            .await?;
+        match tests {
+            None => Ok(affected),
+            Some(AffectedTests::WithTests) => Ok(affected.union(&env.testsof(&affected).await?)),
+            Some(AffectedTests::TestsOnly) => Ok(env
+                .testsof(&affected)
+                .await?
+                .union(&affected.kind("_test$")?)),
+        }
+    }
+
+    async fn affected_targets(
+        &self,
+        env: &Env,
+        functions: &dyn QueryFunctions<Env = Env>,
+        universe: &TargetSet<Env::Target>,
+        changed_files: &FileSet,
+    ) -> anyhow::Result<TargetSet<Env::Target>> {
+        let closure = self.deps(env, functions, universe, None, None).await?;
+        // Build files of the targets and the `.bzl` files they (transitively) load.
+        let all_build_files = env.allbuildfiles(&closure).await?;
+
+        let mut roots = TargetSet::new();
+        let mut build_files = IndexSet::new();
+        let mut package_dirs = Vec::new();
+        let mut config_changed = false;
+        for path in changed_files.iter() {
+            let node = FileNode(path.clone());
+            if is_buckconfig_file(path) {
+                config_changed = true;
+            } else if path.path().file_name().map(|n| n.as_str()) == Some("PACKAGE") {
+                package_dirs.extend(path.parent().map(|p| p.to_owned()));
+            } else if all_build_files.contains(&node) {
+                build_files.insert(node);
+            } else if !path.path().as_str().ends_with(".bzl") {
+                // Files no target owns (anymore) affect no target.
+                roots.extend(&env.owner(&FileSet::new(IndexSet::from([node]))).await?);
+            }
+        }
+
+        if config_changed {
+            return Ok(closure);
+        }
+
+        let affected_build_files = if build_files.is_empty() {
+            FileSet::new(IndexSet::new())
+        } else {
+            env.rbuildfiles(&all_build_files, &FileSet::new(build_files))
+                .await?
+        };
+
+        roots.extend(closure.filter(|t| {
+            let buildfile = t.buildfile_path();
+            Ok(affected_build_files.contains(&FileNode(buildfile.path()))
+                || package_dirs
+                    .iter()
+                    .any(|dir| buildfile.package().as_cell_path().starts_with(dir.as_ref())))
+        })?);
+
+        env.rdeps(&closure, &roots, None).await
+    }
+
     pub async fn testsof_with_default_target_platform(
         &self,
         env: &Env,
@@ -725,6 +920,14 @@ impl<Env: QueryEnvironment> DefaultQueryFunctions<Env> {
     }
 }
 
+/// Whether a changed file is one of the buckconfig files every cell reads from its root
+/// (`.buckconfig`, `.buckconfig.local` and the files in `.buckconfig.d`), which may affect
+/// any target.
+fn is_buckconfig_file(path: &CellPath) -> bool {
+    let path = path.path().as_str();
+    path == ".buckconfig" || path == ".buckconfig.local" || path.starts_with(".buckconfig.d/")
+}
+
 pub struct AugmentedQueryFunctions<'a, Env: QueryEnvironment> {
     inner: &'a dyn QueryFunctions<Env = Env>,
     extra: Box<dyn QueryFunctions<Env = Env> + 'a>,
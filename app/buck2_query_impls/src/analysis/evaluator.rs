@@ -13,16 +13,20 @@ use anyhow::Context;
 use buck2_common::scope::scope_and_collect_with_dispatcher;
 use buck2_events::dispatch::EventDispatcher;
 use buck2_query::query::environment::QueryEnvironment;
-use buck2_query::query::syntax::simple::eval::evaluator::QueryEvaluator;
-use buck2_query::query::syntax::simple::eval::literals::extract_target_literals;
+use buck2_query::query::syntax::simple::eval::evaluator::eval_query_in_environment;
 use buck2_query::query::syntax::simple::eval::multi_query::MultiQueryResult;
+use buck2_query::query::syntax::simple::eval::profile::QueryProfile;
 use buck2_query::query::syntax::simple::eval::values::QueryEvaluationResult;
-use buck2_query::query::syntax::simple::eval::values::QueryEvaluationValue;
 use buck2_query::query::syntax::simple::functions::QueryFunctions;
 use buck2_query_parser::multi_query::MaybeMultiQuery;
 use buck2_query_parser::multi_query::MultiQueryItem;
 use futures::Future;
-use starlark::collections::SmallSet;
+
+#[derive(Debug, buck2_error::Error)]
+enum QueryProfileError {
+    #[error("Query profiling is not supported for multi-queries (queries with `%s` or `%Ss`)")]
+    MultiQuery,
+}
 
 pub(crate) async fn eval_query<
     F: QueryFunctions<Env = Env>,
@@ -34,41 +38,25 @@ pub(crate) async fn eval_query<
     functions: &F,
     query: &str,
     query_args: &[A],
+    profile: Option<&QueryProfile>,
     environment: impl Fn(Vec<String>) -> Fut + Send + Sync,
 ) -> anyhow::Result<QueryEvaluationResult<Env::Target>> {
     let query = MaybeMultiQuery::parse(query, query_args)?;
     match query {
         MaybeMultiQuery::MultiQuery(queries) => {
+            if profile.is_some() {
+                return Err(QueryProfileError::MultiQuery.into());
+            }
             let results = process_multi_query(dispatcher, functions, environment, &queries).await?;
             Ok(QueryEvaluationResult::Multiple(results))
         }
         MaybeMultiQuery::SingleQuery(query) => {
-            let result = eval_single_query(functions, &query, environment).await?;
+            let result = eval_query_in_environment(functions, &query, profile, environment).await?;
             Ok(QueryEvaluationResult::Single(result))
         }
     }
 }
 
-async fn eval_single_query<
-    F: QueryFunctions<Env = Env>,
-    Env: QueryEnvironment,
-    Fut: Future<Output = anyhow::Result<Env>>,
->(
-    functions: &F,
-    query: &str,
-    environment: impl Fn(Vec<String>) -> Fut,
-) -> anyhow::Result<QueryEvaluationValue<<Env as QueryEnvironment>::Target>>
-where
-    F: QueryFunctions<Env = Env>,
-    Env: QueryEnvironment,
-    Fut: Future<Output = anyhow::Result<Env>>,
-{
-    let mut literals = SmallSet::new();
-    extract_target_literals(functions, query, &mut literals)?;
-    let env = environment(literals.into_iter().collect()).await?;
-    QueryEvaluator::new(&env, functions).eval_query(query).await
-}
-
 async fn process_multi_query<Env, EnvFut, Qf>(
     dispatcher: EventDispatcher,
     functions: &Qf,
@@ -89,7 +77,7 @@ where
                 let env = &env;
                 scope.spawn_cancellable(
                     async move {
-                        let result = eval_single_query(functions, &query.query, env);
+                        let result = eval_query_in_environment(functions, &query.query, None, env);
                         let result = result.await;
                         (i, arg, result)
                     },
@@ -15,6 +15,7 @@ use buck2_build_api::actions::query::ActionQueryNode;
 use buck2_build_api::actions::query::ActionQueryNodeRef;
 use buck2_build_api::analysis::AnalysisResult;
 use buck2_build_api::artifact_groups::ArtifactGroup;
+use buck2_build_api::audit_output::AuditOutputResult;
 use buck2_core::configuration::compatibility::MaybeCompatible;
 use buck2_core::provider::label::ConfiguredProvidersLabel;
 use buck2_query::query::environment::QueryEnvironment;
@@ -53,6 +54,10 @@ pub(crate) trait AqueryDelegate: Send + Sync {
         configured_label: &ConfiguredProvidersLabel,
         analysis: AnalysisResult,
     ) -> anyhow::Result<TargetSet<ActionQueryNode>>;
+
+    /// Find the action which produced a buck-out path, like `buck2 audit output`.
+    async fn owner_of_output(&self, output_path: &str)
+    -> anyhow::Result<Option<AuditOutputResult>>;
 }
 
 pub(crate) struct AqueryEnvironment<'c> {
@@ -47,6 +47,7 @@ impl AqueryEvaluator<'_, '_> {
             &functions,
             query,
             query_args,
+            None,
             async move |literals| {
                 let resolved_literals = PreresolvedQueryLiterals::pre_resolve(
                     &**self.dice_query_delegate.query_data(),
@@ -14,6 +14,9 @@ use std::marker::PhantomData;
 use buck2_artifact::artifact::provide_outputs::ProvideActionKey;
 use buck2_build_api::actions::query::ActionQueryNode;
 use buck2_build_api::actions::query::ActionQueryNodeData;
+use buck2_build_api::audit_output::AuditOutputResult;
+use buck2_core::target::label::TargetLabel;
+use buck2_events::dispatch::console_warning;
 use buck2_query::query::syntax::simple::eval::error::QueryError;
 use buck2_query::query::syntax::simple::eval::set::TargetSet;
 use buck2_query::query::syntax::simple::eval::values::QueryValue;
@@ -64,6 +67,14 @@ pub(crate) fn aquery_functions<'a>() -> impl QueryFunctions<Env = AqueryEnvironm
     }
 }
 
+#[derive(Debug, buck2_error::Error)]
+enum AqueryFunctionsError {
+    #[error(
+        "Failed to find an action that produced `{0}` in the configurations known to the daemon. The most relevant target is `{1}`"
+    )]
+    OwnerNotFound(String, TargetLabel),
+}
+
 #[derive(Debug)]
 pub(crate) struct AqueryFunctions<'a>(pub(crate) PhantomData<&'a ()>);
 
@@ -134,4 +145,149 @@ impl<'a> AqueryFunctions<'a> {
 
         Ok(res.into())
     }
+
+    /// Obtain the action which produced the given output path, which is either absolute or
+    /// relative to the project root (starting with `buck-out`), e.g.
+    /// `owner_of_output(buck-out/v2/gen/root/<cfg_hash>/foo/__bar__/out.txt)`.
+    ///
+    /// Outputs of subtargets are found too, since they are declared by the analysis of the
+    /// target. If the configuration hash in the path is not one known to the daemon (as for
+    /// content-based output paths), the action is searched for in the target's default
+    /// configuration, and a warning is shown since it may not be the action which produced the
+    /// path.
+    ///
+    /// Returns an empty set if no action produced the path.
+    pub(crate) async fn owner_of_output(
+        &self,
+        env: &AqueryEnvironment<'a>,
+        path: String,
+    ) -> Result<QueryValue<ActionQueryNode>, QueryError> {
+        match env.delegate.owner_of_output(&path).await? {
+            Some(AuditOutputResult::Match(node)) => Ok(TargetSet::from_iter([node]).into()),
+            Some(AuditOutputResult::ApproximateMatch(node)) => {
+                console_warning(AuditOutputResult::approximate_match_warning(&path));
+                Ok(TargetSet::from_iter([node]).into())
+            }
+            Some(AuditOutputResult::MaybeRelevant(label)) => {
+                Err(anyhow::anyhow!(AqueryFunctionsError::OwnerNotFound(path, label)).into())
+            }
+            None => Ok(TargetSet::new().into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+    use buck2_artifact::actions::key::ActionKey;
+    use buck2_build_api::actions::query::ActionQueryNode;
+    use buck2_build_api::analysis::AnalysisResult;
+    use buck2_build_api::artifact_groups::ArtifactGroup;
+    use buck2_build_api::audit_output::AuditOutputResult;
+    use buck2_core::provider::label::ConfiguredProvidersLabel;
+    use buck2_core::target::label::TargetLabel;
+    use buck2_query::query::syntax::simple::eval::set::TargetSet;
+    use buck2_query::query::syntax::simple::eval::values::QueryValue;
+    use dice::DiceComputations;
+
+    use crate::aquery::environment::AqueryDelegate;
+    use crate::aquery::environment::AqueryEnvironment;
+    use crate::aquery::functions::AqueryFunctions;
+    use crate::cquery::environment::CqueryDelegate;
+    use crate::uquery::environment::QueryLiterals;
+
+    const OUTPUT_PATH: &str = "buck-out/v2/gen/root/abc/foo/__bar__/out.txt";
+
+    /// Finds no action producing `OUTPUT_PATH`, and returns the given target as the most
+    /// relevant one, if any.
+    struct OwnerOfOutputDelegate(Option<TargetLabel>);
+
+    #[async_trait]
+    impl AqueryDelegate for OwnerOfOutputDelegate {
+        fn cquery_delegate(&self) -> &dyn CqueryDelegate {
+            unimplemented!()
+        }
+
+        fn ctx(&self) -> &DiceComputations {
+            unimplemented!()
+        }
+
+        async fn get_node(&self, _key: &ActionKey) -> anyhow::Result<ActionQueryNode> {
+            unimplemented!()
+        }
+
+        async fn expand_artifacts(
+            &self,
+            _artifacts: &[ArtifactGroup],
+        ) -> anyhow::Result<Vec<ActionQueryNode>> {
+            unimplemented!()
+        }
+
+        async fn get_target_set_from_analysis(
+            &self,
+            _configured_label: &ConfiguredProvidersLabel,
+            _analysis: AnalysisResult,
+        ) -> anyhow::Result<TargetSet<ActionQueryNode>> {
+            unimplemented!()
+        }
+
+        async fn owner_of_output(
+            &self,
+            output_path: &str,
+        ) -> anyhow::Result<Option<AuditOutputResult>> {
+            assert_eq!(OUTPUT_PATH, output_path);
+            Ok(self.0.clone().map(AuditOutputResult::MaybeRelevant))
+        }
+    }
+
+    struct NoLiterals;
+
+    #[async_trait]
+    impl QueryLiterals<ActionQueryNode> for NoLiterals {
+        async fn eval_literals(
+            &self,
+            _literals: &[&str],
+            _dice: &DiceComputations<'_>,
+        ) -> anyhow::Result<TargetSet<ActionQueryNode>> {
+            unimplemented!()
+        }
+    }
+
+    async fn owner_of_output(
+        most_relevant: Option<TargetLabel>,
+    ) -> anyhow::Result<TargetSet<ActionQueryNode>> {
+        let env = AqueryEnvironment::new(
+            Arc::new(OwnerOfOutputDelegate(most_relevant)),
+            Arc::new(NoLiterals),
+        );
+        match AqueryFunctions(PhantomData)
+            .owner_of_output(&env, OUTPUT_PATH.to_owned())
+            .await
+        {
+            Ok(QueryValue::TargetSet(targets)) => Ok(targets),
+            Ok(_) => panic!("owner_of_output() should return a target set"),
+            Err(e) => Err(anyhow::anyhow!("{}", e)),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_owner_of_output_not_found() {
+        assert!(owner_of_output(None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_owner_of_output_unknown_configuration() {
+        let err = owner_of_output(Some(TargetLabel::testing_parse("root//foo:bar")))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            format!(
+                "Failed to find an action that produced `{OUTPUT_PATH}` in the configurations known to the daemon. The most relevant target is `root//foo:bar`"
+            ),
+            err.to_string()
+        );
+    }
 }
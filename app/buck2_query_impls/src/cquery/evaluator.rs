@@ -18,6 +18,8 @@ use buck2_core::fs::project_rel_path::ProjectRelativePath;
 use buck2_events::dispatch::console_message;
 use buck2_node::configured_universe::CqueryUniverse;
 use buck2_node::nodes::configured::ConfiguredTargetNode;
+use buck2_query::query::syntax::simple::eval::profile::profile_phase;
+use buck2_query::query::syntax::simple::eval::profile::QueryProfile;
 use buck2_query::query::syntax::simple::eval::values::QueryEvaluationResult;
 use buck2_query::query::syntax::simple::functions::DefaultQueryFunctionsModule;
 use dice::DiceComputations;
@@ -47,8 +49,9 @@ impl CqueryEvaluator<'_, '_> {
         query: &str,
         query_args: &[A],
         target_universe: Option<&[U]>,
+        profile: Option<&QueryProfile>,
     ) -> anyhow::Result<QueryEvaluationResult<ConfiguredTargetNode>> {
-        eval_query(self.dice_query_delegate.ctx().per_transaction_data().get_dispatcher().dupe(), &self.functions, query, query_args, async move |literals| {
+        eval_query(self.dice_query_delegate.ctx().per_transaction_data().get_dispatcher().dupe(), &self.functions, query, query_args, profile, async move |literals| {
             let (universe, resolved_literals) = match target_universe {
                 None => {
                     if literals.is_empty() {
@@ -60,11 +63,11 @@ impl CqueryEvaluator<'_, '_> {
                     }
                     // In the absence of a user-provided target universe, we use the target
                     // literals in the cquery as the universe.
-                    resolve_literals_in_universe(&self.dice_query_delegate, self.dice_query_delegate.query_data().dupe(), &literals, &literals)
+                    resolve_literals_in_universe(&self.dice_query_delegate, self.dice_query_delegate.query_data().dupe(), &literals, &literals, profile)
                         .await?
                 }
                 Some(universe) => {
-                    resolve_literals_in_universe(&self.dice_query_delegate, self.dice_query_delegate.query_data().dupe(), &literals, universe)
+                    resolve_literals_in_universe(&self.dice_query_delegate, self.dice_query_delegate.query_data().dupe(), &literals, universe, profile)
                         .await?
                 }
             };
@@ -118,6 +121,7 @@ async fn resolve_literals_in_universe<L: AsRef<str>, U: AsRef<str>>(
     query_literals: Arc<DiceQueryData>,
     literals: &[L],
     universe: &[U],
+    profile: Option<&QueryProfile>,
 ) -> anyhow::Result<(
     CqueryUniverse,
     PreresolvedQueryLiterals<ConfiguredTargetNode>,
@@ -125,11 +129,13 @@ async fn resolve_literals_in_universe<L: AsRef<str>, U: AsRef<str>>(
     // TODO(cjhopman): We should probably also resolve the literals to TargetNode so that
     // we can get errors for packages or targets that don't exist or fail to load.
     let refs: Vec<_> = universe.map(|v| v.as_ref());
-    let universe_resolved = query_literals
-        .eval_literals(&refs, dice_query_delegate.ctx())
-        .await?;
-
-    let universe = CqueryUniverse::build(&universe_resolved)?;
+    let universe = profile_phase(profile, "configure universe", async {
+        let universe_resolved = query_literals
+            .eval_literals(&refs, dice_query_delegate.ctx())
+            .await?;
+        anyhow::Ok(CqueryUniverse::build(&universe_resolved)?)
+    })
+    .await?;
 
     // capture a reference so the ref can be moved into the future below.
     let universe_ref = &universe;
@@ -150,6 +156,6 @@ async fn resolve_literals_in_universe<L: AsRef<str>, U: AsRef<str>>(
         })
         .collect();
 
-    let resolved = resolution_futs.collect().await;
+    let resolved = profile_phase(profile, "resolve literals", resolution_futs.collect()).await;
     Ok((universe, PreresolvedQueryLiterals::new(resolved)))
 }
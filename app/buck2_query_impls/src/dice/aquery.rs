@@ -9,6 +9,7 @@
 
 use std::future::Future;
 use std::hash::Hash;
+use std::path::Path;
 use std::sync::Arc;
 
 use anyhow::Context;
@@ -26,10 +27,14 @@ use buck2_build_api::analysis::AnalysisResult;
 use buck2_build_api::artifact_groups::ArtifactGroup;
 use buck2_build_api::artifact_groups::ResolvedArtifactGroup;
 use buck2_build_api::artifact_groups::TransitiveSetProjectionKey;
+use buck2_build_api::audit_output::audit_output;
+use buck2_build_api::audit_output::AuditOutputResult;
 use buck2_build_api::deferred::calculation::DeferredCalculation;
 use buck2_build_api::keep_going;
 use buck2_core::configuration::compatibility::MaybeCompatible;
 use buck2_core::fs::artifact_path_resolver::ArtifactFs;
+use buck2_core::fs::paths::abs_path::AbsPath;
+use buck2_core::fs::project::ProjectRoot;
 use buck2_core::pattern::ParsedPattern;
 use buck2_core::provider::label::ConfiguredProvidersLabel;
 use buck2_node::target_calculation::ConfiguredTargetCalculation;
@@ -347,6 +352,38 @@ impl<'c, 'd> AqueryDelegate for DiceAqueryDelegate<'c, 'd> {
         )
         .await
     }
+
+    async fn owner_of_output(
+        &self,
+        output_path: &str,
+    ) -> anyhow::Result<Option<AuditOutputResult>> {
+        let query_data = self.base_delegate.query_data();
+        let literal_parser = query_data.literal_parser();
+        let output_path = project_relative_output_path(&literal_parser.project_root, output_path)?;
+        let working_dir = literal_parser
+            .cell_resolver
+            .resolve_path(literal_parser.working_dir.as_ref())?;
+        audit_output(
+            &output_path,
+            &working_dir,
+            &literal_parser.cell_resolver,
+            &mut self.base_delegate.ctx().bad_dice(/* query */),
+            query_data.global_cfg_options(),
+            true,
+        )
+        .await
+    }
+}
+
+/// Accept absolute output paths too, but the buck-out path parser wants them project relative.
+fn project_relative_output_path(
+    project_root: &ProjectRoot,
+    output_path: &str,
+) -> anyhow::Result<String> {
+    match AbsPath::new(Path::new(output_path)) {
+        Ok(path) => Ok(project_root.relativize_any(path)?.as_str().to_owned()),
+        Err(_) => Ok(output_path.to_owned()),
+    }
 }
 
 async fn get_target_set_from_analysis_inner(
@@ -450,10 +487,13 @@ mod tests {
     use std::task::Poll;
 
     use assert_matches::assert_matches;
+    use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+    use buck2_core::fs::project::ProjectRoot;
     use dupe::Dupe;
     use futures::pin_mut;
     use futures::poll;
 
+    use crate::dice::aquery::project_relative_output_path;
     use crate::dice::aquery::NodeCache;
 
     #[tokio::test]
@@ -480,4 +520,34 @@ mod tests {
         assert_matches!(poll!(&mut fut1), Poll::Ready(1));
         assert_matches!(poll!(&mut fut2), Poll::Ready(1));
     }
+
+    #[test]
+    fn test_project_relative_output_path() {
+        let (root, inside, outside) = if cfg!(windows) {
+            (
+                "c:/repo",
+                "c:/repo/buck-out/v2/gen/root/abc/foo/out.txt",
+                "c:/elsewhere/buck-out/v2/gen/root/abc/foo/out.txt",
+            )
+        } else {
+            (
+                "/repo",
+                "/repo/buck-out/v2/gen/root/abc/foo/out.txt",
+                "/elsewhere/buck-out/v2/gen/root/abc/foo/out.txt",
+            )
+        };
+        let project_root =
+            ProjectRoot::new_unchecked(AbsNormPathBuf::try_from(root.to_owned()).unwrap());
+
+        assert_eq!(
+            "buck-out/v2/gen/root/abc/foo/out.txt",
+            project_relative_output_path(&project_root, inside).unwrap()
+        );
+        assert_eq!(
+            "buck-out/v2/gen/root/abc/foo/out.txt",
+            project_relative_output_path(&project_root, "buck-out/v2/gen/root/abc/foo/out.txt")
+                .unwrap()
+        );
+        assert!(project_relative_output_path(&project_root, outside).is_err());
+    }
 }
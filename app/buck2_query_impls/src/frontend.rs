@@ -17,6 +17,7 @@ use buck2_core::fs::project_rel_path::ProjectRelativePath;
 use buck2_node::configured_universe::CqueryUniverse;
 use buck2_node::nodes::configured::ConfiguredTargetNode;
 use buck2_node::nodes::unconfigured::TargetNode;
+use buck2_query::query::syntax::simple::eval::profile::QueryProfile;
 use buck2_query::query::syntax::simple::eval::values::QueryEvaluationResult;
 use dice::DiceComputations;
 
@@ -56,6 +57,7 @@ impl QueryFrontend for QueryFrontendImpl {
         query_args: &[String],
         global_cfg_options: GlobalCfgOptions,
         target_universe: Option<&[String]>,
+        profile: Option<&QueryProfile>,
     ) -> anyhow::Result<QueryEvaluationResult<ConfiguredTargetNode>> {
         let evaluator =
             get_cquery_evaluator(ctx, working_dir, global_cfg_options, owner_behavior).await?;
@@ -67,7 +69,12 @@ impl QueryFrontend for QueryFrontendImpl {
         //   buck2 cquery --target-universe android//:binary 'deps("some//:lib (<arm32>)")'
         //   ```
         evaluator
-            .eval_query(query, query_args, target_universe.as_ref().map(|v| &v[..]))
+            .eval_query(
+                query,
+                query_args,
+                target_universe.as_ref().map(|v| &v[..]),
+                profile,
+            )
             .await
     }
 
@@ -45,6 +45,7 @@ impl UqueryEvaluator<'_, '_> {
             &self.functions,
             query,
             query_args,
+            None,
             async move |literals| {
                 let resolved_literals = PreresolvedQueryLiterals::pre_resolve(
                     &**self.dice_query_delegate.query_data(),
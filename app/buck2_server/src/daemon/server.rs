@@ -107,6 +107,12 @@ use crate::trace_io::trace_io_command;
 // TODO(cjhopman): Figure out a reasonable value for this.
 static DEFAULT_KILL_TIMEOUT: Duration = Duration::from_millis(500);
 
+/// How long `wait_for_file_changes` waits after the first change before returning. Editors often
+/// write several files (or one file several times) when saving, and a `--watch` iteration that
+/// syncs the file watcher in the middle of that would build a half-saved tree and immediately
+/// start another iteration. 100ms covers a save while staying below what a user notices.
+static WATCH_DEBOUNCE: Duration = Duration::from_millis(100);
+
 static DEFAULT_INACTIVITY_TIMEOUT: Duration = Duration::from_secs(4 * 86400);
 
 pub trait BuckdServerDelegate: Allocative + Send + Sync {
@@ -847,6 +853,21 @@ impl DaemonApi for BuckdServer {
         .await
     }
 
+    async fn wait_for_file_changes(
+        &self,
+        req: Request<WaitForFileChangesRequest>,
+    ) -> Result<Response<CommandResult>, Status> {
+        let daemon_state = self.0.daemon_state.dupe();
+        self.oneshot(req, DefaultCommandOptions, move |req| async move {
+            let WaitForFileChangesRequest {} = req;
+            let file_watcher = daemon_state.data()?.file_watcher.dupe();
+            file_watcher.wait_for_changes().await?;
+            tokio::time::sleep(WATCH_DEBOUNCE).await;
+            Ok(GenericResponse {})
+        })
+        .await
+    }
+
     type FileStatusStream = ResponseStream;
     async fn file_status(
         &self,
@@ -12,6 +12,7 @@ use std::future::Future;
 use std::io;
 use std::io::ErrorKind;
 use std::path::Path;
+use std::path::PathBuf;
 use std::thread;
 
 use buck2_cli_proto::*;
@@ -22,8 +23,10 @@ use buck2_common::package_listing::dice::DicePackageListingResolver;
 use buck2_core::bzl::ImportPath;
 use buck2_core::cells::build_file_cell::BuildFileCell;
 use buck2_core::cells::cell_path::CellPathRef;
+use buck2_core::cells::paths::CellRelativePath;
 use buck2_core::cells::CellResolver;
 use buck2_core::fs::paths::abs_path::AbsPath;
+use buck2_core::fs::paths::file_name::FileNameBuf;
 use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
 use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
 use buck2_core::fs::project::ProjectRoot;
@@ -34,8 +37,10 @@ use buck2_core::target::name::TargetName;
 use buck2_events::dispatch::span_async;
 use buck2_events::dispatch::with_dispatcher;
 use buck2_events::dispatch::with_dispatcher_async;
+use buck2_interpreter::file_type::StarlarkFileType;
 use buck2_interpreter::paths::bxl::BxlFilePath;
 use buck2_interpreter::paths::module::OwnedStarlarkModulePath;
+use buck2_interpreter::paths::package::PackageFilePath;
 use buck2_interpreter::paths::path::StarlarkPath;
 use buck2_interpreter_for_build::interpreter::dice_calculation_delegate::HasCalculationDelegate;
 use buck2_interpreter_for_build::interpreter::global_interpreter_state::HasGlobalInterpreterState;
@@ -64,6 +69,7 @@ use starlark::docs::DocItem;
 use starlark::docs::DocModule;
 use starlark::docs::Identifier;
 use starlark::docs::Location;
+use starlark::environment::Globals;
 use starlark::errors::EvalMessage;
 use starlark::syntax::AstModule;
 use starlark_lsp::error::eval_message_to_lsp_diagnostic;
@@ -323,6 +329,34 @@ fn format_custom_attr_error(keys_and_values: &[(String, String)]) -> String {
     ret
 }
 
+/// Whether a file in a cell is searched for references to symbols: the build files of the
+/// cell, and the files that can be loaded.
+fn is_starlark_file(buildfiles: &[FileNameBuf], file_name: &str) -> bool {
+    buildfiles
+        .iter()
+        .any(|buildfile| buildfile.as_str() == file_name)
+        || matches!(Path::new(file_name).extension(), Some(ext) if ext == "bzl" || ext == "bxl")
+}
+
+/// The type of file buck2 evaluates a file in a cell as.
+fn starlark_file_type(buildfiles: &[FileNameBuf], file_name: &str) -> StarlarkFileType {
+    if buildfiles
+        .iter()
+        .any(|buildfile| buildfile.as_str() == file_name)
+    {
+        StarlarkFileType::Buck
+    } else if file_name == PackageFilePath::PACKAGE_FILE_NAME.as_str() {
+        StarlarkFileType::Package
+    } else if Path::new(file_name)
+        .extension()
+        .is_some_and(|ext| ext == "bxl")
+    {
+        StarlarkFileType::Bxl
+    } else {
+        StarlarkFileType::Bzl
+    }
+}
+
 /// Get the output subdirectory for a [`Doc`] based on the `directory` custom attr, if present.
 pub fn output_subdir_for_doc(doc: &Doc) -> anyhow::Result<ForwardRelativePathBuf> {
     let unknown_keys: Vec<_> = doc
@@ -463,6 +497,79 @@ impl<'a> BuckLspContext<'a> {
         }
     }
 
+    /// The build files and `.bzl` and `.bxl` files in all the cells, skipping ignored
+    /// directories such as `buck-out`.
+    async fn workspace_files(&self) -> anyhow::Result<Vec<LspUrl>> {
+        self.with_dice_ctx(async move |mut dice_ctx| {
+            let cell_resolver = dice_ctx.get_cell_resolver().await?;
+            let file_ops = DiceFileOps(&dice_ctx);
+            let mut files = Vec::new();
+            for (cell_name, cell) in cell_resolver.cells() {
+                let mut queue =
+                    vec![CellPathRef::new(cell_name, CellRelativePath::empty()).to_owned()];
+                while let Some(dir) = queue.pop() {
+                    let listing = file_ops.read_dir(dir.as_ref()).await?;
+                    for entry in listing.included.iter() {
+                        let path = dir.join(&entry.file_name);
+                        if entry.file_type.is_dir() {
+                            // Nested cells are listed on their own.
+                            if cell_resolver.find(&cell_resolver.resolve_path(path.as_ref())?)?
+                                == cell_name
+                            {
+                                queue.push(path);
+                            }
+                        } else if is_starlark_file(cell.buildfiles(), entry.file_name.as_str()) {
+                            let abs_path =
+                                self.fs.resolve(&cell_resolver.resolve_path(path.as_ref())?);
+                            files.push(Url::from_file_path(abs_path).unwrap().try_into()?);
+                        }
+                    }
+                }
+            }
+            Ok(files)
+        })
+        .await
+    }
+
+    /// The type of file buck2 evaluates `path` as, which determines its globals.
+    async fn file_type(&self, path: &Path) -> anyhow::Result<StarlarkFileType> {
+        let relative_path = self.fs.relativize_any(AbsPath::new(path)?)?;
+        let cell_resolver = self
+            .with_dice_ctx(|mut dice_ctx| async move { dice_ctx.get_cell_resolver().await })
+            .await?;
+        let buildfiles = cell_resolver
+            .get(cell_resolver.find(&relative_path)?)?
+            .buildfiles();
+        let file_name = relative_path.file_name().map_or("", |name| name.as_str());
+        Ok(starlark_file_type(buildfiles, file_name))
+    }
+
+    /// The globals buck2 evaluates the file with.
+    async fn globals(&self, uri: &LspUrl) -> anyhow::Result<Globals> {
+        let file_type = match uri {
+            LspUrl::File(path) => self.file_type(path).await?,
+            LspUrl::Starlark(path) => {
+                let import_path = self.starlark_import_path(path).await?;
+                import_path.borrow().starlark_path().file_type()
+            }
+            LspUrl::Other(_) => {
+                return Err(BuckLspContextError::WrongScheme(
+                    "file:// or starlark:".to_owned(),
+                    uri.clone(),
+                )
+                .into());
+            }
+        };
+        self.with_dice_ctx(async move |mut dice_ctx| {
+            Ok(dice_ctx
+                .get_global_interpreter_state()
+                .await?
+                .globals_for_file_type(file_type)
+                .dupe())
+        })
+        .await
+    }
+
     async fn parse_file_with_contents(&self, uri: &LspUrl, content: String) -> LspEvalResult {
         match self
             .parse_file_from_contents_and_handle_diagnostic(uri, content)
@@ -769,6 +876,35 @@ impl<'a> LspContext for BuckLspContext<'a> {
     fn get_environment(&self, _uri: &LspUrl) -> DocModule {
         DocModule::default()
     }
+
+    fn get_globals(&self, uri: &LspUrl) -> Globals {
+        let dispatcher = self.server_ctx.events().dupe();
+        self.runtime
+            .block_on(with_dispatcher_async(dispatcher, async {
+                match self.globals(uri).await {
+                    Ok(globals) => globals,
+                    Err(e) => {
+                        tracing::warn!(
+                            "Typechecking `{}` with the standard globals, as its globals are unknown: {:#}",
+                            uri,
+                            e
+                        );
+                        Globals::standard()
+                    }
+                }
+            }))
+    }
+
+    fn get_workspace_files(
+        &self,
+        _workspace_roots: &[PathBuf],
+    ) -> anyhow::Result<Option<Vec<LspUrl>>> {
+        let dispatcher = self.server_ctx.events().dupe();
+        self.runtime
+            .block_on(with_dispatcher_async(dispatcher, async {
+                Ok(Some(self.workspace_files().await?))
+            }))
+    }
 }
 
 pub(crate) async fn run_lsp_server_command(
@@ -927,6 +1063,8 @@ fn handle_outgoing_lsp_message(
 
 #[cfg(test)]
 mod tests {
+    use buck2_core::fs::paths::file_name::FileNameBuf;
+    use buck2_interpreter::file_type::StarlarkFileType;
     use lsp_types::Url;
     use maplit::hashmap;
     use starlark::docs::Doc;
@@ -936,9 +1074,47 @@ mod tests {
     use starlark::docs::Location;
     use starlark_lsp::server::LspUrl;
 
+    use crate::lsp::is_starlark_file;
+    use crate::lsp::starlark_file_type;
     use crate::lsp::DocsCache;
     use crate::lsp::DOCS_DIRECTORY_KEY;
 
+    #[test]
+    fn finds_starlark_files() -> anyhow::Result<()> {
+        let buildfiles = vec![
+            FileNameBuf::unchecked_new("BUCK"),
+            FileNameBuf::unchecked_new("TARGETS"),
+        ];
+        assert!(is_starlark_file(&buildfiles, "BUCK"));
+        assert!(is_starlark_file(&buildfiles, "TARGETS"));
+        assert!(is_starlark_file(&buildfiles, "defs.bzl"));
+        assert!(is_starlark_file(&buildfiles, "script.bxl"));
+        assert!(!is_starlark_file(&buildfiles, "BUILD"));
+        assert!(!is_starlark_file(&buildfiles, "main.rs"));
+        Ok(())
+    }
+
+    #[test]
+    fn file_types() {
+        let buildfiles = vec![FileNameBuf::unchecked_new("BUCK")];
+        assert_eq!(
+            StarlarkFileType::Buck,
+            starlark_file_type(&buildfiles, "BUCK")
+        );
+        assert_eq!(
+            StarlarkFileType::Package,
+            starlark_file_type(&buildfiles, "PACKAGE")
+        );
+        assert_eq!(
+            StarlarkFileType::Bxl,
+            starlark_file_type(&buildfiles, "script.bxl")
+        );
+        assert_eq!(
+            StarlarkFileType::Bzl,
+            starlark_file_type(&buildfiles, "defs.bzl")
+        );
+    }
+
     #[test]
     fn cache_builds() -> anyhow::Result<()> {
         let docs = vec![
@@ -17,14 +17,17 @@ use buck2_build_api::query::oneshot::CqueryOwnerBehavior;
 use buck2_build_api::query::oneshot::QUERY_FRONTEND;
 use buck2_cli_proto::CqueryRequest;
 use buck2_cli_proto::CqueryResponse;
+use buck2_cli_proto::QueryOutputFormat;
 use buck2_common::dice::cells::HasCellResolver;
 use buck2_core::configuration::compatibility::MaybeCompatible;
 use buck2_core::provider::label::ConfiguredProvidersLabel;
 use buck2_core::provider::label::ProvidersName;
+use buck2_events::dispatch::console_message;
 use buck2_node::attrs::display::AttrDisplayWithContextExt;
 use buck2_node::attrs::fmt_context::AttrFmtContext;
 use buck2_node::attrs::serialize::AttrSerializeWithContext;
 use buck2_node::nodes::configured::ConfiguredTargetNode;
+use buck2_query::query::syntax::simple::eval::profile::QueryProfile;
 use buck2_query::query::syntax::simple::eval::values::QueryEvaluationResult;
 use buck2_server_ctx::ctx::ServerCommandContextTrait;
 use buck2_server_ctx::partial_result_dispatcher::PartialResultDispatcher;
@@ -36,6 +39,8 @@ use dice::DiceComputations;
 use dice::DiceTransaction;
 use dupe::Dupe;
 
+use crate::commands::query::diff::print_diff;
+use crate::commands::query::diff::print_snapshot;
 use crate::commands::query::printer::ProviderLookUp;
 use crate::commands::query::printer::QueryResultPrinter;
 use crate::commands::query::printer::ShouldPrintProviders;
@@ -136,6 +141,9 @@ async fn cquery(
         context,
         show_providers,
         correct_owner,
+        diff_snapshot,
+        diff_against,
+        profile,
         ..
     } = request;
     // The request will always have a universe value, an empty one indicates the user didn't provide a universe.
@@ -158,6 +166,12 @@ async fn cquery(
         false => CqueryOwnerBehavior::Deprecated,
     };
 
+    let query_profile = if *profile {
+        Some(QueryProfile::new())
+    } else {
+        None
+    };
+
     let query_result = QUERY_FRONTEND
         .get()?
         .eval_cquery(
@@ -168,9 +182,35 @@ async fn cquery(
             query_args,
             global_cfg_options,
             target_universe,
+            query_profile.as_ref(),
         )
         .await?;
 
+    if let Some(query_profile) = &query_profile {
+        console_message(format!(
+            "Query profile (times include subexpressions):\n{}",
+            query_profile.render(query)?
+        ));
+    }
+
+    if *diff_snapshot || diff_against.is_some() {
+        let targets = match query_result {
+            QueryEvaluationResult::Single(value) => value.try_into_targets()?,
+            QueryEvaluationResult::Multiple(results) => results.merged()?.try_into_targets()?,
+        };
+        match diff_against {
+            Some(against) => print_diff(
+                &mut stdout,
+                &targets,
+                against,
+                QueryOutputFormat::from_i32(request.unstable_output_format)
+                    .context("Invalid value of output format (internal error)")?,
+            )?,
+            None => print_snapshot(&mut stdout, &targets)?,
+        }
+        return Ok(CqueryResponse {});
+    }
+
     let should_print_providers = if *show_providers {
         ShouldPrintProviders::Yes(&*ctx as &dyn ProviderLookUp<ConfiguredTargetNode>)
     } else {
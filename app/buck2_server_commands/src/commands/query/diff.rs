@@ -0,0 +1,295 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Implementation of `cquery --diff-snapshot` and `cquery --diff-against`.
+
+use std::io::Write;
+
+use anyhow::Context;
+use buck2_cli_proto::QueryOutputFormat;
+use buck2_node::nodes::configured::ConfiguredTargetNode;
+use buck2_node::nodes::configured_diff::diff_targets;
+use buck2_node::nodes::configured_diff::TargetDiff;
+use buck2_node::nodes::configured_diff::TargetDiffEntry;
+use buck2_node::nodes::configured_diff::TargetDiffSnapshot;
+use buck2_query::query::syntax::simple::eval::set::TargetSet;
+use serde::Serialize;
+
+use crate::target_hash::TargetHashes;
+
+#[derive(Debug, buck2_error::Error)]
+enum QueryDiffError {
+    #[error("Only `default` and `json` output formats are supported with `--diff-against`")]
+    UnsupportedOutputFormat,
+}
+
+/// Snapshot the query result. Target hashes are computed with the fast hash and
+/// without looking at files or dependencies, so they only reflect the configured node itself.
+pub(crate) fn snapshot_targets(targets: &TargetSet<ConfiguredTargetNode>) -> TargetDiffSnapshot {
+    TargetDiffSnapshot {
+        targets: targets
+            .iter()
+            .map(|t| {
+                let hash = TargetHashes::compute_immediate_one(t, true);
+                TargetDiffEntry::new(t, Some(hash.to_string()))
+            })
+            .collect(),
+    }
+}
+
+pub(crate) fn print_snapshot(
+    mut stdout: impl Write,
+    targets: &TargetSet<ConfiguredTargetNode>,
+) -> anyhow::Result<()> {
+    serde_json::to_writer_pretty(&mut stdout, &snapshot_targets(targets))?;
+    writeln!(stdout)?;
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct ChangedTargetJson<'a> {
+    label: &'a str,
+    old_configuration: &'a str,
+    new_configuration: &'a str,
+    changed_attrs: &'a [String],
+}
+
+#[derive(Serialize)]
+struct TargetDiffJson<'a> {
+    added: Vec<&'a TargetDiffEntry>,
+    removed: Vec<&'a TargetDiffEntry>,
+    changed: Vec<ChangedTargetJson<'a>>,
+}
+
+impl<'a> TargetDiffJson<'a> {
+    fn new(diff: &'a TargetDiff<TargetDiffEntry>) -> Self {
+        TargetDiffJson {
+            added: diff.added.iter().collect(),
+            removed: diff.removed.iter().collect(),
+            changed: diff
+                .changed
+                .iter()
+                .map(|c| ChangedTargetJson {
+                    label: &c.new.label,
+                    old_configuration: &c.old.configuration,
+                    new_configuration: &c.new.configuration,
+                    changed_attrs: &c.changed_attrs,
+                })
+                .collect(),
+        }
+    }
+}
+
+pub(crate) fn print_diff(
+    mut stdout: impl Write,
+    targets: &TargetSet<ConfiguredTargetNode>,
+    against: &str,
+    output_format: QueryOutputFormat,
+) -> anyhow::Result<()> {
+    let old: TargetDiffSnapshot =
+        serde_json::from_str(against).context("Error parsing `--diff-against` snapshot")?;
+    let new = snapshot_targets(targets);
+    let diff = diff_targets(old.targets, new.targets);
+
+    match output_format {
+        QueryOutputFormat::Default => {
+            for t in &diff.added {
+                writeln!(stdout, "+ {} ({})", t.label, t.configuration)?;
+            }
+            for t in &diff.removed {
+                writeln!(stdout, "- {} ({})", t.label, t.configuration)?;
+            }
+            for c in &diff.changed {
+                if c.configuration_changed() {
+                    writeln!(
+                        stdout,
+                        "~ {} ({} -> {})",
+                        c.new.label, c.old.configuration, c.new.configuration
+                    )?;
+                } else {
+                    writeln!(stdout, "~ {} ({})", c.new.label, c.new.configuration)?;
+                }
+                for attr in &c.changed_attrs {
+                    writeln!(
+                        stdout,
+                        "    {}: {} -> {}",
+                        attr,
+                        c.old.attrs.get(attr).map_or("<unset>", |s| s.as_str()),
+                        c.new.attrs.get(attr).map_or("<unset>", |s| s.as_str()),
+                    )?;
+                }
+            }
+        }
+        QueryOutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut stdout, &TargetDiffJson::new(&diff))?;
+            writeln!(stdout)?;
+        }
+        QueryOutputFormat::Dot | QueryOutputFormat::DotCompact => {
+            return Err(QueryDiffError::UnsupportedOutputFormat.into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_cli_proto::QueryOutputFormat;
+    use buck2_core::configuration::data::ConfigurationData;
+    use buck2_core::target::configured_target_label::ConfiguredTargetLabel;
+    use buck2_node::nodes::configured::ConfiguredTargetNode;
+    use buck2_node::nodes::configured_diff::TargetDiffSnapshot;
+    use buck2_query::query::syntax::simple::eval::set::TargetSet;
+    use dupe::Dupe;
+
+    use crate::commands::query::diff::print_diff;
+    use crate::commands::query::diff::print_snapshot;
+    use crate::commands::query::diff::snapshot_targets;
+
+    fn node(label: &str, cfg: &ConfigurationData) -> ConfiguredTargetNode {
+        ConfiguredTargetNode::testing_new(
+            ConfiguredTargetLabel::testing_parse(label, cfg.dupe()),
+            "foo_lib",
+        )
+    }
+
+    /// A snapshot of `root//pkg:{kept,removed,changed}` and of `root//pkg:moved` in another
+    /// configuration, where `changed` had a `srcs` attribute, and the current targets
+    /// `root//pkg:{kept,changed,moved,added}`.
+    fn old_snapshot_and_new_targets() -> (String, TargetSet<ConfiguredTargetNode>) {
+        let cfg = ConfigurationData::testing_new();
+        let old_cfg = ConfigurationData::unspecified();
+        let mut old = snapshot_targets(&TargetSet::from_iter([
+            node("root//pkg:kept", &cfg),
+            node("root//pkg:removed", &cfg),
+            node("root//pkg:changed", &cfg),
+            node("root//pkg:moved", &old_cfg),
+        ]));
+        let changed = &mut old.targets[2];
+        changed.hash = Some("0".to_owned());
+        changed
+            .attrs
+            .insert("srcs".to_owned(), "[\"a.c\"]".to_owned());
+        let new = TargetSet::from_iter([
+            node("root//pkg:kept", &cfg),
+            node("root//pkg:changed", &cfg),
+            node("root//pkg:moved", &cfg),
+            node("root//pkg:added", &cfg),
+        ]);
+        (serde_json::to_string(&old).unwrap(), new)
+    }
+
+    fn diff(against: &str, output_format: QueryOutputFormat) -> anyhow::Result<String> {
+        let (_, new) = old_snapshot_and_new_targets();
+        let mut out = Vec::new();
+        print_diff(&mut out, &new, against, output_format)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let (_, targets) = old_snapshot_and_new_targets();
+        let mut out = Vec::new();
+        print_snapshot(&mut out, &targets).unwrap();
+
+        let snapshot: TargetDiffSnapshot = serde_json::from_slice(&out).unwrap();
+        assert_eq!(snapshot_targets(&targets).targets, snapshot.targets);
+        assert_eq!(
+            vec![
+                "root//pkg:kept",
+                "root//pkg:changed",
+                "root//pkg:moved",
+                "root//pkg:added"
+            ],
+            snapshot
+                .targets
+                .iter()
+                .map(|t| t.label.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert!(snapshot.targets.iter().all(|t| t.hash.is_some()));
+
+        // Diffing against its own snapshot prints nothing.
+        let against = String::from_utf8(out).unwrap();
+        assert_eq!("", diff(&against, QueryOutputFormat::Default).unwrap());
+        assert_eq!(
+            serde_json::json!({"added": [], "removed": [], "changed": []}),
+            serde_json::from_str::<serde_json::Value>(
+                &diff(&against, QueryOutputFormat::Json).unwrap()
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_diff_default_format() {
+        let (against, _) = old_snapshot_and_new_targets();
+        let cfg = ConfigurationData::testing_new();
+        let old_cfg = ConfigurationData::unspecified();
+        assert_eq!(
+            format!(
+                "+ root//pkg:added ({cfg})\n\
+                 - root//pkg:removed ({cfg})\n\
+                 ~ root//pkg:changed ({cfg})\n    \
+                 srcs: [\"a.c\"] -> <unset>\n\
+                 ~ root//pkg:moved ({old_cfg} -> {cfg})\n"
+            ),
+            diff(&against, QueryOutputFormat::Default).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_diff_json_format() {
+        let (against, _) = old_snapshot_and_new_targets();
+        let cfg = ConfigurationData::testing_new().to_string();
+        let old_cfg = ConfigurationData::unspecified().to_string();
+        let json: serde_json::Value =
+            serde_json::from_str(&diff(&against, QueryOutputFormat::Json).unwrap()).unwrap();
+        assert_eq!("root//pkg:added", json["added"][0]["label"]);
+        assert_eq!(cfg, json["added"][0]["configuration"]);
+        assert_eq!(1, json["added"].as_array().unwrap().len());
+        assert_eq!("root//pkg:removed", json["removed"][0]["label"]);
+        assert_eq!(1, json["removed"].as_array().unwrap().len());
+        assert_eq!(
+            serde_json::json!([
+                {
+                    "label": "root//pkg:changed",
+                    "old_configuration": cfg,
+                    "new_configuration": cfg,
+                    "changed_attrs": ["srcs"],
+                },
+                {
+                    "label": "root//pkg:moved",
+                    "old_configuration": old_cfg,
+                    "new_configuration": cfg,
+                    "changed_attrs": [],
+                },
+            ]),
+            json["changed"]
+        );
+    }
+
+    #[test]
+    fn test_diff_malformed_snapshot() {
+        for against in ["", "{not json", "{\"targets\": 1}", "[]"] {
+            let err = diff(against, QueryOutputFormat::Default).unwrap_err();
+            assert!(
+                format!("{:#}", err).contains("Error parsing `--diff-against` snapshot"),
+                "{:#}",
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn test_diff_dot_format() {
+        let (against, _) = old_snapshot_and_new_targets();
+        assert!(diff(&against, QueryOutputFormat::Dot).is_err());
+        assert!(diff(&against, QueryOutputFormat::DotCompact).is_err());
+    }
+}
@@ -9,6 +9,7 @@
 
 pub mod aquery;
 pub mod cquery;
+mod diff;
 pub mod printer;
 pub(crate) mod query_target_ext;
 pub mod uquery;
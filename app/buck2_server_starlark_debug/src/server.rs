@@ -30,6 +30,7 @@ use starlark::debug::resolve_breakpoints;
 use starlark::debug::DapAdapter;
 use starlark::debug::DapAdapterClient;
 use starlark::debug::DapAdapterEvalHook;
+use starlark::debug::RecordingConfig;
 use starlark::debug::ResolvedBreakpoints;
 use starlark::debug::StepKind;
 use starlark::debug::VariablePath;
@@ -48,6 +49,7 @@ use crate::controller::BuckStarlarkDebugController;
 use crate::dap_api::dap_event;
 use crate::dap_api::dispatch;
 use crate::dap_api::err_response;
+use crate::dap_api::AttachArguments;
 use crate::dap_api::ContinueArguments;
 use crate::dap_api::DebugServer;
 use crate::error::StarlarkDebuggerError;
@@ -70,6 +72,8 @@ fn capabilities() -> serde_json::Value {
         "supportsSetVariable": true,
         "supportsStepInTargetsRequest": true,
         "supportsConditionalBreakpoints": true,
+        // Only available for statements recorded with the `record` attach argument.
+        "supportsStepBack": true,
         // note that some capabilities have the word "support" and some "supports" this seems to be according to the spec
         "supportTerminateDebuggee": false,
         "supportSuspendDebuggee": false,
@@ -267,6 +271,10 @@ struct ServerState {
     /// The currently set breakpoints. New hooks will be initialized with these.
     set_breakpoints: HashMap<String, ResolvedBreakpoints>,
 
+    /// Which statements to record for stepping backward, from the attach request. New hooks
+    /// will be initialized with this.
+    recording: Option<RecordingConfig>,
+
     /// The project root is used to get the current source code to resolve breakpoints.
     project_root: ProjectRoot,
 
@@ -393,7 +401,26 @@ impl DebugServer for ServerState {
         Err(StarlarkDebuggerError::Unimplemented.into())
     }
 
-    fn attach(&mut self, _x: dap::AttachRequestArguments) -> anyhow::Result<()> {
+    fn attach(&mut self, x: AttachArguments) -> anyhow::Result<()> {
+        let recording = match x.record {
+            None => None,
+            Some(record) => Some(RecordingConfig {
+                // vscode sends absolute paths, so need to relativize them (as for breakpoints)
+                file: match record.file {
+                    Some(file) => Some(
+                        self.project_root
+                            .relativize(AbsNormPath::new(&file)?)?
+                            .to_string(),
+                    ),
+                    None => None,
+                },
+                function: record.function,
+            }),
+        };
+        for hook_state in self.current_hooks.values() {
+            hook_state.adapter.set_recording(recording.clone())?;
+        }
+        self.recording = recording;
         Ok(())
     }
 
@@ -565,6 +592,18 @@ impl DebugServer for ServerState {
         Ok(())
     }
 
+    fn step_back(&mut self, x: dap::StepBackArguments) -> anyhow::Result<()> {
+        let hook = self.find_hook_by_pseudo_thread(x.thread_id)?;
+        hook.adapter.step_back()?;
+        Ok(())
+    }
+
+    fn reverse_continue(&mut self, x: dap::ReverseContinueArguments) -> anyhow::Result<()> {
+        let hook = self.find_hook_by_pseudo_thread(x.thread_id)?;
+        hook.adapter.reverse_continue()?;
+        Ok(())
+    }
+
     fn evaluate(&mut self, x: dap::EvaluateArguments) -> anyhow::Result<dap::EvaluateResponseBody> {
         let frame_id = match x.frame_id {
             Some(v) => v,
@@ -640,6 +679,7 @@ impl ServerState {
             next_hook_id: HookId(0),
             set_breakpoints: HashMap::new(),
             variables_by_thread: HashMap::new(),
+            recording: None,
         }
     }
 
@@ -787,6 +827,7 @@ impl ServerState {
         for (source, breakpoints) in &self.set_breakpoints {
             hook_state.adapter.set_breakpoints(source, breakpoints)?;
         }
+        hook_state.adapter.set_recording(self.recording.clone())?;
         self.current_hooks.insert(hook_id, hook_state);
 
         self.to_client.send(ToClientMessage::Event(dap_event(
@@ -29,12 +29,16 @@ use buck2_server_ctx::ctx::ServerCommandContextTrait;
 use buck2_server_ctx::partial_result_dispatcher::PartialResultDispatcher;
 
 use crate::debug::StarlarkDebugAttachCommand;
+use crate::fmt::StarlarkFmtCommand;
 use crate::lint::StarlarkLintCommand;
+use crate::test::StarlarkTestCommand;
 use crate::typecheck::StarlarkTypecheckCommand;
 
 mod debug;
+mod fmt;
 mod lint;
 pub mod server;
+mod test;
 mod typecheck;
 mod util;
 
@@ -53,6 +57,8 @@ pub enum StarlarkCommand {
 pub enum StarlarkOpaqueCommand {
     Lint(StarlarkLintCommand),
     Typecheck(StarlarkTypecheckCommand),
+    Fmt(StarlarkFmtCommand),
+    Test(StarlarkTestCommand),
 }
 
 #[derive(Debug, clap::Parser, serde::Serialize, serde::Deserialize, Default)]
@@ -94,6 +100,8 @@ impl StarlarkOpaqueCommand {
         match self {
             Self::Lint(cmd) => cmd,
             Self::Typecheck(cmd) => cmd,
+            Self::Fmt(cmd) => cmd,
+            Self::Test(cmd) => cmd,
         }
     }
 }
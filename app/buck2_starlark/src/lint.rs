@@ -22,6 +22,9 @@ use buck2_common::dice::file_ops::DiceFileOps;
 use buck2_common::io::IoProvider;
 use buck2_core::cells::name::CellName;
 use buck2_core::cells::CellResolver;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::project::ProjectRoot;
+use buck2_events::dispatch::console_warning;
 use buck2_interpreter::file_type::StarlarkFileType;
 use buck2_interpreter::paths::path::StarlarkPath;
 use buck2_server_ctx::ctx::ServerCommandContextTrait;
@@ -30,11 +33,13 @@ use buck2_server_ctx::partial_result_dispatcher::PartialResultDispatcher;
 use dice::DiceTransaction;
 use dupe::Dupe;
 use dupe::OptionDupedExt;
+use starlark::analysis::apply_lint_fixes;
 use starlark::analysis::AstModuleLint;
 use starlark::codemap::FileSpan;
 use starlark::errors::EvalSeverity;
 use starlark::errors::Lint;
 use starlark::syntax::AstModule;
+use starlark::syntax::Dialect;
 
 use crate::util::environment::Environment;
 use crate::util::paths::starlark_files;
@@ -47,6 +52,10 @@ pub struct StarlarkLintCommand {
     #[clap(flatten)]
     common_opts: StarlarkCommandCommonOptions,
 
+    /// Apply the fixes of the lints which have them to the files, then report the rest.
+    #[clap(long)]
+    fix: bool,
+
     #[clap(value_name = "PATH", required = true)]
     paths: Vec<PathArg>,
 }
@@ -81,21 +90,115 @@ impl<'a> Cache<'a> {
     }
 }
 
+/// How many times to lint and fix a file, fixes which overlap are only applied by later rounds.
+const MAX_FIX_ROUNDS: usize = 10;
+
+/// Outcome of [`fix_lints`].
+struct FixedLints {
+    /// The fixed content, or `None` if nothing was fixed.
+    content: Option<String>,
+    /// The lints remaining in the content.
+    lints: Vec<Lint>,
+    /// Why fixes were left unapplied, if they were.
+    warning: Option<String>,
+}
+
+/// The lints in a warning, e.g. `` `redundant-continue` at foo.bzl:4:9-17 ``.
+fn describe_lints<'a>(lints: impl IntoIterator<Item = &'a Lint>) -> String {
+    lints
+        .into_iter()
+        .map(|lint| format!("`{}` at {}", lint.short_name, lint.location))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Apply the fixes of `lints` to `content` until there are none left, for at most `max_rounds`
+/// rounds.
+fn fix_lints(
+    path_str: &str,
+    content: &str,
+    dialect: &Dialect,
+    names: &HashSet<String>,
+    mut lints: Vec<Lint>,
+    max_rounds: usize,
+) -> FixedLints {
+    let mut fixed: Option<String> = None;
+    let mut rounds = 0;
+    let warning = loop {
+        let current = fixed.as_deref().unwrap_or(content);
+        let Some(new_content) = apply_lint_fixes(current, &lints) else {
+            break None;
+        };
+        if rounds == max_rounds {
+            break Some(format!(
+                "Stopped fixing `{}` after {} rounds, leaving the fixes of {}",
+                path_str,
+                max_rounds,
+                describe_lints(lints.iter().filter(|lint| lint.fix.is_some()))
+            ));
+        }
+        rounds += 1;
+        // A fix which doesn't parse is a bug in the linter, keep the code we had.
+        let Ok(ast) = AstModule::parse(path_str, new_content.clone(), dialect) else {
+            // Blame the fixes which break the code on their own, or all of them if it takes
+            // several.
+            let breaking: Vec<&Lint> = lints
+                .iter()
+                .filter(|lint| {
+                    apply_lint_fixes(current, std::slice::from_ref(*lint))
+                        .map_or(false, |x| AstModule::parse(path_str, x, dialect).is_err())
+                })
+                .collect();
+            let breaking = if breaking.is_empty() {
+                describe_lints(lints.iter().filter(|lint| lint.fix.is_some()))
+            } else {
+                describe_lints(breaking)
+            };
+            break Some(format!(
+                "Stopped fixing `{}`, the fixes of {} make it fail to parse",
+                path_str, breaking
+            ));
+        };
+        lints = ast.lint(Some(names));
+        fixed = Some(new_content);
+    };
+    FixedLints {
+        content: fixed,
+        lints,
+        warning,
+    }
+}
+
 async fn lint_file(
     path: &StarlarkPath<'_>,
     cell_resolver: &CellResolver,
     io: &dyn IoProvider,
     cache: &mut Cache<'_>,
+    fix: Option<&ProjectRoot>,
 ) -> anyhow::Result<Vec<Lint>> {
     let dialect = path.file_type().dialect(false);
     let proj_path = cell_resolver.resolve_path(path.path().as_ref().as_ref())?;
     let path_str = proj_path.to_string();
     let content = io
-        .read_file_if_exists(proj_path)
+        .read_file_if_exists(proj_path.clone())
         .await?
         .with_context(|| format!("File not found: `{}`", path_str))?;
     match AstModule::parse(&path_str, content.clone(), &dialect) {
-        Ok(ast) => Ok(ast.lint(Some(&*cache.get_names(path).await?))),
+        Ok(ast) => {
+            let names = cache.get_names(path).await?;
+            let lints = ast.lint(Some(&*names));
+            let Some(project_root) = fix else {
+                return Ok(lints);
+            };
+            let fixed = fix_lints(&path_str, &content, &dialect, &names, lints, MAX_FIX_ROUNDS);
+            if let Some(warning) = fixed.warning {
+                console_warning(warning);
+            }
+            if let Some(content) = fixed.content {
+                fs_util::write(project_root.resolve(&proj_path), content)?;
+            }
+            Ok(fixed.lints)
+        }
         Err(err) => {
             // There was a parse error, so we don't want to fail, we want to give a nice error message
             // Do the best we can - it is probably a `Diagnostic`, which gives us more precise info.
@@ -108,6 +211,7 @@ async fn lint_file(
                 severity: EvalSeverity::Error,
                 problem: format!("{:#}", err.without_diagnostic()),
                 original: "".to_owned(),
+                fix: None,
             }])
         }
     }
@@ -138,7 +242,14 @@ impl StarlarkOpaqueSubcommand for StarlarkLintCommand {
                 )
                 .await?;
                 for file in &files {
-                    let lints = lint_file(&file.borrow(), &cell_resolver, &*io, &mut cache).await?;
+                    let lints = lint_file(
+                        &file.borrow(),
+                        &cell_resolver,
+                        &*io,
+                        &mut cache,
+                        self.fix.then(|| server_ctx.project_root()),
+                    )
+                    .await?;
                     lint_count += lints.len();
                     for lint in lints {
                         writeln!(stdout, "{}", lint)?;
@@ -162,3 +273,87 @@ impl StarlarkOpaqueSubcommand for StarlarkLintCommand {
         &self.common_opts
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use starlark::analysis::AstModuleLint;
+    use starlark::analysis::LintEdit;
+    use starlark::analysis::LintFix;
+    use starlark::codemap::FileSpan;
+    use starlark::codemap::Pos;
+    use starlark::codemap::Span;
+    use starlark::errors::EvalSeverity;
+    use starlark::errors::Lint;
+    use starlark::syntax::AstModule;
+    use starlark::syntax::Dialect;
+
+    use crate::lint::fix_lints;
+    use crate::lint::FixedLints;
+    use crate::lint::MAX_FIX_ROUNDS;
+
+    const PATH: &str = "foo.bzl";
+    const CONTENT: &str = "def f(x):\n    for y in x:\n        print(y)\n        continue\n";
+
+    fn fix(content: &str, lints: Vec<Lint>, max_rounds: usize) -> FixedLints {
+        let names = HashSet::from(["print".to_owned()]);
+        fix_lints(PATH, content, &Dialect::Extended, &names, lints, max_rounds)
+    }
+
+    fn lints(content: &str) -> Vec<Lint> {
+        let names = HashSet::from(["print".to_owned()]);
+        AstModule::parse(PATH, content.to_owned(), &Dialect::Extended)
+            .unwrap()
+            .lint(Some(&names))
+    }
+
+    #[test]
+    fn test_fix_lints() {
+        let fixed = fix(CONTENT, lints(CONTENT), MAX_FIX_ROUNDS);
+        assert_eq!(
+            fixed.content.as_deref(),
+            Some("def f(x):\n    for y in x:\n        print(y)\n")
+        );
+        assert!(fixed.lints.iter().all(|lint| lint.fix.is_none()));
+        assert_eq!(fixed.warning, None);
+
+        let fixed = fix(CONTENT, Vec::new(), MAX_FIX_ROUNDS);
+        assert_eq!(fixed.content, None);
+        assert_eq!(fixed.warning, None);
+    }
+
+    #[test]
+    fn test_fix_lints_out_of_rounds() {
+        let fixed = fix(CONTENT, lints(CONTENT), 0);
+        assert_eq!(fixed.content, None);
+        let warning = fixed.warning.unwrap();
+        assert!(warning.contains("after 0 rounds"), "{}", warning);
+        assert!(warning.contains("`redundant-continue`"), "{}", warning);
+    }
+
+    #[test]
+    fn test_fix_lints_breaking_fix() {
+        let content = "x = 1\n";
+        let lint = Lint {
+            location: FileSpan::new(PATH.to_owned(), content.to_owned()),
+            short_name: "bad-fix".to_owned(),
+            severity: EvalSeverity::Warning,
+            problem: "Bad fix".to_owned(),
+            original: content.to_owned(),
+            fix: Some(LintFix {
+                description: "Break the code".to_owned(),
+                edits: vec![LintEdit {
+                    span: Span::new(Pos::new(4), Pos::new(5)),
+                    replacement: "(".to_owned(),
+                }],
+            }),
+        };
+        let fixed = fix(content, vec![lint], MAX_FIX_ROUNDS);
+        assert_eq!(fixed.content, None);
+        assert_eq!(fixed.lints.len(), 1);
+        let warning = fixed.warning.unwrap();
+        assert!(warning.contains("`bad-fix` at foo.bzl"), "{}", warning);
+        assert!(warning.contains("fail to parse"), "{}", warning);
+    }
+}
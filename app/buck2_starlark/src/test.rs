@@ -0,0 +1,109 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::io::Write;
+
+use async_trait::async_trait;
+use buck2_cli_proto::ClientContext;
+use buck2_client_ctx::path_arg::PathArg;
+use buck2_common::dice::cells::HasCellResolver;
+use buck2_common::dice::data::HasIoProvider;
+use buck2_common::dice::file_ops::DiceFileOps;
+use buck2_core::fs::fs_util;
+use buck2_interpreter::paths::path::StarlarkPath;
+use buck2_interpreter_for_build::interpreter::dice_calculation_delegate::HasCalculationDelegate;
+use buck2_server_ctx::ctx::ServerCommandContextTrait;
+use buck2_server_ctx::ctx::ServerCommandDiceContext;
+use buck2_server_ctx::partial_result_dispatcher::PartialResultDispatcher;
+use starlark::assert::TestReport;
+
+use crate::util::paths::starlark_files;
+use crate::StarlarkCommandCommonOptions;
+use crate::StarlarkOpaqueSubcommand;
+
+#[derive(Debug, clap::Parser, serde::Serialize, serde::Deserialize)]
+#[clap(
+    name = "starlark-test",
+    about = "Run the `test_*` functions of `.bzl` files, which can use `asserts`."
+)]
+pub struct StarlarkTestCommand {
+    #[clap(flatten)]
+    common_opts: StarlarkCommandCommonOptions,
+
+    /// Also write the results to this file as JUnit XML.
+    #[clap(long, value_name = "PATH")]
+    junit: Option<PathArg>,
+
+    #[clap(value_name = "PATH", required = true)]
+    paths: Vec<PathArg>,
+}
+
+#[async_trait]
+impl StarlarkOpaqueSubcommand for StarlarkTestCommand {
+    async fn server_execute(
+        &self,
+        server_ctx: &dyn ServerCommandContextTrait,
+        mut stdout: PartialResultDispatcher<buck2_cli_proto::StdoutBytes>,
+        _client_ctx: ClientContext,
+    ) -> anyhow::Result<()> {
+        server_ctx
+            .with_dice_ctx(async move |server_ctx, mut ctx| {
+                let cell_resolver = ctx.get_cell_resolver().await?;
+                let io = ctx.global_data().get_io_provider();
+                let files = starlark_files(
+                    &self.paths,
+                    server_ctx,
+                    &cell_resolver,
+                    &DiceFileOps(&ctx),
+                    &*io,
+                )
+                .await?;
+
+                let mut stdout = stdout.as_writer();
+                let mut report = TestReport::new();
+                for file in &files {
+                    // Only `.bzl` files can be loaded, and so define tests.
+                    let path = file.borrow();
+                    let StarlarkPath::LoadFile(import_path) = path else {
+                        continue;
+                    };
+                    let path_str = cell_resolver
+                        .resolve_path(path.path().as_ref().as_ref())?
+                        .to_string();
+                    let results = ctx
+                        .get_interpreter_calculator(path.cell(), path.build_file_cell())
+                        .await?
+                        .run_test_module(import_path)
+                        .await;
+                    report.add_file(path_str, results.map_err(Into::into), &mut stdout)?;
+                }
+
+                if let Some(junit) = &self.junit {
+                    fs_util::write(junit.resolve(server_ctx.working_dir_abs()), report.junit())?;
+                }
+
+                if report.failed() > 0 {
+                    Err(anyhow::anyhow!("{}", report.summary()))
+                } else {
+                    writeln!(
+                        server_ctx.stderr()?,
+                        "Passed {} tests in {} files",
+                        report.total(),
+                        report.files()
+                    )?;
+                    Ok(())
+                }
+            })
+            .await
+    }
+
+    fn common_opts(&self) -> &StarlarkCommandCommonOptions {
+        &self.common_opts
+    }
+}
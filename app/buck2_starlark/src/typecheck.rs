@@ -9,6 +9,7 @@
 
 use std::collections::HashMap;
 use std::io::Write;
+use std::path::Path;
 
 use anyhow::Context;
 use async_recursion::async_recursion;
@@ -22,6 +23,7 @@ use buck2_common::io::IoProvider;
 use buck2_core::cells::name::CellName;
 use buck2_core::cells::CellResolver;
 use buck2_interpreter::file_type::StarlarkFileType;
+use buck2_interpreter::load_module::InterpreterCalculation;
 use buck2_interpreter::paths::module::OwnedStarlarkModulePath;
 use buck2_interpreter::paths::path::OwnedStarlarkPath;
 use buck2_interpreter_for_build::interpreter::dice_calculation_delegate::HasCalculationDelegate;
@@ -31,7 +33,9 @@ use buck2_server_ctx::ctx::ServerCommandDiceContext;
 use buck2_server_ctx::partial_result_dispatcher::PartialResultDispatcher;
 use dice::DiceTransaction;
 use dupe::Dupe;
+use starlark::analysis::LintMessage;
 use starlark::environment::Globals;
+use starlark::errors::EvalMessage;
 use starlark::typing::AstModuleTypecheck;
 use starlark::typing::Interface;
 
@@ -46,6 +50,10 @@ pub struct StarlarkTypecheckCommand {
     #[clap(flatten)]
     common_opts: StarlarkCommandCommonOptions,
 
+    /// Print the errors as JSON lines, instead of the inferred types and the errors as text.
+    #[clap(long)]
+    json: bool,
+
     #[clap(value_name = "PATH", required = true)]
     paths: Vec<PathArg>,
 }
@@ -58,9 +66,11 @@ struct Cache<'a> {
     // Things we have access to write information
     stdout: &'a mut (dyn Write + Send + Sync),
     stderr: &'a mut (dyn Write + Send + Sync),
+    json: bool,
     // Our accumulated state
     oracle: HashMap<(CellName, StarlarkFileType), Globals>,
     cache: HashMap<OwnedStarlarkModulePath, Interface>,
+    error_count: usize,
 }
 
 impl<'a> Cache<'a> {
@@ -86,11 +96,21 @@ impl<'a> Cache<'a> {
         }
     }
 
+    /// The interface of a loaded module. The types the typechecker infers come first,
+    /// the types of the values of the evaluated module fill in those it can't infer,
+    /// like `provider`s and their fields.
     async fn get(&mut self, path: OwnedStarlarkModulePath) -> anyhow::Result<Interface> {
         match self.cache.get(&path) {
             Some(x) => Ok(x.dupe()),
             None => {
-                let res = self.run(path.clone().into_starlark_path()).await?;
+                let inferred = self.run(path.clone().into_starlark_path()).await?;
+                let mut dice = self.dice.clone();
+                let res = match dice.get_loaded_module(path.borrow()).await {
+                    Ok(module) => inferred.with_fallback(&Interface::from_module(module.env())),
+                    // The module fails to evaluate, which is reported when it is evaluated,
+                    // but the types it was checked with are still useful.
+                    Err(_) => inferred,
+                };
                 self.cache.insert(path, res.dupe());
                 Ok(res)
             }
@@ -128,6 +148,21 @@ impl<'a> Cache<'a> {
             .await?;
         let (errors, bindings, interface, approxiomations) = ast.typecheck(&globals, &loads);
 
+        // Report the errors, but carry on, so the modules which load this one
+        // are checked against the types it has.
+        self.error_count += errors.len();
+        if self.json {
+            for x in errors {
+                let message = EvalMessage::from_error(Path::new(&path_str), &x);
+                writeln!(
+                    self.stdout,
+                    "{}",
+                    serde_json::to_string(&LintMessage::new(message))?
+                )?;
+            }
+            return Ok(interface);
+        }
+
         if !approxiomations.is_empty() {
             writeln!(self.stderr, "\n\nAPPROXIMATIONS:")?;
             for x in approxiomations {
@@ -137,16 +172,13 @@ impl<'a> Cache<'a> {
 
         writeln!(self.stderr, "\n\nBINDINGS:\n{bindings}")?;
 
-        let errors_count = errors.len();
-        if errors_count == 0 {
-            Ok(interface)
-        } else {
+        if !errors.is_empty() {
             writeln!(self.stdout, "\n\nERRORS:")?;
             for x in errors {
                 writeln!(self.stdout, "{x}")?;
             }
-            Err(anyhow::anyhow!("Detected {errors_count} errors"))
         }
+        Ok(interface)
     }
 }
 
@@ -179,13 +211,19 @@ impl StarlarkOpaqueSubcommand for StarlarkTypecheckCommand {
                     cell_resolver: &cell_resolver,
                     stdout: &mut stdout,
                     stderr: &mut stderr,
+                    json: self.json,
                     oracle: HashMap::new(),
                     cache: HashMap::new(),
+                    error_count: 0,
                 };
                 for file in files {
                     cache.typecheck(file).await?;
                 }
                 let file_count = cache.cache.len();
+                let error_count = cache.error_count;
+                if error_count > 0 {
+                    return Err(anyhow::anyhow!("Detected {error_count} errors"));
+                }
                 writeln!(stderr, "Found no type errors in {file_count} files")?;
                 Ok(())
             })
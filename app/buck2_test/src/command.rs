@@ -16,13 +16,16 @@ use std::time::Duration;
 
 use anyhow::Context;
 use async_trait::async_trait;
+use buck2_build_api::actions::artifact::get_artifact_fs::GetArtifactFs;
 use buck2_build_api::analysis::calculation::RuleAnalysisCalculation;
 use buck2_build_api::artifact_groups::calculation::ArtifactGroupCalculation;
 use buck2_build_api::artifact_groups::ArtifactGroup;
 use buck2_build_api::interpreter::rule_defs::cmd_args::SimpleCommandLineArtifactVisitor;
 use buck2_build_api::interpreter::rule_defs::provider::collection::FrozenProviderCollection;
 use buck2_build_api::interpreter::rule_defs::provider::test_provider::TestProvider;
+use buck2_cli_proto::local_test_execution::EnvironmentVariable;
 use buck2_cli_proto::HasClientContext;
+use buck2_cli_proto::LocalTestExecution;
 use buck2_cli_proto::TestRequest;
 use buck2_cli_proto::TestResponse;
 use buck2_common::dice::cells::HasCellResolver;
@@ -42,6 +45,8 @@ use buck2_core::cells::CellResolver;
 use buck2_core::configuration::compatibility::MaybeCompatible;
 use buck2_core::fs::fs_util;
 use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::abs_path::AbsPath;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
 use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
 use buck2_core::package::PackageLabel;
 use buck2_core::pattern::pattern_type::ConfiguredProvidersPatternExtra;
@@ -69,9 +74,11 @@ use buck2_server_ctx::pattern::parse_patterns_from_cli_args;
 use buck2_server_ctx::template::run_server_command;
 use buck2_server_ctx::template::ServerCommandTemplate;
 use buck2_server_ctx::test_command::TEST_COMMAND;
+use buck2_test_api::data::PrepareForLocalExecutionResult;
 use buck2_test_api::data::TestResult;
 use buck2_test_api::data::TestStatus;
 use buck2_test_api::protocol::TestExecutor;
+use buck2_test_api::sharding::TestShard;
 use dice::DiceComputations;
 use dice::DiceTransaction;
 use dupe::Dupe;
@@ -88,6 +95,9 @@ use indexmap::IndexSet;
 use itertools::Itertools;
 use serde::Serialize;
 
+use crate::coverage::write_coverage_report;
+use crate::coverage::CoverageOutput;
+use crate::coverage::CoverageTools;
 use crate::downward_api::BuckTestDownwardApi;
 use crate::executor_launcher::ExecutorLaunch;
 use crate::executor_launcher::ExecutorLauncher;
@@ -95,8 +105,12 @@ use crate::executor_launcher::OutOfProcessTestExecutor;
 use crate::local_resource_registry::LocalResourceRegistry;
 use crate::orchestrator::BuckTestOrchestrator;
 use crate::orchestrator::ExecutorMessage;
+use crate::report::TestResultsReport;
+use crate::result_cache::TestResultCache;
+use crate::result_cache::DEFAULT_MAX_ENTRIES;
 use crate::session::TestSession;
 use crate::session::TestSessionOptions;
+use crate::sharding::TestSharding;
 use crate::translations::build_configured_target_handle;
 
 #[derive(Debug, Serialize)]
@@ -110,6 +124,10 @@ struct TestOutcome {
     executor_report: ExecutorReport,
     executor_stdout: String,
     executor_stderr: String,
+    /// Coverage written by the tests, when collecting coverage.
+    coverage: Vec<CoverageOutput>,
+    /// Tests prepared for local execution by the test runner.
+    local_executions: Vec<(ConfiguredProvidersLabel, PrepareForLocalExecutionResult)>,
 }
 
 impl TestOutcome {
@@ -126,13 +144,18 @@ struct ExecutorReport {
     exit_code: Option<i32>,
     statuses: TestStatuses,
     info_messages: Vec<String>,
+    /// All the results, only collected when a report file was requested.
+    results: Option<TestResultsReport>,
 }
 
 impl ExecutorReport {
-    fn ingest(&mut self, status: &ExecutorMessage) {
+    fn ingest(&mut self, status: &ExecutorMessage, session: &TestSession) -> anyhow::Result<()> {
         match status {
             ExecutorMessage::TestResult(res) => {
                 self.statuses.ingest(res);
+                if let Some(results) = &mut self.results {
+                    results.ingest(session.get(res.target)?.to_string(), res);
+                }
             }
             ExecutorMessage::ExitCode(exit_code) => {
                 self.exit_code = Some(*exit_code);
@@ -141,6 +164,7 @@ impl ExecutorReport {
                 self.info_messages.push(message.clone());
             }
         }
+        Ok(())
     }
 }
 
@@ -185,6 +209,7 @@ struct TestStatuses {
     fatals: CounterWithExamples,
     listing_success: CounterWithExamples,
     listing_failed: CounterWithExamples,
+    flaky: CounterWithExamples,
 }
 impl TestStatuses {
     fn ingest(&mut self, result: &TestResult) {
@@ -199,6 +224,7 @@ impl TestStatuses {
             TestStatus::RERUN => {}
             TestStatus::LISTING_SUCCESS => self.listing_success.add(&result.name),
             TestStatus::LISTING_FAILED => self.listing_failed.add(&result.name),
+            TestStatus::FLAKY => self.flaky.add(&result.name),
         }
     }
 }
@@ -208,6 +234,13 @@ impl TestStatuses {
 #[error("This test run exceeded the deadline that was provided")]
 struct DeadlineExpired;
 
+#[derive(Debug, buck2_error_derive::Error)]
+#[buck2(user)]
+#[error(
+    "Running tests locally requires the internal test runner, but `test.v2_test_executor` is set"
+)]
+struct LocalExecutionUnsupported;
+
 async fn test_command(
     ctx: &dyn ServerCommandContextTrait,
     partial_result_dispatcher: PartialResultDispatcher<NoPartialResult>,
@@ -282,6 +315,29 @@ async fn test(
         .await?
         .filter(|s| !s.is_empty());
 
+    let sharding = if request.shard_count > 0 {
+        let shard = TestShard::new(request.shard_index, request.shard_count)?;
+        let durations = if request.shard_durations_path.is_empty() {
+            None
+        } else {
+            Some(fs_util::read_to_string(AbsPath::new(Path::new(
+                &request.shard_durations_path,
+            ))?)?)
+        };
+        Some(TestSharding::new(
+            shard,
+            durations.as_deref(),
+            // Only the internal test runner splits testcases across shards.
+            test_executor_config.is_none(),
+        )?)
+    } else {
+        None
+    };
+
+    if request.prepare_for_local_execution && test_executor_config.is_some() {
+        return Err(LocalExecutionUnsupported.into());
+    }
+
     let (test_executor, test_executor_args) = match test_executor_config {
         Some(config) => {
             let test_executor = post_process_test_executor(config.as_ref())
@@ -293,7 +349,18 @@ async fn test(
         None => {
             // If no v2_test_executor config was set, fall back to the internal test runner.
             let test_executor = std::env::current_exe()?;
-            let test_executor_args = vec!["internal-test-runner".to_owned()];
+            let mut test_executor_args = vec!["internal-test-runner".to_owned()];
+            if let Some(sharding) = &sharding {
+                test_executor_args.extend([
+                    "--shard-index".to_owned(),
+                    sharding.shard().index().to_string(),
+                    "--shard-count".to_owned(),
+                    sharding.shard().count().to_string(),
+                ]);
+            }
+            if request.prepare_for_local_execution {
+                test_executor_args.push("--prepare-for-local-execution".to_owned());
+            }
             (test_executor, test_executor_args)
         }
     };
@@ -316,10 +383,46 @@ async fn test(
         .as_ref()
         .context("Missing `options`")?;
 
+    let cache_results = !options.no_cache_tests
+        && ctx
+            .parse_legacy_config_property(cell_resolver.root_cell(), "test", "cache_results")
+            .await?
+            .unwrap_or(false);
+    let result_cache = if cache_results {
+        let max_entries = ctx
+            .parse_legacy_config_property(
+                cell_resolver.root_cell(),
+                "test",
+                "cache_results_max_entries",
+            )
+            .await?
+            .unwrap_or(DEFAULT_MAX_ENTRIES);
+        let fs = ctx.get_artifact_fs().await?;
+        Some((TestResultCache::in_buck_out(&fs), max_entries))
+    } else {
+        None
+    };
+
+    let coverage = if request.coverage_report_path.is_empty() {
+        None
+    } else {
+        let fs = ctx.get_artifact_fs().await?;
+        // Keyed by trace id, so that concurrent test commands don't merge each other's profiles.
+        let scratch_dir = fs.fs().resolve(
+            &fs.buck_out_path_resolver()
+                .root()
+                .join(ForwardRelativePath::unchecked_new("coverage"))
+                .join(ForwardRelativePath::new(&client_ctx.trace_id)?),
+        );
+        Some((coverage_tools(&ctx, &cell_resolver).await?, scratch_dir))
+    };
+
     let session = TestSession::new(TestSessionOptions {
         allow_re: options.allow_re,
         force_use_project_relative_paths: options.force_use_project_relative_paths,
         force_run_from_project_root: options.force_run_from_project_root,
+        cache_results,
+        collect_coverage: coverage.is_some(),
     });
 
     let build_opts = request
@@ -352,9 +455,35 @@ async fn test(
         build_opts.skip_incompatible_targets,
         MissingTargetBehavior::from_skip(build_opts.skip_missing_targets),
         timeout,
+        !request.junit_xml_path.is_empty() || !request.test_report_json_path.is_empty(),
+        sharding,
     )
     .await?;
 
+    if let Some(results) = &test_outcome.executor_report.results {
+        write_test_reports(request, results)?;
+    }
+
+    if let Some((result_cache, max_entries)) = &result_cache {
+        if let Err(e) = result_cache.evict(*max_entries) {
+            tracing::warn!("Error evicting cached test results: {:#}", e);
+        }
+    }
+
+    if let Some((tools, scratch_dir)) = &coverage {
+        let res = write_coverage_report(
+            &test_outcome.coverage,
+            tools,
+            scratch_dir,
+            AbsPath::new(Path::new(&request.coverage_report_path))?,
+        )
+        .await;
+        if let Err(e) = fs_util::remove_all(scratch_dir) {
+            tracing::warn!("Error removing coverage scratch directory: {:#}", e);
+        }
+        res?;
+    }
+
     // TODO(bobyf) remap exit code for buck reserved exit code
     let exit_code = test_outcome.exit_code().context("No exit code available")?;
 
@@ -401,6 +530,13 @@ async fn test(
                 .listing_failed
                 .to_cli_proto_counter(),
         ),
+        flaky: Some(
+            test_outcome
+                .executor_report
+                .statuses
+                .flaky
+                .to_cli_proto_counter(),
+        ),
     };
 
     Ok(TestResponse {
@@ -410,9 +546,60 @@ async fn test(
         executor_stdout: test_outcome.executor_stdout,
         executor_stderr: test_outcome.executor_stderr,
         executor_info_messages: test_outcome.executor_report.info_messages,
+        local_executions: test_outcome
+            .local_executions
+            .into_iter()
+            .map(|(target, result)| LocalTestExecution {
+                target: target.to_string(),
+                cmd: result.cmd,
+                env: result
+                    .env
+                    .into_iter()
+                    .map(|(key, value)| EnvironmentVariable { key, value })
+                    .collect(),
+                cwd: result.cwd.to_string(),
+            })
+            .collect(),
     })
 }
 
+async fn coverage_tools(
+    ctx: &DiceTransaction,
+    cell_resolver: &CellResolver,
+) -> anyhow::Result<CoverageTools> {
+    let tool = |key: &'static str, default: &'static str| async move {
+        anyhow::Ok(
+            ctx.get_legacy_config_property(cell_resolver.root_cell(), "test", key)
+                .await?
+                .filter(|s| !s.is_empty())
+                .map_or_else(|| default.to_owned(), |s| s.to_string()),
+        )
+    };
+    Ok(CoverageTools {
+        llvm_profdata: tool("llvm_profdata", "llvm-profdata").await?,
+        llvm_cov: tool("llvm_cov", "llvm-cov").await?,
+        gcov: tool("gcov", "gcov").await?,
+    })
+}
+
+fn write_test_reports(request: &TestRequest, results: &TestResultsReport) -> anyhow::Result<()> {
+    if !request.junit_xml_path.is_empty() {
+        fs_util::write(
+            AbsPath::new(Path::new(&request.junit_xml_path))?,
+            results.to_junit_xml(),
+        )
+        .context("Error writing JUnit XML report")?;
+    }
+    if !request.test_report_json_path.is_empty() {
+        fs_util::write(
+            AbsPath::new(Path::new(&request.test_report_json_path))?,
+            results.to_json()?,
+        )
+        .context("Error writing test report")?;
+    }
+    Ok(())
+}
+
 async fn test_targets(
     ctx: DiceTransaction,
     pattern: ResolvedPattern<ConfiguredProvidersPatternExtra>,
@@ -426,6 +613,8 @@ async fn test_targets(
     skip_incompatible_targets: bool,
     missing_target_behavior: MissingTargetBehavior,
     timeout: Option<Duration>,
+    collect_results: bool,
+    sharding: Option<TestSharding>,
 ) -> anyhow::Result<TestOutcome> {
     let session = Arc::new(session);
 
@@ -473,6 +662,7 @@ async fn test_targets(
 
     let test_server = tokio::spawn({
         let test_status_sender = test_status_sender.clone();
+        let session = session.dupe();
         let liveliness_observer = liveliness_observer.dupe();
         with_dispatcher_async(
             ctx.per_transaction_data().get_dispatcher().dupe(),
@@ -504,6 +694,7 @@ async fn test_targets(
                     cell_resolver: &cell_resolver,
                     working_dir_cell,
                     missing_target_behavior,
+                    sharding: sharding.as_ref(),
                 });
 
                 driver.push_pattern(
@@ -533,10 +724,13 @@ async fn test_targets(
 
                 // Wait for the tests to finish running.
 
+                let executor_report = ExecutorReport {
+                    results: collect_results.then(TestResultsReport::default),
+                    ..ExecutorReport::default()
+                };
                 let test_statuses = test_status_receiver
-                    .try_fold(ExecutorReport::default(), |mut acc, result| {
-                        acc.ingest(&result);
-                        future::ready(Ok(acc))
+                    .try_fold(executor_report, |mut acc, result| {
+                        future::ready(acc.ingest(&result, &session).map(|()| acc))
                     })
                     .await
                     .context("Did not receive all results from executor")?;
@@ -601,6 +795,8 @@ async fn test_targets(
         executor_stdout: executor_output.stdout,
         executor_stderr: executor_output.stderr,
         executor_report,
+        coverage: session.take_coverage(),
+        local_executions: session.take_local_executions(),
     })
 }
 
@@ -629,6 +825,7 @@ pub(crate) struct TestDriverState<'a, 'e, 'd> {
     cell_resolver: &'a CellResolver,
     working_dir_cell: CellName,
     missing_target_behavior: MissingTargetBehavior,
+    sharding: Option<&'a TestSharding>,
 }
 
 /// Maintains the state of an ongoing test execution.
@@ -809,6 +1006,7 @@ impl<'a, 'e, 'd> TestDriver<'a, 'e, 'd> {
                 state.label_filtering.dupe(),
                 state.cell_resolver,
                 state.working_dir_cell,
+                state.sharding,
             )
             .await?;
             anyhow::Ok(vec![])
@@ -863,12 +1061,22 @@ async fn test_target(
     label_filtering: Arc<TestLabelFiltering>,
     cell_resolver: &CellResolver,
     working_dir_cell: CellName,
+    sharding: Option<&TestSharding>,
 ) -> anyhow::Result<Option<ConfiguredProvidersLabel>> {
     // NOTE: We fail if we hit an incompatible target here. This can happen if we reach an
     // incompatible target via `tests = [...]`. This should perhaps change, but that's how it works
     // in v1: https://fb.workplace.com/groups/buckeng/posts/8520953297953210
     let frozen_providers = ctx.get_providers(&target).await?.require_compatible()?;
     let providers = frozen_providers.provider_collection();
+
+    // Skip tests of other shards before building them.
+    if let (Some(sharding), Some(test_info)) =
+        (sharding, <dyn TestProvider>::from_collection(providers))
+    {
+        if !sharding.includes(&target.unconfigured(), test_info.labels().into_iter()) {
+            return Ok(None);
+        }
+    }
     build_artifacts(ctx, providers, &label_filtering).await?;
 
     let fut = match <dyn TestProvider>::from_collection(providers) {
@@ -0,0 +1,434 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Code coverage collection for `buck2 test --coverage`.
+//!
+//! Every test gets an extra output directory, which `LLVM_PROFILE_FILE` and `GCOV_PREFIX`
+//! point into. Once all the tests have run, the raw profiles from all the tests are merged into
+//! a single lcov report: LLVM profiles with `llvm-profdata` and `llvm-cov`, gcov data files with
+//! `gcov --json-format`.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::ffi::OsStr;
+use std::fmt::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Stdio;
+
+use anyhow::Context as _;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::abs_path::AbsPath;
+use buck2_core::fs::paths::abs_path::AbsPathBuf;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use buck2_core::provider::label::ConfiguredProvidersLabel;
+use buck2_util::process::async_background_command;
+use serde::Deserialize;
+
+/// Name of the output directory that tests write their coverage data to.
+pub(crate) const COVERAGE_OUTPUT_NAME: &str = "buck2_coverage";
+
+/// Environment variables set on tests to write coverage into the coverage output directory,
+/// with the format of their value (`{}` is the directory).
+pub(crate) const COVERAGE_ENV: &[(&str, &str)] = &[
+    // `%p` and `%m` keep tests running several processes or binaries from overwriting each
+    // other's profiles.
+    ("LLVM_PROFILE_FILE", "{}/%p-%m.profraw"),
+    ("GCOV_PREFIX", "{}"),
+];
+
+/// The coverage output directory of a test execution.
+pub(crate) struct CoverageOutput {
+    pub target: ConfiguredProvidersLabel,
+    /// The test binary, which LLVM profiles need to be interpreted.
+    pub binary: Option<PathBuf>,
+    pub dir: AbsNormPathBuf,
+}
+
+/// The tools used to turn raw profiles into lcov, configured in the `[test]` buckconfig section.
+pub(crate) struct CoverageTools {
+    pub llvm_profdata: String,
+    pub llvm_cov: String,
+    pub gcov: String,
+}
+
+#[derive(Default)]
+struct FileCoverage {
+    /// Hit count by line number.
+    lines: BTreeMap<u32, u64>,
+    /// Start line and hit count by function name.
+    functions: BTreeMap<String, (u32, u64)>,
+}
+
+/// Line and function coverage by source file. Counts for the same source file from several
+/// binaries or tests are added up. Branch coverage is not kept.
+#[derive(Default)]
+pub(crate) struct LcovReport {
+    files: BTreeMap<String, FileCoverage>,
+}
+
+impl LcovReport {
+    fn add_line(&mut self, file: &str, line: u32, count: u64) {
+        *self
+            .files
+            .entry(file.to_owned())
+            .or_default()
+            .lines
+            .entry(line)
+            .or_default() += count;
+    }
+
+    fn add_function(&mut self, file: &str, name: &str, line: u32, count: u64) {
+        let function = self
+            .files
+            .entry(file.to_owned())
+            .or_default()
+            .functions
+            .entry(name.to_owned())
+            .or_insert((line, 0));
+        function.1 += count;
+    }
+
+    /// Add the records of an lcov tracefile, such as the output of `llvm-cov export`.
+    pub(crate) fn add_lcov(&mut self, lcov: &str) -> anyhow::Result<()> {
+        let mut file = None;
+        // `FNDA` records refer to functions declared by `FN` records.
+        let mut function_lines = BTreeMap::new();
+        for line in lcov.lines() {
+            let (record, value) = line.split_once(':').unwrap_or((line, ""));
+            let invalid = || format!("Invalid lcov record `{}`", line);
+            match record {
+                "SF" => {
+                    file = Some(value.to_owned());
+                    function_lines.clear();
+                }
+                "end_of_record" => file = None,
+                "DA" | "FN" | "FNDA" => {
+                    let file = file.as_deref().with_context(invalid)?;
+                    let (first, rest) = value.split_once(',').with_context(invalid)?;
+                    match record {
+                        "DA" => {
+                            // `DA` may have a trailing checksum.
+                            let count = rest.split(',').next().unwrap_or(rest);
+                            self.add_line(
+                                file,
+                                first.parse().with_context(invalid)?,
+                                count.parse().with_context(invalid)?,
+                            )
+                        }
+                        "FN" => {
+                            let line = first.parse().with_context(invalid)?;
+                            function_lines.insert(rest.to_owned(), line);
+                            self.add_function(file, rest, line, 0);
+                        }
+                        _ => {
+                            let line = function_lines.get(rest).copied().unwrap_or_default();
+                            self.add_function(
+                                file,
+                                rest,
+                                line,
+                                first.parse().with_context(invalid)?,
+                            );
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Add the output of `gcov --json-format --stdout`, which has one JSON document per data
+    /// file.
+    pub(crate) fn add_gcov_json(&mut self, json: &str) -> anyhow::Result<()> {
+        #[derive(Deserialize)]
+        struct GcovLine {
+            line_number: u32,
+            count: u64,
+        }
+
+        #[derive(Deserialize)]
+        struct GcovFunction {
+            name: String,
+            #[serde(default)]
+            demangled_name: Option<String>,
+            start_line: u32,
+            execution_count: u64,
+        }
+
+        #[derive(Deserialize)]
+        struct GcovFile {
+            file: String,
+            #[serde(default)]
+            functions: Vec<GcovFunction>,
+            #[serde(default)]
+            lines: Vec<GcovLine>,
+        }
+
+        #[derive(Deserialize)]
+        struct GcovOutput {
+            files: Vec<GcovFile>,
+        }
+
+        for output in serde_json::Deserializer::from_str(json).into_iter::<GcovOutput>() {
+            let output = output.context("Invalid gcov JSON output")?;
+            for file in output.files {
+                for line in file.lines {
+                    self.add_line(&file.file, line.line_number, line.count);
+                }
+                for function in file.functions {
+                    self.add_function(
+                        &file.file,
+                        function.demangled_name.as_ref().unwrap_or(&function.name),
+                        function.start_line,
+                        function.execution_count,
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn to_lcov(&self) -> String {
+        let mut res = String::new();
+        for (file, coverage) in &self.files {
+            res.push_str("TN:\n");
+            writeln!(res, "SF:{}", file).unwrap();
+            for (name, (line, _)) in &coverage.functions {
+                writeln!(res, "FN:{},{}", line, name).unwrap();
+            }
+            for (name, (_, count)) in &coverage.functions {
+                writeln!(res, "FNDA:{},{}", count, name).unwrap();
+            }
+            writeln!(res, "FNF:{}", coverage.functions.len()).unwrap();
+            writeln!(
+                res,
+                "FNH:{}",
+                coverage.functions.values().filter(|(_, c)| *c > 0).count()
+            )
+            .unwrap();
+            for (line, count) in &coverage.lines {
+                writeln!(res, "DA:{},{}", line, count).unwrap();
+            }
+            writeln!(res, "LF:{}", coverage.lines.len()).unwrap();
+            writeln!(
+                res,
+                "LH:{}",
+                coverage.lines.values().filter(|c| **c > 0).count()
+            )
+            .unwrap();
+            res.push_str("end_of_record\n");
+        }
+        res
+    }
+}
+
+fn find_files(
+    dir: &AbsNormPath,
+    extension: &str,
+    found: &mut Vec<AbsNormPathBuf>,
+) -> anyhow::Result<()> {
+    let entries = match fs_util::read_dir_if_exists(dir)? {
+        Some(entries) => entries,
+        None => return Ok(()),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            find_files(&path, extension, found)?;
+        } else if path.extension() == Some(extension.as_ref()) {
+            found.push(path);
+        }
+    }
+    Ok(())
+}
+
+async fn run_tool(tool: &str, args: &[&OsStr], cwd: Option<&Path>) -> anyhow::Result<String> {
+    let mut command = async_background_command(tool);
+    command.args(args).stdin(Stdio::null());
+    if let Some(cwd) = cwd {
+        command.current_dir(cwd);
+    }
+    let output = command
+        .output()
+        .await
+        .with_context(|| format!("Error running `{}`", tool))?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "`{}` failed with {}: {}",
+            tool,
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Merge the LLVM profiles of all the tests, and export the coverage of every test binary that
+/// wrote some.
+async fn add_llvm_coverage(
+    report: &mut LcovReport,
+    outputs: &[CoverageOutput],
+    tools: &CoverageTools,
+    scratch_dir: &AbsNormPath,
+) -> anyhow::Result<()> {
+    let mut profiles = Vec::new();
+    let mut binaries = BTreeSet::new();
+    for output in outputs {
+        let before = profiles.len();
+        find_files(&output.dir, "profraw", &mut profiles)?;
+        if profiles.len() > before {
+            if let Some(binary) = &output.binary {
+                binaries.insert(binary.clone());
+            }
+        }
+    }
+    if profiles.is_empty() {
+        return Ok(());
+    }
+
+    fs_util::create_dir_all(scratch_dir)?;
+    let profdata = scratch_dir.join(ForwardRelativePath::unchecked_new("merged.profdata"));
+    // There can be too many profiles to pass them as arguments.
+    let input_files = scratch_dir.join(ForwardRelativePath::unchecked_new("profraw_files"));
+    fs_util::write(
+        &input_files,
+        profiles
+            .iter()
+            .map(|p| format!("{}\n", p))
+            .collect::<String>(),
+    )?;
+    let args: [&OsStr; 6] = [
+        "merge".as_ref(),
+        "-sparse".as_ref(),
+        "-o".as_ref(),
+        profdata.as_os_str(),
+        "-f".as_ref(),
+        input_files.as_os_str(),
+    ];
+    run_tool(&tools.llvm_profdata, &args, None).await?;
+
+    for binary in binaries {
+        let instr_profile = format!("-instr-profile={}", profdata);
+        let args: [&OsStr; 4] = [
+            "export".as_ref(),
+            "-format=lcov".as_ref(),
+            instr_profile.as_ref(),
+            binary.as_os_str(),
+        ];
+        // Not every test binary is instrumented, e.g. when the test runs through a wrapper.
+        match run_tool(&tools.llvm_cov, &args, None).await {
+            Ok(lcov) => report.add_lcov(&lcov)?,
+            Err(e) => tracing::warn!("Skipping coverage of `{}`: {:#}", binary.display(), e),
+        }
+    }
+    Ok(())
+}
+
+/// `GCOV_PREFIX` makes tests write `.gcda` files under the coverage directory, at the absolute
+/// path of the object they belong to. `gcov` reads the matching `.gcno` notes file from next to
+/// the `.gcda` file, so it is copied there first.
+async fn add_gcov_coverage(
+    report: &mut LcovReport,
+    outputs: &[CoverageOutput],
+    tools: &CoverageTools,
+) -> anyhow::Result<()> {
+    for output in outputs {
+        let mut data_files = Vec::new();
+        find_files(&output.dir, "gcda", &mut data_files)?;
+        for data_file in data_files {
+            let relative = data_file
+                .strip_prefix(&output.dir)
+                .context("gcda file outside of coverage directory")?;
+            let notes = AbsPathBuf::new(
+                Path::new("/")
+                    .join(relative.as_str())
+                    .with_extension("gcno"),
+            )?;
+            if fs_util::try_exists(&notes)? {
+                fs_util::copy(&notes, AbsPathBuf::new(data_file.with_extension("gcno"))?)?;
+            }
+            let args: [&OsStr; 3] = [
+                "--json-format".as_ref(),
+                "--stdout".as_ref(),
+                data_file.as_os_str(),
+            ];
+            let cwd = data_file.parent().map(|p| p.as_path());
+            match run_tool(&tools.gcov, &args, cwd).await {
+                Ok(json) => report.add_gcov_json(&json)?,
+                Err(e) => tracing::warn!(
+                    "Skipping coverage of `{}` for `{}`: {:#}",
+                    data_file,
+                    output.target,
+                    e
+                ),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Merge the coverage written by all the tests into an lcov report at `path`.
+pub(crate) async fn write_coverage_report(
+    outputs: &[CoverageOutput],
+    tools: &CoverageTools,
+    scratch_dir: &AbsNormPath,
+    path: &AbsPath,
+) -> anyhow::Result<()> {
+    let mut report = LcovReport::default();
+    add_llvm_coverage(&mut report, outputs, tools, scratch_dir)
+        .await
+        .context("Error merging LLVM coverage")?;
+    add_gcov_coverage(&mut report, outputs, tools)
+        .await
+        .context("Error merging gcov coverage")?;
+    fs_util::write(path, report.to_lcov()).context("Error writing coverage report")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_lcov() {
+        let mut report = LcovReport::default();
+        let lcov = "TN:\nSF:/src/a.c\nFN:1,main\nFNDA:1,main\nDA:1,1\nDA:2,0\nBRDA:2,0,0,-\nend_of_record\n";
+        report.add_lcov(lcov).unwrap();
+        report.add_lcov(lcov).unwrap();
+        assert_eq!(
+            report.to_lcov(),
+            "TN:\nSF:/src/a.c\nFN:1,main\nFNDA:2,main\nFNF:1\nFNH:1\nDA:1,2\nDA:2,0\nLF:2\nLH:1\nend_of_record\n"
+        );
+    }
+
+    #[test]
+    fn test_gcov_json() {
+        let mut report = LcovReport::default();
+        let json = r#"{"format_version": "1", "files": [{"file": "b.cpp",
+            "functions": [{"name": "_Z1fv", "demangled_name": "f()", "start_line": 3, "execution_count": 0}],
+            "lines": [{"line_number": 3, "count": 0}, {"line_number": 4, "count": 5}]}]}
+            {"files": [{"file": "b.cpp", "lines": [{"line_number": 3, "count": 1}]}]}"#;
+        report.add_gcov_json(json).unwrap();
+        assert_eq!(
+            report.to_lcov(),
+            "TN:\nSF:b.cpp\nFN:3,f()\nFNDA:0,f()\nFNF:1\nFNH:0\nDA:3,1\nDA:4,5\nLF:2\nLH:2\nend_of_record\n"
+        );
+    }
+
+    #[test]
+    fn test_invalid_lcov() {
+        let mut report = LcovReport::default();
+        assert!(report.add_lcov("DA:1,1\n").is_err());
+        assert!(report.add_lcov("SF:a.c\nDA:x,1\n").is_err());
+    }
+}
@@ -11,8 +11,7 @@ use std::collections::BTreeMap;
 
 use buck2_common::local_resource_state::EnvironmentVariable;
 use buck2_common::local_resource_state::LocalResource;
-use buck2_common::local_resource_state::LocalResourceState;
-use buck2_core::target::configured_target_label::ConfiguredTargetLabel;
+use buck2_common::local_resource_state::LocalResourceBatch;
 use indexmap::IndexMap;
 use serde::Deserialize;
 
@@ -30,11 +29,10 @@ pub(crate) struct LocalResourcesSetupResult {
 }
 
 impl LocalResourcesSetupResult {
-    pub(crate) fn into_state(
+    pub(crate) fn into_batch(
         self,
-        resource_target: ConfiguredTargetLabel,
         provider_env_mapping: &IndexMap<String, String>,
-    ) -> anyhow::Result<LocalResourceState> {
+    ) -> anyhow::Result<LocalResourceBatch> {
         fn make_resource(
             alias_to_value: BTreeMap<String, String>,
             env_var_to_alias: &IndexMap<String, String>,
@@ -45,13 +43,16 @@ impl LocalResourcesSetupResult {
             }).collect::<Result<_, anyhow::Error>>()?;
             Ok(LocalResource(env_vars))
         }
-        let specs = self
+        let resources = self
             .resources
             .into_iter()
             .map(|res| make_resource(res, provider_env_mapping))
             .collect::<Result<_, anyhow::Error>>()?;
 
-        Ok(LocalResourceState::new(resource_target, self.pid, specs))
+        Ok(LocalResourceBatch {
+            owning_pid: self.pid,
+            resources,
+        })
     }
 }
 
@@ -59,15 +60,13 @@ impl LocalResourcesSetupResult {
 mod tests {
     use buck2_common::local_resource_state::EnvironmentVariable;
     use buck2_common::local_resource_state::LocalResource;
-    use buck2_core::configuration::data::ConfigurationData;
-    use buck2_core::target::configured_target_label::ConfiguredTargetLabel;
     use indexmap::indexmap;
     use maplit::btreemap;
 
     use crate::local_resource_api::LocalResourcesSetupResult;
 
-    #[tokio::test]
-    async fn test_into_state() -> anyhow::Result<()> {
+    #[test]
+    fn test_into_batch() -> anyhow::Result<()> {
         let setup_result = LocalResourcesSetupResult {
             pid: Some(42),
             resources: vec![
@@ -76,42 +75,26 @@ mod tests {
                 btreemap! { "socket_address".to_owned() => "qux".to_owned() },
             ],
         };
-        let target =
-            ConfiguredTargetLabel::testing_parse("foo//bar:baz", ConfigurationData::testing_new());
         let provider_env_mapping = indexmap! {
             "ENV_SOCKET".to_owned() => "socket_address".to_owned(),
         };
-        let state = setup_result.into_state(target, &provider_env_mapping)?;
-        assert_eq!(state.owning_pid(), Some(42));
-        let holder1 = state.acquire_resource().await;
-        let holder2 = state.acquire_resource().await;
-        let holder3 = state.acquire_resource().await;
+        let batch = setup_result.into_batch(&provider_env_mapping)?;
+        assert_eq!(batch.owning_pid, Some(42));
         assert_eq!(
-            holder1.as_ref(),
-            &LocalResource(vec![EnvironmentVariable {
-                key: "ENV_SOCKET".to_owned(),
-                value: "foo".to_owned()
-            },])
-        );
-        assert_eq!(
-            holder2.as_ref(),
-            &LocalResource(vec![EnvironmentVariable {
-                key: "ENV_SOCKET".to_owned(),
-                value: "baz".to_owned()
-            }])
-        );
-        assert_eq!(
-            holder3.as_ref(),
-            &LocalResource(vec![EnvironmentVariable {
-                key: "ENV_SOCKET".to_owned(),
-                value: "qux".to_owned()
-            }])
+            batch.resources,
+            ["foo", "baz", "qux"]
+                .iter()
+                .map(|value| LocalResource(vec![EnvironmentVariable {
+                    key: "ENV_SOCKET".to_owned(),
+                    value: (*value).to_owned()
+                }]))
+                .collect::<Vec<_>>()
         );
         Ok(())
     }
 
-    #[tokio::test]
-    async fn test_missing_value() -> anyhow::Result<()> {
+    #[test]
+    fn test_missing_value() -> anyhow::Result<()> {
         let setup_result = LocalResourcesSetupResult {
             pid: Some(42),
             resources: vec![
@@ -119,12 +102,10 @@ mod tests {
                 btreemap! { "something_else".to_owned() => "bar".to_owned() },
             ],
         };
-        let target =
-            ConfiguredTargetLabel::testing_parse("foo//bar:baz", ConfigurationData::testing_new());
         let provider_env_mapping = indexmap! {
             "ENV_SOCKET".to_owned() => "socket_address".to_owned(),
         };
-        let result = setup_result.into_state(target, &provider_env_mapping);
+        let result = setup_result.into_batch(&provider_env_mapping);
         assert!(result.is_err());
         let error_msg = result.unwrap_err().to_string();
         assert!(error_msg.contains("Missing value for local resource environment variable `ENV_SOCKET` with `socket_address` alias"));
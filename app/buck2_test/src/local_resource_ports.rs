@@ -0,0 +1,193 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Local ports for `LocalResourceInfo(ports = ...)` pools, which Buck2 sets up itself.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::net::Ipv4Addr;
+use std::net::TcpListener;
+use std::sync::Mutex;
+
+use anyhow::Context;
+use buck2_common::local_resource_state::LocalResourceBatch;
+use indexmap::IndexMap;
+
+use crate::local_resource_api::LocalResourcesSetupResult;
+
+/// Ports handed out to pools that were not released yet, across all test commands running in this
+/// daemon. The OS does not know about them once we stop listening, so it could hand them out again.
+static RESERVED_PORTS: Mutex<BTreeSet<u16>> = Mutex::new(BTreeSet::new());
+
+/// Ports reserved for a pool. They can be handed out again once this is dropped.
+struct ReservedPorts(Vec<u16>);
+
+impl Drop for ReservedPorts {
+    fn drop(&mut self) {
+        let mut reserved = RESERVED_PORTS.lock().unwrap();
+        for port in &self.0 {
+            reserved.remove(port);
+        }
+    }
+}
+
+fn reserve_ports(count: usize) -> anyhow::Result<ReservedPorts> {
+    let mut reserved = RESERVED_PORTS.lock().unwrap();
+    // Keep listening until we have all ports, so that the OS gives us distinct ones.
+    let mut listeners = Vec::with_capacity(count);
+    let mut ports = Vec::with_capacity(count);
+    while ports.len() < count {
+        // Ports we already handed out can come back, give up if that is all we get.
+        if listeners.len() >= count * 2 + 16 {
+            return Err(anyhow::anyhow!(
+                "Failed to find {} free local ports for a local resource pool",
+                count
+            ));
+        }
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .context("Failed to find a free local port")?;
+        let port = listener.local_addr()?.port();
+        if !reserved.contains(&port) {
+            ports.push(port);
+        }
+        listeners.push(listener);
+    }
+    reserved.extend(ports.iter().copied());
+    Ok(ReservedPorts(ports))
+}
+
+/// Creates a pool of `size` resources, each mapping every alias used in `env_var_mapping` to its
+/// own port.
+fn port_pool(
+    size: usize,
+    env_var_mapping: &IndexMap<String, String>,
+) -> anyhow::Result<(LocalResourcesSetupResult, ReservedPorts)> {
+    let aliases: BTreeSet<&String> = env_var_mapping.values().collect();
+    let reserved = reserve_ports(size * aliases.len())?;
+    let resources = reserved
+        .0
+        .chunks(aliases.len())
+        .map(|ports| {
+            aliases
+                .iter()
+                .zip(ports)
+                .map(|(alias, port)| ((*alias).to_owned(), port.to_string()))
+                .collect::<BTreeMap<_, _>>()
+        })
+        .collect();
+    Ok((
+        LocalResourcesSetupResult {
+            pid: None,
+            resources,
+        },
+        reserved,
+    ))
+}
+
+/// Ports of the batches of a `LocalResourceInfo(ports = ...)` pool which were not released yet.
+#[derive(Default)]
+pub(crate) struct PortBatches(Mutex<Vec<(LocalResourceBatch, ReservedPorts)>>);
+
+impl PortBatches {
+    /// Reserves the ports of a new batch of `size` resources.
+    pub(crate) fn setup(
+        &self,
+        size: usize,
+        env_var_mapping: &IndexMap<String, String>,
+    ) -> anyhow::Result<LocalResourceBatch> {
+        let (data, ports) = port_pool(size, env_var_mapping)?;
+        let batch = data.into_batch(env_var_mapping)?;
+        self.0.lock().unwrap().push((batch.clone(), ports));
+        Ok(batch)
+    }
+
+    /// Frees the ports of `batch`, so that they can be handed out again.
+    pub(crate) fn release(&self, batch: &LocalResourceBatch) {
+        self.0.lock().unwrap().retain(|(b, _)| b != batch);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use buck2_common::local_resource_state::LocalResourceBatch;
+    use indexmap::indexmap;
+
+    use crate::local_resource_ports::port_pool;
+    use crate::local_resource_ports::PortBatches;
+    use crate::local_resource_ports::RESERVED_PORTS;
+
+    fn ports(batch: &LocalResourceBatch) -> Vec<u16> {
+        batch
+            .resources
+            .iter()
+            .flat_map(|r| &r.0)
+            .map(|var| var.value.parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_port_pool() -> anyhow::Result<()> {
+        let mapping = indexmap! {
+            "DB_PORT".to_owned() => "db".to_owned(),
+            "ADMIN_PORT".to_owned() => "admin".to_owned(),
+            "DB_PORT_AGAIN".to_owned() => "db".to_owned(),
+        };
+        let (pool, reserved) = port_pool(3, &mapping)?;
+        assert_eq!(pool.resources.len(), 3);
+
+        let ports: BTreeSet<&String> = pool.resources.iter().flat_map(|r| r.values()).collect();
+        assert_eq!(ports.len(), 6);
+        for resource in &pool.resources {
+            assert_eq!(resource.keys().collect::<Vec<_>>(), vec!["admin", "db"]);
+        }
+
+        let (other_pool, _other_reserved) = port_pool(3, &mapping)?;
+        for resource in &other_pool.resources {
+            for port in resource.values() {
+                assert!(!ports.contains(port));
+            }
+        }
+
+        let port: u16 = pool.resources[0]["db"].parse()?;
+        assert!(RESERVED_PORTS.lock().unwrap().contains(&port));
+        drop(reserved);
+        assert!(!RESERVED_PORTS.lock().unwrap().contains(&port));
+        Ok(())
+    }
+
+    #[test]
+    fn test_replenish_port_pool() -> anyhow::Result<()> {
+        let mapping = indexmap! {
+            "PORT".to_owned() => "port".to_owned(),
+        };
+        let batches = PortBatches::default();
+        let first = batches.setup(2, &mapping)?;
+        let second = batches.setup(2, &mapping)?;
+        assert_eq!(batches.0.lock().unwrap().len(), 2);
+        for port in ports(&first).into_iter().chain(ports(&second)) {
+            assert!(RESERVED_PORTS.lock().unwrap().contains(&port));
+        }
+
+        // Releasing a retired batch only frees its own ports.
+        batches.release(&first);
+        assert_eq!(batches.0.lock().unwrap().len(), 1);
+        for port in ports(&first) {
+            assert!(!RESERVED_PORTS.lock().unwrap().contains(&port));
+        }
+        for port in ports(&second) {
+            assert!(RESERVED_PORTS.lock().unwrap().contains(&port));
+        }
+
+        batches.release(&second);
+        assert!(batches.0.lock().unwrap().is_empty());
+        Ok(())
+    }
+}
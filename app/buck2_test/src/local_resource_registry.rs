@@ -20,22 +20,24 @@ use dashmap::DashMap;
 use futures::future::BoxFuture;
 use futures::future::Shared;
 
-pub struct LocalResourceRegistry<'a>(
-    pub  DashMap<
+pub struct LocalResourceRegistry<'a> {
+    pub states: DashMap<
         ConfiguredTargetLabel,
         Shared<BoxFuture<'a, buck2_error::Result<LocalResourceState>>>,
     >,
-);
+}
 
 impl<'a> LocalResourceRegistry<'a> {
     pub(crate) fn new() -> Self {
-        LocalResourceRegistry(DashMap::new())
+        LocalResourceRegistry {
+            states: DashMap::new(),
+        }
     }
 
     pub(crate) async fn release_all_resources(&self) -> anyhow::Result<()> {
         // We setup resources prior to running tests so at this point everything should be set up, so just resolve all futures.
         let resource_futs = self
-            .0
+            .states
             .iter()
             .map(|entry| entry.value().clone())
             .collect::<Vec<_>>();
@@ -45,14 +47,21 @@ impl<'a> LocalResourceRegistry<'a> {
         }
 
         let cleanup = async move || -> anyhow::Result<()> {
-            let futs = futures::future::join_all(resource_futs)
+            let states = futures::future::join_all(resource_futs)
                 .await
                 .into_iter()
                 // Failed setup most likely means the test failed and problem will be reported in the test status.
                 .flat_map(|r| r.into_iter())
-                .filter(|s| s.owning_pid().is_some())
-                .map(|s| async move {
-                    let pid = s.owning_pid().unwrap();
+                .collect::<Vec<_>>();
+
+            // Tear down resources while whatever serves them is still running.
+            let teardown_result = futures::future::join_all(states.iter().map(|s| s.teardown()))
+                .await
+                .into_iter()
+                .collect::<Result<Vec<_>, _>>();
+
+            let futs = states.iter().flat_map(|s| {
+                s.owning_pids().into_iter().map(move |pid| async move {
                     try_terminate_process_gracefully(pid, Duration::from_secs(20))
                         .await
                         .context(format!(
@@ -60,12 +69,14 @@ impl<'a> LocalResourceRegistry<'a> {
                             pid,
                             s.source_target()
                         ))
-                });
+                })
+            });
 
             futures::future::join_all(futs)
                 .await
                 .into_iter()
                 .collect::<Result<_, _>>()?;
+            teardown_result?;
 
             Ok(())
         };
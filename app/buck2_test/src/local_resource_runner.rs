@@ -0,0 +1,255 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use buck2_common::liveliness_observer::LivelinessObserver;
+use buck2_common::local_resource_state::LocalResource;
+use buck2_common::local_resource_state::LocalResourceBatch;
+use buck2_common::local_resource_state::LocalResourceCommand;
+use buck2_common::local_resource_state::LocalResourceRunner;
+use buck2_core::target::configured_target_label::ConfiguredTargetLabel;
+use buck2_data::SetupLocalResourcesEnd;
+use buck2_data::SetupLocalResourcesStart;
+use buck2_data::ToProtoMessage;
+use buck2_error::Context;
+use buck2_events::dispatch::EventDispatcher;
+use buck2_execute::digest_config::DigestConfig;
+use buck2_execute::execute::claim::MutexClaimManager;
+use buck2_execute::execute::command_executor::CommandExecutor;
+use buck2_execute::execute::manager::CommandExecutionManager;
+use buck2_execute::execute::prepared::PreparedCommand;
+use buck2_execute::execute::request::CommandExecutionPaths;
+use buck2_execute::execute::request::CommandExecutionRequest;
+use buck2_execute::execute::result::CommandExecutionReport;
+use buck2_execute::execute::result::CommandExecutionResult;
+use buck2_execute::execute::result::CommandExecutionStatus;
+use buck2_execute::execute::target::CommandExecutionTarget;
+use buck2_futures::cancellation::CancellationContext;
+use dupe::Dupe;
+use indexmap::indexset;
+use indexmap::IndexMap;
+
+use crate::local_resource_api::LocalResourcesSetupResult;
+use crate::local_resource_ports::PortBatches;
+
+/// How the resources of a pool are set up.
+pub(crate) enum LocalResourceRunnerSetup {
+    /// Setup CLI command, run with this timeout.
+    Command(Vec<String>, Duration),
+    /// Buck2 allocates a pool of local ports of this size.
+    Ports(usize),
+}
+
+/// Runs the setup, health check and teardown commands of a local resource on the executor
+/// which runs the setup command, so all of them are run the same way.
+pub(crate) struct ExecutorLocalResourceRunner {
+    pub(crate) target: ConfiguredTargetLabel,
+    pub(crate) setup: LocalResourceRunnerSetup,
+    pub(crate) env_var_mapping: IndexMap<String, String>,
+    pub(crate) events: EventDispatcher,
+    pub(crate) liveliness_observer: Arc<dyn LivelinessObserver>,
+    pub(crate) digest_config: DigestConfig,
+    pub(crate) executor: CommandExecutor,
+    /// Ports of the batches of a `LocalResourceInfo(ports = ...)` pool, held until released.
+    pub(crate) port_batches: PortBatches,
+}
+
+impl ExecutorLocalResourceRunner {
+    /// Runs the setup command of the first batch of resources, which has the inputs of all
+    /// the commands of the local resource.
+    pub(crate) async fn setup_with_inputs(
+        &self,
+        request: CommandExecutionRequest,
+        cancellations: &CancellationContext<'_>,
+    ) -> anyhow::Result<LocalResourceBatch> {
+        let start = SetupLocalResourcesStart {
+            target_label: Some(self.target.as_proto()),
+        };
+        let end = SetupLocalResourcesEnd {};
+        let stdout = self
+            .events
+            .span_async(start, async move {
+                (
+                    self.exec("setup command", &request, cancellations).await,
+                    end,
+                )
+            })
+            .await?;
+        let string_content = String::from_utf8_lossy(&stdout);
+        let data: LocalResourcesSetupResult = serde_json::from_str(&string_content)
+            .context("Error parsing local resource setup command output")?;
+        data.into_batch(&self.env_var_mapping)
+    }
+
+    /// The request of a command without inputs, which were materialized for the first setup.
+    fn request(
+        &self,
+        argv: Vec<String>,
+        resource: Option<&LocalResource>,
+        timeout: Duration,
+    ) -> anyhow::Result<CommandExecutionRequest> {
+        let paths = CommandExecutionPaths::new(
+            vec![],
+            indexset![],
+            self.executor.fs(),
+            self.digest_config,
+        )?;
+        let env = resource
+            .into_iter()
+            .flat_map(|resource| &resource.0)
+            .map(|var| (var.key.clone(), var.value.clone()))
+            .collect();
+        Ok(CommandExecutionRequest::new(vec![], argv, paths, env).with_timeout(timeout))
+    }
+
+    /// Runs a command, returning its stdout if it succeeds. `kind` describes the command in errors.
+    async fn exec(
+        &self,
+        kind: &str,
+        request: &CommandExecutionRequest,
+        cancellations: &CancellationContext<'_>,
+    ) -> anyhow::Result<Vec<u8>> {
+        let manager = CommandExecutionManager::new(
+            Box::new(MutexClaimManager::new()),
+            self.events.dupe(),
+            self.liveliness_observer.dupe(),
+        );
+        let local_resource_target = LocalResourceTarget {
+            target: &self.target,
+        };
+        let prepared_action = self.executor.prepare_action(request, self.digest_config)?;
+        let prepared_command = PreparedCommand {
+            target: &local_resource_target as _,
+            request,
+            prepared_action: &prepared_action,
+            digest_config: self.digest_config,
+        };
+        let CommandExecutionResult {
+            outputs: _,
+            report:
+                CommandExecutionReport {
+                    std_streams,
+                    exit_code,
+                    status,
+                    timing: _,
+                    ..
+                },
+            rejected_execution: _,
+            did_cache_upload: _,
+            did_dep_file_cache_upload: _,
+            dep_file_key: _,
+            eligible_for_full_hybrid: _,
+            dep_file_metadata: _,
+        } = self
+            .executor
+            .exec_cmd(manager, &prepared_command, cancellations)
+            .await;
+
+        let std_streams = std_streams
+            .into_bytes()
+            .await
+            .with_context(|| format!("Error accessing output of local resource {}", kind))?;
+
+        match status {
+            CommandExecutionStatus::Success { .. } => Ok(std_streams.stdout),
+            CommandExecutionStatus::Failure { .. } => Err(anyhow::anyhow!(
+                "Local resource {} failed with `{}` exit code, stdout:\n{}\nstderr:\n{}\n",
+                kind,
+                exit_code.unwrap_or(1),
+                String::from_utf8_lossy(&std_streams.stdout),
+                String::from_utf8_lossy(&std_streams.stderr),
+            )),
+            CommandExecutionStatus::TimedOut { duration, .. } => Err(anyhow::anyhow!(
+                "Local resource {} timed out after `{}s`, stdout:\n{}\nstderr:\n{}\n",
+                kind,
+                duration.as_secs(),
+                String::from_utf8_lossy(&std_streams.stdout),
+                String::from_utf8_lossy(&std_streams.stderr),
+            )),
+            CommandExecutionStatus::Error {
+                stage: _,
+                error,
+                execution_kind: _,
+            } => Err(error),
+            CommandExecutionStatus::Cancelled => {
+                Err(anyhow::anyhow!("Local resource {} cancelled", kind))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl LocalResourceRunner for ExecutorLocalResourceRunner {
+    async fn setup(&self) -> anyhow::Result<LocalResourceBatch> {
+        match &self.setup {
+            LocalResourceRunnerSetup::Command(argv, timeout) => {
+                let request = self.request(argv.clone(), None, *timeout)?;
+                // Replacements are set up in the background of the tests waiting for them.
+                self.setup_with_inputs(request, CancellationContext::never_cancelled())
+                    .await
+            }
+            LocalResourceRunnerSetup::Ports(size) => {
+                self.port_batches.setup(*size, &self.env_var_mapping)
+            }
+        }
+    }
+
+    async fn run(
+        &self,
+        command: &LocalResourceCommand,
+        resource: &LocalResource,
+    ) -> anyhow::Result<()> {
+        let request = self.request(command.argv.clone(), Some(resource), command.timeout)?;
+        self.exec(
+            &format!("command `{}`", command.argv.join(" ")),
+            &request,
+            CancellationContext::never_cancelled(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    fn release(&self, batch: &LocalResourceBatch) {
+        self.port_batches.release(batch);
+    }
+}
+
+struct LocalResourceTarget<'a> {
+    target: &'a ConfiguredTargetLabel,
+}
+
+impl CommandExecutionTarget for LocalResourceTarget<'_> {
+    fn re_action_key(&self) -> String {
+        String::new()
+    }
+
+    fn re_affinity_key(&self) -> String {
+        String::new()
+    }
+
+    fn as_proto_action_key(&self) -> buck2_data::ActionKey {
+        buck2_data::ActionKey {
+            id: Default::default(),
+            owner: Some(buck2_data::action_key::Owner::LocalResourceSetup(
+                self.target.as_proto(),
+            )),
+            key: Default::default(),
+        }
+    }
+
+    fn as_proto_action_name(&self) -> buck2_data::ActionName {
+        buck2_data::ActionName {
+            category: "setup_local_resource".to_owned(),
+            identifier: "".to_owned(),
+        }
+    }
+}
@@ -12,6 +12,7 @@ use std::time::Duration;
 use anyhow::Context;
 use buck2_build_api::analysis::calculation::RuleAnalysisCalculation;
 use buck2_build_api::artifact_groups::ArtifactGroup;
+use buck2_build_api::interpreter::rule_defs::cmd_args::CommandLineArgLike;
 use buck2_build_api::interpreter::rule_defs::cmd_args::DefaultCommandLineContext;
 use buck2_build_api::interpreter::rule_defs::cmd_args::SimpleCommandLineArtifactVisitor;
 use buck2_build_api::interpreter::rule_defs::provider::builtin::external_runner_test_info::FrozenExternalRunnerTestInfo;
@@ -25,19 +26,33 @@ use dice::DiceTransaction;
 use dupe::Dupe;
 use indexmap::IndexMap;
 
+/// How the pool of resources is created.
+#[derive(Debug)]
+pub(crate) enum LocalResourceSetup {
+    /// Setup CLI command.
+    Command(Vec<String>),
+    /// Buck2 allocates a pool of local ports of this size.
+    Ports(usize),
+}
+
 /// Container for everything needed to set up a local resource.
 #[derive(Debug)]
 pub(crate) struct LocalResourceSetupContext {
     /// Configured target providing a local resource.
     pub target: ConfiguredTargetLabel,
-    /// Setup CLI command.
-    pub cmd: Vec<String>,
-    /// Artifacts referenced in setup command.
+    pub setup: LocalResourceSetup,
+    /// Command run on a resource before handing it out.
+    pub health_check: Option<Vec<String>>,
+    /// Command run on every resource when the pool is released.
+    pub teardown: Option<Vec<String>>,
+    /// Number of times a resource can be handed out.
+    pub max_uses: Option<u64>,
+    /// Artifacts referenced in setup, health check and teardown commands.
     pub input_artifacts: Vec<ArtifactGroup>,
     /// Mapping from keys in JSON output of setup command to environment variable names
     /// which should be added to executions dependent on this local resource.
     pub env_var_mapping: IndexMap<String, String>,
-    /// Timeout for setup command.
+    /// Timeout for setup, health check and teardown commands.
     pub timeout: Option<Duration>,
 }
 
@@ -51,16 +66,40 @@ pub(crate) async fn required_local_resources_setup_contexts(
     let mut cmd_line_context = DefaultCommandLineContext::new(executor_fs);
     let mut result = vec![];
     for (source_target_label, provider) in providers {
-        let setup_command_line = provider.setup_command_line();
-        let mut cmd: Vec<String> = vec![];
-        setup_command_line.add_to_command_line(&mut cmd, &mut cmd_line_context)?;
-
         let mut artifact_visitor = SimpleCommandLineArtifactVisitor::new();
-        setup_command_line.visit_artifacts(&mut artifact_visitor)?;
+        let mut expand = |command_line: Option<&dyn CommandLineArgLike>| {
+            command_line
+                .map(|command_line| {
+                    let mut cmd: Vec<String> = vec![];
+                    command_line.add_to_command_line(&mut cmd, &mut cmd_line_context)?;
+                    command_line.visit_artifacts(&mut artifact_visitor)?;
+                    anyhow::Ok(cmd)
+                })
+                .transpose()
+        };
+
+        let setup = match (
+            expand(provider.setup_command_line())?,
+            provider.port_pool_size(),
+        ) {
+            (Some(cmd), _) => LocalResourceSetup::Command(cmd),
+            (None, Some(size)) => LocalResourceSetup::Ports(size),
+            (None, None) => {
+                return Err(anyhow::anyhow!(
+                    "`LocalResourceInfo` in `{}` has neither `setup` nor `ports`",
+                    source_target_label
+                ));
+            }
+        };
+        let health_check = expand(provider.health_check_command_line())?;
+        let teardown = expand(provider.teardown_command_line())?;
 
         result.push(LocalResourceSetupContext {
             target: source_target_label.dupe(),
-            cmd,
+            setup,
+            health_check,
+            teardown,
+            max_uses: provider.max_uses(),
             input_artifacts: artifact_visitor.inputs.into_iter().collect(),
             env_var_mapping: provider.env_var_mapping(),
             timeout: provider.setup_timeout(),
@@ -34,6 +34,9 @@ use buck2_build_api::interpreter::rule_defs::provider::builtin::external_runner_
 use buck2_common::dice::cells::HasCellResolver;
 use buck2_common::events::HasEvents;
 use buck2_common::liveliness_observer::LivelinessObserver;
+use buck2_common::local_resource_state::LocalResourceCommand;
+use buck2_common::local_resource_state::LocalResourcePoolOptions;
+use buck2_common::local_resource_state::LocalResourceRunner;
 use buck2_common::local_resource_state::LocalResourceState;
 use buck2_core::cells::cell_root_path::CellRootPathBuf;
 use buck2_core::execution_types::executor_config::CommandExecutorConfig;
@@ -41,16 +44,17 @@ use buck2_core::execution_types::executor_config::CommandGenerationOptions;
 use buck2_core::execution_types::executor_config::Executor;
 use buck2_core::execution_types::executor_config::LocalExecutorOptions;
 use buck2_core::execution_types::executor_config::PathSeparatorKind;
+use buck2_core::execution_types::timeout_escalation::StackDump;
+use buck2_core::execution_types::timeout_escalation::TimeoutEscalation;
 use buck2_core::fs::artifact_path_resolver::ArtifactFs;
 use buck2_core::fs::buck_out_path::BuckOutTestPath;
+use buck2_core::fs::fs_util;
 use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
 use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
 use buck2_core::fs::project_rel_path::ProjectRelativePath;
 use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
 use buck2_core::provider::label::ConfiguredProvidersLabel;
 use buck2_core::target::configured_target_label::ConfiguredTargetLabel;
-use buck2_data::SetupLocalResourcesEnd;
-use buck2_data::SetupLocalResourcesStart;
 use buck2_data::TestDiscovery;
 use buck2_data::TestDiscoveryEnd;
 use buck2_data::TestDiscoveryStart;
@@ -123,15 +127,25 @@ use sorted_vector_map::SortedVectorMap;
 use starlark::values::FrozenRef;
 use uuid::Uuid;
 
-use crate::local_resource_api::LocalResourcesSetupResult;
+use crate::coverage::CoverageOutput;
+use crate::coverage::COVERAGE_ENV;
+use crate::coverage::COVERAGE_OUTPUT_NAME;
+use crate::local_resource_ports::PortBatches;
 use crate::local_resource_registry::LocalResourceRegistry;
+use crate::local_resource_runner::ExecutorLocalResourceRunner;
+use crate::local_resource_runner::LocalResourceRunnerSetup;
 use crate::local_resource_setup::required_local_resources_setup_contexts;
+use crate::local_resource_setup::LocalResourceSetup;
 use crate::local_resource_setup::LocalResourceSetupContext;
+use crate::result_cache::TestResultCache;
 use crate::session::TestSession;
 use crate::translations;
 
 const MAX_SUFFIX_LEN: usize = 1024;
 
+/// Name of the test output holding the `gdb` stack dump of a test that timed out.
+const STACK_DUMP_OUTPUT_NAME: &str = "buck2_stack_dump";
+
 #[derive(Debug, Eq, PartialEq)]
 pub enum ExecutorMessage {
     TestResult(TestResult),
@@ -214,6 +228,7 @@ impl<'a> BuckTestOrchestrator<'a> {
         pre_create_dirs: Vec<DeclaredOutput>,
         executor_override: Option<ExecutorConfigOverride>,
         required_local_resources: RequiredLocalResources,
+        timeout_escalation: Option<TimeoutEscalation<()>>,
     ) -> Result<ExecutionResult2, ExecuteError> {
         self.require_alive().await?;
 
@@ -225,6 +240,31 @@ impl<'a> BuckTestOrchestrator<'a> {
         let test_executor = self
             .get_test_executor(&test_target, &test_info, executor_override, &fs)
             .await?;
+
+        let collect_coverage = self.session.options().collect_coverage
+            && matches!(metadata, DisplayMetadata::Testing { .. });
+        let mut env = env;
+        let mut pre_create_dirs = pre_create_dirs;
+        if collect_coverage {
+            let output = DeclaredOutput {
+                name: ForwardRelativePathBuf::unchecked_new(COVERAGE_OUTPUT_NAME.to_owned()),
+                supports_remote: false,
+            };
+            for (var, format) in COVERAGE_ENV {
+                // Tests that set these themselves handle their own coverage.
+                if !env.contains_key(*var) {
+                    env.insert(
+                        (*var).to_owned(),
+                        ArgValue {
+                            content: ArgValueContent::DeclaredOutput(output.clone()),
+                            format: Some((*format).to_owned()),
+                        },
+                    );
+                }
+            }
+            pre_create_dirs.push(output);
+        }
+
         let test_executable_expanded = self
             .expand_test_executable(
                 &test_target,
@@ -247,6 +287,38 @@ impl<'a> BuckTestOrchestrator<'a> {
 
         let executor_preference = self.executor_preference(supports_re)?;
 
+        let binary = expanded_cmd
+            .first()
+            .map(|binary| fs.fs().resolve(&cwd).as_path().join(binary));
+
+        // A `gdb` stack dump is written by the forkserver when it kills the test, so it can't be
+        // a declared output: we only know after the fact whether it exists.
+        let stack_dump_path = match &timeout_escalation {
+            Some(TimeoutEscalation {
+                stack_dump: Some(StackDump::Gdb(())),
+                ..
+            }) if matches!(metadata, DisplayMetadata::Testing { .. }) => {
+                let test_path = BuckOutTestPath::new(
+                    self.session
+                        .prefix()
+                        .join(ForwardRelativePathBuf::unchecked_new(
+                            Uuid::new_v4().to_string(),
+                        )),
+                    ForwardRelativePathBuf::unchecked_new(STACK_DUMP_OUTPUT_NAME.to_owned()),
+                );
+                let path = fs
+                    .fs()
+                    .resolve(&fs.buck_out_path_resolver().resolve_test(&test_path));
+                if let Some(parent) = path.parent() {
+                    fs_util::create_dir_all(parent)?;
+                }
+                Some(path)
+            }
+            _ => None,
+        };
+        let timeout_escalation = timeout_escalation
+            .map(|escalation| escalation.map_gdb_output(|()| stack_dump_path.clone()));
+
         let required_resources = if test_executor.is_local_execution_possible(executor_preference) {
             let setup_local_resources_executor = self.get_local_executor(&fs).await?;
 
@@ -281,6 +353,7 @@ impl<'a> BuckTestOrchestrator<'a> {
                 declared_outputs,
                 &fs,
                 Some(timeout),
+                timeout_escalation,
                 Some(host_sharing_requirements),
                 Some(executor_preference),
                 required_resources,
@@ -294,6 +367,7 @@ impl<'a> BuckTestOrchestrator<'a> {
             timing,
             execution_kind,
             outputs,
+            cached,
         } = self
             .execute_request(&test_target, metadata, &test_executor, execution_request)
             .await?;
@@ -306,6 +380,16 @@ impl<'a> BuckTestOrchestrator<'a> {
         for (test_path, artifact) in outputs {
             let project_relative_path = fs.buck_out_path_resolver().resolve_test(&test_path);
             let output_name = test_path.into_path();
+            if collect_coverage && output_name.as_str() == COVERAGE_OUTPUT_NAME {
+                // Coverage is merged after all tests ran, the test runner does not know about it.
+                paths_to_materialize.push(project_relative_path.clone());
+                self.session.record_coverage(CoverageOutput {
+                    target: test_target.clone(),
+                    binary: binary.clone(),
+                    dir: fs.fs().resolve(&project_relative_path),
+                });
+                continue;
+            }
             // It's OK to search iteratively here because there will be few entries in `pre_create_dirs`
             let supports_remote = pre_create_dirs
                 .iter()
@@ -340,6 +424,20 @@ impl<'a> BuckTestOrchestrator<'a> {
             output_map.insert(declared_output, output);
         }
 
+        if let Some(path) = stack_dump_path {
+            if matches!(status, ExecutionStatus::TimedOut { .. }) && fs_util::try_exists(&path)? {
+                output_map.insert(
+                    DeclaredOutput {
+                        name: ForwardRelativePathBuf::unchecked_new(
+                            STACK_DUMP_OUTPUT_NAME.to_owned(),
+                        ),
+                        supports_remote: false,
+                    },
+                    Output::LocalPath(path),
+                );
+            }
+        }
+
         // Request materialization in case this ran on RE. Eventually Tpx should be able to
         // understand remote outputs but currently we don't have this.
         self.dice
@@ -358,6 +456,7 @@ impl<'a> BuckTestOrchestrator<'a> {
             execution_time: timing.execution_time,
             execution_details: ExecutionDetails {
                 execution_kind: execution_kind.map(|k| k.to_proto(false)),
+                cached,
             },
         })
     }
@@ -365,8 +464,22 @@ impl<'a> BuckTestOrchestrator<'a> {
 
 struct PreparedLocalResourceSetupContext {
     pub target: ConfiguredTargetLabel,
-    pub execution_request: CommandExecutionRequest,
+    /// The setup command with the inputs of all the commands, if the setup runs a command.
+    pub request: Option<CommandExecutionRequest>,
+    pub setup: LocalResourceRunnerSetup,
     pub env_var_mapping: IndexMap<String, String>,
+    pub options: LocalResourcePoolOptions,
+}
+
+#[derive(Debug, buck2_error_derive::Error)]
+#[buck2(user)]
+#[error(
+    "`{target}` requires local resources ({}), which can't be set up when running a test locally",
+    .resources.join(", ")
+)]
+struct LocalResourcesUnsupported {
+    target: ConfiguredProvidersLabel,
+    resources: Vec<String>,
 }
 
 // A token used to implement From
@@ -392,6 +505,7 @@ impl<'a> TestOrchestrator for BuckTestOrchestrator<'a> {
         pre_create_dirs: Vec<DeclaredOutput>,
         executor_override: Option<ExecutorConfigOverride>,
         required_local_resources: RequiredLocalResources,
+        timeout_escalation: Option<TimeoutEscalation<()>>,
     ) -> anyhow::Result<ExecuteResponse> {
         let res = BuckTestOrchestrator::execute2(
             self,
@@ -404,6 +518,7 @@ impl<'a> TestOrchestrator for BuckTestOrchestrator<'a> {
             pre_create_dirs,
             executor_override,
             required_local_resources,
+            timeout_escalation,
         )
         .await;
 
@@ -476,6 +591,20 @@ impl<'a> TestOrchestrator for BuckTestOrchestrator<'a> {
         let fs = self.dice.clone().get_artifact_fs().await?;
 
         let test_info = self.get_test_info(&test_target).await?;
+        // The client runs the test after this command exits, so nothing would keep the
+        // resources alive or tear them down.
+        let local_resources = test_info
+            .local_resources()
+            .into_iter()
+            .filter_map(|(resource_type, target)| target.map(|_| resource_type.to_owned()))
+            .collect::<Vec<_>>();
+        if !local_resources.is_empty() {
+            return Err(LocalResourcesUnsupported {
+                target: test_target,
+                resources: local_resources,
+            }
+            .into());
+        }
         // Tests are not run, so there is no executor override.
         let executor = self
             .get_test_executor(&test_target, &test_info, None, &fs)
@@ -511,6 +640,7 @@ impl<'a> TestOrchestrator for BuckTestOrchestrator<'a> {
                 None,
                 None,
                 None,
+                None,
                 vec![],
             )
             .await?;
@@ -529,10 +659,10 @@ impl<'a> TestOrchestrator for BuckTestOrchestrator<'a> {
         )
         .await?;
 
-        Ok(create_prepare_for_local_execution_result(
-            &fs,
-            execution_request,
-        ))
+        let result = create_prepare_for_local_execution_result(&fs, execution_request);
+        self.session
+            .record_local_execution(test_target, result.clone());
+        Ok(result)
     }
 
     async fn attach_info_message(&self, message: String) -> anyhow::Result<()> {
@@ -550,6 +680,8 @@ struct ExecuteData {
     pub timing: CommandExecutionMetadata,
     pub execution_kind: Option<CommandExecutionKind>,
     pub outputs: Vec<(BuckOutTestPath, ArtifactValue)>,
+    /// Whether this is a result from the [`TestResultCache`] rather than an execution.
+    pub cached: bool,
 }
 
 impl<'b> BuckTestOrchestrator<'b> {
@@ -599,9 +731,41 @@ impl<'b> BuckTestOrchestrator<'b> {
             action_key_suffix,
         };
 
-        // For test execution, we currently do not do any cache queries
+        // Only passing tests without outputs are cached, since their outputs are not kept.
+        // Listings are always run.
+        let result_cache = if self.session.options().cache_results
+            && matches!(metadata, DisplayMetadata::Testing { .. })
+            && request.outputs().next().is_none()
+        {
+            Some(TestResultCache::in_buck_out(executor.fs()))
+        } else {
+            None
+        };
 
         let prepared_action = executor.prepare_action(&request, self.digest_config)?;
+        let action_digest = &prepared_action.action_and_blobs.action;
+
+        if let Some(result_cache) = &result_cache {
+            match result_cache.get(action_digest) {
+                Ok(Some(cached)) => {
+                    return Ok(ExecuteData {
+                        stdout: ExecutionStream::Inline(cached.stdout),
+                        stderr: ExecutionStream::Inline(cached.stderr),
+                        status: ExecutionStatus::Finished { exitcode: 0 },
+                        timing: CommandExecutionMetadata {
+                            execution_time: cached.execution_time,
+                            ..Default::default()
+                        },
+                        execution_kind: None,
+                        outputs: Vec::new(),
+                        cached: true,
+                    });
+                }
+                Ok(None) => {}
+                Err(e) => tracing::warn!("Error reading cached test result: {:#}", e),
+            }
+        }
+
         let prepared_command = PreparedCommand {
             target: &test_target as _,
             request: &request,
@@ -685,6 +849,22 @@ impl<'b> BuckTestOrchestrator<'b> {
             .into_bytes()
             .await
             .context("Error accessing test output")?;
+
+        if let Some(result_cache) = &result_cache {
+            if matches!(status, CommandExecutionStatus::Success { .. })
+                && exit_code.unwrap_or(0) == 0
+            {
+                if let Err(e) = result_cache.put(
+                    action_digest,
+                    &std_streams.stdout,
+                    &std_streams.stderr,
+                    timing.execution_time,
+                ) {
+                    tracing::warn!("Error caching test result: {:#}", e);
+                }
+            }
+        }
+
         let stdout = ExecutionStream::Inline(std_streams.stdout);
         let stderr = ExecutionStream::Inline(std_streams.stderr);
 
@@ -698,6 +878,7 @@ impl<'b> BuckTestOrchestrator<'b> {
                 timing,
                 execution_kind: Some(execution_kind),
                 outputs,
+                cached: false,
             },
             CommandExecutionStatus::Failure { execution_kind } => ExecuteData {
                 stdout,
@@ -708,6 +889,7 @@ impl<'b> BuckTestOrchestrator<'b> {
                 timing,
                 execution_kind: Some(execution_kind),
                 outputs,
+                cached: false,
             },
             CommandExecutionStatus::TimedOut {
                 duration,
@@ -719,6 +901,7 @@ impl<'b> BuckTestOrchestrator<'b> {
                 timing,
                 execution_kind: Some(execution_kind),
                 outputs,
+                cached: false,
             },
             CommandExecutionStatus::Error {
                 stage: _,
@@ -733,6 +916,7 @@ impl<'b> BuckTestOrchestrator<'b> {
                 timing,
                 execution_kind,
                 outputs,
+                cached: false,
             },
             CommandExecutionStatus::Cancelled => {
                 return Err(ExecuteError::Cancelled(Cancelled));
@@ -944,6 +1128,7 @@ impl<'b> BuckTestOrchestrator<'b> {
         declared_outputs: IndexMap<BuckOutTestPath, OutputCreationBehavior>,
         fs: &ArtifactFs,
         timeout: Option<Duration>,
+        timeout_escalation: Option<TimeoutEscalation>,
         host_sharing_requirements: Option<HostSharingRequirements>,
         executor_preference: Option<ExecutorPreference>,
         required_local_resources: Vec<LocalResourceState>,
@@ -978,6 +1163,9 @@ impl<'b> BuckTestOrchestrator<'b> {
         if let Some(timeout) = timeout {
             request = request.with_timeout(timeout)
         }
+        if let Some(timeout_escalation) = timeout_escalation {
+            request = request.with_timeout_escalation(timeout_escalation);
+        }
         if let Some(host_sharing_requirements) = host_sharing_requirements {
             request = request.with_host_sharing_requirements(host_sharing_requirements);
         }
@@ -1004,7 +1192,7 @@ impl<'b> BuckTestOrchestrator<'b> {
         let resource_futs = setup_commands.into_iter().map(|context| {
             let local_resource_target = context.target.dupe();
             self.local_resource_state_registry
-                .0
+                .states
                 .entry(local_resource_target.dupe())
                 .or_insert_with(|| {
                     let setup = Self::start_local_resource(
@@ -1052,15 +1240,41 @@ impl<'b> BuckTestOrchestrator<'b> {
             .into_iter()
             .map(|group_values| CommandExecutionInput::Artifact(Box::new(group_values)))
             .collect();
+        let timeout = context.timeout.unwrap_or(default_timeout);
         let paths = CommandExecutionPaths::new(inputs, indexset![], fs, self.digest_config)?;
-        let mut execution_request =
-            CommandExecutionRequest::new(vec![], context.cmd, paths, Default::default());
-        execution_request =
-            execution_request.with_timeout(context.timeout.unwrap_or(default_timeout));
+        let (request, setup) = match context.setup {
+            LocalResourceSetup::Command(cmd) => {
+                let execution_request =
+                    CommandExecutionRequest::new(vec![], cmd.clone(), paths, Default::default())
+                        .with_timeout(timeout);
+                (
+                    Some(execution_request),
+                    LocalResourceRunnerSetup::Command(cmd, timeout),
+                )
+            }
+            LocalResourceSetup::Ports(size) => {
+                // There is no setup command whose execution would materialize the inputs of the
+                // health check and teardown commands.
+                materialize_inputs(
+                    fs,
+                    self.dice.per_transaction_data().get_materializer().as_ref(),
+                    &CommandExecutionRequest::new(vec![], vec![], paths, Default::default()),
+                )
+                .await?;
+                (None, LocalResourceRunnerSetup::Ports(size))
+            }
+        };
+        let command = |argv| LocalResourceCommand { argv, timeout };
         Ok(PreparedLocalResourceSetupContext {
             target: context.target,
-            execution_request,
+            request,
+            setup,
             env_var_mapping: context.env_var_mapping,
+            options: LocalResourcePoolOptions {
+                health_check: context.health_check.map(command),
+                teardown: context.teardown.map(command),
+                max_uses: context.max_uses,
+            },
         })
     }
 
@@ -1072,91 +1286,26 @@ impl<'b> BuckTestOrchestrator<'b> {
         context: PreparedLocalResourceSetupContext,
         cancellations: &'b CancellationContext<'b>,
     ) -> buck2_error::Result<LocalResourceState> {
-        let manager = CommandExecutionManager::new(
-            Box::new(MutexClaimManager::new()),
-            events.dupe(),
+        let runner = ExecutorLocalResourceRunner {
+            target: context.target.dupe(),
+            setup: context.setup,
+            env_var_mapping: context.env_var_mapping,
+            events,
             liveliness_observer,
-        );
-
-        let local_resource_target = LocalResourceTarget {
-            target: &context.target,
-        };
-        let prepared_action = executor.prepare_action(&context.execution_request, digest_config)?;
-        let prepared_command = PreparedCommand {
-            target: &local_resource_target as _,
-            request: &context.execution_request,
-            prepared_action: &prepared_action,
             digest_config,
+            executor,
+            port_batches: PortBatches::default(),
         };
-        let command = executor.exec_cmd(manager, &prepared_command, cancellations);
-
-        let start = SetupLocalResourcesStart {
-            target_label: Some(context.target.as_proto()),
-        };
-        let end = SetupLocalResourcesEnd {};
-        let execution_result = events
-            .span_async(start, async move { (command.await, end) })
-            .await;
-
-        let CommandExecutionResult {
-            outputs: _,
-            report:
-                CommandExecutionReport {
-                    std_streams,
-                    exit_code,
-                    status,
-                    timing: _,
-                    ..
-                },
-            rejected_execution: _,
-            did_cache_upload: _,
-            did_dep_file_cache_upload: _,
-            dep_file_key: _,
-            eligible_for_full_hybrid: _,
-            dep_file_metadata: _,
-        } = execution_result;
-
-        let std_streams = std_streams
-            .into_bytes()
-            .await
-            .context("Error accessing setup local resource output")?;
-
-        match status {
-            CommandExecutionStatus::Success { .. } => {}
-            CommandExecutionStatus::Failure { .. } => {
-                return Err(anyhow::anyhow!(
-                    "Local resource setup command failed with `{}` exit code, stdout:\n{}\nstderr:\n{}\n",
-                    exit_code.unwrap_or(1),
-                    String::from_utf8_lossy(&std_streams.stdout),
-                    String::from_utf8_lossy(&std_streams.stderr),
-                ).into());
-            }
-            CommandExecutionStatus::TimedOut { duration, .. } => {
-                return Err(anyhow::anyhow!(
-                    "Local resource setup command timed out after `{}s`, stdout:\n{}\nstderr:\n{}\n",
-                    duration.as_secs(),
-                    String::from_utf8_lossy(&std_streams.stdout),
-                    String::from_utf8_lossy(&std_streams.stderr),
-                ).into());
-            }
-            CommandExecutionStatus::Error {
-                stage: _,
-                error,
-                execution_kind: _,
-            } => {
-                return Err(error.into());
-            }
-            CommandExecutionStatus::Cancelled => {
-                return Err(anyhow::anyhow!("Local resource setup command cancelled").into());
-            }
+        let batch = match context.request {
+            Some(request) => runner.setup_with_inputs(request, cancellations).await?,
+            None => runner.setup().await?,
         };
-
-        let string_content = String::from_utf8_lossy(&std_streams.stdout);
-        let data: LocalResourcesSetupResult = serde_json::from_str(&string_content)
-            .context("Error parsing local resource setup command output")?;
-        let state = data.into_state(context.target.clone(), &context.env_var_mapping)?;
-
-        Ok(state)
+        Ok(LocalResourceState::new(
+            context.target,
+            batch,
+            context.options,
+            Arc::new(runner),
+        ))
     }
 }
 
@@ -1427,37 +1576,6 @@ impl CommandExecutionTarget for TestTarget<'_> {
 }
 
 #[derive(Debug)]
-struct LocalResourceTarget<'a> {
-    target: &'a ConfiguredTargetLabel,
-}
-
-impl CommandExecutionTarget for LocalResourceTarget<'_> {
-    fn re_action_key(&self) -> String {
-        String::new()
-    }
-
-    fn re_affinity_key(&self) -> String {
-        String::new()
-    }
-
-    fn as_proto_action_key(&self) -> buck2_data::ActionKey {
-        buck2_data::ActionKey {
-            id: Default::default(),
-            owner: Some(buck2_data::action_key::Owner::LocalResourceSetup(
-                self.target.as_proto(),
-            )),
-            key: Default::default(),
-        }
-    }
-
-    fn as_proto_action_name(&self) -> buck2_data::ActionName {
-        buck2_data::ActionName {
-            category: "setup_local_resource".to_owned(),
-            identifier: "".to_owned(),
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use buck2_build_api::context::SetBuildContextData;
@@ -1535,6 +1653,7 @@ mod tests {
                     name: "First - test".to_owned(),
                     duration: Some(Duration::from_micros(1)),
                     details: "1".to_owned(),
+                    cached: false,
                 })
                 .await?;
 
@@ -1546,6 +1665,7 @@ mod tests {
                     name: "Second - test".to_owned(),
                     duration: Some(Duration::from_micros(2)),
                     details: "2".to_owned(),
+                    cached: false,
                 })
                 .await?;
 
@@ -1567,6 +1687,7 @@ mod tests {
                     name: "First - test".to_owned(),
                     duration: Some(Duration::from_micros(1)),
                     details: "1".to_owned(),
+                    cached: false,
                 }),
                 ExecutorMessage::TestResult(TestResult {
                     target,
@@ -1576,6 +1697,7 @@ mod tests {
                     name: "Second - test".to_owned(),
                     duration: Some(Duration::from_micros(2)),
                     details: "2".to_owned(),
+                    cached: false,
                 }),
                 ExecutorMessage::ExitCode(0),
             ]
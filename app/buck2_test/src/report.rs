@@ -0,0 +1,379 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Machine readable reports of test results, written by `buck2 test --junit-xml` and
+//! `buck2 test --test-report-json`.
+
+use std::fmt::Write;
+use std::time::Duration;
+
+use buck2_test_api::data::TestResult;
+use buck2_test_api::data::TestStatus;
+use indexmap::IndexMap;
+use serde::Serialize;
+use starlark::xml::escape_xml;
+
+/// Output of a test is truncated to this many bytes in reports, keeping the end of it.
+const MAX_OUTPUT_BYTES: usize = 64 * 1024;
+
+/// Bump when making incompatible changes to the JSON report.
+const JSON_REPORT_VERSION: u32 = 1;
+
+struct TestReportEntry {
+    target: String,
+    name: String,
+    status: TestStatus,
+    msg: Option<String>,
+    duration: Option<Duration>,
+    output: String,
+    output_truncated: bool,
+    /// How many times the test was reported before its final result.
+    retries: u32,
+    /// Whether the final result was reused from an earlier run rather than executed.
+    cached: bool,
+}
+
+/// All the test results of a test run, keyed by target and test name.
+#[derive(Default)]
+pub(crate) struct TestResultsReport {
+    entries: IndexMap<(String, String), TestReportEntry>,
+}
+
+fn status_name(status: &TestStatus) -> &'static str {
+    match status {
+        TestStatus::PASS => "PASS",
+        TestStatus::FAIL => "FAIL",
+        TestStatus::SKIP => "SKIP",
+        TestStatus::OMITTED => "OMITTED",
+        TestStatus::FATAL => "FATAL",
+        TestStatus::TIMEOUT => "TIMEOUT",
+        TestStatus::UNKNOWN => "UNKNOWN",
+        TestStatus::RERUN => "RERUN",
+        TestStatus::LISTING_SUCCESS => "LISTING_SUCCESS",
+        TestStatus::LISTING_FAILED => "LISTING_FAILED",
+        TestStatus::FLAKY => "FLAKY",
+    }
+}
+
+/// Keep the last `MAX_OUTPUT_BYTES` of `output`, since that is where failures usually are.
+fn truncate_output(output: &str) -> (String, bool) {
+    if output.len() <= MAX_OUTPUT_BYTES {
+        return (output.to_owned(), false);
+    }
+    let mut start = output.len() - MAX_OUTPUT_BYTES;
+    while !output.is_char_boundary(start) {
+        start += 1;
+    }
+    (output[start..].to_owned(), true)
+}
+
+#[derive(Default, Serialize)]
+struct Summary {
+    total: u64,
+    passed: u64,
+    failed: u64,
+    skipped: u64,
+    fatal: u64,
+    listing_failed: u64,
+    flaky: u64,
+}
+
+impl Summary {
+    fn add(&mut self, status: &TestStatus) {
+        self.total += 1;
+        match status {
+            TestStatus::PASS => self.passed += 1,
+            TestStatus::FAIL | TestStatus::TIMEOUT => self.failed += 1,
+            TestStatus::SKIP | TestStatus::OMITTED => self.skipped += 1,
+            TestStatus::FATAL | TestStatus::UNKNOWN | TestStatus::RERUN => self.fatal += 1,
+            TestStatus::LISTING_FAILED => self.listing_failed += 1,
+            TestStatus::FLAKY => self.flaky += 1,
+            TestStatus::LISTING_SUCCESS => {}
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonTestResult<'a> {
+    target: &'a str,
+    name: &'a str,
+    status: &'static str,
+    duration_secs: Option<f64>,
+    message: Option<&'a str>,
+    output: &'a str,
+    output_truncated: bool,
+    retries: u32,
+    cached: bool,
+}
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    version: u32,
+    summary: Summary,
+    results: Vec<JsonTestResult<'a>>,
+}
+
+impl TestResultsReport {
+    /// Record a result for a test of `target`. A test reported more than once (e.g. after a
+    /// `RERUN`) keeps its first position and its last result, and counts as retried.
+    pub(crate) fn ingest(&mut self, target: String, result: &TestResult) {
+        if result.status == TestStatus::LISTING_SUCCESS {
+            return;
+        }
+        let key = (target, result.name.clone());
+        let retries = self.entries.get(&key).map_or(0, |prev| prev.retries + 1);
+        let (output, output_truncated) = truncate_output(&result.details);
+        let entry = TestReportEntry {
+            target: key.0.clone(),
+            name: key.1.clone(),
+            status: result.status.clone(),
+            msg: result.msg.clone(),
+            duration: result.duration,
+            output,
+            output_truncated,
+            retries,
+            cached: result.cached,
+        };
+        self.entries.insert(key, entry);
+    }
+
+    pub(crate) fn to_json(&self) -> anyhow::Result<String> {
+        let mut summary = Summary::default();
+        let results = self
+            .entries
+            .values()
+            .map(|e| {
+                summary.add(&e.status);
+                JsonTestResult {
+                    target: &e.target,
+                    name: &e.name,
+                    status: status_name(&e.status),
+                    duration_secs: e.duration.map(|d| d.as_secs_f64()),
+                    message: e.msg.as_deref(),
+                    output: &e.output,
+                    output_truncated: e.output_truncated,
+                    retries: e.retries,
+                    cached: e.cached,
+                }
+            })
+            .collect();
+        Ok(serde_json::to_string_pretty(&JsonReport {
+            version: JSON_REPORT_VERSION,
+            summary,
+            results,
+        })?)
+    }
+
+    /// Render the results in the JUnit XML format, with one `<testsuite>` per target.
+    pub(crate) fn to_junit_xml(&self) -> String {
+        let mut suites: IndexMap<&str, Vec<&TestReportEntry>> = IndexMap::new();
+        for entry in self.entries.values() {
+            suites.entry(&entry.target).or_default().push(entry);
+        }
+
+        let mut total = Summary::default();
+        let mut total_time = Duration::ZERO;
+        let mut body = String::new();
+        for (target, entries) in suites {
+            let mut summary = Summary::default();
+            let mut time = Duration::ZERO;
+            let mut cases = String::new();
+            for e in entries {
+                summary.add(&e.status);
+                total.add(&e.status);
+                time += e.duration.unwrap_or_default();
+                write_testcase(&mut cases, e);
+            }
+            total_time += time;
+            writeln!(
+                body,
+                "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+                escape_xml(target),
+                summary.total,
+                summary.failed,
+                summary.fatal + summary.listing_failed,
+                summary.skipped,
+                time.as_secs_f64(),
+            )
+            .unwrap();
+            body.push_str(&cases);
+            body.push_str("  </testsuite>\n");
+        }
+
+        let mut res = String::new();
+        res.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        writeln!(
+            res,
+            "<testsuites tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+            total.total,
+            total.failed,
+            total.fatal + total.listing_failed,
+            total.skipped,
+            total_time.as_secs_f64(),
+        )
+        .unwrap();
+        res.push_str(&body);
+        res.push_str("</testsuites>\n");
+        res
+    }
+}
+
+fn write_testcase(out: &mut String, e: &TestReportEntry) {
+    write!(
+        out,
+        "    <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\"",
+        escape_xml(&e.target),
+        escape_xml(&e.name),
+        e.duration.unwrap_or_default().as_secs_f64(),
+    )
+    .unwrap();
+    if e.retries > 0 {
+        write!(out, " retries=\"{}\"", e.retries).unwrap();
+    }
+    out.push_str(">\n");
+
+    let message = escape_xml(e.msg.as_deref().unwrap_or(status_name(&e.status)));
+    match e.status {
+        TestStatus::PASS | TestStatus::LISTING_SUCCESS | TestStatus::FLAKY => {}
+        TestStatus::SKIP | TestStatus::OMITTED => {
+            writeln!(out, "      <skipped message=\"{}\"/>", message).unwrap();
+        }
+        TestStatus::FAIL | TestStatus::TIMEOUT => {
+            writeln!(
+                out,
+                "      <failure message=\"{}\" type=\"{}\"/>",
+                message,
+                status_name(&e.status)
+            )
+            .unwrap();
+        }
+        TestStatus::FATAL
+        | TestStatus::UNKNOWN
+        | TestStatus::RERUN
+        | TestStatus::LISTING_FAILED => {
+            writeln!(
+                out,
+                "      <error message=\"{}\" type=\"{}\"/>",
+                message,
+                status_name(&e.status)
+            )
+            .unwrap();
+        }
+    }
+    if !e.output.is_empty() {
+        writeln!(
+            out,
+            "      <system-out>{}{}</system-out>",
+            if e.output_truncated {
+                "[output truncated]\n"
+            } else {
+                ""
+            },
+            escape_xml(&e.output)
+        )
+        .unwrap();
+    }
+    out.push_str("    </testcase>\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_test_api::data::ConfiguredTargetHandle;
+
+    use super::*;
+
+    fn result(name: &str, status: TestStatus, details: &str) -> TestResult {
+        TestResult {
+            target: ConfiguredTargetHandle::from(0),
+            name: name.to_owned(),
+            status,
+            msg: None,
+            duration: Some(Duration::from_millis(1500)),
+            details: details.to_owned(),
+            cached: false,
+        }
+    }
+
+    #[test]
+    fn test_junit_xml() {
+        let mut report = TestResultsReport::default();
+        report.ingest("root//:t".to_owned(), &result("a", TestStatus::PASS, ""));
+        report.ingest(
+            "root//:t".to_owned(),
+            &result("b<1>", TestStatus::RERUN, "flaky"),
+        );
+        report.ingest(
+            "root//:t".to_owned(),
+            &result("b<1>", TestStatus::FAIL, "\x1b[31mboom\x1b[0m & bust"),
+        );
+        report.ingest(
+            "root//:t".to_owned(),
+            &result("c", TestStatus::LISTING_SUCCESS, ""),
+        );
+
+        assert_eq!(
+            report.to_junit_xml(),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites tests="2" failures="1" errors="0" skipped="0" time="3.000">
+  <testsuite name="root//:t" tests="2" failures="1" errors="0" skipped="0" time="3.000">
+    <testcase classname="root//:t" name="a" time="1.500">
+    </testcase>
+    <testcase classname="root//:t" name="b&lt;1&gt;" time="1.500" retries="1">
+      <failure message="FAIL" type="FAIL"/>
+      <system-out>[31mboom[0m &amp; bust</system-out>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#
+        );
+    }
+
+    #[test]
+    fn test_json_report() {
+        let mut report = TestResultsReport::default();
+        report.ingest("root//:t".to_owned(), &result("a", TestStatus::SKIP, ""));
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "version": 1,
+                "summary": {
+                    "total": 1,
+                    "passed": 0,
+                    "failed": 0,
+                    "skipped": 1,
+                    "fatal": 0,
+                    "listing_failed": 0,
+                    "flaky": 0,
+                },
+                "results": [{
+                    "target": "root//:t",
+                    "name": "a",
+                    "status": "SKIP",
+                    "duration_secs": 1.5,
+                    "message": null,
+                    "output": "",
+                    "output_truncated": false,
+                    "retries": 0,
+                    "cached": false,
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn test_truncate_output() {
+        let output = "é".repeat(MAX_OUTPUT_BYTES);
+        let (truncated, was_truncated) = truncate_output(&output);
+        assert!(was_truncated);
+        assert!(truncated.len() <= MAX_OUTPUT_BYTES);
+        assert!(truncated.chars().all(|c| c == 'é'));
+    }
+}
@@ -0,0 +1,255 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Local cache of passing test executions, enabled by `test.cache_results`.
+//!
+//! Entries are keyed by the digest of the action the test executes, which covers the expanded
+//! command, its environment and the digests of all its inputs. A test whose digest did not
+//! change since it last passed does not need to run again.
+//!
+//! The cache keeps at most `test.cache_results_max_entries` entries: at the end of each test
+//! command, the least recently used entries beyond that are removed.
+
+use std::time::Duration;
+use std::time::SystemTime;
+
+use anyhow::Context as _;
+use buck2_core::fs::artifact_path_resolver::ArtifactFs;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use buck2_execute::execute::action_digest::ActionDigest;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// Number of entries kept when `test.cache_results_max_entries` is not set.
+pub(crate) const DEFAULT_MAX_ENTRIES: usize = 10000;
+
+/// Written last, so that an entry interrupted while being written is a cache miss.
+const METADATA_FILE: &str = "metadata.json";
+const STDOUT_FILE: &str = "stdout";
+const STDERR_FILE: &str = "stderr";
+
+#[derive(Serialize, Deserialize)]
+struct CachedMetadata {
+    execution_time: Duration,
+    /// When the entry was last written or read, used to pick the entries to evict.
+    last_used: SystemTime,
+}
+
+/// A passing test execution, as recorded in the cache.
+#[derive(Debug, PartialEq)]
+pub(crate) struct CachedTestExecution {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub execution_time: Duration,
+}
+
+pub(crate) struct TestResultCache {
+    dir: AbsNormPathBuf,
+}
+
+impl TestResultCache {
+    pub(crate) fn new(dir: AbsNormPathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// The cache shared by all the test commands of this project.
+    pub(crate) fn in_buck_out(fs: &ArtifactFs) -> Self {
+        Self::new(
+            fs.fs().resolve(
+                &fs.buck_out_path_resolver()
+                    .root()
+                    .join(ForwardRelativePath::unchecked_new("test_results_cache")),
+            ),
+        )
+    }
+
+    fn entry_dir(&self, digest: &ActionDigest) -> AbsNormPathBuf {
+        // Hex digests are always valid file names.
+        self.dir.join(ForwardRelativePath::unchecked_new(
+            &digest.raw_digest().to_string(),
+        ))
+    }
+
+    fn read_metadata(entry_dir: &AbsNormPath) -> anyhow::Result<Option<CachedMetadata>> {
+        let metadata = match fs_util::read_to_string_if_exists(
+            entry_dir.join(ForwardRelativePath::unchecked_new(METADATA_FILE)),
+        )? {
+            Some(metadata) => metadata,
+            None => return Ok(None),
+        };
+        Ok(Some(serde_json::from_str(&metadata).with_context(
+            || format!("Invalid test result cache entry `{}`", entry_dir),
+        )?))
+    }
+
+    fn write_metadata(entry_dir: &AbsNormPath, metadata: &CachedMetadata) -> anyhow::Result<()> {
+        fs_util::write(
+            entry_dir.join(ForwardRelativePath::unchecked_new(METADATA_FILE)),
+            serde_json::to_vec(metadata)?,
+        )
+    }
+
+    pub(crate) fn get(&self, digest: &ActionDigest) -> anyhow::Result<Option<CachedTestExecution>> {
+        let dir = self.entry_dir(digest);
+        let mut metadata = match Self::read_metadata(&dir)? {
+            Some(metadata) => metadata,
+            None => return Ok(None),
+        };
+        let execution = CachedTestExecution {
+            stdout: fs_util::read(dir.join(ForwardRelativePath::unchecked_new(STDOUT_FILE)))?,
+            stderr: fs_util::read(dir.join(ForwardRelativePath::unchecked_new(STDERR_FILE)))?,
+            execution_time: metadata.execution_time,
+        };
+        metadata.last_used = SystemTime::now();
+        Self::write_metadata(&dir, &metadata)?;
+        Ok(Some(execution))
+    }
+
+    pub(crate) fn put(
+        &self,
+        digest: &ActionDigest,
+        stdout: &[u8],
+        stderr: &[u8],
+        execution_time: Duration,
+    ) -> anyhow::Result<()> {
+        let dir = self.entry_dir(digest);
+        fs_util::create_dir_all(&dir)?;
+        fs_util::write(
+            dir.join(ForwardRelativePath::unchecked_new(STDOUT_FILE)),
+            stdout,
+        )?;
+        fs_util::write(
+            dir.join(ForwardRelativePath::unchecked_new(STDERR_FILE)),
+            stderr,
+        )?;
+        Self::write_metadata(
+            &dir,
+            &CachedMetadata {
+                execution_time,
+                last_used: SystemTime::now(),
+            },
+        )
+    }
+
+    /// Remove the least recently used entries until at most `max_entries` are left.
+    /// Entries which were never completely written are removed first.
+    pub(crate) fn evict(&self, max_entries: usize) -> anyhow::Result<()> {
+        let entries = match fs_util::read_dir_if_exists(&self.dir)? {
+            Some(entries) => entries,
+            None => return Ok(()),
+        };
+        let mut entries = entries
+            .map(|entry| {
+                let dir = entry?.path();
+                let last_used = Self::read_metadata(&dir)?
+                    .map_or(SystemTime::UNIX_EPOCH, |metadata| metadata.last_used);
+                anyhow::Ok((last_used, dir))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if entries.len() <= max_entries {
+            return Ok(());
+        }
+        entries.sort();
+        for (_, dir) in &entries[..entries.len() - max_entries] {
+            fs_util::remove_all(dir)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_common::cas_digest::CasDigestConfig;
+
+    use super::*;
+
+    fn digest(action: &str) -> ActionDigest {
+        ActionDigest::from_content(action.as_bytes(), CasDigestConfig::testing_default())
+    }
+
+    fn cache() -> (tempfile::TempDir, TestResultCache) {
+        let tempdir = tempfile::tempdir().unwrap();
+        let cache = TestResultCache::new(AbsNormPathBuf::new(tempdir.path().to_owned()).unwrap());
+        (tempdir, cache)
+    }
+
+    fn put(cache: &TestResultCache, action: &str) {
+        cache
+            .put(
+                &digest(action),
+                action.as_bytes(),
+                b"stderr",
+                Duration::from_secs(1),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_hit() {
+        let (_tempdir, cache) = cache();
+        put(&cache, "a");
+        assert_eq!(
+            Some(CachedTestExecution {
+                stdout: b"a".to_vec(),
+                stderr: b"stderr".to_vec(),
+                execution_time: Duration::from_secs(1),
+            }),
+            cache.get(&digest("a")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_miss() {
+        let (_tempdir, cache) = cache();
+        assert_eq!(None, cache.get(&digest("a")).unwrap());
+
+        // The action digest changes whenever the command, its environment or its inputs do,
+        // so a test whose inputs changed since it passed is not found.
+        put(&cache, "a");
+        assert_eq!(None, cache.get(&digest("a with changed inputs")).unwrap());
+    }
+
+    #[test]
+    fn test_incomplete_entry_is_a_miss() {
+        let (_tempdir, cache) = cache();
+        put(&cache, "a");
+        fs_util::remove_file(
+            cache
+                .entry_dir(&digest("a"))
+                .join(ForwardRelativePath::unchecked_new(METADATA_FILE)),
+        )
+        .unwrap();
+        assert_eq!(None, cache.get(&digest("a")).unwrap());
+    }
+
+    #[test]
+    fn test_evict_least_recently_used() {
+        let (_tempdir, cache) = cache();
+        put(&cache, "a");
+        put(&cache, "b");
+        put(&cache, "c");
+        // Reading `a` makes `b` the least recently used entry.
+        assert!(cache.get(&digest("a")).unwrap().is_some());
+
+        cache.evict(3).unwrap();
+        assert!(cache.get(&digest("b")).unwrap().is_some());
+
+        cache.evict(2).unwrap();
+        assert!(cache.get(&digest("a")).unwrap().is_some());
+        assert!(cache.get(&digest("b")).unwrap().is_some());
+        assert_eq!(None, cache.get(&digest("c")).unwrap());
+
+        cache.evict(0).unwrap();
+        assert_eq!(None, cache.get(&digest("a")).unwrap());
+        assert_eq!(None, cache.get(&digest("b")).unwrap());
+    }
+}
@@ -9,22 +9,31 @@
 
 use std::sync::atomic::AtomicU64;
 use std::sync::atomic::Ordering;
+use std::sync::Mutex;
 
 use anyhow::Context as _;
 use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
 use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
 use buck2_core::provider::label::ConfiguredProvidersLabel;
 use buck2_test_api::data::ConfiguredTargetHandle;
+use buck2_test_api::data::PrepareForLocalExecutionResult;
 use chrono::Local;
 use dashmap::DashMap;
 use dupe::Dupe;
 
+use crate::coverage::CoverageOutput;
+
 #[derive(Debug, Clone, Copy, Dupe, Default)]
 pub struct TestSessionOptions {
     /// Whether this session should allow things to run on RE.
     pub allow_re: bool,
     pub force_use_project_relative_paths: bool,
     pub force_run_from_project_root: bool,
+    /// Whether passing test executions are cached and reused while their action digest does
+    /// not change.
+    pub cache_results: bool,
+    /// Whether tests write code coverage into an extra output, to be merged after the run.
+    pub collect_coverage: bool,
 }
 
 /// The state of a buck2 test command.
@@ -41,6 +50,10 @@ pub struct TestSession {
     /// Options overriding the behavior of tests executed in this session. This is primarily
     /// intended for unstable or debugging features.
     options: TestSessionOptions,
+    /// Coverage written by the tests that ran so far, when collecting coverage.
+    coverage: Mutex<Vec<CoverageOutput>>,
+    /// Tests prepared for local execution so far.
+    local_executions: Mutex<Vec<(ConfiguredProvidersLabel, PrepareForLocalExecutionResult)>>,
 }
 
 impl TestSession {
@@ -57,6 +70,8 @@ impl TestSession {
             labels: DashMap::new(),
             prefix,
             options,
+            coverage: Mutex::new(Vec::new()),
+            local_executions: Mutex::new(Vec::new()),
         }
     }
 
@@ -85,4 +100,26 @@ impl TestSession {
 
         Ok(res.clone())
     }
+
+    pub(crate) fn record_coverage(&self, output: CoverageOutput) {
+        self.coverage.lock().unwrap().push(output);
+    }
+
+    pub(crate) fn take_coverage(&self) -> Vec<CoverageOutput> {
+        std::mem::take(&mut *self.coverage.lock().unwrap())
+    }
+
+    pub(crate) fn record_local_execution(
+        &self,
+        target: ConfiguredProvidersLabel,
+        result: PrepareForLocalExecutionResult,
+    ) {
+        self.local_executions.lock().unwrap().push((target, result));
+    }
+
+    pub(crate) fn take_local_executions(
+        &self,
+    ) -> Vec<(ConfiguredProvidersLabel, PrepareForLocalExecutionResult)> {
+        std::mem::take(&mut *self.local_executions.lock().unwrap())
+    }
 }
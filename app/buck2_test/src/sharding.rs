@@ -0,0 +1,156 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Selection of the test targets to run on one shard of `buck2 test --shard-count`.
+
+use std::collections::HashMap;
+
+use anyhow::Context;
+use buck2_core::provider::label::ProvidersLabel;
+use buck2_test_api::sharding::TestShard;
+use buck2_test_api::sharding::SHARD_TESTCASES_LABEL;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct ReportResult {
+    target: String,
+    duration_secs: Option<f64>,
+}
+
+/// The parts of a `buck2 test --test-report-json` report used for sharding.
+#[derive(Deserialize)]
+struct Report {
+    results: Vec<ReportResult>,
+}
+
+pub(crate) struct TestSharding {
+    shard: TestShard,
+    /// Shards of the targets with a known duration, balanced so each shard takes about the
+    /// same time. Other targets are assigned by [`TestShard::contains`].
+    by_duration: HashMap<String, u32>,
+    /// Whether targets with the [`SHARD_TESTCASES_LABEL`] run on every shard. Only the internal
+    /// test runner knows how to split their testcases.
+    split_testcases: bool,
+}
+
+impl TestSharding {
+    /// `report` is the JSON test report of a previous test run to take durations from.
+    pub(crate) fn new(
+        shard: TestShard,
+        report: Option<&str>,
+        split_testcases: bool,
+    ) -> anyhow::Result<Self> {
+        let by_duration = match report {
+            Some(report) => {
+                let report: Report =
+                    serde_json::from_str(report).context("Error parsing test durations")?;
+                assign_by_duration(target_durations(&report), shard.count())
+            }
+            None => HashMap::new(),
+        };
+        Ok(Self {
+            shard,
+            by_duration,
+            split_testcases,
+        })
+    }
+
+    pub(crate) fn shard(&self) -> TestShard {
+        self.shard
+    }
+
+    /// Whether to build and run the test `target` on this shard.
+    pub(crate) fn includes<'a>(
+        &self,
+        target: &ProvidersLabel,
+        mut labels: impl Iterator<Item = &'a str>,
+    ) -> bool {
+        if self.split_testcases && labels.any(|l| l == SHARD_TESTCASES_LABEL) {
+            return true;
+        }
+        let target = target.to_string();
+        match self.by_duration.get(&target) {
+            Some(index) => *index == self.shard.index(),
+            None => self.shard.contains(&target),
+        }
+    }
+}
+
+/// Total duration of each target in the report, keyed by unconfigured label.
+fn target_durations(report: &Report) -> Vec<(String, f64)> {
+    let mut durations: HashMap<&str, f64> = HashMap::new();
+    for result in &report.results {
+        // Strip the configuration, e.g. `root//foo:bar (cfg#hash)`.
+        let target = match result.target.split_once(" (") {
+            Some((target, _)) => target,
+            None => &result.target,
+        };
+        *durations.entry(target).or_default() += result.duration_secs.unwrap_or_default();
+    }
+    durations
+        .into_iter()
+        .map(|(target, duration)| (target.to_owned(), duration))
+        .collect()
+}
+
+/// Assign the longest target to the least loaded shard, until all are assigned. This is
+/// deterministic, so every shard computes the same assignment from the same durations.
+fn assign_by_duration(mut durations: Vec<(String, f64)>, count: u32) -> HashMap<String, u32> {
+    durations.sort_by(|(a_name, a), (b_name, b)| b.total_cmp(a).then_with(|| a_name.cmp(b_name)));
+    let mut loads = vec![0.0f64; count as usize];
+    let mut assigned = HashMap::with_capacity(durations.len());
+    for (target, duration) in durations {
+        let (index, load) = loads
+            .iter_mut()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .expect("shard count is not zero");
+        *load += duration;
+        assigned.insert(target, index as u32);
+    }
+    assigned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assign_by_duration() {
+        let durations = vec![
+            ("root//:a".to_owned(), 10.0),
+            ("root//:b".to_owned(), 6.0),
+            ("root//:c".to_owned(), 5.0),
+            ("root//:d".to_owned(), 1.0),
+        ];
+        let assigned = assign_by_duration(durations, 2);
+        assert_eq!(Some(&0), assigned.get("root//:a"));
+        assert_eq!(Some(&1), assigned.get("root//:b"));
+        assert_eq!(Some(&1), assigned.get("root//:c"));
+        assert_eq!(Some(&0), assigned.get("root//:d"));
+    }
+
+    #[test]
+    fn test_target_durations() {
+        let report: Report = serde_json::from_str(
+            r#"{"results": [
+                {"target": "root//:a (cfg#1)", "duration_secs": 1.5},
+                {"target": "root//:a (cfg#1)", "duration_secs": 2.0},
+                {"target": "root//:b (cfg#1)", "duration_secs": null}
+            ]}"#,
+        )
+        .unwrap();
+        let mut durations = target_durations(&report);
+        durations.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            vec![("root//:a".to_owned(), 3.5), ("root//:b".to_owned(), 0.0)],
+            durations
+        );
+    }
+}
@@ -58,6 +58,7 @@ pub(crate) fn convert_test_result(
         duration,
         details,
         target: test_target,
+        cached,
     } = test_result;
 
     let test_target = session.get(test_target)?;
@@ -69,6 +70,7 @@ pub(crate) fn convert_test_result(
         duration: duration.and_then(|d| d.try_into().ok()),
         details,
         target_label: Some(test_target.target().as_proto()),
+        cached,
     })
 }
 
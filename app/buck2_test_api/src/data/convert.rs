@@ -37,9 +37,11 @@ use crate::data::Output;
 use crate::data::RemoteDir;
 use crate::data::RemoteFile;
 use crate::data::RemoteObject;
+use crate::data::StackDump;
 use crate::data::TestExecutable;
 use crate::data::TestResult;
 use crate::data::TestStatus;
+use crate::data::TimeoutEscalation;
 use crate::protocol::convert::host_sharing_requirements_from_grpc;
 use crate::protocol::convert::host_sharing_requirements_to_grpc;
 
@@ -216,6 +218,7 @@ impl TryFrom<i32> for TestStatus {
             buck2_test_proto::TestStatus::Rerun => TestStatus::RERUN,
             buck2_test_proto::TestStatus::ListingSuccess => TestStatus::LISTING_SUCCESS,
             buck2_test_proto::TestStatus::ListingFailed => TestStatus::LISTING_FAILED,
+            buck2_test_proto::TestStatus::Flaky => TestStatus::FLAKY,
         })
     }
 }
@@ -235,6 +238,7 @@ impl TryInto<i32> for TestStatus {
             TestStatus::RERUN => buck2_test_proto::TestStatus::Rerun,
             TestStatus::LISTING_SUCCESS => buck2_test_proto::TestStatus::ListingSuccess,
             TestStatus::LISTING_FAILED => buck2_test_proto::TestStatus::ListingFailed,
+            TestStatus::FLAKY => buck2_test_proto::TestStatus::Flaky,
         } as i32)
     }
 }
@@ -250,6 +254,7 @@ impl TryFrom<buck2_test_proto::TestResult> for TestResult {
             msg,
             duration,
             details,
+            cached,
         } = s;
 
         let duration = duration
@@ -267,6 +272,7 @@ impl TryFrom<buck2_test_proto::TestResult> for TestResult {
             msg: msg.map(|m| m.msg),
             duration,
             details,
+            cached,
         })
     }
 }
@@ -284,6 +290,7 @@ impl TryInto<buck2_test_proto::TestResult> for TestResult {
             details: self.details,
             msg: self.msg.map(|msg| OptionalMsg { msg }),
             duration: self.duration.try_map(|d| d.try_into())?,
+            cached: self.cached,
         })
     }
 }
@@ -514,6 +521,7 @@ impl TryFrom<buck2_test_proto::ExecuteRequest2> for ExecuteRequest2 {
             host_sharing_requirements,
             executor_override,
             required_local_resources,
+            timeout_escalation,
         } = s;
 
         let test_executable = test_executable
@@ -536,12 +544,18 @@ impl TryFrom<buck2_test_proto::ExecuteRequest2> for ExecuteRequest2 {
             resources: required_local_resources.into_map(|r| r.into()),
         };
 
+        let timeout_escalation = timeout_escalation
+            .map(timeout_escalation_from_grpc)
+            .transpose()
+            .context("Invalid `timeout_escalation`")?;
+
         Ok(ExecuteRequest2 {
             test_executable,
             timeout,
             host_sharing_requirements,
             executor_override,
             required_local_resources,
+            timeout_escalation,
         })
     }
 }
@@ -568,10 +582,54 @@ impl TryInto<buck2_test_proto::ExecuteRequest2> for ExecuteRequest2 {
                 .required_local_resources
                 .resources
                 .into_map(|r| r.into()),
+            timeout_escalation: self
+                .timeout_escalation
+                .map(timeout_escalation_to_grpc)
+                .transpose()
+                .context("Invalid `timeout_escalation`")?,
         })
     }
 }
 
+fn timeout_escalation_from_grpc(
+    escalation: buck2_test_proto::TimeoutEscalation,
+) -> anyhow::Result<TimeoutEscalation> {
+    let buck2_test_proto::TimeoutEscalation {
+        grace_period,
+        stack_dump,
+    } = escalation;
+
+    let grace_period = convert::to_std_duration(grace_period.context("Missing `grace_period`")?)
+        .context("Invalid `grace_period`")?;
+
+    let stack_dump =
+        match buck2_test_proto::StackDump::from_i32(stack_dump).context("Invalid `stack_dump`")? {
+            buck2_test_proto::StackDump::None => None,
+            buck2_test_proto::StackDump::Sigquit => Some(StackDump::Sigquit),
+            buck2_test_proto::StackDump::Gdb => Some(StackDump::Gdb(())),
+        };
+
+    Ok(TimeoutEscalation {
+        grace_period,
+        stack_dump,
+    })
+}
+
+fn timeout_escalation_to_grpc(
+    escalation: TimeoutEscalation,
+) -> anyhow::Result<buck2_test_proto::TimeoutEscalation> {
+    let stack_dump = match escalation.stack_dump {
+        None => buck2_test_proto::StackDump::None,
+        Some(StackDump::Sigquit) => buck2_test_proto::StackDump::Sigquit,
+        Some(StackDump::Gdb(())) => buck2_test_proto::StackDump::Gdb,
+    };
+
+    Ok(buck2_test_proto::TimeoutEscalation {
+        grace_period: Some(escalation.grace_period.try_into()?),
+        stack_dump: stack_dump as i32,
+    })
+}
+
 impl TryInto<buck2_test_proto::RemoteObject> for RemoteObject {
     type Error = anyhow::Error;
 
@@ -978,6 +1036,10 @@ mod tests {
                 name: "foo".to_owned(),
             }),
             required_local_resources: RequiredLocalResources { resources: vec![] },
+            timeout_escalation: Some(TimeoutEscalation {
+                grace_period: Duration::from_secs(5),
+                stack_dump: Some(StackDump::Gdb(())),
+            }),
         };
         assert_roundtrips::<buck2_test_proto::ExecuteRequest2, ExecuteRequest2>(&request);
     }
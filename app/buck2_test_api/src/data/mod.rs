@@ -95,6 +95,8 @@ pub struct TestResult {
     pub duration: Option<Duration>,
     // the output of the test execution (combining stdout and stderr)
     pub details: String,
+    // whether the result was reused from an earlier run of the same test rather than executed
+    pub cached: bool,
 }
 
 /// different possible test results
@@ -114,6 +116,8 @@ pub enum TestStatus {
     RERUN,
     LISTING_SUCCESS,
     LISTING_FAILED,
+    // Failed at least once, then passed when retried.
+    FLAKY,
 }
 
 /// The set of information about a test rule that is passed to the test executor
@@ -210,6 +214,13 @@ pub struct RequiredLocalResources {
     pub resources: Vec<LocalResourceType>,
 }
 
+/// How to collect the stacks of a test that is still running after its timeout grace period.
+/// The orchestrator picks the file of a `gdb` stack dump.
+pub type StackDump = buck2_core::execution_types::timeout_escalation::StackDump<()>;
+
+/// What to do with a test that exceeds its timeout before it is killed.
+pub type TimeoutEscalation = buck2_core::execution_types::timeout_escalation::TimeoutEscalation<()>;
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct ExecuteRequest2 {
     pub test_executable: TestExecutable,
@@ -217,6 +228,7 @@ pub struct ExecuteRequest2 {
     pub host_sharing_requirements: HostSharingRequirements,
     pub executor_override: Option<ExecutorConfigOverride>,
     pub required_local_resources: RequiredLocalResources,
+    pub timeout_escalation: Option<TimeoutEscalation>,
 }
 
 #[derive(Debug, Clone)]
@@ -58,6 +58,7 @@ use crate::data::PrepareForLocalExecutionResult;
 use crate::data::RequiredLocalResources;
 use crate::data::TestExecutable;
 use crate::data::TestResult;
+use crate::data::TimeoutEscalation;
 use crate::protocol::TestOrchestrator;
 
 /// Test runner client to buck2 test orchestrator.
@@ -143,6 +144,7 @@ impl TestOrchestratorClient {
         pre_create_dirs: Vec<DeclaredOutput>,
         executor_override: Option<ExecutorConfigOverride>,
         required_local_resources: RequiredLocalResources,
+        timeout_escalation: Option<TimeoutEscalation>,
     ) -> anyhow::Result<ExecuteResponse> {
         let test_executable = TestExecutable {
             display: ui_prints,
@@ -158,6 +160,7 @@ impl TestOrchestratorClient {
             host_sharing_requirements,
             executor_override,
             required_local_resources,
+            timeout_escalation,
         };
 
         let req: buck2_test_proto::ExecuteRequest2 =
@@ -302,6 +305,7 @@ where
                 host_sharing_requirements,
                 executor_override,
                 required_local_resources,
+                timeout_escalation,
             } = request
                 .into_inner()
                 .try_into()
@@ -327,6 +331,7 @@ where
                     pre_create_dirs,
                     executor_override,
                     required_local_resources,
+                    timeout_escalation,
                 )
                 .await
                 .context("Execution failed")?;
@@ -30,6 +30,7 @@ use crate::data::ExternalRunnerSpec;
 use crate::data::PrepareForLocalExecutionResult;
 use crate::data::RequiredLocalResources;
 use crate::data::TestResult;
+use crate::data::TimeoutEscalation;
 
 /// available to buck to interact with the test executor
 #[async_trait::async_trait]
@@ -65,6 +66,8 @@ pub trait TestOrchestrator: Send + Sync {
         // ExternalRunnerTestInfo to work.
         executor_override: Option<ExecutorConfigOverride>,
         required_local_resources: RequiredLocalResources,
+        // what to do before killing the command when it times out
+        timeout_escalation: Option<TimeoutEscalation>,
     ) -> anyhow::Result<ExecuteResponse>;
 
     /// reports a test is done
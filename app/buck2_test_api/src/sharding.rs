@@ -0,0 +1,99 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Splitting the tests of a test run across several machines.
+//!
+//! Buck shards test targets before building them. Targets with the
+//! [`SHARD_TESTCASES_LABEL`] are instead run on every shard, and the test executor splits
+//! their testcases across shards.
+
+use std::fmt;
+
+use dupe::Dupe;
+
+/// Label to opt a test target into having its testcases split across shards, instead of
+/// running all of them on a single shard.
+pub const SHARD_TESTCASES_LABEL: &str = "shard_testcases";
+
+/// One of `count` shards of a test run.
+#[derive(Debug, Clone, Copy, Dupe, PartialEq, Eq)]
+pub struct TestShard {
+    index: u32,
+    count: u32,
+}
+
+impl TestShard {
+    pub fn new(index: u32, count: u32) -> anyhow::Result<TestShard> {
+        if index >= count {
+            return Err(anyhow::anyhow!(
+                "Shard index must be less than the shard count, got index {} of {}",
+                index,
+                count
+            ));
+        }
+        Ok(TestShard { index, count })
+    }
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Whether the test or testcase with this name is run on this shard. This only depends on
+    /// the name, so every shard agrees on it.
+    pub fn contains(&self, name: &str) -> bool {
+        stable_hash(name) % u64::from(self.count) == u64::from(self.index)
+    }
+}
+
+impl fmt::Display for TestShard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+/// FNV-1a, which unlike `std` hashers is stable across processes, versions and platforms.
+fn stable_hash(s: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in s.bytes() {
+        hash ^= u64::from(b);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_name_is_in_one_shard() {
+        let shards = (0..3)
+            .map(|i| TestShard::new(i, 3).unwrap())
+            .collect::<Vec<_>>();
+        for name in ["root//foo:bar", "root//foo:baz", "root//foo:bar - test_a"] {
+            assert_eq!(1, shards.iter().filter(|s| s.contains(name)).count());
+        }
+    }
+
+    #[test]
+    fn test_stable_hash() {
+        assert_eq!(0xcbf29ce484222325, stable_hash(""));
+        assert_eq!(0xaf63dc4c8601ec8c, stable_hash("a"));
+    }
+
+    #[test]
+    fn test_invalid_shard() {
+        assert!(TestShard::new(3, 3).is_err());
+        assert!(TestShard::new(0, 0).is_err());
+    }
+}
@@ -23,6 +23,15 @@ pub struct Config {
     #[clap(long, default_value = "600", parse(try_from_str=try_parse_timeout_from_str))]
     pub timeout: Duration,
 
+    /// Seconds to give a test that timed out to exit after SIGTERM before it is killed. By
+    /// default, tests are killed as soon as they time out.
+    #[clap(long, parse(try_from_str=try_parse_timeout_from_str))]
+    pub timeout_grace_period: Option<Duration>,
+
+    /// How to collect the stacks of a test still running at the end of its grace period.
+    #[clap(long, ignore_case = true, arg_enum, requires = "timeout-grace-period")]
+    pub timeout_stack_dump: Option<TimeoutStackDump>,
+
     /// Ignored arg included for backwards compatibility.
     #[clap(long, hidden = true)]
     buck_test_info: String,
@@ -31,6 +40,52 @@ pub struct Config {
     /// Available as a workaround for when test features are available.
     #[clap(long, multiple = true, allow_hyphen_values = true)]
     pub test_arg: Vec<String>,
+
+    /// Run each test target as a whole instead of listing and running its testcases
+    /// individually.
+    #[clap(long)]
+    pub no_testcase_discovery: bool,
+
+    /// How many times to retry a failing test before reporting it as failed. Overridden for a
+    /// test target by a `retries:N` label.
+    #[clap(long, default_value = "0")]
+    pub max_retries: u32,
+
+    /// Whether tests that only passed when retried fail the test run.
+    #[clap(long, ignore_case = true, default_value = "pass", arg_enum)]
+    pub flaky_policy: FlakyPolicy,
+
+    /// The shard of the test run to run testcases of, for targets whose testcases are split
+    /// across shards.
+    #[clap(long, requires = "shard-count")]
+    pub shard_index: Option<u32>,
+
+    /// The number of shards of the test run.
+    #[clap(long, requires = "shard-index")]
+    pub shard_count: Option<u32>,
+
+    /// Prepare each test for Buck to run it locally, e.g. under a debugger, instead of running
+    /// it.
+    #[clap(long)]
+    pub prepare_for_local_execution: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ArgEnum)]
+#[clap(rename_all = "lower")]
+pub enum FlakyPolicy {
+    /// Flaky tests do not fail the test run.
+    Pass,
+    /// Flaky tests fail the test run, like tests that never passed.
+    Fail,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ArgEnum)]
+#[clap(rename_all = "lower")]
+pub enum TimeoutStackDump {
+    /// Send SIGQUIT, on which e.g. the JVM and Go print their stacks to the test's output.
+    Sigquit,
+    /// Attach gdb, and report the stacks of all threads as a `buck2_stack_dump` test output.
+    Gdb,
 }
 
 /// Uiltity that can be used to parse Env values from CLI arguments.
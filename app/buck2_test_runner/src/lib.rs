@@ -15,6 +15,7 @@ mod executor;
 mod runner;
 mod service;
 pub mod tcp;
+mod testcases;
 
 #[cfg(unix)]
 pub mod unix;
@@ -7,6 +7,8 @@
  * of this source tree.
  */
 
+use std::future::Future;
+
 use anyhow::Context;
 use buck2_test_api::data::ArgValue;
 use buck2_test_api::data::ArgValueContent;
@@ -15,23 +17,35 @@ use buck2_test_api::data::DisplayMetadata;
 use buck2_test_api::data::ExecuteResponse;
 use buck2_test_api::data::ExecutionResult2;
 use buck2_test_api::data::ExecutionStatus;
+use buck2_test_api::data::ExecutionStream;
 use buck2_test_api::data::ExternalRunnerSpec;
 use buck2_test_api::data::ExternalRunnerSpecValue;
 use buck2_test_api::data::RequiredLocalResources;
+use buck2_test_api::data::StackDump;
 use buck2_test_api::data::TestResult;
 use buck2_test_api::data::TestStatus;
+use buck2_test_api::data::TimeoutEscalation;
 use buck2_test_api::grpc::TestOrchestratorClient;
+use buck2_test_api::sharding::TestShard;
+use buck2_test_api::sharding::SHARD_TESTCASES_LABEL;
 use clap::Parser;
 use futures::channel::mpsc::UnboundedReceiver;
 use futures::StreamExt;
 use host_sharing::HostSharingRequirements;
 use parking_lot::Mutex;
+use sorted_vector_map::SortedVectorMap;
 
 use crate::config::Config;
 use crate::config::EnvValue;
+use crate::config::FlakyPolicy;
+use crate::config::TimeoutStackDump;
+use crate::testcases::TestFramework;
 
 pub type SpecReceiver = UnboundedReceiver<ExternalRunnerSpec>;
 
+/// Label to set the number of retries of a test target, e.g. `retries:2`.
+const RETRIES_LABEL_PREFIX: &str = "retries:";
+
 /// Internal test runner implementation for Buck2.
 ///
 /// This is a basic test runner intended to be used by the open-source Buck2 build
@@ -43,6 +57,7 @@ pub struct Buck2TestRunner {
     orchestrator_client: TestOrchestratorClient,
     spec_receiver: Mutex<Option<SpecReceiver>>,
     config: Config,
+    shard: Option<TestShard>,
 }
 
 impl Buck2TestRunner {
@@ -52,10 +67,15 @@ impl Buck2TestRunner {
         args: Vec<String>,
     ) -> anyhow::Result<Self> {
         let config = Config::try_parse_from(args).context("Error parsing test runner arguments")?;
+        let shard = match (config.shard_index, config.shard_count) {
+            (Some(index), Some(count)) => Some(TestShard::new(index, count)?),
+            _ => None,
+        };
         Ok(Self {
             orchestrator_client,
             spec_receiver: Mutex::new(Some(spec_receiver)),
             config,
+            shard,
         })
     }
 
@@ -68,32 +88,12 @@ impl Buck2TestRunner {
                 .context("Spec channel has already been consumed")?;
             drop(maybe_receiver);
         }
+        let flaky_policy = self.config.flaky_policy;
         let run_verdict = receiver
             .map(async move |spec| {
-                let name = format!(
-                    "{}//{}:{}",
-                    spec.target.cell, spec.target.package, spec.target.target
-                );
-                let target_handle = spec.target.handle.to_owned();
-
-                let execution_response = self
-                    .execute_test_from_spec(spec)
-                    .await
-                    .expect("Test execution request failed");
-
-                let execution_result = match execution_response {
-                    ExecuteResponse::Result(r) => r,
-                    ExecuteResponse::Cancelled => return TestStatus::OMITTED,
-                };
-
-                let test_result = get_test_result(name, target_handle, execution_result);
-                let test_status = test_result.status.clone();
-
-                self.report_test_result(test_result)
+                self.run_spec(spec)
                     .await
-                    .expect("Test result reporting failed");
-
-                test_status
+                    .expect("Test execution request failed")
             })
             // Use an arbitrarily large buffer -- execution throttling will be handled by the Buck2
             // executor, so no need to hold back on requests here.
@@ -102,7 +102,12 @@ impl Buck2TestRunner {
             .fold(
                 RunVerdict::Pass,
                 async move |mut run_verdict, test_status| {
-                    if test_status != TestStatus::PASS {
+                    let passed = match test_status {
+                        TestStatus::PASS => true,
+                        TestStatus::FLAKY => flaky_policy == FlakyPolicy::Pass,
+                        _ => false,
+                    };
+                    if !passed {
                         run_verdict = RunVerdict::Fail;
                     }
                     run_verdict
@@ -115,15 +120,212 @@ impl Buck2TestRunner {
             .await
     }
 
-    async fn execute_test_from_spec(
-        &self,
-        spec: ExternalRunnerSpec,
-    ) -> anyhow::Result<ExecuteResponse> {
-        let display_metadata = DisplayMetadata::Testing {
-            suite: spec.target.target,
-            testcases: Vec::new(),
+    /// Run the tests of one target, returning `PASS` if all of them passed, `FLAKY` if some of
+    /// them only passed when retried, or otherwise the status of a test that failed.
+    ///
+    /// For known test frameworks, the testcases are listed first and then each testcase is
+    /// run and reported individually. Otherwise, the test command is run once for the target.
+    async fn run_spec(&self, spec: ExternalRunnerSpec) -> anyhow::Result<TestStatus> {
+        let name = format!(
+            "{}//{}:{}",
+            spec.target.cell, spec.target.package, spec.target.target
+        );
+
+        if self.config.prepare_for_local_execution {
+            let (command, env) = self.test_command(&spec, std::iter::empty());
+            self.orchestrator_client
+                .prepare_for_local_execution(
+                    DisplayMetadata::Testing {
+                        suite: spec.target.target.clone(),
+                        testcases: Vec::new(),
+                    },
+                    spec.target.handle.to_owned(),
+                    command,
+                    env,
+                    Vec::new(),
+                )
+                .await?;
+            return Ok(TestStatus::PASS);
+        }
+
+        // Buck runs targets whose testcases are split across shards on every shard, so
+        // select the testcases, or the whole target if they can't be listed, of this shard.
+        let shard = match self.shard {
+            Some(shard) if spec.labels.iter().any(|l| l == SHARD_TESTCASES_LABEL) => Some(shard),
+            _ => None,
+        };
+
+        let framework = if self.config.no_testcase_discovery {
+            None
+        } else {
+            TestFramework::from_spec(&spec)
+        };
+        let framework = match framework {
+            Some(framework) => framework,
+            None => return self.run_target_in_shard(&spec, name, shard).await,
         };
 
+        let listing = self
+            .execute_test_from_spec(
+                &spec,
+                DisplayMetadata::Listing(spec.target.target.clone()),
+                framework.list_args().iter().map(|a| (*a).to_owned()),
+            )
+            .await?;
+        let listing = match listing {
+            ExecuteResponse::Result(r) => r,
+            ExecuteResponse::Cancelled => return Ok(TestStatus::OMITTED),
+        };
+        let ExecutionStream::Inline(stdout) = &listing.stdout;
+        let testcases = match listed_testcases(&name, framework, &listing.status, stdout, shard) {
+            ListedTestcases::Failed => {
+                let mut test_result = get_test_result(name, spec.target.handle.to_owned(), listing);
+                test_result.status = TestStatus::LISTING_FAILED;
+                self.report_test_result(test_result).await?;
+                return Ok(TestStatus::LISTING_FAILED);
+            }
+            ListedTestcases::Unlisted(message) => {
+                if let Some(message) = message {
+                    self.orchestrator_client
+                        .attach_info_message(message)
+                        .await?;
+                }
+                return self.run_target_in_shard(&spec, name, shard).await;
+            }
+            ListedTestcases::Testcases(testcases) => testcases,
+        };
+        if testcases.is_empty() {
+            return Ok(TestStatus::PASS);
+        }
+
+        self.orchestrator_client
+            .report_tests_discovered(
+                spec.target.handle.to_owned(),
+                spec.target.target.clone(),
+                testcases.clone(),
+            )
+            .await?;
+
+        let statuses = futures::stream::iter(testcases)
+            .map(|testcase| {
+                let spec = &spec;
+                let name = &name;
+                async move {
+                    self.run_with_retries(
+                        spec,
+                        format!("{} - {}", name, testcase),
+                        DisplayMetadata::Testing {
+                            suite: spec.target.target.clone(),
+                            testcases: vec![testcase.clone()],
+                        },
+                        framework.run_args(&testcase),
+                    )
+                    .await
+                }
+            })
+            .buffer_unordered(10000)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut run_status = TestStatus::PASS;
+        for status in statuses {
+            match status? {
+                TestStatus::PASS => {}
+                TestStatus::FLAKY => {
+                    if run_status == TestStatus::PASS {
+                        run_status = TestStatus::FLAKY;
+                    }
+                }
+                status => run_status = status,
+            }
+        }
+        Ok(run_status)
+    }
+
+    /// Run a target as a whole if it belongs to `shard`, when it is split across shards.
+    async fn run_target_in_shard(
+        &self,
+        spec: &ExternalRunnerSpec,
+        name: String,
+        shard: Option<TestShard>,
+    ) -> anyhow::Result<TestStatus> {
+        match shard {
+            Some(shard) if !shard.contains(&name) => Ok(TestStatus::PASS),
+            _ => self.run_target(spec, name).await,
+        }
+    }
+
+    /// Run the test command of a target as a whole and report a single result for it.
+    async fn run_target(
+        &self,
+        spec: &ExternalRunnerSpec,
+        name: String,
+    ) -> anyhow::Result<TestStatus> {
+        self.run_with_retries(
+            spec,
+            name,
+            DisplayMetadata::Testing {
+                suite: spec.target.target.clone(),
+                testcases: Vec::new(),
+            },
+            Vec::new(),
+        )
+        .await
+    }
+
+    /// Run a test and report its result, retrying it while it fails as many times as allowed
+    /// for its target, see [`run_with_retries`].
+    async fn run_with_retries(
+        &self,
+        spec: &ExternalRunnerSpec,
+        name: String,
+        display_metadata: DisplayMetadata,
+        extra_args: Vec<String>,
+    ) -> anyhow::Result<TestStatus> {
+        run_with_retries(
+            self.max_retries(spec),
+            || {
+                let name = name.clone();
+                let display_metadata = display_metadata.clone();
+                let extra_args = extra_args.clone();
+                async move {
+                    let execution_response = self
+                        .execute_test_from_spec(spec, display_metadata, extra_args)
+                        .await?;
+                    Ok(match execution_response {
+                        ExecuteResponse::Result(r) => {
+                            Some(get_test_result(name, spec.target.handle.to_owned(), r))
+                        }
+                        ExecuteResponse::Cancelled => None,
+                    })
+                }
+            },
+            |test_result| self.report_test_result(test_result),
+        )
+        .await
+    }
+
+    /// The number of retries for a target, from its `retries:N` label or the config.
+    fn max_retries(&self, spec: &ExternalRunnerSpec) -> u32 {
+        spec.labels
+            .iter()
+            .find_map(|l| l.strip_prefix(RETRIES_LABEL_PREFIX)?.parse().ok())
+            .unwrap_or(self.config.max_retries)
+    }
+
+    /// The command and environment of a test, with `extra_args` added to the test command.
+    fn test_command(
+        &self,
+        spec: &ExternalRunnerSpec,
+        extra_args: impl IntoIterator<Item = String>,
+    ) -> (Vec<ArgValue>, SortedVectorMap<String, ArgValue>) {
+        let extra_args = extra_args.into_iter().map(|arg| ArgValue {
+            content: ArgValueContent::ExternalRunnerSpecValue(ExternalRunnerSpecValue::Verbatim(
+                arg,
+            )),
+            format: None,
+        });
+
         let config_args = self.config.test_arg.iter().map(|arg| ArgValue {
             content: ArgValueContent::ExternalRunnerSpecValue(ExternalRunnerSpecValue::Verbatim(
                 arg.to_owned(),
@@ -133,11 +335,12 @@ impl Buck2TestRunner {
 
         let command = spec
             .command
-            .into_iter()
+            .iter()
             .map(|spec_value| ArgValue {
-                content: ArgValueContent::ExternalRunnerSpecValue(spec_value),
+                content: ArgValueContent::ExternalRunnerSpecValue(spec_value.clone()),
                 format: None,
             })
+            .chain(extra_args)
             .chain(config_args)
             .collect();
 
@@ -155,12 +358,12 @@ impl Buck2TestRunner {
 
         let env = spec
             .env
-            .into_iter()
+            .iter()
             .map(|(key, value)| {
                 (
-                    key,
+                    key.clone(),
                     ArgValue {
-                        content: ArgValueContent::ExternalRunnerSpecValue(value),
+                        content: ArgValueContent::ExternalRunnerSpecValue(value.clone()),
                         format: None,
                     },
                 )
@@ -168,10 +371,33 @@ impl Buck2TestRunner {
             .chain(config_env)
             .collect();
 
-        let target_handle = spec.target.handle;
+        (command, env)
+    }
+
+    async fn execute_test_from_spec(
+        &self,
+        spec: &ExternalRunnerSpec,
+        display_metadata: DisplayMetadata,
+        extra_args: impl IntoIterator<Item = String>,
+    ) -> anyhow::Result<ExecuteResponse> {
+        let (command, env) = self.test_command(spec, extra_args);
+        let target_handle = spec.target.handle.to_owned();
         let host_sharing_requirements = HostSharingRequirements::default();
         let pre_create_dirs = Vec::new();
         let executor_override = None;
+        let timeout_escalation =
+            self.config
+                .timeout_grace_period
+                .map(|grace_period| TimeoutEscalation {
+                    grace_period,
+                    stack_dump: self
+                        .config
+                        .timeout_stack_dump
+                        .map(|stack_dump| match stack_dump {
+                            TimeoutStackDump::Sigquit => StackDump::Sigquit,
+                            TimeoutStackDump::Gdb => StackDump::Gdb(()),
+                        }),
+                });
 
         self.orchestrator_client
             .execute2(
@@ -184,6 +410,7 @@ impl Buck2TestRunner {
                 pre_create_dirs,
                 executor_override,
                 RequiredLocalResources { resources: vec![] },
+                timeout_escalation,
             )
             .await
     }
@@ -195,6 +422,102 @@ impl Buck2TestRunner {
     }
 }
 
+/// What to run for a target after listing its testcases.
+#[derive(Debug, PartialEq)]
+enum ListedTestcases {
+    /// The listing command failed.
+    Failed,
+    /// No testcases were found, so the target is run as a whole. Has a message for the user
+    /// if the listing had output, which probably means that it could not be parsed.
+    Unlisted(Option<String>),
+    /// The testcases in the shard being run, which can be none.
+    Testcases(Vec<String>),
+}
+
+/// Parse the result of listing the testcases of the target `name` with `framework`.
+fn listed_testcases(
+    name: &str,
+    framework: TestFramework,
+    status: &ExecutionStatus,
+    stdout: &[u8],
+    shard: Option<TestShard>,
+) -> ListedTestcases {
+    if *status != (ExecutionStatus::Finished { exitcode: 0 }) {
+        return ListedTestcases::Failed;
+    }
+    let stdout = String::from_utf8_lossy(stdout);
+    let testcases = framework.parse_listing(&stdout);
+    if testcases.is_empty() {
+        // Either there are no tests or we failed to parse the listing. In both cases
+        // running the target as a whole gives the right answer.
+        let message = (!stdout.trim().is_empty()).then(|| {
+            format!(
+                "Found no testcases in the listing of `{}`, running it as a whole. \
+                The listing may not be in the format of {:?} tests.",
+                name, framework
+            )
+        });
+        return ListedTestcases::Unlisted(message);
+    }
+    ListedTestcases::Testcases(match shard {
+        Some(shard) => testcases
+            .into_iter()
+            .filter(|testcase| shard.contains(&format!("{} - {}", name, testcase)))
+            .collect(),
+        None => testcases,
+    })
+}
+
+/// Whether a test which finished with `status` is run again, if it has retries left.
+/// Tests which were skipped or omitted would only be skipped or omitted again, so only
+/// failures are retried.
+fn is_retryable(status: &TestStatus) -> bool {
+    matches!(
+        status,
+        TestStatus::FAIL | TestStatus::TIMEOUT | TestStatus::FATAL
+    )
+}
+
+/// Run attempts of a test until one is not retryable or `max_retries` retries were made,
+/// reporting the result of each. `attempt` returns `None` if the test was cancelled.
+/// Attempts that are retried are reported as `RERUN`, and a test that passes after a retry
+/// is reported as `FLAKY`.
+async fn run_with_retries<A, R>(
+    max_retries: u32,
+    mut attempt: impl FnMut() -> A,
+    mut report: impl FnMut(TestResult) -> R,
+) -> anyhow::Result<TestStatus>
+where
+    A: Future<Output = anyhow::Result<Option<TestResult>>>,
+    R: Future<Output = anyhow::Result<()>>,
+{
+    let mut retries = 0;
+    loop {
+        let mut test_result = match attempt().await? {
+            Some(test_result) => test_result,
+            None => return Ok(TestStatus::OMITTED),
+        };
+
+        if is_retryable(&test_result.status) && retries < max_retries {
+            retries += 1;
+            test_result.status = TestStatus::RERUN;
+            test_result.msg = Some(format!("Failed, retrying ({} of {})", retries, max_retries));
+            report(test_result).await?;
+            continue;
+        }
+
+        if test_result.status == TestStatus::PASS && retries > 0 {
+            test_result.status = TestStatus::FLAKY;
+            test_result.msg = Some(format!("Passed after {} failed attempt(s)", retries));
+        }
+        let test_status = test_result.status.clone();
+
+        report(test_result).await?;
+
+        return Ok(test_status);
+    }
+}
+
 fn get_test_result(
     name: String,
     target: ConfiguredTargetHandle,
@@ -217,6 +540,7 @@ fn get_test_result(
             "---- STDOUT ----\n{:?}\n---- STDERR ----\n{:?}\n",
             execution_result.stdout, execution_result.stderr
         ),
+        cached: execution_result.execution_details.cached,
     }
 }
 
@@ -234,3 +558,191 @@ impl RunVerdict {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::time::Duration;
+
+    use buck2_test_api::data::ConfiguredTargetHandle;
+    use buck2_test_api::data::ExecutionStatus;
+    use buck2_test_api::data::TestResult;
+    use buck2_test_api::data::TestStatus;
+    use buck2_test_api::sharding::TestShard;
+
+    use crate::runner::listed_testcases;
+    use crate::runner::run_with_retries;
+    use crate::runner::ListedTestcases;
+    use crate::testcases::TestFramework;
+
+    /// Run a test whose attempts finish with `attempts` in turn, returning its final status and
+    /// the statuses reported.
+    async fn run(
+        max_retries: u32,
+        attempts: Vec<Option<TestStatus>>,
+    ) -> (TestStatus, Vec<(TestStatus, Option<String>)>) {
+        let attempts = RefCell::new(VecDeque::from(attempts));
+        let reported = RefCell::new(Vec::new());
+        let status = run_with_retries(
+            max_retries,
+            || {
+                let status = attempts
+                    .borrow_mut()
+                    .pop_front()
+                    .expect("Test run more times than expected");
+                async move {
+                    Ok(status.map(|status| TestResult {
+                        target: ConfiguredTargetHandle::from(0),
+                        name: "test".to_owned(),
+                        status,
+                        msg: None,
+                        duration: None,
+                        details: String::new(),
+                        cached: false,
+                    }))
+                }
+            },
+            |test_result| {
+                reported
+                    .borrow_mut()
+                    .push((test_result.status, test_result.msg));
+                async { Ok(()) }
+            },
+        )
+        .await
+        .unwrap();
+        assert!(attempts.borrow().is_empty(), "Not every attempt was run");
+        (status, reported.into_inner())
+    }
+
+    #[tokio::test]
+    async fn test_pass_is_not_retried() {
+        let (status, reported) = run(2, vec![Some(TestStatus::PASS)]).await;
+        assert_eq!(status, TestStatus::PASS);
+        assert_eq!(reported, vec![(TestStatus::PASS, None)]);
+    }
+
+    #[tokio::test]
+    async fn test_retry_count() {
+        let (status, reported) = run(
+            2,
+            vec![
+                Some(TestStatus::FAIL),
+                Some(TestStatus::TIMEOUT),
+                Some(TestStatus::FATAL),
+            ],
+        )
+        .await;
+        assert_eq!(status, TestStatus::FATAL);
+        assert_eq!(
+            reported,
+            vec![
+                (
+                    TestStatus::RERUN,
+                    Some("Failed, retrying (1 of 2)".to_owned())
+                ),
+                (
+                    TestStatus::RERUN,
+                    Some("Failed, retrying (2 of 2)".to_owned())
+                ),
+                (TestStatus::FATAL, None),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_no_retries() {
+        let (status, reported) = run(0, vec![Some(TestStatus::FAIL)]).await;
+        assert_eq!(status, TestStatus::FAIL);
+        assert_eq!(reported, vec![(TestStatus::FAIL, None)]);
+    }
+
+    #[tokio::test]
+    async fn test_flaky_pass() {
+        let (status, reported) = run(3, vec![Some(TestStatus::FAIL), Some(TestStatus::PASS)]).await;
+        assert_eq!(status, TestStatus::FLAKY);
+        assert_eq!(
+            reported,
+            vec![
+                (
+                    TestStatus::RERUN,
+                    Some("Failed, retrying (1 of 3)".to_owned())
+                ),
+                (
+                    TestStatus::FLAKY,
+                    Some("Passed after 1 failed attempt(s)".to_owned())
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_skipped_and_omitted_are_not_retried() {
+        for skipped in [TestStatus::SKIP, TestStatus::OMITTED] {
+            let (status, reported) = run(2, vec![Some(skipped.clone())]).await;
+            assert_eq!(status, skipped);
+            assert_eq!(reported, vec![(skipped, None)]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_is_not_retried() {
+        let (status, reported) = run(2, vec![None]).await;
+        assert_eq!(status, TestStatus::OMITTED);
+        assert_eq!(reported, vec![]);
+    }
+
+    const PASSED: ExecutionStatus = ExecutionStatus::Finished { exitcode: 0 };
+
+    fn list(status: &ExecutionStatus, stdout: &str, shard: Option<TestShard>) -> ListedTestcases {
+        listed_testcases(
+            "root//:t",
+            TestFramework::Libtest,
+            status,
+            stdout.as_bytes(),
+            shard,
+        )
+    }
+
+    #[test]
+    fn test_failed_listing() {
+        let failed = ExecutionStatus::Finished { exitcode: 1 };
+        assert_eq!(list(&failed, "a: test\n", None), ListedTestcases::Failed);
+        let timed_out = ExecutionStatus::TimedOut {
+            duration: Duration::from_secs(1),
+        };
+        assert_eq!(list(&timed_out, "", None), ListedTestcases::Failed);
+    }
+
+    #[test]
+    fn test_listing() {
+        assert_eq!(
+            list(&PASSED, "a: test\nb: test\n", None),
+            ListedTestcases::Testcases(vec!["a".to_owned(), "b".to_owned()])
+        );
+        assert_eq!(list(&PASSED, "\n", None), ListedTestcases::Unlisted(None));
+        match list(&PASSED, "running 2 tests\n", None) {
+            ListedTestcases::Unlisted(Some(message)) => {
+                assert!(message.contains("`root//:t`"), "{}", message)
+            }
+            listed => panic!("Expected a message, got {:?}", listed),
+        }
+    }
+
+    #[test]
+    fn test_sharded_listing() {
+        let stdout: String = (0..20).map(|i| format!("t{}: test\n", i)).collect();
+        let mut testcases = Vec::new();
+        for index in 0..2 {
+            match list(&PASSED, &stdout, Some(TestShard::new(index, 2).unwrap())) {
+                ListedTestcases::Testcases(shard) => testcases.extend(shard),
+                listed => panic!("Expected testcases, got {:?}", listed),
+            }
+        }
+        testcases.sort();
+        let mut expected: Vec<String> = (0..20).map(|i| format!("t{}", i)).collect();
+        expected.sort();
+        assert_eq!(testcases, expected);
+    }
+}
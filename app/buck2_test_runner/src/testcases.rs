@@ -0,0 +1,146 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Listing and running individual testcases for the test frameworks the runner knows about.
+
+use buck2_test_api::data::ExternalRunnerSpec;
+
+/// Label to select the framework of a test explicitly, e.g. `testcase_discovery:pytest`,
+/// or to disable testcase discovery with `testcase_discovery:none`.
+const DISCOVERY_LABEL_PREFIX: &str = "testcase_discovery:";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TestFramework {
+    /// GoogleTest binaries.
+    GTest,
+    /// Rust libtest harness.
+    Libtest,
+    /// Tests collected by pytest.
+    Pytest,
+}
+
+impl TestFramework {
+    fn from_name(name: &str) -> Option<TestFramework> {
+        match name {
+            "gtest" => Some(TestFramework::GTest),
+            "rust" | "libtest" => Some(TestFramework::Libtest),
+            "pytest" => Some(TestFramework::Pytest),
+            _ => None,
+        }
+    }
+
+    /// Detect the framework of a test from its discovery label, or otherwise its test type.
+    pub(crate) fn from_spec(spec: &ExternalRunnerSpec) -> Option<TestFramework> {
+        match spec
+            .labels
+            .iter()
+            .find_map(|l| l.strip_prefix(DISCOVERY_LABEL_PREFIX))
+        {
+            Some(name) => Self::from_name(name),
+            None => Self::from_name(&spec.test_type),
+        }
+    }
+
+    /// Arguments to append to the test command to list the testcases instead of running them.
+    pub(crate) fn list_args(self) -> &'static [&'static str] {
+        match self {
+            TestFramework::GTest => &["--gtest_list_tests"],
+            TestFramework::Libtest => &["--list", "--format", "terse"],
+            TestFramework::Pytest => &["--collect-only", "-q"],
+        }
+    }
+
+    /// Parse the output of running the test command with [`Self::list_args`].
+    pub(crate) fn parse_listing(self, stdout: &str) -> Vec<String> {
+        match self {
+            TestFramework::GTest => parse_gtest_listing(stdout),
+            TestFramework::Libtest => stdout
+                .lines()
+                .filter_map(|l| l.strip_suffix(": test"))
+                .map(str::to_owned)
+                .collect(),
+            TestFramework::Pytest => stdout
+                .lines()
+                .map(str::trim)
+                // Collection output ends with a blank line followed by a summary.
+                .take_while(|l| !l.is_empty())
+                .filter(|l| l.contains("::"))
+                .map(str::to_owned)
+                .collect(),
+        }
+    }
+
+    /// Arguments to append to the test command to run a single testcase.
+    pub(crate) fn run_args(self, testcase: &str) -> Vec<String> {
+        match self {
+            TestFramework::GTest => vec![format!("--gtest_filter={}", testcase)],
+            TestFramework::Libtest => vec![testcase.to_owned(), "--exact".to_owned()],
+            TestFramework::Pytest => vec![testcase.to_owned()],
+        }
+    }
+}
+
+/// GoogleTest lists suites unindented with a trailing dot and their tests indented below,
+/// either of which may be followed by a `# TypeParam = ...` style comment.
+fn parse_gtest_listing(stdout: &str) -> Vec<String> {
+    let mut suite: Option<&str> = None;
+    let mut testcases = Vec::new();
+    for line in stdout.lines() {
+        let name = line.split('#').next().unwrap_or_default().trim_end();
+        if name.trim().is_empty() {
+            continue;
+        }
+        if name.starts_with(' ') {
+            if let Some(suite) = suite {
+                testcases.push(format!("{}{}", suite, name.trim()));
+            }
+        } else if name.ends_with('.') {
+            suite = Some(name);
+        } else {
+            // Anything else (e.g. a banner printed by the binary) is not part of the listing.
+            suite = None;
+        }
+    }
+    testcases
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testcases::TestFramework;
+
+    #[test]
+    fn test_parse_gtest_listing() {
+        let stdout = "Running main() from gtest_main.cc\n\
+                      FooTest.\n  Bar\n  Baz\n\
+                      TypedTest/0.  # TypeParam = int\n  Works\n";
+        assert_eq!(
+            vec!["FooTest.Bar", "FooTest.Baz", "TypedTest/0.Works"],
+            TestFramework::GTest.parse_listing(stdout)
+        );
+    }
+
+    #[test]
+    fn test_parse_libtest_listing() {
+        let stdout = "tests::a: test\ntests::b: test\nbench::c: benchmark\n";
+        assert_eq!(
+            vec!["tests::a", "tests::b"],
+            TestFramework::Libtest.parse_listing(stdout)
+        );
+    }
+
+    #[test]
+    fn test_parse_pytest_listing() {
+        let stdout =
+            "test_foo.py::test_a\ntest_foo.py::TestBar::test_b[1]\n\n2 tests collected in 0.01s\n";
+        assert_eq!(
+            vec!["test_foo.py::test_a", "test_foo.py::TestBar::test_b[1]"],
+            TestFramework::Pytest.parse_listing(stdout)
+        );
+    }
+}
@@ -0,0 +1,113 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::analysis::types::LintEdit;
+use crate::analysis::Lint;
+
+/// Apply the fixes of `lints` to `source`, the program they were produced for.
+///
+/// Fixes which overlap the edits of an earlier fix are skipped, linting the result again
+/// and fixing that will apply them. Returns `None` if there was nothing to fix.
+pub fn apply_lint_fixes(source: &str, lints: &[Lint]) -> Option<String> {
+    let mut fixes: Vec<&[LintEdit]> = lints
+        .iter()
+        .filter_map(|lint| lint.fix.as_ref())
+        .map(|fix| fix.edits.as_slice())
+        .filter(|edits| !edits.is_empty())
+        .collect();
+    if fixes.is_empty() {
+        return None;
+    }
+    fixes.sort_by_key(|edits| edits.iter().map(|x| x.span.begin()).min());
+
+    let mut edits: Vec<&LintEdit> = Vec::new();
+    for fix in fixes {
+        // Several lints can come with the same fix, e.g. removing a whole `load`.
+        if fix.iter().all(|edit| edits.contains(&edit)) {
+            continue;
+        }
+        let overlaps = fix.iter().any(|edit| {
+            edits.iter().any(|applied| {
+                edit.span.begin() < applied.span.end() && applied.span.begin() < edit.span.end()
+                    || edit.span.begin() == applied.span.begin()
+            })
+        });
+        if !overlaps {
+            edits.extend(fix);
+        }
+    }
+    edits.sort_by_key(|edit| (edit.span.begin(), edit.span.end()));
+
+    let mut res = String::with_capacity(source.len());
+    let mut pos = 0;
+    for edit in edits {
+        res.push_str(&source[pos..edit.span.begin().get() as usize]);
+        res.push_str(&edit.replacement);
+        pos = edit.span.end().get() as usize;
+    }
+    res.push_str(&source[pos..]);
+    Some(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analysis::apply_lint_fixes;
+    use crate::analysis::AstModuleLint;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+
+    fn fix(program: &str) -> String {
+        let lints = AstModule::parse("X", program.to_owned(), &Dialect::Extended)
+            .unwrap()
+            .lint(None);
+        let fixed = apply_lint_fixes(program, &lints).unwrap_or_else(|| program.to_owned());
+        AstModule::parse("X", fixed.clone(), &Dialect::Extended)
+            .unwrap_or_else(|e| panic!("Fixed code does not parse: {}\n{}", e, fixed));
+        fixed
+    }
+
+    #[test]
+    fn test_fix_unused_loads() {
+        assert_eq!(
+            fix("load(\"a\", \"x\", \"y\")\nload(\"b\", \"z\")\nprint(y)\n"),
+            "load(\"a\", \"y\")\nprint(y)\n"
+        );
+    }
+
+    #[test]
+    fn test_fix_expressions() {
+        assert_eq!(
+            fix("def f(x, **kwargs):\n    return [type(x) == list, dict(**kwargs)]\n"),
+            "def f(x, **kwargs):\n    return [type(x) == type([]), dict(kwargs)]\n"
+        );
+    }
+
+    #[test]
+    fn test_fix_redundant_statements() {
+        assert_eq!(
+            fix(
+                "def f(xs):\n    for x in xs:\n        print(x)\n        continue\n    return\ndef g():\n    return\n"
+            ),
+            "def f(xs):\n    for x in xs:\n        print(x)\ndef g():\n    pass\n"
+        );
+    }
+
+    #[test]
+    fn test_no_fixes() {
+        assert_eq!(fix("x = 1\n"), "x = 1\n");
+    }
+}
@@ -26,6 +26,7 @@ use starlark_syntax::syntax::ast::Stmt;
 use starlark_syntax::syntax::module::AstModuleFields;
 use thiserror::Error;
 
+use crate::analysis::types::LintFix;
 use crate::analysis::types::LintT;
 use crate::analysis::types::LintWarning;
 use crate::analysis::EvalSeverity;
@@ -237,22 +238,33 @@ fn reachable(codemap: &CodeMap, x: &AstStmt, res: &mut Vec<LintT<FlowIssue>>) ->
 // If you have a definition which ends with return, or a loop which ends with continue
 // that is a useless statement that just
 fn redundant(codemap: &CodeMap, x: &AstStmt, res: &mut Vec<LintT<FlowIssue>>) {
-    fn check(is_loop: bool, codemap: &CodeMap, x: &AstStmt, res: &mut Vec<LintT<FlowIssue>>) {
+    // `only` is true if `x` is the only statement of its block, so removing it needs a `pass`.
+    fn check(
+        is_loop: bool,
+        only: bool,
+        codemap: &CodeMap,
+        x: &AstStmt,
+        res: &mut Vec<LintT<FlowIssue>>,
+    ) {
         match &**x {
-            Stmt::Continue if is_loop => {
-                res.push(LintT::new(codemap, x.span, FlowIssue::RedundantContinue))
-            }
-            Stmt::Return(None) if !is_loop => {
-                res.push(LintT::new(codemap, x.span, FlowIssue::RedundantReturn))
-            }
+            Stmt::Continue if is_loop => res.push(
+                LintT::new(codemap, x.span, FlowIssue::RedundantContinue).with_fix(
+                    LintFix::remove_statement("Remove redundant `continue`", codemap, x.span, only),
+                ),
+            ),
+            Stmt::Return(None) if !is_loop => res.push(
+                LintT::new(codemap, x.span, FlowIssue::RedundantReturn).with_fix(
+                    LintFix::remove_statement("Remove redundant `return`", codemap, x.span, only),
+                ),
+            ),
             Stmt::Statements(xs) if !xs.is_empty() => {
-                check(is_loop, codemap, xs.last().unwrap(), res)
+                check(is_loop, xs.len() == 1, codemap, xs.last().unwrap(), res)
             }
-            Stmt::If(_, x) => check(is_loop, codemap, x, res),
+            Stmt::If(_, x) => check(is_loop, true, codemap, x, res),
             Stmt::IfElse(_, x_y) => {
                 let (x, y) = &**x_y;
-                check(is_loop, codemap, x, res);
-                check(is_loop, codemap, y, res);
+                check(is_loop, true, codemap, x, res);
+                check(is_loop, true, codemap, y, res);
             }
             _ => {}
         }
@@ -260,8 +272,8 @@ fn redundant(codemap: &CodeMap, x: &AstStmt, res: &mut Vec<LintT<FlowIssue>>) {
 
     fn f(codemap: &CodeMap, x: &AstStmt, res: &mut Vec<LintT<FlowIssue>>) {
         match &**x {
-            Stmt::For(ForP { body, .. }) => check(true, codemap, body, res),
-            Stmt::Def(DefP { body, .. }) => check(false, codemap, body, res),
+            Stmt::For(ForP { body, .. }) => check(true, true, codemap, body, res),
+            Stmt::Def(DefP { body, .. }) => check(false, true, codemap, body, res),
             _ => {}
         }
         // We always want to look inside everything for other types of violation
@@ -19,10 +19,13 @@
 
 use std::collections::HashSet;
 
+pub use fix::apply_lint_fixes;
 pub use lint_message::LintMessage;
 pub use types::EvalMessage;
 pub use types::EvalSeverity;
 pub use types::Lint;
+pub use types::LintEdit;
+pub use types::LintFix;
 pub use unused_loads::remove::remove_unused_loads;
 
 use crate::analysis::types::LintT;
@@ -30,6 +33,7 @@ use crate::syntax::AstModule;
 
 mod dubious;
 pub mod find_call_name;
+mod fix;
 mod flow;
 mod incompatible;
 mod lint_message;
@@ -44,8 +44,10 @@ use starlark_syntax::syntax::ast::ForP;
 use starlark_syntax::syntax::ast::LoadArgP;
 use starlark_syntax::syntax::ast::Stmt;
 use starlark_syntax::syntax::module::AstModuleFields;
+use starlark_syntax::syntax::top_level_stmts::top_level_stmts;
 use thiserror::Error;
 
+use crate::analysis::types::LintFix;
 use crate::analysis::types::LintT;
 use crate::analysis::types::LintWarning;
 use crate::analysis::EvalSeverity;
@@ -555,7 +557,53 @@ pub(crate) fn lint(
         loop_depth: 0,
     };
     state.module(module);
-    state.warnings
+    let mut warnings = state.warnings;
+    add_unused_load_fixes(module, &mut warnings);
+    warnings
+}
+
+/// Attach fixes to the unused load warnings, removing the argument, or the whole `load`
+/// if none of its arguments are used.
+fn add_unused_load_fixes(module: &AstModule, warnings: &mut [LintT<NameWarning>]) {
+    for stmt in top_level_stmts(module.statement()) {
+        let Stmt::Load(load) = &stmt.node else {
+            continue;
+        };
+        let is_unused = |arg: &LoadArgP<_>| {
+            warnings.iter().any(|x| {
+                matches!(x.problem, NameWarning::UnusedLoad(_)) && x.location.span == arg.local.span
+            })
+        };
+        let all_unused = load.args.iter().all(is_unused);
+        let mut previous_end = load.module.span.end();
+        let mut fixes = Vec::new();
+        for arg in &load.args {
+            if is_unused(arg) {
+                let fix = if all_unused {
+                    LintFix::remove_statement(
+                        "Remove unused load",
+                        module.codemap(),
+                        stmt.span,
+                        false,
+                    )
+                } else {
+                    // Remove the argument along with the comma before it.
+                    LintFix::replace(
+                        "Remove unused load",
+                        Span::new(previous_end, arg.span().end()),
+                        String::new(),
+                    )
+                };
+                fixes.push((arg.local.span, fix));
+            }
+            previous_end = arg.span().end();
+        }
+        for (span, fix) in fixes {
+            if let Some(warning) = warnings.iter_mut().find(|x| x.location.span == span) {
+                warning.fix = Some(fix);
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -21,6 +21,7 @@ use starlark_syntax::syntax::ast::Expr;
 use starlark_syntax::syntax::module::AstModuleFields;
 use thiserror::Error;
 
+use crate::analysis::types::LintFix;
 use crate::analysis::types::LintT;
 use crate::analysis::types::LintWarning;
 use crate::analysis::EvalSeverity;
@@ -59,13 +60,18 @@ fn match_dict_copy(codemap: &CodeMap, x: &AstExpr, res: &mut Vec<LintT<Performan
     // If we see `dict(**x)` suggest `dict(x)`
     match &**x {
         Expr::Call(fun, args) if args.len() == 1 => match (&***fun, &*args[0]) {
-            (Expr::Identifier(f), Argument::KwArgs(arg)) if f.node.ident == "dict" => {
-                res.push(LintT::new(
+            (Expr::Identifier(f), Argument::KwArgs(arg)) if f.node.ident == "dict" => res.push(
+                LintT::new(
                     codemap,
                     x.span,
                     Performance::DictWithoutStarStar(x.to_string(), format!("dict({})", arg.node)),
-                ))
-            }
+                )
+                .with_fix(LintFix::replace(
+                    "Remove `**`",
+                    x.span,
+                    format!("dict({})", codemap.source_span(arg.span)),
+                )),
+            ),
             _ => {}
         },
         _ => {}
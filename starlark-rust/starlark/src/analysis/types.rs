@@ -24,6 +24,7 @@ use serde::Serialize;
 
 use crate::codemap::CodeMap;
 use crate::codemap::FileSpan;
+use crate::codemap::Pos;
 use crate::codemap::ResolvedSpan;
 use crate::codemap::Span;
 
@@ -39,6 +40,63 @@ pub(crate) struct LintT<T> {
     pub location: FileSpan,
     pub original: String,
     pub problem: T,
+    pub fix: Option<LintFix>,
+}
+
+/// A replacement of some of the source code of the file a [`Lint`] was produced for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintEdit {
+    /// The code to replace. Empty to insert at its position.
+    pub span: Span,
+    /// The code to replace it with. Empty to delete the code.
+    pub replacement: String,
+}
+
+/// Edits which fix a [`Lint`] without changing what the program means.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintFix {
+    /// What the fix does, e.g. `Remove unused load`.
+    pub description: String,
+    /// The edits, which do not overlap.
+    pub edits: Vec<LintEdit>,
+}
+
+impl LintFix {
+    /// A fix replacing `span` with `replacement`.
+    pub(crate) fn replace(description: &str, span: Span, replacement: String) -> Self {
+        Self {
+            description: description.to_owned(),
+            edits: vec![LintEdit { span, replacement }],
+        }
+    }
+
+    /// A fix removing the statement at `span`. If it is the only statement of a block it is
+    /// replaced with `pass`, otherwise its lines are removed if it has them to itself.
+    pub(crate) fn remove_statement(
+        description: &str,
+        codemap: &CodeMap,
+        span: Span,
+        only_statement: bool,
+    ) -> Self {
+        if only_statement {
+            return Self::replace(description, span, "pass".to_owned());
+        }
+        let source = codemap.source();
+        let begin = span.begin().get() as usize;
+        let end = span.end().get() as usize;
+        let line_begin = source[..begin].rfind('\n').map_or(0, |x| x + 1);
+        let line_end = source[end..]
+            .find('\n')
+            .map_or(source.len(), |x| end + x + 1);
+        let span = if source[line_begin..begin].trim().is_empty()
+            && source[end..line_end].trim().is_empty()
+        {
+            Span::new(Pos::new(line_begin as u32), Pos::new(line_end as u32))
+        } else {
+            span
+        };
+        Self::replace(description, span, String::new())
+    }
 }
 
 /// A lint produced by `AstModule::lint`.
@@ -55,6 +113,8 @@ pub struct Lint {
     pub problem: String,
     /// The source code at [`location`](Lint::location).
     pub original: String,
+    /// How to fix the lint automatically, if it can be.
+    pub fix: Option<LintFix>,
 }
 
 impl Display for Lint {
@@ -76,6 +136,14 @@ impl<T: LintWarning> LintT<T> {
             original: location.file.source_span(span).to_owned(),
             location,
             problem,
+            fix: None,
+        }
+    }
+
+    pub(crate) fn with_fix(self, fix: LintFix) -> Self {
+        Self {
+            fix: Some(fix),
+            ..self
         }
     }
 
@@ -86,6 +154,7 @@ impl<T: LintWarning> LintT<T> {
             severity: self.problem.severity(),
             problem: self.problem.to_string(),
             original: self.original,
+            fix: self.fix,
         }
     }
 }
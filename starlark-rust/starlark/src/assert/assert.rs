@@ -70,6 +70,25 @@ static ASSERTS_STAR: Lazy<FrozenModule> = Lazy::new(|| {
     m.freeze().unwrap()
 });
 
+static ASSERT_MODULE: Lazy<FrozenModule> = Lazy::new(|| {
+    let g = GlobalsBuilder::new()
+        .with_struct("asserts", asserts_star)
+        .build();
+    let m = Module::new();
+    m.frozen_heap().add_reference(g.heap());
+    m.set("asserts", g.get("asserts").unwrap());
+    m.freeze().unwrap()
+});
+
+/// A module defining `asserts`, with the functions `asserts.eq`, `asserts.ne`, `asserts.lt`,
+/// `asserts.contains`, `asserts.true` and `asserts.fails`, for tests written in Starlark.
+/// (`assert` is a reserved word in Starlark.)
+///
+/// Make it available to a module with [`Module::import_public_symbols`].
+pub fn assert_module() -> FrozenModule {
+    Lazy::force(&ASSERT_MODULE).dupe()
+}
+
 fn assert_equals<'v>(a: Value<'v>, b: Value<'v>) -> starlark::Result<NoneType> {
     if !a.equals(b)? {
         Err(anyhow::anyhow!("assert_eq: expected {}, got {}", a, b).into())
@@ -39,9 +39,14 @@
 //! The tests in question may be run multiple times, in different modes, to maximise test coverage.
 //! For example, execution tests are run at different garbage collection settings. Parsing tests are run
 //! with both Unix and Windows newlines.
+//!
+//! Tests can also be written in Starlark, as `test_*` functions which use the `asserts` of
+//! [`assert_module`]. Find them with [`test_names`] and run them with [`run_test`].
 
 #[allow(clippy::module_inception)] // This seems a perfectly reasonable thing to do
 mod assert;
 mod conformance;
+mod runner;
 
 pub use assert::*;
+pub use runner::*;
@@ -0,0 +1,344 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Running the tests written in Starlark, the `test_*` functions of a module.
+
+use std::fmt::Write;
+use std::io;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::environment::FrozenModule;
+use crate::eval::Evaluator;
+use crate::xml::escape_xml;
+
+/// The outcome of running one test function.
+#[derive(Debug)]
+pub struct TestResult {
+    /// The name of the test function.
+    pub name: String,
+    /// How long the test ran for.
+    pub duration: Duration,
+    /// The error the test failed with, including the call stack, or `None` if it passed.
+    pub error: Option<crate::Error>,
+}
+
+impl TestResult {
+    /// Did the test pass.
+    pub fn passed(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// The names of the test functions of a module, its public functions whose name starts
+/// with `test_`, in the order they are defined.
+pub fn test_names(module: &FrozenModule) -> Vec<String> {
+    module
+        .names()
+        .filter(|name| name.as_str().starts_with("test_"))
+        .filter(|name| {
+            module
+                .get(name.as_str())
+                .is_ok_and(|x| x.value().get_type() == "function")
+        })
+        .map(|name| name.as_str().to_owned())
+        .collect()
+}
+
+/// Call the test function `name` of `module` without arguments.
+///
+/// Use a new evaluator over a new [`Module`](crate::environment::Module) for each test,
+/// so tests can't see the values other tests created.
+pub fn run_test(module: &FrozenModule, name: &str, eval: &mut Evaluator) -> TestResult {
+    let start = Instant::now();
+    let error = match module.get(name) {
+        Ok(test) => {
+            let test = test.owned_value(eval.frozen_heap());
+            eval.eval_function(test, &[], &[]).err()
+        }
+        Err(e) => Some(e.into()),
+    };
+    TestResult {
+        name: name.to_owned(),
+        duration: start.elapsed(),
+        error,
+    }
+}
+
+/// A JUnit XML report of the tests run, with a test suite for each file.
+pub fn junit_report<'a>(files: impl IntoIterator<Item = (&'a str, &'a [TestResult])>) -> String {
+    let mut res = String::new();
+    writeln!(res, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+    writeln!(res, "<testsuites>").unwrap();
+    for (file, results) in files {
+        let failures = results.iter().filter(|x| !x.passed()).count();
+        let time: Duration = results.iter().map(|x| x.duration).sum();
+        writeln!(
+            res,
+            r#"  <testsuite name="{}" tests="{}" failures="{}" errors="0" time="{:.3}">"#,
+            escape_xml(file),
+            results.len(),
+            failures,
+            time.as_secs_f64()
+        )
+        .unwrap();
+        for result in results {
+            write!(
+                res,
+                r#"    <testcase name="{}" classname="{}" time="{:.3}""#,
+                escape_xml(&result.name),
+                escape_xml(file),
+                result.duration.as_secs_f64()
+            )
+            .unwrap();
+            match &result.error {
+                None => writeln!(res, "/>").unwrap(),
+                Some(e) => {
+                    writeln!(res, ">").unwrap();
+                    writeln!(
+                        res,
+                        r#"      <failure message="{}">{}</failure>"#,
+                        escape_xml(&e.without_diagnostic().to_string()),
+                        escape_xml(&e.to_string())
+                    )
+                    .unwrap();
+                    writeln!(res, "    </testcase>").unwrap();
+                }
+            }
+        }
+        writeln!(res, "  </testsuite>").unwrap();
+    }
+    writeln!(res, "</testsuites>").unwrap();
+    res
+}
+
+/// The results of the tests of several files, as reported by the test runners.
+#[derive(Debug, Default)]
+pub struct TestReport {
+    files: Vec<(String, Vec<TestResult>)>,
+}
+
+impl TestReport {
+    /// An empty report.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the results of the tests of `file`, printing `PASS` or `FAIL` with the error
+    /// for each test to `out`. A file which failed to load is reported as a failed test
+    /// named `<load>`, so it is not missed.
+    pub fn add_file(
+        &mut self,
+        file: String,
+        results: crate::Result<Vec<TestResult>>,
+        out: &mut dyn io::Write,
+    ) -> io::Result<()> {
+        let results = results.unwrap_or_else(|e| {
+            vec![TestResult {
+                name: "<load>".to_owned(),
+                duration: Duration::ZERO,
+                error: Some(e),
+            }]
+        });
+        for result in &results {
+            match &result.error {
+                None => writeln!(out, "PASS {}::{}", file, result.name)?,
+                Some(e) => {
+                    writeln!(out, "FAIL {}::{}", file, result.name)?;
+                    writeln!(out, "{}", e)?;
+                }
+            }
+        }
+        self.files.push((file, results));
+        Ok(())
+    }
+
+    /// The number of files recorded.
+    pub fn files(&self) -> usize {
+        self.files.len()
+    }
+
+    /// The number of tests recorded.
+    pub fn total(&self) -> usize {
+        self.files.iter().map(|(_, results)| results.len()).sum()
+    }
+
+    /// The number of tests which failed.
+    pub fn failed(&self) -> usize {
+        self.files
+            .iter()
+            .flat_map(|(_, results)| results)
+            .filter(|x| !x.passed())
+            .count()
+    }
+
+    /// A one line summary of the counts of tests.
+    pub fn summary(&self) -> String {
+        format!(
+            "{} tests, {} passed, {} failed",
+            self.total(),
+            self.total() - self.failed(),
+            self.failed()
+        )
+    }
+
+    /// The report as JUnit XML, see [`junit_report`].
+    pub fn junit(&self) -> String {
+        junit_report(
+            self.files
+                .iter()
+                .map(|(file, results)| (file.as_str(), results.as_slice())),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assert::assert_module;
+    use crate::assert::junit_report;
+    use crate::assert::run_test;
+    use crate::assert::test_names;
+    use crate::assert::TestReport;
+    use crate::assert::TestResult;
+    use crate::environment::FrozenModule;
+    use crate::environment::Globals;
+    use crate::environment::Module;
+    use crate::eval::Evaluator;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+
+    fn test_module(program: &str) -> FrozenModule {
+        let module = Module::new();
+        module.import_public_symbols(&assert_module());
+        {
+            let mut eval = Evaluator::new(&module);
+            let ast =
+                AstModule::parse("lib_test.bzl", program.to_owned(), &Dialect::Extended).unwrap();
+            eval.eval_module(ast, &Globals::standard()).unwrap();
+        }
+        module.freeze().unwrap()
+    }
+
+    fn run_tests(module: &FrozenModule) -> Vec<TestResult> {
+        test_names(module)
+            .iter()
+            .map(|name| {
+                let env = Module::new();
+                let mut eval = Evaluator::new(&env);
+                run_test(module, name, &mut eval)
+            })
+            .collect()
+    }
+
+    const PROGRAM: &str = r#"
+def double(x):
+    return x * 2
+
+def test_double():
+    asserts.eq(double(2), 4)
+
+test_not_a_function = 1
+
+def _test_private():
+    fail("private")
+
+def test_fails():
+    asserts.eq(double(3), 7)
+
+def test_mutation():
+    xs = []
+    xs.append(1)
+    asserts.eq(xs, [1])
+"#;
+
+    #[test]
+    fn test_discovery() {
+        assert_eq!(
+            vec!["test_double", "test_fails", "test_mutation"],
+            test_names(&test_module(PROGRAM))
+        );
+    }
+
+    #[test]
+    fn test_results() {
+        let results = run_tests(&test_module(PROGRAM));
+        assert_eq!(
+            vec![true, false, true],
+            results.iter().map(|x| x.passed()).collect::<Vec<_>>()
+        );
+        let error = results[1].error.as_ref().unwrap().to_string();
+        assert!(error.contains("assert_eq: expected 6, got 7"), "{}", error);
+        assert!(error.contains("in test_fails"), "{}", error);
+    }
+
+    #[test]
+    fn test_junit_report() {
+        let results = run_tests(&test_module(PROGRAM));
+        let report = junit_report([("lib_test.bzl", results.as_slice())]);
+        assert!(
+            report.contains(r#"<testsuite name="lib_test.bzl" tests="3" failures="1""#),
+            "{}",
+            report
+        );
+        assert!(
+            report.contains(r#"<testcase name="test_double" classname="lib_test.bzl""#),
+            "{}",
+            report
+        );
+        assert!(
+            report.contains(r#"<failure message="assert_eq: expected 6, got 7">"#),
+            "{}",
+            report
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        let mut report = TestReport::new();
+        let mut out = Vec::new();
+        report
+            .add_file(
+                "lib_test.bzl".to_owned(),
+                Ok(run_tests(&test_module(PROGRAM))),
+                &mut out,
+            )
+            .unwrap();
+        report
+            .add_file(
+                "broken_test.bzl".to_owned(),
+                Err(anyhow::anyhow!("bad load").into()),
+                &mut out,
+            )
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("PASS lib_test.bzl::test_double\n"), "{}", out);
+        assert!(out.contains("FAIL lib_test.bzl::test_fails\n"), "{}", out);
+        assert!(
+            out.contains("FAIL broken_test.bzl::<load>\nbad load"),
+            "{}",
+            out
+        );
+        assert_eq!(2, report.files());
+        assert_eq!("4 tests, 2 passed, 2 failed", report.summary());
+        let junit = report.junit();
+        assert!(
+            junit.contains(r#"<testcase name="&lt;load&gt;" classname="broken_test.bzl""#),
+            "{}",
+            junit
+        );
+    }
+}
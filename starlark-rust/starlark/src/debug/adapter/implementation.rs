@@ -16,7 +16,9 @@
  */
 
 use std::collections::HashMap;
+use std::collections::VecDeque;
 use std::fmt::Debug;
+use std::mem;
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
 use std::sync::mpsc::channel;
@@ -41,10 +43,12 @@ use crate::debug::adapter::ResolvedBreakpoints;
 use crate::debug::DapAdapter;
 use crate::debug::DapAdapterClient;
 use crate::debug::DapAdapterEvalHook;
+use crate::debug::RecordingConfig;
 use crate::debug::ScopesInfo;
 use crate::debug::StepKind;
 use crate::debug::Variable;
 use crate::debug::VariablesInfo;
+use crate::errors::Frame;
 use crate::eval::BeforeStmtFuncDyn;
 use crate::eval::Evaluator;
 use crate::syntax::AstModule;
@@ -59,6 +63,7 @@ pub(crate) fn prepare_dap_adapter(
         client,
         breakpoints: Arc::new(Mutex::new(BreakpointConfig::new())),
         disable_breakpoints: Arc::new(0usize.into()),
+        recording: Mutex::new(Recording::default()),
     });
 
     (
@@ -108,7 +113,8 @@ fn evaluate_expr<'v>(
 
 impl<'a> BeforeStmtFuncDyn<'a> for DapAdapterEvalHookImpl {
     fn call<'v>(&mut self, span_loc: FileSpanRef, eval: &mut Evaluator<'v, 'a>) {
-        let stop = if self.state.disable_breakpoints.load(Ordering::SeqCst) > 0 {
+        let evaluating = self.state.disable_breakpoints.load(Ordering::SeqCst) > 0;
+        let stop = if evaluating {
             false
         } else {
             let breaks = self.state.breakpoints.lock().unwrap();
@@ -136,6 +142,14 @@ impl<'a> BeforeStmtFuncDyn<'a> for DapAdapterEvalHookImpl {
             Some((StepKind::Out, stack_size)) => eval.call_stack_count() < stack_size,
         };
 
+        if !evaluating {
+            self.state
+                .recording
+                .lock()
+                .unwrap()
+                .record(span_loc, eval, stop);
+        }
+
         if stop || step_stop {
             self.step = None;
             self.state.client.event_stopped();
@@ -229,6 +243,199 @@ struct SharedAdapterState {
     breakpoints: Arc<Mutex<BreakpointConfig>>,
     // Set while we are doing evaluate calls (>= 1 means disable)
     disable_breakpoints: Arc<AtomicUsize>,
+    recording: Mutex<Recording>,
+}
+
+/// Maximum number of statement states kept while recording, older ones are dropped.
+const MAX_SNAPSHOTS: usize = 10000;
+/// Maximum total size of the statement states kept while recording, older ones are dropped.
+const MAX_SNAPSHOT_BYTES: usize = 64 << 20;
+/// Recorded values are truncated to this length, rather than the longer limit used when
+/// showing the live state, so a few large values do not take up the whole recording.
+const MAX_RECORDED_VALUE_LEN: usize = 1000;
+
+/// The state of the evaluation before a recorded statement.
+#[derive(Debug)]
+pub(crate) struct Snapshot {
+    pub(crate) location: FileSpan,
+    /// Stack frames as for `stack_trace`, innermost first.
+    pub(crate) frames: Vec<StackFrame>,
+    pub(crate) locals: Vec<Variable>,
+    pub(crate) at_breakpoint: bool,
+}
+
+impl Snapshot {
+    /// Approximate heap and inline size of the snapshot.
+    pub(crate) fn bytes(&self) -> usize {
+        let frames: usize = self
+            .frames
+            .iter()
+            .map(|frame| {
+                mem::size_of::<StackFrame>()
+                    + frame.name.len()
+                    + frame
+                        .source
+                        .as_ref()
+                        .and_then(|source| source.path.as_ref())
+                        .map_or(0, |path| path.len())
+            })
+            .sum();
+        let locals: usize = self
+            .locals
+            .iter()
+            .map(|local| {
+                mem::size_of::<Variable>()
+                    + local.name.to_string().len()
+                    + local.value.len()
+                    + local.type_.len()
+            })
+            .sum();
+        mem::size_of::<Snapshot>() + frames + locals
+    }
+
+    fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    fn same_state(&self, other: &Snapshot) -> bool {
+        self.location == other.location
+            && self.depth() == other.depth()
+            && self.locals.len() == other.locals.len()
+            && self.locals.iter().zip(&other.locals).all(|(x, y)| {
+                x.name.to_string() == y.name.to_string() && x.value == y.value && x.type_ == y.type_
+            })
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Recording {
+    config: Option<RecordingConfig>,
+    pub(crate) snapshots: VecDeque<Snapshot>,
+    /// Whether the last snapshot is the statement evaluation is paused at.
+    last_is_live: bool,
+    /// The snapshot being shown while paused, or `None` for the live evaluation state.
+    cursor: Option<usize>,
+    /// Total `Snapshot::bytes` of `snapshots`.
+    pub(crate) bytes: usize,
+}
+
+impl Recording {
+    fn set_config(&mut self, config: Option<RecordingConfig>) {
+        *self = Recording {
+            config,
+            ..Recording::default()
+        };
+    }
+
+    fn record(&mut self, span_loc: FileSpanRef, eval: &Evaluator, at_breakpoint: bool) {
+        self.last_is_live = false;
+        let Some(config) = &self.config else {
+            return;
+        };
+        if config
+            .file
+            .as_ref()
+            .is_some_and(|file| file.as_str() != span_loc.filename())
+        {
+            return;
+        }
+        let call_frames = eval.call_stack().into_frames();
+        if config
+            .function
+            .as_ref()
+            .is_some_and(|function| !call_frames.iter().any(|x| &x.name == function))
+        {
+            return;
+        }
+        let snapshot = Snapshot {
+            location: span_loc.to_file_span(),
+            frames: stack_frames(span_loc, &call_frames),
+            locals: eval
+                .local_variables()
+                .into_iter()
+                .map(|(name, value)| {
+                    let mut local = Variable::from_value(PathSegment::Attr(name), value);
+                    local.value = Variable::truncate_string(local.value, MAX_RECORDED_VALUE_LEN);
+                    local
+                })
+                .collect(),
+            at_breakpoint,
+        };
+        // Top-level statements are visited twice, keep only one state for them.
+        match self.snapshots.back_mut() {
+            Some(last) if last.same_state(&snapshot) => {
+                last.at_breakpoint |= snapshot.at_breakpoint;
+            }
+            _ => self.push(snapshot, MAX_SNAPSHOTS, MAX_SNAPSHOT_BYTES),
+        }
+        self.last_is_live = true;
+    }
+
+    /// Adds a snapshot, dropping the oldest ones to stay within the limits.
+    /// The new snapshot is always kept.
+    pub(crate) fn push(&mut self, snapshot: Snapshot, max_snapshots: usize, max_bytes: usize) {
+        let bytes = snapshot.bytes();
+        while !self.snapshots.is_empty()
+            && (self.snapshots.len() >= max_snapshots || self.bytes + bytes > max_bytes)
+        {
+            let dropped = self.snapshots.pop_front().unwrap();
+            self.bytes -= dropped.bytes();
+        }
+        self.bytes += bytes;
+        self.snapshots.push_back(snapshot);
+    }
+
+    /// The position of the live evaluation state, past the snapshots unless it was recorded.
+    fn live_index(&self) -> usize {
+        if self.last_is_live {
+            self.snapshots.len() - 1
+        } else {
+            self.snapshots.len()
+        }
+    }
+
+    fn current(&self) -> Option<&Snapshot> {
+        self.cursor.map(|i| &self.snapshots[i])
+    }
+
+    /// Moves back to the last snapshot before the current one matching `pred`, or to the first
+    /// snapshot if none match. Returns `false` if there is no earlier snapshot.
+    fn move_back(&mut self, pred: impl Fn(&Snapshot, usize) -> bool) -> bool {
+        let from = self.cursor.unwrap_or_else(|| self.live_index());
+        if from == 0 {
+            return false;
+        }
+        let depth = match self.cursor {
+            Some(i) => self.snapshots[i].depth(),
+            None if self.last_is_live => self.snapshots[from].depth(),
+            None => usize::MAX,
+        };
+        let to = (0..from)
+            .rev()
+            .find(|&i| pred(&self.snapshots[i], depth))
+            .unwrap_or(0);
+        self.cursor = Some(to);
+        true
+    }
+
+    /// Moves forward to the next snapshot matching `pred`, or back to the live state if none
+    /// match before it. Returns `false` if not replaying, so evaluation needs to resume.
+    fn move_forward(&mut self, pred: impl Fn(&Snapshot, usize) -> bool) -> bool {
+        let Some(from) = self.cursor else {
+            return false;
+        };
+        let depth = self.snapshots[from].depth();
+        self.cursor = (from + 1..self.live_index()).find(|&i| pred(&self.snapshots[i], depth));
+        true
+    }
+}
+
+fn step_matches(kind: StepKind, snapshot: &Snapshot, depth: usize) -> bool {
+    match kind {
+        StepKind::Into => true,
+        StepKind::Over => snapshot.depth() <= depth,
+        StepKind::Out => snapshot.depth() < depth,
+    }
 }
 
 #[derive(Debug, Clone, Copy, Dupe)]
@@ -238,6 +445,21 @@ enum Next {
     Step(StepKind),
 }
 
+/// Converts the call stack to DAP stack frames, innermost first.
+fn stack_frames(span: FileSpanRef, frames: &[Frame]) -> Vec<StackFrame> {
+    // Our model of a Frame and the debugger model are a bit different.
+    // We record the location of the call, but DAP wants the location we are at.
+    // We also have them in the wrong order
+    let mut next = Some(span.to_file_span());
+    let mut res = Vec::with_capacity(frames.len() + 1);
+    for (i, x) in frames.iter().rev().enumerate() {
+        res.push(convert_frame(i, x.name.clone(), next));
+        next = x.location.dupe();
+    }
+    res.push(convert_frame(frames.len(), "Root".to_owned(), next));
+    res
+}
+
 fn convert_frame(id: usize, name: String, location: Option<FileSpan>) -> StackFrame {
     let mut s = StackFrame {
         id: id as i64,
@@ -278,6 +500,9 @@ impl DapAdapter for DapAdapterImpl {
     }
 
     fn top_frame(&self) -> anyhow::Result<Option<StackFrame>> {
+        if let Some(snapshot) = self.state.recording.lock().unwrap().current() {
+            return Ok(snapshot.frames.first().cloned());
+        }
         self.with_ctx(Box::new(|span, eval| {
             let frame = eval.call_stack_top_frame();
             let name = frame.map_or("".to_owned(), |v| v.name);
@@ -286,18 +511,14 @@ impl DapAdapter for DapAdapterImpl {
     }
 
     fn stack_trace(&self, _: StackTraceArguments) -> anyhow::Result<StackTraceResponseBody> {
-        // Our model of a Frame and the debugger model are a bit different.
-        // We record the location of the call, but DAP wants the location we are at.
-        // We also have them in the wrong order
+        if let Some(snapshot) = self.state.recording.lock().unwrap().current() {
+            return Ok(StackTraceResponseBody {
+                total_frames: Some(snapshot.frames.len() as i64),
+                stack_frames: snapshot.frames.clone(),
+            });
+        }
         self.with_ctx(Box::new(|span, eval| {
-            let frames = eval.call_stack().into_frames();
-            let mut next = Some(span.to_file_span());
-            let mut res = Vec::with_capacity(frames.len() + 1);
-            for (i, x) in frames.iter().rev().enumerate() {
-                res.push(convert_frame(i, x.name.clone(), next));
-                next = x.location.dupe();
-            }
-            res.push(convert_frame(frames.len(), "Root".to_owned(), next));
+            let res = stack_frames(span, &eval.call_stack().into_frames());
             Ok(StackTraceResponseBody {
                 total_frames: Some(res.len() as i64),
                 stack_frames: res,
@@ -306,6 +527,11 @@ impl DapAdapter for DapAdapterImpl {
     }
 
     fn scopes(&self) -> anyhow::Result<ScopesInfo> {
+        if let Some(snapshot) = self.state.recording.lock().unwrap().current() {
+            return Ok(ScopesInfo {
+                num_locals: snapshot.locals.len(),
+            });
+        }
         self.with_ctx(Box::new(|_, eval| {
             let vars = eval.local_variables();
             Ok(ScopesInfo {
@@ -315,6 +541,11 @@ impl DapAdapter for DapAdapterImpl {
     }
 
     fn variables(&self) -> anyhow::Result<VariablesInfo> {
+        if let Some(snapshot) = self.state.recording.lock().unwrap().current() {
+            return Ok(VariablesInfo {
+                locals: snapshot.locals.clone(),
+            });
+        }
         self.with_ctx(Box::new(|_, eval| {
             let vars = eval.local_variables();
             Ok(VariablesInfo {
@@ -327,6 +558,7 @@ impl DapAdapter for DapAdapterImpl {
     }
 
     fn inspect_variable(&self, path: VariablePath) -> anyhow::Result<InspectVariableInfo> {
+        self.check_not_replaying()?;
         let state = self.state.dupe();
         self.with_ctx(Box::new(move |_span, eval| {
             let access_path = &path.access_path;
@@ -353,16 +585,50 @@ impl DapAdapter for DapAdapterImpl {
     }
 
     fn continue_(&self) -> anyhow::Result<()> {
-        self.inject_next(Next::Continue);
+        let replayed = self
+            .state
+            .recording
+            .lock()
+            .unwrap()
+            .move_forward(|snapshot, _| snapshot.at_breakpoint);
+        if replayed {
+            self.state.client.event_stopped();
+        } else {
+            self.inject_next(Next::Continue);
+        }
         Ok(())
     }
 
     fn step(&self, kind: StepKind) -> anyhow::Result<()> {
-        self.inject_next(Next::Step(kind));
+        let replayed = self
+            .state
+            .recording
+            .lock()
+            .unwrap()
+            .move_forward(|snapshot, depth| step_matches(kind, snapshot, depth));
+        if replayed {
+            self.state.client.event_stopped();
+        } else {
+            self.inject_next(Next::Step(kind));
+        }
         Ok(())
     }
 
+    fn set_recording(&self, config: Option<RecordingConfig>) -> anyhow::Result<()> {
+        self.state.recording.lock().unwrap().set_config(config);
+        Ok(())
+    }
+
+    fn step_back(&self) -> anyhow::Result<()> {
+        self.move_back(|snapshot, depth| step_matches(StepKind::Over, snapshot, depth))
+    }
+
+    fn reverse_continue(&self) -> anyhow::Result<()> {
+        self.move_back(|snapshot, _| snapshot.at_breakpoint)
+    }
+
     fn evaluate(&self, expr: &str) -> anyhow::Result<EvaluateExprInfo> {
+        self.check_not_replaying()?;
         let state = self.state.dupe();
         let expression = expr.to_owned();
         self.with_ctx(Box::new(move |_, eval| {
@@ -375,6 +641,30 @@ impl DapAdapter for DapAdapterImpl {
 }
 
 impl DapAdapterImpl {
+    fn move_back(&self, pred: impl Fn(&Snapshot, usize) -> bool) -> anyhow::Result<()> {
+        let mut recording = self.state.recording.lock().unwrap();
+        if recording.config.is_none() {
+            return Err(anyhow::anyhow!(
+                "Stepping back requires recording to be enabled"
+            ));
+        }
+        if !recording.move_back(pred) {
+            return Err(anyhow::anyhow!("No earlier statement was recorded"));
+        }
+        drop(recording);
+        self.state.client.event_stopped();
+        Ok(())
+    }
+
+    fn check_not_replaying(&self) -> anyhow::Result<()> {
+        if self.state.recording.lock().unwrap().cursor.is_some() {
+            return Err(anyhow::anyhow!(
+                "Only local variables were recorded for this statement, step forward to the current statement to evaluate expressions"
+            ));
+        }
+        Ok(())
+    }
+
     fn inject<T: 'static + Send>(
         &self,
         f: Box<dyn Fn(FileSpanRef, &mut Evaluator) -> (Next, T) + Send>,
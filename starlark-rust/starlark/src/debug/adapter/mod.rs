@@ -52,6 +52,7 @@ pub struct ScopesInfo {
 /// by debugger from the stack or from the heap can be broken down into "variables"
 /// this is how structured data is managed by the debugger.
 /// Something similar to LLDB's SBValue
+#[derive(Clone, Debug)]
 pub struct Variable {
     /// Name of the variable.
     pub name: PathSegment,
@@ -258,6 +259,17 @@ pub enum StepKind {
     Out,
 }
 
+/// Which statements to record the frame state at, so the debugger can later step backward
+/// through them. Statements must match both the file and the function, if given.
+#[derive(Debug, Clone, Default)]
+pub struct RecordingConfig {
+    /// Only record statements in this file.
+    pub file: Option<String>,
+    /// Only record statements evaluated while this function is on the call stack,
+    /// so including the functions it calls.
+    pub function: Option<String>,
+}
+
 /// Information about variables in scope.
 pub struct VariablesInfo {
     /// Local variables.
@@ -395,6 +407,29 @@ pub trait DapAdapter: Debug + Send + 'static {
     /// <https://microsoft.github.io/debug-adapter-protocol/specification#Requests_StepIn>
     /// <https://microsoft.github.io/debug-adapter-protocol/specification#Requests_StepOut>
     fn step(&self, kind: StepKind) -> anyhow::Result<()>;
+
+    /// Starts recording the frame state at each statement matching `config`, or stops
+    /// recording and drops the recorded states if `None`.
+    ///
+    /// While paused, the recorded states can be visited with `step_back` and `reverse_continue`,
+    /// after which `step` and `continue_` move forward through them, back to the statement
+    /// evaluation is paused at.
+    /// Only local variables are recorded, with long values truncated, so `inspect_variable` and
+    /// `evaluate` are not available for a recorded state. The oldest states are dropped once the
+    /// recording exceeds its limits on the number and total size of states.
+    fn set_recording(&self, config: Option<RecordingConfig>) -> anyhow::Result<()>;
+
+    /// Moves to the previous recorded statement in the current function.
+    ///
+    /// See <https://microsoft.github.io/debug-adapter-protocol/specification#Requests_StepBack>
+    fn step_back(&self) -> anyhow::Result<()>;
+
+    /// Moves back to the previous recorded statement with a breakpoint, or the first recorded
+    /// statement if there is none.
+    ///
+    /// See <https://microsoft.github.io/debug-adapter-protocol/specification#Requests_ReverseContinue>
+    fn reverse_continue(&self) -> anyhow::Result<()>;
+
     /// Evaluates in expression in the context of the top-most frame.
     ///
     /// See <https://microsoft.github.io/debug-adapter-protocol/specification#Requests_Evaluate>
@@ -442,6 +477,7 @@ pub fn dap_capabilities() -> Capabilities {
         supports_set_variable: Some(true),
         supports_step_in_targets_request: Some(true),
         supports_conditional_breakpoints: Some(true),
+        supports_step_back: Some(true),
         ..Capabilities::default()
     }
 }
@@ -29,12 +29,18 @@ mod t {
     use dupe::Dupe;
 
     use crate::assert::test_functions;
+    use crate::codemap::FileSpan;
     use crate::debug::adapter::implementation::prepare_dap_adapter;
     use crate::debug::adapter::implementation::resolve_breakpoints;
+    use crate::debug::adapter::implementation::Recording;
+    use crate::debug::adapter::implementation::Snapshot;
     use crate::debug::DapAdapter;
     use crate::debug::DapAdapterClient;
     use crate::debug::DapAdapterEvalHook;
+    use crate::debug::PathSegment;
+    use crate::debug::RecordingConfig;
     use crate::debug::StepKind;
+    use crate::debug::Variable;
     use crate::debug::VariablePath;
     use crate::environment::GlobalsBuilder;
     use crate::environment::Module;
@@ -623,6 +629,137 @@ print(do())
         );
     }
 
+    #[test]
+    fn test_step_back() -> crate::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let controller = BreakpointController::new();
+        let (adapter, eval_hook) = prepare_dap_adapter(controller.get_client());
+        let file_contents = "
+def add(a, b):
+    c = a + b
+    d = c * 2 # line 4
+    return d
+x = add(1, 2)
+y = add(x, 1)
+print(y) # line 8
+        ";
+        let result = std::thread::scope(|s| {
+            let ast = AstModule::parse("test.bzl", file_contents.to_owned(), &Dialect::Extended)?;
+            let breakpoints =
+                resolve_breakpoints(&breakpoints_args("test.bzl", &[(8, None)]), &ast)?;
+            adapter.set_breakpoints("test.bzl", &breakpoints)?;
+            adapter.set_recording(Some(RecordingConfig {
+                file: Some("test.bzl".to_owned()),
+                function: Some("add".to_owned()),
+            }))?;
+            let eval_result =
+                s.spawn(move || -> crate::Result<_> { eval_with_hook(ast, eval_hook) });
+            controller.wait_for_eval_stopped(1, TIMEOUT);
+
+            let mut states = Vec::new();
+            let mut state = |adapter: &dyn DapAdapter| -> anyhow::Result<_> {
+                let line = adapter.top_frame()?.map_or(0, |frame| frame.line);
+                let locals = adapter
+                    .variables()?
+                    .locals
+                    .into_iter()
+                    .map(|v| format!("{}={}", v.name, v.value))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                states.push(format!("{}: {}", line, locals));
+                Ok(())
+            };
+            adapter.step_back()?;
+            controller.wait_for_eval_stopped(2, TIMEOUT);
+            state(&adapter)?;
+            // Only local variables were recorded.
+            let evaluate = adapter.evaluate("a");
+            adapter.step_back()?;
+            controller.wait_for_eval_stopped(3, TIMEOUT);
+            state(&adapter)?;
+            adapter.reverse_continue()?;
+            controller.wait_for_eval_stopped(4, TIMEOUT);
+            state(&adapter)?;
+            let step_back_at_start = adapter.step_back();
+            adapter.step(StepKind::Over)?;
+            controller.wait_for_eval_stopped(5, TIMEOUT);
+            state(&adapter)?;
+            // Back to the statement evaluation is paused at.
+            adapter.continue_()?;
+            controller.wait_for_eval_stopped(6, TIMEOUT);
+            state(&adapter)?;
+            let evaluate_live = adapter.evaluate("y");
+
+            // TODO(cjhopman): we currently hit breakpoints on top-level statements twice (once for the gc bytecode, once for the actual statement).
+            adapter.continue_()?;
+            controller.wait_for_eval_stopped(7, TIMEOUT);
+            adapter.continue_()?;
+            join_timeout(eval_result, TIMEOUT)?;
+            crate::Result::Ok((
+                states,
+                evaluate.is_err(),
+                step_back_at_start.is_err(),
+                evaluate_live.map(|v| v.result),
+            ))
+        })?;
+
+        // It's easier to handle errors outside of thread::scope block as the test is quite flaky
+        // and hangs in case error propagates
+        let (states, evaluate_failed, step_back_at_start_failed, evaluate_live) = result;
+        assert_eq!(
+            vec![
+                "5: a=6 b=1 c=7 d=14",
+                "4: a=6 b=1 c=7",
+                "3: a=1 b=2",
+                "4: a=1 b=2 c=3",
+                "8: add=<function> x=6 y=14",
+            ],
+            states
+        );
+        assert!(evaluate_failed);
+        assert!(step_back_at_start_failed);
+        assert_eq!("14", evaluate_live.map_err(crate::Error::from)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_recording_limits() {
+        let snapshot = |value: &str| Snapshot {
+            location: FileSpan::new("test.bzl".to_owned(), "x = 1".to_owned()),
+            frames: Vec::new(),
+            locals: vec![Variable {
+                name: PathSegment::Attr("x".to_owned()),
+                value: value.to_owned(),
+                type_: "string".to_owned(),
+                has_children: false,
+            }],
+            at_breakpoint: false,
+        };
+        let small = snapshot("a").bytes();
+        let large = snapshot(&"a".repeat(1000)).bytes();
+
+        let mut recording = Recording::default();
+        for _ in 0..5 {
+            recording.push(snapshot("a"), 3, usize::MAX);
+        }
+        assert_eq!(3, recording.snapshots.len());
+        assert_eq!(3 * small, recording.bytes);
+
+        // A large snapshot drops as many old ones as needed to fit.
+        recording.push(snapshot(&"a".repeat(1000)), 3, large + small);
+        assert_eq!(2, recording.snapshots.len());
+        assert_eq!(large + small, recording.bytes);
+
+        // And is kept even if it does not fit alone.
+        recording.push(snapshot(&"a".repeat(1000)), 3, small);
+        assert_eq!(1, recording.snapshots.len());
+        assert_eq!(large, recording.bytes);
+    }
+
     #[test]
     pub fn test_truncate_string() {
         assert_eq!(
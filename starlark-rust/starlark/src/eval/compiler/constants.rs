@@ -46,6 +46,9 @@ pub(crate) struct Constants {
     pub(crate) fn_dict: BuiltinFn,
     pub(crate) fn_tuple: BuiltinFn,
     pub(crate) fn_isinstance: BuiltinFn,
+    pub(crate) fn_record: BuiltinFn,
+    pub(crate) fn_field: BuiltinFn,
+    pub(crate) fn_enum: BuiltinFn,
 }
 
 impl Constants {
@@ -59,6 +62,9 @@ impl Constants {
                 fn_dict: BuiltinFn(g.get_frozen("dict").unwrap()),
                 fn_tuple: BuiltinFn(g.get_frozen("tuple").unwrap()),
                 fn_isinstance: BuiltinFn(g.get_frozen("isinstance").unwrap()),
+                fn_record: BuiltinFn(g.get_frozen("record").unwrap()),
+                fn_field: BuiltinFn(g.get_frozen("field").unwrap()),
+                fn_enum: BuiltinFn(g.get_frozen("enum").unwrap()),
             }
         });
         Lazy::force(&RES)
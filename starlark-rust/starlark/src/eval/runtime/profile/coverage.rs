@@ -0,0 +1,337 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use anyhow::Context;
+use starlark_syntax::syntax::ast::AstLiteral;
+use starlark_syntax::syntax::ast::AstStmt;
+use starlark_syntax::syntax::ast::Expr;
+use starlark_syntax::syntax::ast::Stmt;
+use starlark_syntax::syntax::module::AstModuleFields;
+
+use crate::codemap::CodeMap;
+use crate::codemap::Span;
+use crate::syntax::AstModule;
+use crate::syntax::Dialect;
+use crate::xml::escape_xml;
+
+/// Per-line hit counts of the Starlark files evaluated with [`ProfileMode::Coverage`](crate::eval::ProfileMode::Coverage).
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LineCoverage {
+    /// Hit counts by file name, then by one-based line. Lines with statements which never ran
+    /// have a count of zero.
+    files: BTreeMap<String, BTreeMap<usize, u64>>,
+    /// Lines of module level statements by file name. These run at most once per evaluation,
+    /// but also get a `before_stmt` call for the GC point compiled before them.
+    top_level: BTreeMap<String, BTreeSet<usize>>,
+}
+
+/// The lines where statements which are compiled start. `pass`, `load` and docstrings
+/// don't run, so they are not reported as missed.
+fn statement_lines(
+    codemap: &CodeMap,
+    stmt: &AstStmt,
+    top_level: bool,
+    lines: &mut BTreeMap<usize, u64>,
+    top_level_lines: &mut BTreeSet<usize>,
+) {
+    match &stmt.node {
+        Stmt::Statements(_) => {}
+        Stmt::Pass | Stmt::Load(_) => {}
+        Stmt::Expression(x) if matches!(&x.node, Expr::Literal(AstLiteral::String(_))) => {}
+        _ => {
+            let line = codemap.find_line(stmt.span.begin()) + 1;
+            lines.insert(line, 0);
+            if top_level {
+                top_level_lines.insert(line);
+            }
+        }
+    }
+    // The branches of a module level `if` are module level too, loop and function bodies are not.
+    let top_level = top_level
+        && matches!(
+            &stmt.node,
+            Stmt::Statements(_) | Stmt::If(..) | Stmt::IfElse(..)
+        );
+    stmt.visit_stmt(|x| statement_lines(codemap, x, top_level, lines, top_level_lines));
+}
+
+impl LineCoverage {
+    /// Record the lines of a file which could run, if it was not recorded before.
+    pub(crate) fn add_file(&mut self, codemap: &CodeMap) -> anyhow::Result<()> {
+        if self.files.contains_key(codemap.filename()) {
+            return Ok(());
+        }
+        // Parse with all the features enabled, the file has been evaluated so it should parse.
+        let dialect = Dialect {
+            enable_f_strings: true,
+            ..Dialect::Extended
+        };
+        let ast = AstModule::parse(codemap.filename(), codemap.source().to_owned(), &dialect)
+            .map_err(|e| e.into_anyhow())
+            .with_context(|| {
+                format!(
+                    "Failed to parse `{}` to find its lines for coverage",
+                    codemap.filename()
+                )
+            })?;
+        let mut lines = BTreeMap::new();
+        let mut top_level_lines = BTreeSet::new();
+        statement_lines(
+            codemap,
+            ast.statement(),
+            true,
+            &mut lines,
+            &mut top_level_lines,
+        );
+        self.files.insert(codemap.filename().to_owned(), lines);
+        self.top_level
+            .insert(codemap.filename().to_owned(), top_level_lines);
+        Ok(())
+    }
+
+    /// Record that the statement at `span` ran `count` times. Statements on the same line
+    /// count as one.
+    pub(crate) fn add_hits(&mut self, codemap: &CodeMap, span: Span, count: u64) {
+        let line = codemap.find_line(span.begin()) + 1;
+        let count = match self.top_level.get(codemap.filename()) {
+            Some(top_level) if top_level.contains(&line) => count.min(1),
+            _ => count,
+        };
+        let hits = self
+            .files
+            .entry(codemap.filename().to_owned())
+            .or_default()
+            .entry(line)
+            .or_default();
+        *hits = (*hits).max(count);
+    }
+
+    /// Aggregate the coverage of several evaluations, which can be of the same files.
+    pub(crate) fn merge<'a>(coverages: impl IntoIterator<Item = &'a LineCoverage>) -> Self {
+        let mut res = LineCoverage::default();
+        for coverage in coverages {
+            for (file, lines) in &coverage.files {
+                let res_lines = res.files.entry(file.clone()).or_default();
+                for (line, hits) in lines {
+                    *res_lines.entry(*line).or_default() += hits;
+                }
+            }
+        }
+        res
+    }
+
+    fn hit_lines(lines: &BTreeMap<usize, u64>) -> usize {
+        lines.values().filter(|hits| **hits > 0).count()
+    }
+
+    fn line_rate(lines_hit: usize, lines_found: usize) -> f64 {
+        if lines_found == 0 {
+            1.0
+        } else {
+            lines_hit as f64 / lines_found as f64
+        }
+    }
+
+    /// The coverage in the [LCOV](https://github.com/linux-test-project/lcov) tracefile format.
+    pub(crate) fn gen_lcov(&self) -> String {
+        let mut res = String::new();
+        for (file, lines) in &self.files {
+            writeln!(res, "TN:").unwrap();
+            writeln!(res, "SF:{}", file).unwrap();
+            for (line, hits) in lines {
+                writeln!(res, "DA:{},{}", line, hits).unwrap();
+            }
+            writeln!(res, "LF:{}", lines.len()).unwrap();
+            writeln!(res, "LH:{}", Self::hit_lines(lines)).unwrap();
+            writeln!(res, "end_of_record").unwrap();
+        }
+        res
+    }
+
+    /// The coverage in the Cobertura XML format, with a package for each directory.
+    pub(crate) fn gen_cobertura(&self) -> String {
+        let mut packages: BTreeMap<&str, Vec<(&str, &BTreeMap<usize, u64>)>> = BTreeMap::new();
+        for (file, lines) in &self.files {
+            let package = file.rfind('/').map_or("", |i| &file[..i]);
+            packages.entry(package).or_default().push((file, lines));
+        }
+        let lines_found: usize = self.files.values().map(|x| x.len()).sum();
+        let lines_hit: usize = self.files.values().map(Self::hit_lines).sum();
+
+        let mut res = String::new();
+        writeln!(res, r#"<?xml version="1.0" ?>"#).unwrap();
+        writeln!(
+            res,
+            r#"<!DOCTYPE coverage SYSTEM "http://cobertura.sourceforge.net/xml/coverage-04.dtd">"#
+        )
+        .unwrap();
+        writeln!(
+            res,
+            r#"<coverage line-rate="{:.4}" branch-rate="0" lines-covered="{}" lines-valid="{}" branches-covered="0" branches-valid="0" complexity="0" version="0" timestamp="0">"#,
+            Self::line_rate(lines_hit, lines_found),
+            lines_hit,
+            lines_found
+        )
+        .unwrap();
+        writeln!(res, "  <sources><source>.</source></sources>").unwrap();
+        writeln!(res, "  <packages>").unwrap();
+        for (package, files) in packages {
+            let found: usize = files.iter().map(|(_, lines)| lines.len()).sum();
+            let hit: usize = files.iter().map(|(_, lines)| Self::hit_lines(lines)).sum();
+            writeln!(
+                res,
+                r#"    <package name="{}" line-rate="{:.4}" branch-rate="0" complexity="0">"#,
+                escape_xml(package),
+                Self::line_rate(hit, found)
+            )
+            .unwrap();
+            writeln!(res, "      <classes>").unwrap();
+            for (file, lines) in files {
+                writeln!(
+                    res,
+                    r#"        <class name="{0}" filename="{0}" line-rate="{1:.4}" branch-rate="0" complexity="0">"#,
+                    escape_xml(file),
+                    Self::line_rate(Self::hit_lines(lines), lines.len())
+                )
+                .unwrap();
+                writeln!(res, "          <methods/>").unwrap();
+                writeln!(res, "          <lines>").unwrap();
+                for (line, hits) in lines {
+                    writeln!(
+                        res,
+                        r#"            <line number="{}" hits="{}"/>"#,
+                        line, hits
+                    )
+                    .unwrap();
+                }
+                writeln!(res, "          </lines>").unwrap();
+                writeln!(res, "        </class>").unwrap();
+            }
+            writeln!(res, "      </classes>").unwrap();
+            writeln!(res, "    </package>").unwrap();
+        }
+        writeln!(res, "  </packages>").unwrap();
+        writeln!(res, "</coverage>").unwrap();
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assert::test_functions;
+    use crate::codemap::CodeMap;
+    use crate::environment::GlobalsBuilder;
+    use crate::environment::Module;
+    use crate::eval::runtime::profile::coverage::LineCoverage;
+    use crate::eval::runtime::profile::data::ProfileDataImpl;
+    use crate::eval::Evaluator;
+    use crate::eval::ProfileData;
+    use crate::eval::ProfileMode;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+
+    fn coverage(program: &str) -> ProfileData {
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        eval.enable_profile(&ProfileMode::Coverage).unwrap();
+        let mut globals = GlobalsBuilder::standard();
+        test_functions(&mut globals);
+        let ast = AstModule::parse("pkg/cov.star", program.to_owned(), &Dialect::Extended).unwrap();
+        eval.eval_module(ast, &globals.build()).unwrap();
+        eval.gen_profile().unwrap()
+    }
+
+    const PROGRAM: &str = r#"
+def f(x):
+    """Docstring."""
+    if x:
+        return noop(x)
+    else:
+        pass
+        return noop(None)
+
+f(1)
+f(2)
+"#;
+
+    #[test]
+    fn test_lcov() {
+        assert_eq!(
+            "TN:\nSF:pkg/cov.star\nDA:2,1\nDA:4,2\nDA:5,2\nDA:8,0\nDA:10,1\nDA:11,1\nLF:6\nLH:5\nend_of_record\n",
+            coverage(PROGRAM).gen().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let profile = coverage(PROGRAM);
+        let merged = ProfileData::merge([&profile, &profile]).unwrap();
+        let ProfileDataImpl::Coverage(merged) = &merged.profile else {
+            panic!("Expected coverage");
+        };
+        assert_eq!(
+            Some(&4),
+            merged.files.get("pkg/cov.star").and_then(|x| x.get(&5))
+        );
+        assert_eq!(
+            Some(&0),
+            merged.files.get("pkg/cov.star").and_then(|x| x.get(&8))
+        );
+        assert_eq!(
+            LineCoverage::merge([]).gen_lcov(),
+            LineCoverage::default().gen_lcov()
+        );
+    }
+
+    #[test]
+    fn test_cobertura() {
+        let xml = coverage(PROGRAM).gen_cobertura().unwrap();
+        assert!(
+            xml.contains(r#"lines-covered="5" lines-valid="6""#),
+            "{}",
+            xml
+        );
+        assert!(
+            xml.contains(r#"<package name="pkg" line-rate="0.8333""#),
+            "{}",
+            xml
+        );
+        assert!(
+            xml.contains(r#"<class name="pkg/cov.star" filename="pkg/cov.star""#),
+            "{}",
+            xml
+        );
+        assert!(xml.contains(r#"<line number="8" hits="0"/>"#), "{}", xml);
+    }
+
+    #[test]
+    fn test_unparseable_file() {
+        let codemap = CodeMap::new("pkg/bad.star".to_owned(), "def f(:\n".to_owned());
+        let mut coverage = LineCoverage::default();
+        let error = format!("{:#}", coverage.add_file(&codemap).unwrap_err());
+        assert!(
+            error.contains("Failed to parse `pkg/bad.star`"),
+            "{}",
+            error
+        );
+        assert!(coverage.files.is_empty());
+    }
+}
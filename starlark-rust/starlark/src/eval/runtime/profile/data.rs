@@ -24,6 +24,7 @@ use starlark_syntax::slice_vec_ext::SliceExt;
 
 use crate::eval::runtime::profile::bc::BcPairsProfileData;
 use crate::eval::runtime::profile::bc::BcProfileData;
+use crate::eval::runtime::profile::coverage::LineCoverage;
 use crate::eval::runtime::profile::flamegraph::FlameGraphData;
 use crate::eval::ProfileMode;
 use crate::values::AggregateHeapProfileInfo;
@@ -38,6 +39,8 @@ enum ProfileDataError {
     DifferentProfileModes,
     #[error("Merge of profile data for profile mode `{0}` is not implemented")]
     MergeNotImplemented(ProfileMode),
+    #[error("Profile mode `{0}` is not coverage")]
+    NotCoverage(ProfileMode),
 }
 
 #[derive(Clone, Debug)]
@@ -47,6 +50,7 @@ pub(crate) enum ProfileDataImpl {
     AggregateHeapProfileInfo(Box<AggregateHeapProfileInfo>),
     /// Flame graph data is in milliseconds.
     TimeFlameProfile(FlameGraphData),
+    Coverage(LineCoverage),
     Other(String),
 }
 
@@ -87,6 +91,19 @@ impl ProfileData {
             (ProfileDataImpl::TimeFlameProfile(_), _) => {
                 Err(ProfileDataError::ProfileDataNotConsistent.into())
             }
+            (ProfileDataImpl::Coverage(coverage), ProfileMode::Coverage) => Ok(coverage.gen_lcov()),
+            (ProfileDataImpl::Coverage(_), _) => {
+                Err(ProfileDataError::ProfileDataNotConsistent.into())
+            }
+        }
+    }
+
+    /// Generate Cobertura XML from a coverage profile, which [`gen`](ProfileData::gen)
+    /// writes in the LCOV format.
+    pub fn gen_cobertura(&self) -> anyhow::Result<String> {
+        match &self.profile {
+            ProfileDataImpl::Coverage(coverage) => Ok(coverage.gen_cobertura()),
+            _ => Err(ProfileDataError::NotCoverage(self.profile_mode.dupe()).into()),
         }
     }
 
@@ -152,6 +169,13 @@ impl ProfileData {
                 let profile = FlameGraphData::merge(profiles);
                 ProfileDataImpl::TimeFlameProfile(profile)
             }
+            ProfileMode::Coverage => {
+                let profiles = profiles.try_map(|p| match &p.profile {
+                    ProfileDataImpl::Coverage(coverage) => Ok(coverage),
+                    _ => Err(ProfileDataError::ProfileDataNotConsistent),
+                })?;
+                ProfileDataImpl::Coverage(LineCoverage::merge(profiles))
+            }
             profile_mode => {
                 return Err(ProfileDataError::MergeNotImplemented(profile_mode.dupe()).into());
             }
@@ -22,6 +22,7 @@ use allocative::Allocative;
 use dupe::Dupe;
 
 pub(crate) mod bc;
+pub(crate) mod coverage;
 pub(crate) mod csv;
 pub(crate) mod data;
 pub(crate) mod flamegraph;
@@ -48,7 +49,7 @@ pub enum ProfileMode {
     HeapFlameRetained,
     /// The statement profile mode provides information about time spent in each statement.
     Statement,
-    /// Code coverage.
+    /// Code coverage, with the number of times each line ran, written in the LCOV format.
     Coverage,
     /// The bytecode profile mode provides information about bytecode instructions.
     Bytecode,
@@ -29,8 +29,10 @@ use crate::codemap::FileSpan;
 use crate::codemap::FileSpanRef;
 use crate::codemap::ResolvedFileSpan;
 use crate::codemap::Span;
+use crate::eval::runtime::profile::coverage::LineCoverage;
 use crate::eval::runtime::profile::csv::CsvWriter;
 use crate::eval::runtime::profile::data::ProfileData;
+use crate::eval::runtime::profile::data::ProfileDataImpl;
 use crate::eval::runtime::small_duration::SmallDuration;
 use crate::eval::ProfileMode;
 
@@ -151,6 +153,23 @@ impl StmtProfileData {
         csv.finish()
     }
 
+    fn line_coverage(&self, now: Instant) -> anyhow::Result<LineCoverage> {
+        // Count the statement running last, like `write_to_string` does.
+        let mut data = self.clone();
+        data.add_last(now);
+
+        let mut res = LineCoverage::default();
+        for codemap in data.files.values() {
+            res.add_file(codemap)?;
+        }
+        for ((file, span), (count, _time)) in &data.stmts {
+            if *file != CodeMapId::EMPTY {
+                res.add_hits(&data.files[file], *span, *count as u64);
+            }
+        }
+        Ok(res)
+    }
+
     fn coverage(&self) -> HashSet<ResolvedFileSpan> {
         self.stmts
             .keys()
@@ -194,6 +213,17 @@ impl StmtProfile {
         }
     }
 
+    pub(crate) fn gen_coverage(&self) -> anyhow::Result<ProfileData> {
+        let now = Instant::now();
+        match &self.0 {
+            Some(data) => Ok(ProfileData {
+                profile_mode: ProfileMode::Coverage,
+                profile: ProfileDataImpl::Coverage(data.line_coverage(now)?),
+            }),
+            None => Err(StmtProfileError::NotEnabled.into()),
+        }
+    }
+
     pub(crate) fn coverage(&self) -> anyhow::Result<HashSet<ResolvedFileSpan>> {
         Ok(self
             .0
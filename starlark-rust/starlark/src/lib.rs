@@ -454,6 +454,7 @@ mod hint;
 mod stdlib;
 pub mod values;
 pub mod wasm;
+pub mod xml;
 
 pub mod coerce;
 #[cfg(test)]
@@ -94,11 +94,38 @@ pub(crate) struct Bindings<'a> {
     /// ```
     pub(crate) check: Vec<&'a CstExpr>,
     pub(crate) check_type: Vec<(Span, Option<&'a CstExpr>, Ty)>,
+    /// `return` statements of the function the bindings are collected for,
+    /// but not of the functions nested in it, with their required type.
+    /// Used to infer the result type of the function when it is not annotated.
+    pub(crate) returns: Vec<(Span, Option<&'a CstExpr>, Ty)>,
+    /// Whether the end of the function body can be reached, returning `None`.
+    pub(crate) falls_through: bool,
 }
 
 pub(crate) struct BindingsCollect<'a, 'b> {
     pub(crate) bindings: Bindings<'a>,
     pub(crate) approximations: &'b mut Vec<Approximation>,
+    /// How many `def`s the visited statement is nested in.
+    def_depth: usize,
+}
+
+/// Whether a statement never finishes normally, because it ends with `return` or `fail`.
+fn final_return(x: &CstStmt) -> bool {
+    match &**x {
+        StmtP::Return(_) => true,
+        StmtP::Expression(x) => match &**x {
+            ExprP::Call(f, _) => {
+                matches!(&***f, ExprP::Identifier(name) if name.node.ident == "fail")
+            }
+            _ => false,
+        },
+        StmtP::Statements(xs) => xs.last().is_some_and(final_return),
+        StmtP::IfElse(_, x_y) => {
+            let (x, y) = &**x_y;
+            final_return(x) && final_return(y)
+        }
+        _ => false,
+    }
 }
 
 impl<'a, 'b> BindingsCollect<'a, 'b> {
@@ -114,6 +141,7 @@ impl<'a, 'b> BindingsCollect<'a, 'b> {
         let mut res = BindingsCollect {
             bindings: Bindings::default(),
             approximations,
+            def_depth: 0,
         };
 
         res.visit(Visit::Stmt(x), &Ty::any(), typecheck_mode, codemap)?;
@@ -257,7 +285,12 @@ impl<'a, 'b> BindingsCollect<'a, 'b> {
             name.resolved_binding_id(codemap)?,
             Ty::function(params2, ret_ty.clone()),
         );
+        self.def_depth += 1;
+        if self.def_depth == 1 {
+            self.bindings.falls_through = !final_return(&def.body);
+        }
         def.visit_children_err(|x| self.visit(x, &ret_ty, typecheck_mode, codemap))?;
+        self.def_depth -= 1;
         Ok(())
     }
 
@@ -299,9 +332,12 @@ impl<'a, 'b> BindingsCollect<'a, 'b> {
                 }
                 StmtP::Load(..) => {}
                 StmtP::Return(ret) => {
-                    self.bindings
-                        .check_type
-                        .push((x.span, ret.as_ref(), return_type.clone()))
+                    let check = (x.span, ret.as_ref(), return_type.clone());
+                    if self.def_depth == 1 {
+                        self.bindings.returns.push(check)
+                    } else {
+                        self.bindings.check_type.push(check)
+                    }
                 }
                 StmtP::Expression(x) => {
                     // We want to find ident.append(), ident.extend(), ident.extend()
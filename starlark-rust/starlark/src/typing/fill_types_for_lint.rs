@@ -21,6 +21,7 @@ use std::iter;
 use starlark_map::unordered_map;
 use starlark_map::unordered_map::UnorderedMap;
 use starlark_syntax::slice_vec_ext::SliceExt;
+use starlark_syntax::syntax::ast::ArgumentP;
 use starlark_syntax::syntax::ast::AssignP;
 use starlark_syntax::syntax::ast::AssignTargetP;
 use starlark_syntax::syntax::ast::AstLiteral;
@@ -39,6 +40,7 @@ use starlark_syntax::syntax::type_expr::TypeExprUnpackP;
 use crate::codemap::Span;
 use crate::codemap::Spanned;
 use crate::environment::slots::ModuleSlotId;
+use crate::environment::Module;
 use crate::eval::compiler::constants::Constants;
 use crate::eval::compiler::scope::payload::CstArgument;
 use crate::eval::compiler::scope::payload::CstAssignIdent;
@@ -51,6 +53,7 @@ use crate::eval::compiler::scope::payload::CstTypeExpr;
 use crate::eval::compiler::scope::ModuleScopeData;
 use crate::eval::compiler::scope::ResolvedIdent;
 use crate::eval::compiler::scope::Slot;
+use crate::eval::Evaluator;
 use crate::typing::error::InternalError;
 use crate::typing::error::TypingError;
 use crate::typing::Approximation;
@@ -69,6 +72,9 @@ struct GlobalValue<'v> {
     /// `None` means we don't know (or know it may have different value depending on condition).
     value: Option<Value<'v>>,
     ty: Ty,
+    /// The type this denotes in type expressions when the value is not known,
+    /// like for a record type loaded from another module.
+    as_type: Option<Ty>,
 }
 
 impl<'v> GlobalValue<'v> {
@@ -86,6 +92,7 @@ impl<'v> GlobalValue<'v> {
         GlobalValue {
             value: None,
             ty: Ty::union2(a.ty, b.ty),
+            as_type: None,
         }
     }
 }
@@ -95,6 +102,7 @@ impl<'v> GlobalValue<'v> {
         GlobalValue {
             value: Some(value),
             ty: Ty::of_value(value),
+            as_type: None,
         }
     }
 
@@ -102,16 +110,31 @@ impl<'v> GlobalValue<'v> {
         GlobalValue {
             value: None,
             ty: Ty::any(),
+            as_type: None,
         }
     }
 
     fn ty(ty: Ty) -> GlobalValue<'v> {
-        GlobalValue { value: None, ty }
+        GlobalValue {
+            value: None,
+            ty,
+            as_type: None,
+        }
+    }
+
+    /// The type of a type expression, if this is known to be a type.
+    fn as_type(&self) -> Option<Ty> {
+        match self.value {
+            Some(value) => value.get_ref().eval_type(),
+            None => self.as_type.clone(),
+        }
     }
 }
 
 struct GlobalTypesBuilder<'a, 'v> {
     approximations: &'a mut Vec<Approximation>,
+    /// Module to evaluate the calls which define types in, like `record(...)`.
+    module: &'v Module,
     heap: &'v Heap,
     values: UnorderedMap<ModuleSlotId, GlobalValue<'v>>,
     errors: Vec<TypingError>,
@@ -130,13 +153,56 @@ impl<'a, 'v> GlobalTypesBuilder<'a, 'v> {
         GlobalValue::any()
     }
 
+    /// Functions which can be called when partially evaluating globals:
+    /// those without side effects, and those which create types.
+    fn can_call(f: Value<'v>) -> bool {
+        let Some(f) = f.unpack_frozen() else {
+            return false;
+        };
+        let constants = Constants::get();
+        f.speculative_exec_safe()
+            || f == constants.fn_record
+            || f == constants.fn_field
+            || f == constants.fn_enum
+    }
+
     fn call(
         &mut self,
-        _f: &CstExpr,
-        _args: &[CstArgument],
+        span: Span,
+        f: &CstExpr,
+        args: &[CstArgument],
     ) -> Result<GlobalValue<'v>, InternalError> {
-        // TODO(nga): could be a call like `record(...)`, and we need to evaluate it.
-        Ok(GlobalValue::any())
+        let Some(f) = self.expr(f)?.value else {
+            return Ok(GlobalValue::any());
+        };
+        if !Self::can_call(f) {
+            return Ok(GlobalValue::any());
+        }
+        let mut pos = Vec::new();
+        let mut named = Vec::new();
+        for arg in args {
+            let value = self.expr(arg.expr())?.value;
+            match (&arg.node, value) {
+                (ArgumentP::Positional(_), Some(value)) => pos.push(value),
+                (ArgumentP::Named(name, _), Some(value)) => named.push((name.as_str(), value)),
+                _ => return Ok(GlobalValue::any()),
+            }
+        }
+        let mut eval = Evaluator::new(self.module);
+        match eval.eval_function(f, &pos, &named) {
+            Ok(value) => Ok(GlobalValue::value(value)),
+            Err(e) => Ok(self.err(span, e)),
+        }
+    }
+
+    /// Give the value assigned to a module variable its name,
+    /// like evaluation does, so record types get their type.
+    fn export_as(&mut self, ident: &CstAssignIdent, value: Value<'v>) -> GlobalValue<'v> {
+        let mut eval = Evaluator::new(self.module);
+        match value.export_as(ident.ident.as_str(), &mut eval) {
+            Ok(()) => GlobalValue::value(value),
+            Err(e) => self.err(ident.span, e),
+        }
     }
 
     fn expr_ident(&self, ident: &CstIdent) -> Result<GlobalValue<'v>, InternalError> {
@@ -254,7 +320,7 @@ impl<'a, 'v> GlobalTypesBuilder<'a, 'v> {
         match &expr.node {
             ExprP::Tuple(xs) => self.tuple(xs),
             ExprP::Dot(object, field) => self.dot(span, object, field),
-            ExprP::Call(f, args) => self.call(f, args),
+            ExprP::Call(f, args) => self.call(span, f, args),
             ExprP::Index(a_i) => {
                 let (a, i) = &**a_i;
                 self.index(span, a, i)
@@ -293,7 +359,15 @@ impl<'a, 'v> GlobalTypesBuilder<'a, 'v> {
     fn load(&mut self, load: &LoadP<CstPayload>) -> Result<(), InternalError> {
         for LoadArgP { local, their, .. } in &load.args {
             let ty = load.payload.get(their).cloned().unwrap_or_else(Ty::any);
-            self.assign_ident_value(local, GlobalValue::ty(ty))?;
+            let as_type = load.payload.get_as_type(their).cloned();
+            self.assign_ident_value(
+                local,
+                GlobalValue {
+                    value: None,
+                    ty,
+                    as_type,
+                },
+            )?;
         }
         Ok(())
     }
@@ -350,7 +424,13 @@ impl<'a, 'v> GlobalTypesBuilder<'a, 'v> {
             }
             AssignTargetP::Index(_) => Ok(()),
             AssignTargetP::Dot(_, _) => Ok(()),
-            AssignTargetP::Identifier(ident) => self.assign_ident_value(ident, rhs),
+            AssignTargetP::Identifier(ident) => {
+                let rhs = match rhs.value {
+                    Some(value) => self.export_as(ident, value),
+                    None => rhs,
+                };
+                self.assign_ident_value(ident, rhs)
+            }
         }
     }
 
@@ -506,8 +586,12 @@ impl<'a, 'v> GlobalTypesBuilder<'a, 'v> {
         first: &CstIdent,
         rem: &[Spanned<&str>],
     ) -> Result<Option<Ty>, InternalError> {
-        let Some(mut value) = self.expr_ident(first)?.value else {
-            return Ok(None);
+        let first_value = self.expr_ident(first)?;
+        let Some(mut value) = first_value.value else {
+            return Ok(match rem {
+                [] => first_value.as_type,
+                _ => None,
+            });
         };
         for x in rem {
             match value.get_attr_error(x, self.heap) {
@@ -699,6 +783,8 @@ impl<'a, 'v> GlobalTypesBuilder<'a, 'v> {
 #[derive(Default)]
 pub(crate) struct ModuleVarTypes {
     pub(crate) types: UnorderedMap<ModuleSlotId, Ty>,
+    /// Types denoted by the module-level variables which are types, like record types.
+    pub(crate) as_types: UnorderedMap<ModuleSlotId, Ty>,
 }
 
 /// Populate `TypeExprP` type payload when running lint typechecker.
@@ -709,9 +795,10 @@ pub(crate) fn fill_types_for_lint_typechecker(
     module_scope_data: &ModuleScopeData,
     approximations: &mut Vec<Approximation>,
 ) -> Result<(Vec<TypingError>, ModuleVarTypes), InternalError> {
-    let heap = Heap::new();
+    let env = Module::new();
     let mut builder = GlobalTypesBuilder {
-        heap: &heap,
+        module: &env,
+        heap: env.heap(),
         ctx,
         values: UnorderedMap::new(),
         errors: Vec::new(),
@@ -722,6 +809,10 @@ pub(crate) fn fill_types_for_lint_typechecker(
         builder.top_level_stmt(stmt)?;
     }
     let GlobalTypesBuilder { errors, values, .. } = builder;
+    let as_types = values
+        .entries_unordered()
+        .filter_map(|(k, v)| Some((*k, v.as_type()?)))
+        .collect();
     let types = values.map_values(|v| v.ty);
-    Ok((errors, ModuleVarTypes { types }))
+    Ok((errors, ModuleVarTypes { types, as_types }))
 }
@@ -147,6 +147,11 @@ impl Param {
         }
     }
 
+    /// The type of the parameter. For `*args` and `**kwargs`, the type of each argument.
+    pub fn ty(&self) -> &Ty {
+        &self.ty
+    }
+
     /// Get a display name for this parameter.
     pub fn name(&self) -> &str {
         match &self.mode {
@@ -280,6 +285,16 @@ impl TyFunction {
         }
     }
 
+    /// The parameters of the function.
+    pub fn params(&self) -> &[Param] {
+        &self.params
+    }
+
+    /// The type the function returns.
+    pub fn result(&self) -> &Ty {
+        &self.result
+    }
+
     fn maybe_intern_params(params: Vec<Param>) -> SmallArcVec1OrStatic<Param> {
         if params.as_slice() == Self::any_params() {
             SmallArcVec1OrStatic::new_static(Self::any_params())
@@ -20,11 +20,17 @@ use std::sync::Arc;
 
 use dupe::Dupe;
 
+use crate::environment::FrozenModule;
 use crate::typing::Ty;
 
 /// Interface representing the types of all bindings in a module.
 #[derive(Default, Dupe, Clone, Debug)]
-pub struct Interface(Arc<HashMap<String, Ty>>);
+pub struct Interface {
+    bindings: Arc<HashMap<String, Ty>>,
+    /// Types the bindings denote when used in type expressions,
+    /// for example, the type of the records created by `MyRecord = record(...)`.
+    types: Arc<HashMap<String, Ty>>,
+}
 
 impl Interface {
     /// Create an empty interface, with no bindings.
@@ -34,11 +40,66 @@ impl Interface {
 
     /// Create a new interface with the given bindings.
     pub fn new(bindings: HashMap<String, Ty>) -> Self {
-        Self(Arc::new(bindings))
+        Self::new_with_types(bindings, HashMap::new())
+    }
+
+    /// Create a new interface with the given bindings,
+    /// and the types of those of them which can be used in type expressions.
+    pub fn new_with_types(bindings: HashMap<String, Ty>, types: HashMap<String, Ty>) -> Self {
+        Self {
+            bindings: Arc::new(bindings),
+            types: Arc::new(types),
+        }
+    }
+
+    /// Create an interface from the public bindings of an evaluated module.
+    ///
+    /// The values are known, so this is more precise than the interface the typechecker
+    /// infers for values created by calls, like records or `provider`s in Buck2.
+    pub fn from_module(module: &FrozenModule) -> Self {
+        let mut bindings = HashMap::new();
+        let mut types = HashMap::new();
+        for name in module.names() {
+            let Ok(Some(value)) = module.get_option(name.as_str()) else {
+                continue;
+            };
+            let value = value.value();
+            bindings.insert(name.as_str().to_owned(), Ty::of_value(value));
+            if let Some(ty) = value.get_ref().eval_type() {
+                types.insert(name.as_str().to_owned(), ty);
+            }
+        }
+        Self::new_with_types(bindings, types)
+    }
+
+    /// Use the bindings of `fallback` for the bindings this interface doesn't know
+    /// the type of.
+    pub fn with_fallback(&self, fallback: &Interface) -> Self {
+        let mut bindings = (*self.bindings).clone();
+        for (name, ty) in fallback.bindings.iter() {
+            match bindings.get_mut(name) {
+                Some(x) if x.is_any() => *x = ty.clone(),
+                Some(_) => {}
+                None => {
+                    bindings.insert(name.clone(), ty.clone());
+                }
+            }
+        }
+        let mut types = (*self.types).clone();
+        for (name, ty) in fallback.types.iter() {
+            types.entry(name.clone()).or_insert_with(|| ty.clone());
+        }
+        Self::new_with_types(bindings, types)
     }
 
     /// Get the type for a given binding.
     pub fn get(&self, name: &str) -> Option<&Ty> {
-        self.0.get(name)
+        self.bindings.get(name)
+    }
+
+    /// Get the type a binding denotes when used in a type expression,
+    /// if it is a type.
+    pub fn get_as_type(&self, name: &str) -> Option<&Ty> {
+        self.types.get(name)
     }
 }
@@ -40,6 +40,7 @@ pub(crate) mod starlark_value;
 pub(crate) mod structs;
 pub(crate) mod tuple;
 pub(crate) mod ty;
+pub(crate) mod type_var;
 pub(crate) mod typecheck;
 pub(crate) mod user;
 
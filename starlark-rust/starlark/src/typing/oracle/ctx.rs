@@ -33,6 +33,7 @@ use crate::typing::function::ParamMode;
 use crate::typing::function::TyFunction;
 use crate::typing::starlark_value::TyStarlarkValue;
 use crate::typing::tuple::TyTuple;
+use crate::typing::type_var::TypeVarBindings;
 use crate::typing::Ty;
 use crate::typing::TyName;
 use crate::typing::TypingBinOp;
@@ -138,6 +139,7 @@ impl<'a> TypingOracleCtx<'a> {
         params: &[Param],
         args: &[Spanned<Arg>],
         span: Span,
+        type_vars: &mut TypeVarBindings,
     ) -> Result<(), TypingOrInternalError> {
         // Want to figure out which arguments go in which positions
         let mut param_args: Vec<Vec<Spanned<&Ty>>> = vec![vec![]; params.len()];
@@ -224,12 +226,14 @@ impl<'a> TypingOracleCtx<'a> {
             match param.mode {
                 ParamMode::PosOnly | ParamMode::PosOrName(_) | ParamMode::NameOnly(_) => {
                     self.validate_type(args[0], &param.ty)?;
+                    type_vars.bind(&param.ty, args[0].node, self);
                 }
                 ParamMode::Args => {
                     for ty in args {
                         // For an arg, we require the type annotation to be inner value,
                         // rather than the outer (which is always a tuple)
                         self.validate_type(ty, &param.ty)?;
+                        type_vars.bind(&param.ty, ty.node, self);
                     }
                 }
                 ParamMode::Kwargs => {
@@ -246,6 +250,7 @@ impl<'a> TypingOracleCtx<'a> {
                         let require = Ty::unions(val_types);
                         for ty in args {
                             self.validate_type(ty, &require)?;
+                            type_vars.bind(&require, ty.node, self);
                         }
                     }
                 }
@@ -260,8 +265,9 @@ impl<'a> TypingOracleCtx<'a> {
         fun: &TyFunction,
         args: &[Spanned<Arg>],
     ) -> Result<Ty, TypingOrInternalError> {
-        self.validate_args(&fun.params, args, span)?;
-        Ok(fun.result.clone())
+        let mut type_vars = TypeVarBindings::default();
+        self.validate_args(&fun.params, args, span, &mut type_vars)?;
+        Ok(type_vars.substitute(&fun.result))
     }
 
     fn validate_call_for_type_name(
@@ -339,7 +345,7 @@ impl<'a> TypingOracleCtx<'a> {
         }
     }
 
-    fn iter_item_basic(&self, ty: &TyBasic) -> Result<Ty, ()> {
+    pub(crate) fn iter_item_basic(&self, ty: &TyBasic) -> Result<Ty, ()> {
         match ty {
             TyBasic::Any => Ok(Ty::any()),
             TyBasic::StarlarkValue(ty) => ty.iter_item(),
@@ -212,6 +212,103 @@ def test():
         );
 }
 
+#[test]
+fn test_load_inferred_return() {
+    let (interface, module) = TypeCheck::new().check(
+        "load_inferred_return_0",
+        r#"
+def foo(x):
+    if x:
+        return 1
+    return None
+
+def bar():
+    return "bar"
+"#,
+    );
+    TypeCheck::new()
+        .load("foo.bzl", interface, module)
+        .ty("x")
+        .ty("y")
+        .check(
+            "load_inferred_return_1",
+            r#"
+load("foo.bzl", "foo", "bar")
+def test():
+    x = foo(True)
+    y = bar()
+"#,
+        );
+}
+
+#[test]
+fn test_record() {
+    TypeCheck::new().ty("x").check(
+        "record",
+        r#"
+MyRec = record(x = int, y = field(str, "y"))
+
+def foo(r: MyRec):
+    x = r.x
+
+def bar():
+    foo(1)
+"#,
+    );
+}
+
+#[test]
+fn test_load_record() {
+    let (interface, module) = TypeCheck::new().check(
+        "load_record_0",
+        r#"
+MyRec = record(x = int)
+
+def make() -> MyRec:
+    return MyRec(x = 1)
+"#,
+    );
+    TypeCheck::new()
+        .load("foo.bzl", interface, module)
+        .ty("x")
+        .check(
+            "load_record_1",
+            r#"
+load("foo.bzl", "MyRec", "make")
+def foo(r: MyRec) -> int:
+    return r.x
+
+def test():
+    x = foo(make())
+    foo("")
+"#,
+        );
+}
+
+#[test]
+fn test_load_from_module() {
+    let (_, module) = TypeCheck::new().check(
+        "load_from_module_0",
+        r#"
+def _make_rec():
+    return record(x = int)
+
+MyRec = _make_rec()
+"#,
+    );
+    TypeCheck::new()
+        .load("foo.bzl", Interface::from_module(&module), module)
+        .ty("x")
+        .check(
+            "load_from_module_1",
+            r#"
+load("foo.bzl", "MyRec")
+def test(r: MyRec):
+    x = r.x
+"#,
+        );
+}
+
 /// Test things that have previous claimed incorrectly they were type errors
 #[test]
 fn test_false_negative() {
@@ -637,3 +734,68 @@ def test(s: str):
 "#,
     );
 }
+
+#[test]
+fn test_type_var() {
+    TypeCheck::new().ty("x").ty("y").ty("z").check(
+        "type_var",
+        r#"
+T = typing.TypeVar("T")
+K = typing.TypeVar("K")
+
+def first(xs: list[T]) -> T:
+    return xs[0]
+
+def keys(d: dict[K, T]) -> list[K]:
+    return list(d.keys())
+
+def or_default(v: T | None, default: T) -> T:
+    return default if v == None else v
+
+def test(o: int | None):
+    x = first([1, 2])
+    y = keys({"a": True})
+    z = or_default(o, "")
+"#,
+    );
+}
+
+#[test]
+fn test_type_var_record() {
+    TypeCheck::new().ty("x").check(
+        "type_var_record",
+        r#"
+T = typing.TypeVar("T")
+
+Box = record(value = T)
+
+def test():
+    x = Box(value = 1).value
+"#,
+    );
+}
+
+#[test]
+fn test_load_type_var() {
+    let (interface, module) = TypeCheck::new().check(
+        "load_type_var_0",
+        r#"
+T = typing.TypeVar("T")
+
+def first(xs: list[T]) -> T:
+    return xs[0]
+"#,
+    );
+    TypeCheck::new()
+        .load("foo.bzl", interface, module)
+        .ty("x")
+        .check(
+            "load_type_var_1",
+            r#"
+load("foo.bzl", "first")
+
+def test():
+    x = first(["a"])
+"#,
+        );
+}
@@ -0,0 +1,249 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Type variables, declared with `typing.TypeVar("T")`, and their inference at call sites.
+
+use std::fmt;
+use std::fmt::Display;
+use std::iter;
+
+use allocative::Allocative;
+use dupe::Dupe;
+use starlark_map::small_map::SmallMap;
+
+use crate::codemap::Span;
+use crate::codemap::Spanned;
+use crate::typing::custom::TyCustomImpl;
+use crate::typing::error::TypingOrInternalError;
+use crate::typing::tuple::TyTuple;
+use crate::typing::Arg;
+use crate::typing::Ty;
+use crate::typing::TyBasic;
+use crate::typing::TypingBinOp;
+use crate::typing::TypingOracleCtx;
+use crate::values::layout::heap::profile::arc_str::ArcStr;
+use crate::values::typing::type_compiled::alloc::TypeMatcherAlloc;
+
+/// Type variable, like `T` in `def first(xs: list[T]) -> T`.
+///
+/// Inside the function body the variable is checked like `typing.Any`.
+/// At call sites it is bound to the types of the arguments, and substituted in the result type.
+#[derive(Debug, Clone, Dupe, PartialEq, Eq, Hash, PartialOrd, Ord, Allocative)]
+pub(crate) struct TyTypeVar {
+    name: ArcStr,
+}
+
+impl TyTypeVar {
+    pub(crate) fn new(name: &str) -> TyTypeVar {
+        TyTypeVar {
+            name: ArcStr::from(name),
+        }
+    }
+
+    fn from_basic(ty: &TyBasic) -> Option<&TyTypeVar> {
+        match ty {
+            TyBasic::Custom(c) => c.0.as_any().downcast_ref::<TyTypeVar>(),
+            _ => None,
+        }
+    }
+}
+
+impl Display for TyTypeVar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name.as_str())
+    }
+}
+
+impl TyCustomImpl for TyTypeVar {
+    fn as_name(&self) -> Option<&str> {
+        None
+    }
+
+    fn validate_call(
+        &self,
+        _span: Span,
+        _args: &[Spanned<Arg>],
+        _oracle: TypingOracleCtx,
+    ) -> Result<Ty, TypingOrInternalError> {
+        Ok(Ty::any())
+    }
+
+    fn is_callable(&self) -> bool {
+        true
+    }
+
+    fn bin_op(
+        &self,
+        _bin_op: TypingBinOp,
+        _rhs: &TyBasic,
+        _ctx: &TypingOracleCtx,
+    ) -> Result<Ty, ()> {
+        Ok(Ty::any())
+    }
+
+    fn iter_item(&self) -> Result<Ty, ()> {
+        Ok(Ty::any())
+    }
+
+    fn index(&self, _item: &TyBasic, _ctx: &TypingOracleCtx) -> Result<Ty, ()> {
+        Ok(Ty::any())
+    }
+
+    fn attribute(&self, _attr: &str) -> Result<Ty, ()> {
+        Ok(Ty::any())
+    }
+
+    fn intersects_with(&self, _other: &TyBasic) -> bool {
+        true
+    }
+
+    fn matcher<T: TypeMatcherAlloc>(&self, factory: T) -> T::Result {
+        factory.any()
+    }
+}
+
+/// Types bound to type variables while checking a single call.
+#[derive(Default)]
+pub(crate) struct TypeVarBindings {
+    bindings: SmallMap<ArcStr, Ty>,
+}
+
+impl TypeVarBindings {
+    /// Bind the type variables of parameter type `param` to the matching parts of `arg`.
+    pub(crate) fn bind(&mut self, param: &Ty, arg: &Ty, ctx: &TypingOracleCtx) {
+        if arg.is_never() {
+            return;
+        }
+        let (vars, rest): (Vec<&TyBasic>, Vec<&TyBasic>) = param
+            .iter_union()
+            .iter()
+            .partition(|p| TyTypeVar::from_basic(p).is_some());
+        if vars.is_empty() {
+            for p in rest {
+                self.bind_basic(p, arg, ctx);
+            }
+            return;
+        }
+        // For `T | None` only the part of the argument which is not `None` binds `T`.
+        for a in arg.iter_union() {
+            let matching: Vec<&TyBasic> = rest
+                .iter()
+                .copied()
+                .filter(|p| ctx.intersects_basic(p, a))
+                .collect();
+            if matching.is_empty() || a == &TyBasic::Any {
+                for v in &vars {
+                    self.bind_basic(v, &Ty::basic(a.dupe()), ctx);
+                }
+            } else {
+                for p in matching {
+                    self.bind_basic(p, &Ty::basic(a.dupe()), ctx);
+                }
+            }
+        }
+    }
+
+    fn bind_basic(&mut self, param: &TyBasic, arg: &Ty, ctx: &TypingOracleCtx) {
+        if let Some(var) = TyTypeVar::from_basic(param) {
+            let ty = match self.bindings.get(&var.name) {
+                Some(prev) => Ty::union2(prev.dupe(), arg.dupe()),
+                None => arg.dupe(),
+            };
+            self.bindings.insert(var.name.dupe(), ty);
+            return;
+        }
+        for a in arg.iter_union() {
+            match (param, a) {
+                (TyBasic::List(p), TyBasic::List(a)) => self.bind(p, a, ctx),
+                (TyBasic::Dict(pk, pv), TyBasic::Dict(ak, av)) => {
+                    self.bind(pk, ak, ctx);
+                    self.bind(pv, av, ctx);
+                }
+                (TyBasic::Tuple(TyTuple::Elems(ps)), TyBasic::Tuple(TyTuple::Elems(xs)))
+                    if ps.len() == xs.len() =>
+                {
+                    for (p, x) in iter::zip(&**ps, &**xs) {
+                        self.bind(p, x, ctx);
+                    }
+                }
+                (TyBasic::Tuple(TyTuple::Of(p)), TyBasic::Tuple(a)) => {
+                    self.bind(p, &a.item_ty(), ctx)
+                }
+                (TyBasic::Tuple(TyTuple::Elems(ps)), TyBasic::Tuple(TyTuple::Of(a))) => {
+                    for p in &**ps {
+                        self.bind(p, a, ctx);
+                    }
+                }
+                (TyBasic::Iter(p), a) => {
+                    if let Ok(item) = ctx.iter_item_basic(a) {
+                        self.bind(p, &item, ctx);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Replace the type variables in `ty` with their bound types, or `Any` if unbound.
+    pub(crate) fn substitute(&self, ty: &Ty) -> Ty {
+        if !contains_type_var(ty) {
+            return ty.dupe();
+        }
+        Ty::unions(
+            ty.iter_union()
+                .iter()
+                .map(|basic| self.substitute_basic(basic))
+                .collect(),
+        )
+    }
+
+    fn substitute_basic(&self, ty: &TyBasic) -> Ty {
+        if let Some(var) = TyTypeVar::from_basic(ty) {
+            return self
+                .bindings
+                .get(&var.name)
+                .map_or_else(Ty::any, |ty| ty.dupe());
+        }
+        match ty {
+            TyBasic::List(x) => Ty::list(self.substitute(x)),
+            TyBasic::Dict(k, v) => Ty::dict(self.substitute(k), self.substitute(v)),
+            TyBasic::Iter(x) => Ty::iter(self.substitute(x)),
+            TyBasic::Tuple(TyTuple::Elems(xs)) => {
+                Ty::tuple(xs.iter().map(|x| self.substitute(x)).collect())
+            }
+            TyBasic::Tuple(TyTuple::Of(x)) => Ty::tuple_of(self.substitute(x)),
+            _ => Ty::basic(ty.dupe()),
+        }
+    }
+}
+
+/// Replace the type variables in `ty` with `Any`, for types outside of a generic function,
+/// where the variables cannot be bound.
+pub(crate) fn erase_type_vars(ty: &Ty) -> Ty {
+    TypeVarBindings::default().substitute(ty)
+}
+
+/// Does `ty` mention any type variables (outside of function types).
+fn contains_type_var(ty: &Ty) -> bool {
+    ty.iter_union().iter().any(|basic| match basic {
+        TyBasic::List(x) | TyBasic::Iter(x) => contains_type_var(x),
+        TyBasic::Dict(k, v) => contains_type_var(k) || contains_type_var(v),
+        TyBasic::Tuple(TyTuple::Elems(xs)) => xs.iter().any(contains_type_var),
+        TyBasic::Tuple(TyTuple::Of(x)) => contains_type_var(x),
+        basic => TyTypeVar::from_basic(basic).is_some(),
+    })
+}
@@ -23,6 +23,7 @@ use std::fmt::Display;
 use dupe::Dupe;
 use starlark_map::unordered_map::UnorderedMap;
 use starlark_syntax::slice_vec_ext::VecExt;
+use starlark_syntax::syntax::ast::DefP;
 use starlark_syntax::syntax::ast::StmtP;
 use starlark_syntax::syntax::ast::Visibility;
 use starlark_syntax::syntax::module::AstModuleFields;
@@ -33,12 +34,17 @@ use crate::codemap::FileSpanRef;
 use crate::codemap::Span;
 use crate::codemap::Spanned;
 use crate::environment::names::MutableNames;
+use crate::environment::slots::ModuleSlotId;
 use crate::environment::Globals;
+use crate::eval::compiler::scope::payload::CstAssignIdentExt;
+use crate::eval::compiler::scope::payload::CstPayload;
 use crate::eval::compiler::scope::payload::CstStmt;
 use crate::eval::compiler::scope::scope_resolver_globals::ScopeResolverGlobals;
 use crate::eval::compiler::scope::BindingId;
 use crate::eval::compiler::scope::BindingSource;
+use crate::eval::compiler::scope::ModuleScopeData;
 use crate::eval::compiler::scope::ModuleScopes;
+use crate::eval::compiler::scope::Slot;
 use crate::syntax::AstModule;
 use crate::syntax::Dialect;
 use crate::typing::bindings::Bindings;
@@ -48,6 +54,7 @@ use crate::typing::error::InternalError;
 use crate::typing::error::TypingError;
 use crate::typing::fill_types_for_lint::fill_types_for_lint_typechecker;
 use crate::typing::fill_types_for_lint::ModuleVarTypes;
+use crate::typing::function::TyFunction;
 use crate::typing::interface::Interface;
 use crate::typing::mode::TypecheckMode;
 use crate::typing::oracle::ctx::TypingOracleCtx;
@@ -55,12 +62,22 @@ use crate::typing::ty::Approximation;
 use crate::typing::ty::Ty;
 use crate::values::FrozenHeap;
 
+/// Solve the bindings of a function. Returns the errors, the types of the bindings,
+/// the type of the values the function returns, and the approximations made.
 // Things which are None in the map have type void - they are never constructed
 pub(crate) fn solve_bindings(
     bindings: Bindings,
     oracle: TypingOracleCtx,
     module_var_types: &ModuleVarTypes,
-) -> Result<(Vec<TypingError>, HashMap<BindingId, Ty>, Vec<Approximation>), InternalError> {
+) -> Result<
+    (
+        Vec<TypingError>,
+        HashMap<BindingId, Ty>,
+        Ty,
+        Vec<Approximation>,
+    ),
+    InternalError,
+> {
     let mut types = bindings
         .expressions
         .keys()
@@ -107,7 +124,16 @@ pub(crate) fn solve_bindings(
     for x in &bindings.check {
         ctx.expression_type(x)?;
     }
-    for (span, e, require) in &bindings.check_type {
+    let mut returns = Vec::new();
+    if bindings.falls_through {
+        returns.push(Ty::none());
+    }
+    for (is_return, (span, e, require)) in bindings
+        .check_type
+        .iter()
+        .map(|x| (false, x))
+        .chain(bindings.returns.iter().map(|x| (true, x)))
+    {
         let ty = match e {
             None => Ty::none(),
             Some(x) => ctx.expression_type(x)?,
@@ -119,10 +145,14 @@ pub(crate) fn solve_bindings(
             },
             require,
         );
+        if is_return {
+            returns.push(ty);
+        }
     }
     Ok((
         ctx.errors.into_inner(),
         ctx.types.into_hash_map(),
+        Ty::unions(returns),
         ctx.approximoations.into_inner(),
     ))
 }
@@ -154,6 +184,14 @@ impl Display for TypeMap {
 }
 
 impl TypeMap {
+    /// The inferred types of the bindings, with their names and where they are bound.
+    pub fn bindings(&self) -> impl Iterator<Item = (&str, Span, &Ty)> {
+        self.bindings
+            .entries_sorted()
+            .into_iter()
+            .map(|(_binding_id, (name, span, ty))| (name.as_str(), *span, ty))
+    }
+
     #[cfg(test)]
     pub(crate) fn find_bindings_by_name<'a>(&'a self, name: &str) -> Vec<&'a Ty> {
         self.bindings
@@ -168,6 +206,28 @@ impl TypeMap {
     }
 }
 
+/// The module slot of a top-level function without a return type annotation.
+/// The type of the values it returns is used as its result type, so the code
+/// which calls it, including in other modules, is checked against it.
+fn unannotated_def_slot(
+    def: &DefP<CstPayload>,
+    scope_data: &ModuleScopeData,
+    codemap: &CodeMap,
+) -> Result<Option<ModuleSlotId>, InternalError> {
+    if def.return_type.is_some() {
+        return Ok(None);
+    }
+    let binding_id = def.name.resolved_binding_id(codemap)?;
+    match scope_data.get_binding(binding_id).resolved_slot(codemap)? {
+        Slot::Module(slot) => Ok(Some(slot)),
+        Slot::Local(_) => Err(InternalError::msg(
+            "local slot for top-level def",
+            def.name.span,
+            codemap,
+        )),
+    }
+}
+
 /// Typecheck a module.
 pub trait AstModuleTypecheck {
     /// Typecheck a module.
@@ -212,7 +272,7 @@ impl AstModuleTypecheck for AstModule {
         let oracle = TypingOracleCtx { codemap: &codemap };
 
         let mut approximations = Vec::new();
-        let (fill_types_errors, module_var_types) = match fill_types_for_lint_typechecker(
+        let (fill_types_errors, mut module_var_types) = match fill_types_for_lint_typechecker(
             &mut cst,
             oracle,
             &scope_data,
@@ -236,7 +296,21 @@ impl AstModuleTypecheck for AstModule {
         let mut all_solve_errors = Vec::new();
 
         for top in cst.iter_mut() {
-            if let StmtP::Def(_) = &mut top.node {
+            if let StmtP::Def(def) = &top.node {
+                let def_slot = match unannotated_def_slot(def, &scope_data, &codemap) {
+                    Ok(def_slot) => def_slot,
+                    Err(e) => {
+                        return (
+                            vec![e.into_error()],
+                            TypeMap {
+                                codemap,
+                                bindings: UnorderedMap::new(),
+                            },
+                            Interface::default(),
+                            Vec::new(),
+                        );
+                    }
+                };
                 let bindings = match BindingsCollect::collect_one(
                     top,
                     TypecheckMode::Lint,
@@ -256,7 +330,7 @@ impl AstModuleTypecheck for AstModule {
                         );
                     }
                 };
-                let (solve_errors, types, solve_approximations) =
+                let (solve_errors, types, result, solve_approximations) =
                     match solve_bindings(bindings.bindings, oracle, &module_var_types) {
                         Ok(x) => x,
                         Err(e) => {
@@ -275,6 +349,18 @@ impl AstModuleTypecheck for AstModule {
                 all_solve_errors.extend(solve_errors);
                 approximations.extend(solve_approximations);
 
+                if let Some(def_slot) = def_slot {
+                    // The variable has a different type if it is also assigned elsewhere.
+                    if let Some(ty) = module_var_types.types.get_mut(&def_slot) {
+                        if let Some(f) = ty.as_function() {
+                            *ty = Ty::ty_function(TyFunction {
+                                result,
+                                ..f.clone()
+                            });
+                        }
+                    }
+                }
+
                 for (id, ty) in &types {
                     let binding = scope_data.get_binding(*id);
                     let name = binding.name.as_str().to_owned();
@@ -299,6 +385,7 @@ impl AstModuleTypecheck for AstModule {
             .collect();
 
         let mut res = HashMap::new();
+        let mut as_types = HashMap::new();
         for (name, module_slot_id, vis) in names.all_names_slots_and_visibilities() {
             if vis == Visibility::Public {
                 let ty = module_var_types
@@ -307,9 +394,12 @@ impl AstModuleTypecheck for AstModule {
                     .cloned()
                     .unwrap_or_else(Ty::any);
                 res.insert(name.as_str().to_owned(), ty);
+                if let Some(ty) = module_var_types.as_types.get(&module_slot_id) {
+                    as_types.insert(name.as_str().to_owned(), ty.clone());
+                }
             }
         }
-        let interface = Interface::new(res);
+        let interface = Interface::new_with_types(res, as_types);
 
         (errors, typemap, interface, approximations)
     }
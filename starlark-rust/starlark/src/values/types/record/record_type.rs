@@ -45,6 +45,7 @@ use crate::eval::Evaluator;
 use crate::eval::ParametersSpec;
 use crate::starlark_complex_values;
 use crate::typing::starlark_value::TyStarlarkValue;
+use crate::typing::type_var::erase_type_vars;
 use crate::typing::user::TyUser;
 use crate::typing::user::TyUserFields;
 use crate::typing::user::TyUserParams;
@@ -296,10 +297,12 @@ where
 
     fn export_as(&self, variable_name: &str, _eval: &mut Evaluator<'v, '_>) -> crate::Result<()> {
         V::get_or_init_ty(&self.ty_record_data, || {
+            // Record types are not parameterized, so fields declared with a type variable
+            // are checked like `typing.Any`.
             let fields: SortedMap<String, Ty> = self
                 .fields
                 .iter()
-                .map(|(name, field)| (name.clone(), field.ty()))
+                .map(|(name, field)| (name.clone(), erase_type_vars(&field.ty())))
                 .collect();
 
             let ty_record = Ty::custom(TyUser::new(
@@ -21,6 +21,7 @@ use crate::values::typing::callable::TypingCallable;
 use crate::values::typing::iter::TypingIterable;
 use crate::values::typing::never::TypingNever;
 use crate::values::typing::type_compiled::globals::register_eval_type;
+use crate::values::typing::type_var::register_type_var;
 
 pub(crate) fn register_typing(globals: &mut GlobalsBuilder) {
     register_eval_type(globals);
@@ -29,5 +30,6 @@ pub(crate) fn register_typing(globals: &mut GlobalsBuilder) {
         globals.set("Never", TypingNever);
         globals.set("Callable", TypingCallable);
         globals.set("Iterable", TypingIterable);
+        register_type_var(globals);
     });
 }
@@ -25,6 +25,7 @@ pub mod macro_refs;
 pub(crate) mod never;
 pub(crate) mod ty;
 pub(crate) mod type_compiled;
+pub(crate) mod type_var;
 
 pub use crate::values::types::type_instance_id::TypeInstanceId;
 pub use crate::values::typing::callable::FrozenStarlarkCallable;
@@ -0,0 +1,83 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! `typing.TypeVar`.
+
+use std::fmt;
+use std::fmt::Display;
+
+use allocative::Allocative;
+use starlark_derive::starlark_module;
+use starlark_derive::starlark_value;
+use starlark_derive::NoSerialize;
+
+use crate as starlark;
+use crate::any::ProvidesStaticType;
+use crate::environment::GlobalsBuilder;
+use crate::starlark_simple_value;
+use crate::typing::type_var::TyTypeVar;
+use crate::typing::Ty;
+use crate::values::StarlarkValue;
+
+/// Type variable, created with `typing.TypeVar("T")`.
+#[derive(Debug, ProvidesStaticType, NoSerialize, Allocative)]
+pub(crate) struct TypingTypeVar {
+    name: String,
+}
+
+impl Display for TypingTypeVar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "typing.TypeVar(\"{}\")", self.name)
+    }
+}
+
+starlark_simple_value!(TypingTypeVar);
+
+#[starlark_value(type = "typing.TypeVar")]
+impl<'v> StarlarkValue<'v> for TypingTypeVar {
+    fn eval_type(&self) -> Option<Ty> {
+        Some(Ty::custom(TyTypeVar::new(&self.name)))
+    }
+}
+
+#[starlark_module]
+pub(crate) fn register_type_var(globals: &mut GlobalsBuilder) {
+    /// Declare a type variable, to be used in the type annotations of functions and records.
+    ///
+    /// Any value matches a type variable at runtime. The typechecker infers the type
+    /// of the variable at each call from the arguments, so for
+    /// `def first(xs: list[T]) -> T`, `first([1, 2])` is known to be an `int`.
+    /// Record types are not parameterized, so in record fields a type variable
+    /// is checked like `typing.Any`.
+    ///
+    /// ```
+    /// # starlark::assert::pass(r#"
+    /// T = typing.TypeVar("T")
+    ///
+    /// def first(xs: list[T]) -> T:
+    ///     return xs[0]
+    ///
+    /// assert_eq(first(["a"]), "a")
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn TypeVar(#[starlark(require = pos)] name: &str) -> anyhow::Result<TypingTypeVar> {
+        Ok(TypingTypeVar {
+            name: name.to_owned(),
+        })
+    }
+}
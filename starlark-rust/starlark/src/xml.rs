@@ -0,0 +1,60 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Helpers for the XML reports, of coverage and of test results.
+
+/// Escape text for use in XML content or a quoted attribute.
+///
+/// Control characters other than tab, newline and carriage return are dropped, since XML 1.0
+/// doesn't allow them at all, not even as character references.
+pub fn escape_xml(x: &str) -> String {
+    let mut res = String::with_capacity(x.len());
+    for c in x.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&apos;"),
+            '\t' | '\n' | '\r' => res.push(c),
+            c if (c as u32) < 0x20 => {}
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::xml::escape_xml;
+
+    #[test]
+    fn test_escape_xml() {
+        assert_eq!("plain", escape_xml("plain"));
+        assert_eq!(
+            "&lt;a href=&quot;x&quot; title=&apos;y&apos;&gt;&amp;amp;&lt;/a&gt;",
+            escape_xml(r#"<a href="x" title='y'>&amp;</a>"#)
+        );
+    }
+
+    #[test]
+    fn test_escape_xml_control_chars() {
+        assert_eq!("a\tb\nc\rd", escape_xml("a\tb\nc\rd"));
+        assert_eq!("abcd", escape_xml("a\x00b\x1bc\x1fd"));
+        assert_eq!("\u{7f}é", escape_xml("\u{7f}é"));
+    }
+}
@@ -61,6 +61,7 @@ use starlark_lsp::server::LspUrl;
 use starlark_lsp::server::StringLiteralResult;
 
 use self::label::Label;
+use crate::eval::find_workspace_files;
 use crate::eval::ContextMode;
 use crate::eval::EvalResult;
 
@@ -788,6 +789,27 @@ impl LspContext for BazelContext {
         DocModule::default()
     }
 
+    fn get_globals(&self, _uri: &LspUrl) -> Globals {
+        self.globals.clone()
+    }
+
+    fn get_workspace_files(
+        &self,
+        workspace_roots: &[PathBuf],
+    ) -> anyhow::Result<Option<Vec<LspUrl>>> {
+        Ok(Some(find_workspace_files(workspace_roots, |path| {
+            let is_build_file = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| Self::BUILD_FILE_NAMES.contains(&name));
+            let is_loadable = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| Self::LOADABLE_EXTENSIONS.contains(&ext));
+            is_build_file || is_loadable
+        })))
+    }
+
     fn get_url_for_global_symbol(
         &self,
         _current_file: &LspUrl,
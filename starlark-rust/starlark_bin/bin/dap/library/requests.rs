@@ -35,6 +35,8 @@ pub(crate) trait DebugServer {
     fn scopes(&self, x: ScopesArguments) -> anyhow::Result<ScopesResponseBody>;
     fn variables(&self, x: VariablesArguments) -> anyhow::Result<VariablesResponseBody>;
     fn continue_(&self, x: ContinueArguments) -> anyhow::Result<ContinueResponseBody>;
+    fn step_back(&self, x: StepBackArguments) -> anyhow::Result<()>;
+    fn reverse_continue(&self, x: ReverseContinueArguments) -> anyhow::Result<()>;
     fn evaluate(&self, x: EvaluateArguments) -> anyhow::Result<EvaluateResponseBody>;
     fn disconnect(&self, _x: DisconnectArguments) -> anyhow::Result<()> {
         Ok(())
@@ -84,6 +86,8 @@ pub(crate) fn dispatch(server: &impl DebugServer, r: &Request) -> Response {
         "scopes" => ret_some(r, server.scopes(arg(r))),
         "variables" => ret_some(r, server.variables(arg(r))),
         "continue" => ret_some(r, server.continue_(arg(r))),
+        "stepBack" => ret_none(r, server.step_back(arg(r))),
+        "reverseContinue" => ret_none(r, server.reverse_continue(arg(r))),
         "evaluate" => ret_some(r, server.evaluate(arg(r))),
         "disconnect" => ret_none(r, server.disconnect(arg(r))),
         _ => ret_none(r, Err(anyhow::anyhow!("Unknown command: {}", r.command))),
@@ -32,6 +32,7 @@ use starlark::debug::resolve_breakpoints;
 use starlark::debug::DapAdapter;
 use starlark::debug::DapAdapterClient;
 use starlark::debug::DapAdapterEvalHook;
+use starlark::debug::RecordingConfig;
 use starlark::environment::Globals;
 use starlark::environment::Module;
 use starlark::eval::Evaluator;
@@ -142,6 +143,14 @@ impl DebugServer for Backend {
     }
 
     fn launch(&self, _: LaunchRequestArguments, args: Map<String, Value>) -> anyhow::Result<()> {
+        // Optionally `record: {file, function}` to record statements for stepping backward
+        if let Some(record) = args.get("record") {
+            let field = |name: &str| record.get(name).and_then(Value::as_str).map(str::to_owned);
+            self.adapter.set_recording(Some(RecordingConfig {
+                file: field("file"),
+                function: field("function"),
+            }))?;
+        }
         // Expecting program of type string
         match args.get("program") {
             Some(Value::String(path)) => {
@@ -221,6 +230,14 @@ impl DebugServer for Backend {
         self.adapter.continue_()?;
         Ok(ContinueResponseBody::default())
     }
+
+    fn step_back(&self, _: StepBackArguments) -> anyhow::Result<()> {
+        self.adapter.step_back()
+    }
+
+    fn reverse_continue(&self, _: ReverseContinueArguments) -> anyhow::Result<()> {
+        self.adapter.reverse_continue()
+    }
 }
 
 pub(crate) fn server(dialect: Dialect, globals: Globals) {
@@ -17,15 +17,21 @@
 
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::ffi::OsStr;
 use std::fs;
 use std::io;
 use std::iter;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::Mutex;
 
 use itertools::Either;
 use lsp_types::Url;
 use starlark::analysis::AstModuleLint;
+use starlark::assert::assert_module;
+use starlark::assert::run_test;
+use starlark::assert::test_names;
+use starlark::assert::TestResult;
 use starlark::docs::get_registered_starlark_docs;
 use starlark::docs::render_docs_as_code;
 use starlark::docs::Doc;
@@ -36,13 +42,17 @@ use starlark::environment::Globals;
 use starlark::environment::Module;
 use starlark::errors::EvalMessage;
 use starlark::eval::Evaluator;
+use starlark::eval::ProfileData;
+use starlark::eval::ProfileMode;
 use starlark::syntax::AstModule;
 use starlark::syntax::Dialect;
+use starlark::values::Value;
 use starlark_lsp::error::eval_message_to_lsp_diagnostic;
 use starlark_lsp::server::LspContext;
 use starlark_lsp::server::LspEvalResult;
 use starlark_lsp::server::LspUrl;
 use starlark_lsp::server::StringLiteralResult;
+use walkdir::WalkDir;
 
 #[derive(Debug)]
 pub(crate) enum ContextMode {
@@ -70,6 +80,10 @@ pub(crate) struct Context {
     pub(crate) globals: Globals,
     pub(crate) builtin_docs: HashMap<LspUrl, String>,
     pub(crate) builtin_symbols: HashMap<String, LspUrl>,
+    /// The coverage of each file run, if it is collected.
+    pub(crate) coverage: Option<Mutex<Vec<ProfileData>>>,
+    /// The extension of the files in the workspace, searched for references by the LSP.
+    pub(crate) extension: String,
 }
 
 /// The outcome of evaluating (checking, parsing or running) given starlark code.
@@ -143,6 +157,8 @@ impl Context {
             globals,
             builtin_docs,
             builtin_symbols,
+            coverage: None,
+            extension: "bzl".to_owned(),
         })
     }
 
@@ -252,7 +268,7 @@ impl Context {
         eval.enable_terminal_breakpoint_console();
         Self::err(
             file,
-            eval.eval_module(ast, &self.globals)
+            self.eval_module(&mut eval, ast)
                 .map(|v| {
                     if self.print_non_none && !v.is_none() {
                         println!("{}", v);
@@ -266,6 +282,52 @@ impl Context {
         )
     }
 
+    fn eval_module<'v>(
+        &self,
+        eval: &mut Evaluator<'v, '_>,
+        ast: AstModule,
+    ) -> starlark::Result<Value<'v>> {
+        self.with_coverage(eval, |eval| eval.eval_module(ast, &self.globals))?
+    }
+
+    /// Run `f`, recording the coverage of what it evaluates if coverage is collected.
+    fn with_coverage<'v, 'a, R>(
+        &self,
+        eval: &mut Evaluator<'v, 'a>,
+        f: impl FnOnce(&mut Evaluator<'v, 'a>) -> R,
+    ) -> anyhow::Result<R> {
+        if self.coverage.is_some() {
+            eval.enable_profile(&ProfileMode::Coverage)?;
+        }
+        // Lines run before an error are covered too.
+        let res = f(eval);
+        if let Some(coverage) = &self.coverage {
+            coverage.lock().unwrap().push(eval.gen_profile()?);
+        }
+        Ok(res)
+    }
+
+    /// Evaluate a file with the `asserts` of [`assert_module`] available, then run each of
+    /// its `test_*` functions in a module of its own.
+    pub(crate) fn test(&self, file: &Path) -> starlark::Result<Vec<TestResult>> {
+        let ast = AstModule::parse_file(file, &self.dialect)?;
+        let module = Self::new_module(&self.prelude);
+        module.import_public_symbols(&assert_module());
+        {
+            let mut eval = Evaluator::new(&module);
+            self.eval_module(&mut eval, ast)?;
+        }
+        let module = module.freeze()?;
+
+        let mut results = Vec::new();
+        for name in test_names(&module) {
+            let env = Module::new();
+            let mut eval = Evaluator::new(&env);
+            results.push(self.with_coverage(&mut eval, |eval| run_test(&module, &name, eval))?);
+        }
+        Ok(results)
+    }
+
     fn check(&self, module: &AstModule) -> impl Iterator<Item = EvalMessage> {
         let globals = if self.prelude.is_empty() {
             None
@@ -379,4 +441,71 @@ impl LspContext for Context {
     fn get_environment(&self, _uri: &LspUrl) -> DocModule {
         DocModule::default()
     }
+
+    fn get_globals(&self, _uri: &LspUrl) -> Globals {
+        self.globals.clone()
+    }
+
+    fn get_workspace_files(
+        &self,
+        workspace_roots: &[PathBuf],
+    ) -> anyhow::Result<Option<Vec<LspUrl>>> {
+        let extension = OsStr::new(&self.extension);
+        Ok(Some(find_workspace_files(workspace_roots, |path| {
+            path.extension() == Some(extension)
+        })))
+    }
+}
+
+/// The files under `workspace_roots` that `is_starlark` accepts. Hidden directories, such as
+/// `.git`, are skipped, and symlinks are not followed.
+pub(crate) fn find_workspace_files(
+    workspace_roots: &[PathBuf],
+    is_starlark: impl Fn(&Path) -> bool,
+) -> Vec<LspUrl> {
+    workspace_roots
+        .iter()
+        .flat_map(|root| {
+            WalkDir::new(root)
+                .into_iter()
+                .filter_entry(|e| {
+                    e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.')
+                })
+                .filter_map(|e| e.ok())
+        })
+        .filter(|e| e.file_type().is_file() && is_starlark(e.path()))
+        .map(|e| LspUrl::File(e.into_path()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use starlark_lsp::server::LspUrl;
+
+    use super::find_workspace_files;
+
+    #[test]
+    fn test_find_workspace_files() -> anyhow::Result<()> {
+        let root = std::env::temp_dir().join(format!("starlark_workspace_{}", std::process::id()));
+        for dir in ["pkg/sub", ".git", "other"] {
+            fs::create_dir_all(root.join(dir))?;
+        }
+        for file in ["a.bzl", "pkg/sub/b.bzl", "pkg/c.txt", ".git/d.bzl", "other/e.star"] {
+            fs::write(root.join(file), "")?;
+        }
+
+        let mut files = find_workspace_files(&[root.clone()], |path| {
+            path.extension().is_some_and(|ext| ext == "bzl")
+        });
+        files.sort_by_key(|uri| uri.path().to_owned());
+        let expected = vec![
+            LspUrl::File(root.join("a.bzl")),
+            LspUrl::File(root.join("pkg/sub/b.bzl")),
+        ];
+        fs::remove_dir_all(&root)?;
+        assert_eq!(expected, files);
+        Ok(())
+    }
 }
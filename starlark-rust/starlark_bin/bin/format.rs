@@ -0,0 +1,54 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! `--format` and `--check-format`.
+
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::Context as _;
+use starlark::syntax::AstModule;
+use starlark::syntax::Dialect;
+
+/// Format the files in place, or with `check` only print those which are not formatted.
+pub(crate) fn format_files(
+    files: impl Iterator<Item = PathBuf>,
+    dialect: &Dialect,
+    check: bool,
+) -> anyhow::Result<()> {
+    let mut unformatted = 0;
+    for file in files {
+        let content =
+            fs::read_to_string(&file).with_context(|| format!("reading `{}`", file.display()))?;
+        let ast = AstModule::parse(&file.to_string_lossy(), content.clone(), dialect)
+            .map_err(starlark::Error::into_anyhow)?;
+        let formatted = ast.format();
+        if formatted == content {
+            continue;
+        }
+        if check {
+            println!("{}", file.display());
+            unformatted += 1;
+        } else {
+            fs::write(&file, formatted).with_context(|| format!("writing `{}`", file.display()))?;
+        }
+    }
+    if unformatted > 0 {
+        return Err(anyhow::anyhow!("{} files are not formatted", unformatted));
+    }
+    Ok(())
+}
@@ -22,8 +22,11 @@
 use std::ffi::OsStr;
 use std::fmt;
 use std::fmt::Display;
+use std::fs;
+use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::Mutex;
 
 use anyhow::Context as _;
 use clap::Parser;
@@ -42,6 +45,7 @@ use starlark::docs::RenderMarkdown;
 use starlark::environment::Globals;
 use starlark::errors::EvalMessage;
 use starlark::errors::EvalSeverity;
+use starlark::eval::ProfileData;
 use starlark::read_line::ReadLine;
 use starlark::syntax::Dialect;
 use walkdir::WalkDir;
@@ -51,6 +55,8 @@ use crate::eval::ContextMode;
 mod bazel;
 mod dap;
 mod eval;
+mod format;
+mod testing;
 
 #[derive(Debug, Parser)]
 #[command(name = "starlark", about = "Evaluate Starlark code", version)]
@@ -63,6 +69,8 @@ struct Args {
             "check",
             "json",
             "docs",
+            "format",
+            "check_format",
             "evaluate",
             "files",
         ],
@@ -78,6 +86,8 @@ struct Args {
             "check",
             "json",
             "docs",
+            "format",
+            "check_format",
             "extension",
             "prelude",
             "evaluate",
@@ -107,6 +117,20 @@ struct Args {
     )]
     docs: Option<ArgsDoc>,
 
+    #[arg(
+        long = "format",
+        help = "Format the files in place.",
+        conflicts_with_all = &["lsp", "dap", "check", "docs", "evaluate", "check_format"],
+    )]
+    format: bool,
+
+    #[arg(
+        long = "check-format",
+        help = "Print the files which are not formatted, and fail if there are any.",
+        conflicts_with_all = &["lsp", "dap", "check", "docs", "evaluate"],
+    )]
+    check_format: bool,
+
     #[arg(
         long = "extension",
         help = "File extension when searching directories."
@@ -134,6 +158,29 @@ struct Args {
     )]
     dialect: ArgsDialect,
 
+    #[arg(
+        long = "test",
+        help = "Run the `test_*` functions of the files, which can use `asserts`.",
+        conflicts_with_all = &["lsp", "dap", "check", "evaluate"],
+    )]
+    test: bool,
+
+    #[arg(
+        long = "junit",
+        value_name = "PATH",
+        help = "Also write the test results to this file as JUnit XML.",
+        requires = "test"
+    )]
+    junit: Option<PathBuf>,
+
+    #[arg(
+        long = "coverage",
+        value_name = "PATH",
+        help = "Write the line coverage of the files run, in the LCOV format or as Cobertura XML if PATH ends with `.xml`.",
+        conflicts_with_all = &["lsp", "dap", "check"],
+    )]
+    coverage: Option<PathBuf>,
+
     #[arg(
         id = "files",
         value_name = "FILE",
@@ -255,6 +302,20 @@ fn interactive(ctx: &Context) -> anyhow::Result<()> {
     }
 }
 
+fn write_coverage(ctx: &Context, path: &Path) -> anyhow::Result<()> {
+    let profiles = ctx.coverage.as_ref().unwrap().lock().unwrap();
+    if profiles.is_empty() {
+        return Err(anyhow::anyhow!("No files were run to collect coverage of"));
+    }
+    let coverage = ProfileData::merge(profiles.iter())?;
+    let report = if path.extension() == Some(OsStr::new("xml")) {
+        coverage.gen_cobertura()?
+    } else {
+        coverage.gen()?
+    };
+    fs::write(path, report).with_context(|| format!("writing coverage to `{}`", path.display()))
+}
+
 /// starlark-rust does not support panic.
 /// Terminate on panic even if compiled without `-Cpanic=abort`.
 fn terminate_on_panic() {
@@ -278,6 +339,12 @@ fn main() -> anyhow::Result<()> {
 
     if args.dap {
         dap::server(dialect, globals);
+    } else if args.format || args.check_format {
+        let ext = args
+            .extension
+            .as_ref()
+            .map_or("bzl", |x| x.strip_prefix('.').unwrap_or(x.as_str()));
+        format::format_files(expand_dirs(ext, args.files), &dialect, args.check_format)?;
     } else {
         let is_interactive = args.evaluate.is_empty() && args.files.is_empty();
 
@@ -314,9 +381,13 @@ fn main() -> anyhow::Result<()> {
             dialect,
             globals,
         )?;
+        if args.coverage.is_some() {
+            ctx.coverage = Some(Mutex::new(Vec::new()));
+        }
 
         if args.lsp {
             ctx.mode = ContextMode::Check;
+            ctx.extension = ext.to_owned();
             starlark_lsp::server::stdio_server(ctx)?;
         } else if let Some(docs) = args.docs {
             let mut builtin = get_registered_starlark_docs();
@@ -339,6 +410,15 @@ fn main() -> anyhow::Result<()> {
                 }
                 ArgsDoc::Code => println!("{}", render_docs_as_code(&builtin)),
             };
+        } else if args.test {
+            let failed =
+                testing::run_tests(&ctx, expand_dirs(ext, args.files), args.junit.as_deref())?;
+            if let Some(path) = &args.coverage {
+                write_coverage(&ctx, path)?;
+            }
+            if failed > 0 {
+                return Err(anyhow::anyhow!("{} tests failed", failed));
+            }
         } else if is_interactive {
             interactive(&ctx)?;
         } else {
@@ -353,6 +433,10 @@ fn main() -> anyhow::Result<()> {
                 drain(ctx.file(&file).messages, args.json, &mut stats)?;
             }
 
+            if let Some(path) = &args.coverage {
+                write_coverage(&ctx, path)?;
+            }
+
             if !args.json {
                 println!("{}", stats);
                 if stats.error > 0 {
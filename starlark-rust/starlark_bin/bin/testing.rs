@@ -0,0 +1,51 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Running the `test_*` functions of files for `starlark --test`.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context as _;
+use starlark::assert::TestReport;
+
+use crate::eval::Context;
+
+/// Run the tests of `files`, printing the result of each, and optionally writing them as
+/// JUnit XML to `junit`. Returns the number of failed tests.
+pub(crate) fn run_tests(
+    ctx: &Context,
+    files: impl Iterator<Item = PathBuf>,
+    junit: Option<&Path>,
+) -> anyhow::Result<usize> {
+    let mut report = TestReport::new();
+    let mut stdout = io::stdout();
+    for file in files {
+        let filename = file.to_string_lossy().into_owned();
+        report.add_file(filename, ctx.test(&file), &mut stdout)?;
+    }
+
+    if let Some(junit) = junit {
+        fs::write(junit, report.junit())
+            .with_context(|| format!("writing test results to `{}`", junit.display()))?;
+    }
+
+    println!("{}", report.summary());
+    Ok(report.failed())
+}
@@ -0,0 +1,347 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Parses of the files which are not open and typecheck results of all files, reused between
+//! requests until the file, or a file it loads, changes.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use dupe::Dupe;
+use dupe::OptionDupedExt;
+use starlark::environment::Globals;
+use starlark::syntax::AstModule;
+use starlark::syntax::Dialect;
+use starlark::typing::AstModuleTypecheck;
+use starlark::typing::Interface;
+use starlark::typing::TypeMap;
+use starlark_syntax::syntax::module::AstModuleFields;
+
+use crate::definition::LspModule;
+use crate::server::Backend;
+use crate::server::FileStamp;
+use crate::server::LspContext;
+use crate::server::LspUrl;
+
+/// The result of typechecking a file against the interfaces of the files it loads.
+pub(crate) struct Typechecked {
+    /// The inferred types of the bindings in the file.
+    pub(crate) types: TypeMap,
+    /// The types of the symbols the file exports.
+    pub(crate) interface: Interface,
+    /// The files it loads, which invalidate this result when they change.
+    loads: Vec<LspUrl>,
+}
+
+/// Number of files which are not open whose parse is kept. Workspace-wide requests (references,
+/// rename, workspace symbols) parse every file of the workspace, which would otherwise all stay
+/// in memory for the rest of the session.
+const MAX_PARSED_FILES: usize = 1000;
+
+/// A file which is not open, as last read from disk.
+struct Parsed {
+    /// The version that was read.
+    stamp: Option<FileStamp>,
+    module: Arc<LspModule>,
+    /// The value of `ModuleCache::clock` when the parse was last used.
+    last_used: AtomicU64,
+}
+
+pub(crate) struct ModuleCache {
+    parsed: HashMap<LspUrl, Parsed>,
+    typechecked: HashMap<LspUrl, Arc<Typechecked>>,
+    /// Incremented whenever a parse is used, to find the least recently used one.
+    clock: AtomicU64,
+    max_parsed: usize,
+}
+
+impl Default for ModuleCache {
+    fn default() -> Self {
+        Self::new(MAX_PARSED_FILES)
+    }
+}
+
+impl ModuleCache {
+    fn new(max_parsed: usize) -> Self {
+        Self {
+            parsed: HashMap::new(),
+            typechecked: HashMap::new(),
+            clock: AtomicU64::new(0),
+            max_parsed,
+        }
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// The parse of `uri` and the version it was read at, if it's cached.
+    fn get_parsed(&self, uri: &LspUrl) -> Option<(Option<FileStamp>, &Arc<LspModule>)> {
+        let parsed = self.parsed.get(uri)?;
+        parsed.last_used.store(self.tick(), Ordering::Relaxed);
+        Some((parsed.stamp, &parsed.module))
+    }
+
+    /// Cache the parse of `uri`, evicting the least recently used parses (and the typecheck
+    /// results which depend on them) beyond `max_parsed`.
+    fn insert_parsed(&mut self, uri: LspUrl, stamp: Option<FileStamp>, module: Arc<LspModule>) {
+        let last_used = AtomicU64::new(self.tick());
+        self.parsed.insert(
+            uri,
+            Parsed {
+                stamp,
+                module,
+                last_used,
+            },
+        );
+        while self.parsed.len() > self.max_parsed {
+            let Some(lru) = self
+                .parsed
+                .iter()
+                .min_by_key(|(_, parsed)| parsed.last_used.load(Ordering::Relaxed))
+                .map(|(uri, _)| uri.clone())
+            else {
+                break;
+            };
+            self.invalidate(&lru);
+        }
+    }
+
+    /// Forget everything derived from the contents of `uri`, including the typecheck results
+    /// of the files which load it, directly or not.
+    pub(crate) fn invalidate(&mut self, uri: &LspUrl) {
+        self.parsed.remove(uri);
+        let mut changed = vec![uri.clone()];
+        while let Some(uri) = changed.pop() {
+            self.typechecked.remove(&uri);
+            changed.extend(
+                self.typechecked
+                    .iter()
+                    .filter(|(_, x)| x.loads.contains(&uri))
+                    .map(|(dependent, _)| dependent.clone()),
+            );
+        }
+    }
+}
+
+/// Typecheck `ast` with the interfaces of the modules it loads, keyed by the path in the `load()`.
+pub(crate) fn typecheck(
+    ast: &AstModule,
+    globals: &Globals,
+    loads: &HashMap<String, Interface>,
+) -> Option<(TypeMap, Interface)> {
+    // Typechecking consumes the module, so check a copy of it.
+    let codemap = ast.codemap();
+    let copy = AstModule::parse(
+        codemap.filename(),
+        codemap.source().to_owned(),
+        &Dialect::Extended,
+    )
+    .ok()?;
+    let (_errors, types, interface, _approximations) = copy.typecheck(globals, loads);
+    Some((types, interface))
+}
+
+impl<T: LspContext> Backend<T> {
+    /// The last parse of a file which is not open, reading it from disk if it wasn't before.
+    pub(crate) fn get_cached_or_load_from_disk(
+        &self,
+        uri: &LspUrl,
+    ) -> anyhow::Result<Option<Arc<LspModule>>> {
+        let stamp = self.context.get_file_stamp(uri);
+        let cached_stamp = match self.cache.read().unwrap().get_parsed(uri) {
+            Some((cached_stamp, module)) if cached_stamp == stamp => {
+                return Ok(Some(module.dupe()));
+            }
+            Some((cached_stamp, _)) => Some(cached_stamp),
+            None => None,
+        };
+        if cached_stamp.is_some() {
+            // Changed outside the editor, so the typecheck results of its dependents are stale too.
+            self.cache.write().unwrap().invalidate(uri);
+        }
+        let module = self
+            .context
+            .parse_file(uri)?
+            .and_then(|eval_result| eval_result.ast.map(|ast| Arc::new(LspModule::new(ast))));
+        if let Some(module) = &module {
+            self.cache
+                .write()
+                .unwrap()
+                .insert_parsed(uri.clone(), stamp, module.clone());
+        }
+        Ok(module)
+    }
+
+    /// Typecheck a file, reusing the last result if neither it nor the files it loads
+    /// changed since.
+    pub(crate) fn typecheck(
+        &self,
+        uri: &LspUrl,
+        workspace_root: Option<&Path>,
+    ) -> Option<Arc<Typechecked>> {
+        self.invalidate_changed_on_disk(uri);
+        self.typecheck_impl(uri, workspace_root, &mut HashSet::new())
+    }
+
+    /// Forget the files loaded by `uri`, directly or not, which changed on disk since they
+    /// were read, so the cached typecheck results which depend on them are not reused.
+    fn invalidate_changed_on_disk(&self, uri: &LspUrl) {
+        let changed = {
+            let cache = self.cache.read().unwrap();
+            let mut changed = Vec::new();
+            let mut visited = HashSet::new();
+            let mut todo = vec![uri.clone()];
+            while let Some(uri) = todo.pop() {
+                if !visited.insert(uri.clone()) {
+                    continue;
+                }
+                if let Some(parsed) = cache.parsed.get(&uri) {
+                    if parsed.stamp != self.context.get_file_stamp(&uri) {
+                        changed.push(uri.clone());
+                    }
+                }
+                if let Some(res) = cache.typechecked.get(&uri) {
+                    todo.extend(res.loads.iter().cloned());
+                }
+            }
+            changed
+        };
+        if !changed.is_empty() {
+            let mut cache = self.cache.write().unwrap();
+            for uri in &changed {
+                cache.invalidate(uri);
+            }
+        }
+    }
+
+    fn typecheck_impl(
+        &self,
+        uri: &LspUrl,
+        workspace_root: Option<&Path>,
+        visiting: &mut HashSet<LspUrl>,
+    ) -> Option<Arc<Typechecked>> {
+        if let Some(res) = self.cache.read().unwrap().typechecked.get(uri).duped() {
+            return Some(res);
+        }
+        // A cycle of loads is an error reported elsewhere, typecheck without the interface.
+        if !visiting.insert(uri.clone()) {
+            return None;
+        }
+        let module = self.get_ast_or_load_from_disk(uri).ok()??;
+
+        let mut loads = Vec::new();
+        let mut interfaces = HashMap::new();
+        for load in module.loads() {
+            let Ok(loaded_uri) = self.resolve_load_path(&load.module, uri, workspace_root) else {
+                continue;
+            };
+            if let Some(loaded) = self.typecheck_impl(&loaded_uri, workspace_root, visiting) {
+                interfaces.insert(load.module.node.clone(), loaded.interface.clone());
+            }
+            loads.push(loaded_uri);
+        }
+
+        let (types, interface) =
+            typecheck(&module.ast, &self.context.get_globals(uri), &interfaces)?;
+        let res = Arc::new(Typechecked {
+            types,
+            interface,
+            loads,
+        });
+        self.cache
+            .write()
+            .unwrap()
+            .typechecked
+            .insert(uri.clone(), res.clone());
+        Some(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use starlark::environment::Globals;
+    use starlark::syntax::AstModule;
+    use starlark::syntax::Dialect;
+
+    use super::typecheck;
+    use super::ModuleCache;
+    use super::Typechecked;
+    use crate::definition::LspModule;
+    use crate::server::LspUrl;
+
+    fn url(name: &str) -> LspUrl {
+        LspUrl::File(PathBuf::from(format!("/{}.bzl", name)))
+    }
+
+    fn module(name: &str) -> Arc<LspModule> {
+        let ast = AstModule::parse(
+            &format!("{}.bzl", name),
+            "X = 1".to_owned(),
+            &Dialect::Extended,
+        )
+        .unwrap();
+        Arc::new(LspModule::new(ast))
+    }
+
+    fn typechecked(loads: &[&str]) -> Arc<Typechecked> {
+        let (types, interface) =
+            typecheck(&module("x").ast, &Globals::standard(), &HashMap::new()).unwrap();
+        Arc::new(Typechecked {
+            types,
+            interface,
+            loads: loads.iter().map(|name| url(name)).collect(),
+        })
+    }
+
+    #[test]
+    fn test_evict_least_recently_used() {
+        let mut cache = ModuleCache::new(2);
+        cache.insert_parsed(url("a"), None, module("a"));
+        cache.insert_parsed(url("b"), None, module("b"));
+        // Using `a` makes `b` the least recently used parse.
+        assert!(cache.get_parsed(&url("a")).is_some());
+
+        cache.insert_parsed(url("c"), None, module("c"));
+        assert!(cache.get_parsed(&url("a")).is_some());
+        assert!(cache.get_parsed(&url("b")).is_none());
+        assert!(cache.get_parsed(&url("c")).is_some());
+    }
+
+    #[test]
+    fn test_evict_drops_dependent_typecheck_results() {
+        let mut cache = ModuleCache::new(1);
+        cache.insert_parsed(url("lib"), None, module("lib"));
+        cache.typechecked.insert(url("lib"), typechecked(&[]));
+        cache.typechecked.insert(url("main"), typechecked(&["lib"]));
+        cache.typechecked.insert(url("other"), typechecked(&[]));
+
+        cache.insert_parsed(url("new"), None, module("new"));
+        assert!(cache.get_parsed(&url("lib")).is_none());
+        assert!(!cache.typechecked.contains_key(&url("lib")));
+        assert!(!cache.typechecked.contains_key(&url("main")));
+        assert!(cache.typechecked.contains_key(&url("other")));
+    }
+}
@@ -0,0 +1,165 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Quick fixes for the lints which can be fixed automatically.
+
+use std::collections::HashMap;
+
+use lsp_types::CodeAction;
+use lsp_types::CodeActionKind;
+use lsp_types::CodeActionOrCommand;
+use lsp_types::CodeActionParams;
+use lsp_types::NumberOrString;
+use lsp_types::Range;
+use lsp_types::TextEdit;
+use lsp_types::Url;
+use lsp_types::WorkspaceEdit;
+use starlark::analysis::AstModuleLint;
+use starlark::syntax::AstModule;
+use starlark_syntax::syntax::module::AstModuleFields;
+
+use crate::server::Backend;
+use crate::server::LspContext;
+
+/// The quick fixes for the lints in `ast` which overlap `range`. The fixes are attached to the
+/// matching `diagnostics` the client sent, so it can offer them next to those.
+pub(crate) fn get_code_actions(
+    ast: &AstModule,
+    uri: &Url,
+    range: Range,
+    diagnostics: &[lsp_types::Diagnostic],
+) -> Vec<CodeActionOrCommand> {
+    let codemap = ast.codemap();
+    ast.lint(None)
+        .into_iter()
+        .filter_map(|lint| {
+            let fix = lint.fix?;
+            let lint_range = Range::from(codemap.resolve_span(lint.location.span));
+            if lint_range.end < range.start || range.end < lint_range.start {
+                return None;
+            }
+            let edits = fix
+                .edits
+                .iter()
+                .map(|edit| {
+                    TextEdit::new(
+                        codemap.resolve_span(edit.span).into(),
+                        edit.replacement.clone(),
+                    )
+                })
+                .collect();
+            let fixed: Vec<_> = diagnostics
+                .iter()
+                .filter(|x| {
+                    x.range == lint_range
+                        && x.code == Some(NumberOrString::String(lint.short_name.clone()))
+                })
+                .cloned()
+                .collect();
+            Some(CodeActionOrCommand::CodeAction(CodeAction {
+                title: fix.description,
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: (!fixed.is_empty()).then_some(fixed),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(uri.clone(), edits)])),
+                    ..WorkspaceEdit::default()
+                }),
+                ..CodeAction::default()
+            }))
+        })
+        .collect()
+}
+
+impl<T: LspContext> Backend<T> {
+    /// The quick fixes for the lints in the requested part of a document.
+    pub(crate) fn code_actions(
+        &self,
+        params: CodeActionParams,
+    ) -> anyhow::Result<Vec<CodeActionOrCommand>> {
+        let uri = params.text_document.uri;
+        Ok(match self.get_ast(&uri.clone().try_into()?) {
+            Some(module) => {
+                get_code_actions(&module.ast, &uri, params.range, &params.context.diagnostics)
+            }
+            None => Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::CodeActionOrCommand;
+    use lsp_types::Position;
+    use lsp_types::Range;
+    use lsp_types::Url;
+    use starlark::syntax::AstModule;
+    use starlark::syntax::Dialect;
+
+    use super::get_code_actions;
+
+    #[test]
+    fn fixes_lints_in_range() -> anyhow::Result<()> {
+        let ast = AstModule::parse(
+            "foo.bzl",
+            "load(\"a.bzl\", \"x\", \"y\")\ny\ndef f():\n    print(1)\n    return\n".to_owned(),
+            &Dialect::Extended,
+        )
+        .map_err(starlark::Error::into_anyhow)?;
+        let uri = Url::parse("file:///foo.bzl")?;
+        let actions = |range| -> Vec<(String, Vec<(Range, String)>)> {
+            get_code_actions(&ast, &uri, range, &[])
+                .into_iter()
+                .map(|action| {
+                    let CodeActionOrCommand::CodeAction(action) = action else {
+                        unreachable!()
+                    };
+                    let mut changes = action.edit.unwrap().changes.unwrap();
+                    let edits = changes
+                        .remove(&uri)
+                        .unwrap()
+                        .into_iter()
+                        .map(|x| (x.range, x.new_text))
+                        .collect();
+                    (action.title, edits)
+                })
+                .collect()
+        };
+
+        assert_eq!(
+            vec![(
+                "Remove unused load".to_owned(),
+                vec![(
+                    Range::new(Position::new(0, 12), Position::new(0, 17)),
+                    String::new()
+                )]
+            )],
+            actions(Range::new(Position::new(0, 15), Position::new(0, 15)))
+        );
+        assert_eq!(
+            vec![(
+                "Remove redundant `return`".to_owned(),
+                vec![(
+                    Range::new(Position::new(4, 0), Position::new(5, 0)),
+                    String::new()
+                )]
+            )],
+            actions(Range::new(Position::new(4, 6), Position::new(4, 6)))
+        );
+        assert!(actions(Range::new(Position::new(3, 0), Position::new(3, 4))).is_empty());
+        Ok(())
+    }
+}
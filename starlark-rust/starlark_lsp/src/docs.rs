@@ -23,6 +23,7 @@ use starlark::docs::DocStringKind;
 use starlark::typing::Ty;
 use starlark_syntax::syntax::ast::AstAssignTargetP;
 use starlark_syntax::syntax::ast::AstLiteral;
+use starlark_syntax::syntax::ast::AstNoPayload;
 use starlark_syntax::syntax::ast::AstPayload;
 use starlark_syntax::syntax::ast::AstStmtP;
 use starlark_syntax::syntax::ast::DefP;
@@ -65,6 +66,57 @@ pub(crate) fn get_doc_item_for_def<P: AstPayload>(def: &DefP<P>) -> Option<DocFu
     }
 }
 
+/// Given the AST node for a `def` statement, return a `DocFunction` describing how to call it,
+/// with the docs from its docstring if it has one.
+///
+/// `ty` is the type the typechecker inferred for the function, which has the types of the
+/// annotated parameters and result. Without it, they are `Any`.
+pub(crate) fn get_signature_for_def(def: &DefP<AstNoPayload>, ty: Option<&Ty>) -> DocFunction {
+    let function = ty.and_then(Ty::as_function);
+    // The typechecker names `*args` and `**kwargs` after their kind, not after the parameter.
+    let param_ty = |name: &str| {
+        function
+            .and_then(|f| f.params().iter().find(|p| p.name() == name))
+            .map_or_else(Ty::any, |p| p.ty().clone())
+    };
+    let params = def
+        .params
+        .iter()
+        .map(|param| match &param.node {
+            ParameterP::Normal(p, _) => DocParam::Arg {
+                name: p.ident.to_owned(),
+                docs: None,
+                typ: param_ty(&p.ident),
+                default_value: None,
+            },
+            ParameterP::WithDefaultValue(p, _, default) => DocParam::Arg {
+                name: p.ident.to_owned(),
+                docs: None,
+                typ: param_ty(&p.ident),
+                default_value: Some(default.to_string()),
+            },
+            ParameterP::NoArgs => DocParam::NoArgs,
+            ParameterP::Args(p, _) => DocParam::Args {
+                name: format!("*{}", p.ident),
+                docs: None,
+                typ: param_ty("*args"),
+            },
+            ParameterP::KwArgs(p, _) => DocParam::Kwargs {
+                name: format!("**{}", p.ident),
+                docs: None,
+                typ: param_ty("**kwargs"),
+            },
+        })
+        .collect();
+    DocFunction::from_docstring(
+        DocStringKind::Starlark,
+        params,
+        function.map_or_else(Ty::any, |f| f.result().clone()),
+        peek_docstring(&def.body),
+        None,
+    )
+}
+
 pub(crate) fn get_doc_item_for_assign<P: AstPayload>(
     previous_node: &AstStmtP<P>,
     _assign: &AstAssignTargetP<P>,
@@ -0,0 +1,314 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Symbols for the outline of a document, and for searching the whole workspace.
+
+use lsp_types::DocumentSymbol;
+use lsp_types::InitializeParams;
+use lsp_types::Location;
+use lsp_types::SymbolInformation;
+use lsp_types::SymbolKind;
+use lsp_types::Url;
+use starlark::codemap::CodeMap;
+use starlark::codemap::Span;
+use starlark_syntax::syntax::ast::ArgumentP;
+use starlark_syntax::syntax::ast::AstExpr;
+use starlark_syntax::syntax::ast::AstLiteral;
+use starlark_syntax::syntax::ast::AstStmt;
+use starlark_syntax::syntax::ast::Expr;
+use starlark_syntax::syntax::ast::Stmt;
+
+use crate::exported::SymbolKind as ExportedSymbolKind;
+use crate::server::Backend;
+use crate::server::LspContext;
+
+#[allow(deprecated)] // The `deprecated` field is deprecated, but has to be set.
+fn symbol(
+    codemap: &CodeMap,
+    name: String,
+    detail: Option<String>,
+    kind: SymbolKind,
+    range: Span,
+    selection_range: Span,
+    children: Vec<DocumentSymbol>,
+) -> DocumentSymbol {
+    DocumentSymbol {
+        name,
+        detail,
+        kind,
+        tags: None,
+        deprecated: None,
+        range: codemap.resolve_span(range).into(),
+        selection_range: codemap.resolve_span(selection_range).into(),
+        children: (!children.is_empty()).then_some(children),
+    }
+}
+
+/// The kind of symbol a variable assigned `value` is.
+fn assigned_kind(value: &AstExpr) -> SymbolKind {
+    match &value.node {
+        Expr::Lambda(_) => SymbolKind::FUNCTION,
+        Expr::Call(function, _) => match &function.node {
+            Expr::Identifier(name) if name.ident == "struct" => SymbolKind::STRUCT,
+            _ => SymbolKind::VARIABLE,
+        },
+        _ => SymbolKind::VARIABLE,
+    }
+}
+
+/// A call with a literal `name` argument, which is how targets are declared in build files.
+fn named_call(codemap: &CodeMap, expr: &AstExpr) -> Option<DocumentSymbol> {
+    let Expr::Call(function, args) = &expr.node else {
+        return None;
+    };
+    args.iter().find_map(|arg| match &arg.node {
+        ArgumentP::Named(arg_name, value) if arg_name.node == "name" => match &value.node {
+            Expr::Literal(AstLiteral::String(name)) => Some(symbol(
+                codemap,
+                name.node.clone(),
+                Some(function.to_string()),
+                SymbolKind::OBJECT,
+                expr.span,
+                name.span,
+                Vec::new(),
+            )),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+fn statement_symbols(
+    codemap: &CodeMap,
+    stmt: &AstStmt,
+    top_level: bool,
+    res: &mut Vec<DocumentSymbol>,
+) {
+    match &stmt.node {
+        Stmt::Statements(xs) => {
+            for x in xs {
+                statement_symbols(codemap, x, top_level, res);
+            }
+        }
+        Stmt::If(_, body) => statement_symbols(codemap, body, top_level, res),
+        Stmt::For(for_) => statement_symbols(codemap, &for_.body, top_level, res),
+        Stmt::IfElse(_, then_else) => {
+            statement_symbols(codemap, &then_else.0, top_level, res);
+            statement_symbols(codemap, &then_else.1, top_level, res);
+        }
+        Stmt::Def(def) => {
+            let mut children: Vec<DocumentSymbol> = def
+                .params
+                .iter()
+                .filter_map(|param| param.split().0)
+                .map(|name| {
+                    symbol(
+                        codemap,
+                        name.ident.clone(),
+                        None,
+                        SymbolKind::VARIABLE,
+                        name.span,
+                        name.span,
+                        Vec::new(),
+                    )
+                })
+                .collect();
+            statement_symbols(codemap, &def.body, false, &mut children);
+            res.push(symbol(
+                codemap,
+                def.name.ident.clone(),
+                None,
+                SymbolKind::FUNCTION,
+                stmt.span,
+                def.name.span,
+                children,
+            ));
+        }
+        // Local variables would clutter the outline, only show the top level ones.
+        Stmt::Assign(assign) if top_level => {
+            assign.lhs.visit_lvalue(|name| {
+                let kind = assigned_kind(&assign.rhs);
+                let children = match &assign.rhs.node {
+                    Expr::Call(_, args) if kind == SymbolKind::STRUCT => args
+                        .iter()
+                        .filter_map(|arg| match &arg.node {
+                            ArgumentP::Named(arg_name, value) => Some(symbol(
+                                codemap,
+                                arg_name.node.clone(),
+                                None,
+                                assigned_kind(value),
+                                arg.span,
+                                arg_name.span,
+                                Vec::new(),
+                            )),
+                            _ => None,
+                        })
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                res.push(symbol(
+                    codemap,
+                    name.ident.clone(),
+                    None,
+                    kind,
+                    stmt.span,
+                    name.span,
+                    children,
+                ));
+            });
+        }
+        Stmt::Load(load) => {
+            let children = load
+                .args
+                .iter()
+                .map(|arg| {
+                    symbol(
+                        codemap,
+                        arg.local.ident.clone(),
+                        Some(arg.their.node.clone()),
+                        SymbolKind::CONSTANT,
+                        arg.span(),
+                        arg.local.span,
+                        Vec::new(),
+                    )
+                })
+                .collect();
+            res.push(symbol(
+                codemap,
+                load.module.node.clone(),
+                None,
+                SymbolKind::MODULE,
+                stmt.span,
+                load.module.span,
+                children,
+            ));
+        }
+        Stmt::Expression(expr) if top_level => res.extend(named_call(codemap, expr)),
+        _ => {}
+    }
+}
+
+/// The symbols in a module, nested like the definitions are.
+pub(crate) fn get_document_symbols(codemap: &CodeMap, ast: &AstStmt) -> Vec<DocumentSymbol> {
+    let mut res = Vec::new();
+    statement_symbols(codemap, ast, true, &mut res);
+    res
+}
+
+impl<T: LspContext> Backend<T> {
+    /// Find the symbols exported by any file in the workspace that match `query`.
+    pub(crate) fn get_workspace_symbols(
+        &self,
+        query: &str,
+        initialize_params: &InitializeParams,
+    ) -> anyhow::Result<Vec<SymbolInformation>> {
+        let query = query.to_lowercase();
+        let mut res = Vec::new();
+        for uri in self.workspace_files(initialize_params)?.files {
+            let Ok(Some(module)) = self.get_ast_or_load_from_disk(&uri) else {
+                continue;
+            };
+            for exported in module.get_exported_symbols() {
+                if !exported.name.to_lowercase().contains(&query) {
+                    continue;
+                }
+                #[allow(deprecated)] // The `deprecated` field is deprecated, but has to be set.
+                res.push(SymbolInformation {
+                    name: exported.name,
+                    kind: match exported.kind {
+                        ExportedSymbolKind::Function { .. } => SymbolKind::FUNCTION,
+                        ExportedSymbolKind::Any => SymbolKind::VARIABLE,
+                    },
+                    tags: None,
+                    deprecated: None,
+                    location: Location {
+                        uri: Url::try_from(&uri)?,
+                        range: exported.span.resolve_span().into(),
+                    },
+                    container_name: None,
+                });
+            }
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use starlark::syntax::AstModule;
+    use starlark::syntax::Dialect;
+    use starlark_syntax::syntax::module::AstModuleFields;
+
+    use super::get_document_symbols;
+
+    #[test]
+    fn document_symbols() -> starlark::Result<()> {
+        let ast = AstModule::parse(
+            "foo.bzl",
+            r#"
+load(":bar.bzl", "bar", baz = "qux")
+X = 1
+def f(a, *, b = 2):
+    y = a
+    def g():
+        pass
+    return g
+rules = struct(f = f, g = lambda: 1)
+if X:
+    Y = 2
+cxx_library(name = "lib", srcs = ["a.c"])
+"#
+            .to_owned(),
+            &Dialect::Extended,
+        )?;
+        let symbols = get_document_symbols(ast.codemap(), ast.statement());
+
+        fn flatten(symbols: &[lsp_types::DocumentSymbol], depth: usize, res: &mut Vec<String>) {
+            for x in symbols {
+                res.push(format!(
+                    "{}{} {:?} {}",
+                    "  ".repeat(depth),
+                    x.name,
+                    x.kind,
+                    x.detail.as_deref().unwrap_or_default()
+                ));
+                flatten(x.children.as_deref().unwrap_or_default(), depth + 1, res);
+            }
+        }
+        let mut res = Vec::new();
+        flatten(&symbols, 0, &mut res);
+        assert_eq!(
+            vec![
+                ":bar.bzl Module",
+                "  bar Constant bar",
+                "  baz Constant qux",
+                "X Variable",
+                "f Function",
+                "  a Variable",
+                "  b Variable",
+                "  g Function",
+                "rules Struct",
+                "  f Variable",
+                "  g Function",
+                "Y Variable",
+                "lib Object cxx_library",
+            ],
+            res.iter().map(|x| x.trim_end()).collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Inlay hints with the types the typechecker infers for unannotated locals.
+
+use std::collections::HashSet;
+
+use lsp_types::InitializeParams;
+use lsp_types::InlayHint;
+use lsp_types::InlayHintKind;
+use lsp_types::InlayHintLabel;
+use lsp_types::InlayHintParams;
+use lsp_types::Range;
+use starlark::codemap::Span;
+use starlark::syntax::AstModule;
+use starlark::typing::Ty;
+use starlark::typing::TypeMap;
+use starlark_syntax::syntax::ast::AstStmt;
+use starlark_syntax::syntax::ast::Stmt;
+use starlark_syntax::syntax::module::AstModuleFields;
+
+use crate::server::Backend;
+use crate::server::LspContext;
+
+/// The identifiers assigned to without a type annotation inside the functions in `stmt`.
+/// Only locals get types from the typechecker, so module level assignments are skipped.
+fn unannotated_locals(stmt: &AstStmt, in_def: bool, res: &mut HashSet<Span>) {
+    match &stmt.node {
+        Stmt::Def(def) => unannotated_locals(&def.body, true, res),
+        Stmt::Assign(assign) if in_def && assign.ty.is_none() => assign.lhs.visit_lvalue(|name| {
+            res.insert(name.span);
+        }),
+        Stmt::For(for_) if in_def => {
+            for_.var.visit_lvalue(|name| {
+                res.insert(name.span);
+            });
+            unannotated_locals(&for_.body, in_def, res);
+        }
+        _ => stmt.visit_stmt(|x| unannotated_locals(x, in_def, res)),
+    }
+}
+
+/// Hints with the inferred types of the unannotated locals in `ast` within `range`.
+pub(crate) fn get_inlay_hints(ast: &AstModule, types: &TypeMap, range: Range) -> Vec<InlayHint> {
+    let mut unannotated = HashSet::new();
+    unannotated_locals(ast.statement(), false, &mut unannotated);
+    let codemap = ast.codemap();
+    types
+        .bindings()
+        .filter(|(_name, span, ty)| unannotated.contains(span) && **ty != Ty::any())
+        .filter_map(|(_name, span, ty)| {
+            let position = Range::from(codemap.resolve_span(span)).end;
+            (range.start <= position && position <= range.end).then(|| InlayHint {
+                position,
+                label: InlayHintLabel::String(format!(": {}", ty)),
+                kind: Some(InlayHintKind::TYPE),
+                text_edits: None,
+                tooltip: None,
+                padding_left: None,
+                padding_right: None,
+                data: None,
+            })
+        })
+        .collect()
+}
+
+impl<T: LspContext> Backend<T> {
+    /// The inlay hints for the requested part of a document.
+    pub(crate) fn inlay_hints(
+        &self,
+        params: InlayHintParams,
+        initialize_params: &InitializeParams,
+    ) -> anyhow::Result<Vec<InlayHint>> {
+        let uri = params.text_document.uri.try_into()?;
+        let Some(module) = self.get_ast(&uri) else {
+            return Ok(Vec::new());
+        };
+        let workspace_root =
+            Self::get_workspace_root(initialize_params.workspace_folders.as_ref(), &uri);
+        Ok(match self.typecheck(&uri, workspace_root.as_deref()) {
+            Some(typechecked) => get_inlay_hints(&module.ast, &typechecked.types, params.range),
+            None => Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use lsp_types::InlayHintLabel;
+    use lsp_types::Position;
+    use lsp_types::Range;
+    use starlark::environment::Globals;
+    use starlark::syntax::AstModule;
+    use starlark::syntax::Dialect;
+
+    use super::get_inlay_hints;
+    use crate::cache::typecheck;
+
+    #[test]
+    fn hints_unannotated_locals() -> starlark::Result<()> {
+        let ast = AstModule::parse(
+            "foo.bzl",
+            r#"
+X = 1
+def f(a: int):
+    x = a + 1
+    y: str = "y"
+    for z in ["z"]:
+        pass
+    return x
+"#
+            .to_owned(),
+            &Dialect::Extended,
+        )?;
+        let (types, _interface) = typecheck(&ast, &Globals::standard(), &HashMap::new()).unwrap();
+        let everything = Range::new(Position::new(0, 0), Position::new(100, 0));
+        let hints: Vec<_> = get_inlay_hints(&ast, &types, everything)
+            .into_iter()
+            .map(|hint| {
+                let InlayHintLabel::String(label) = hint.label else {
+                    unreachable!()
+                };
+                (hint.position.line, hint.position.character, label)
+            })
+            .collect();
+        assert_eq!(
+            vec![(3, 5, ": int".to_owned()), (5, 9, ": str".to_owned())],
+            hints
+        );
+
+        let first_line = Range::new(Position::new(3, 0), Position::new(4, 0));
+        assert_eq!(1, get_inlay_hints(&ast, &types, first_line).len());
+        Ok(())
+    }
+}
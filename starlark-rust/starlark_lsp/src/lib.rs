@@ -22,14 +22,21 @@
 #[allow(clippy::needless_lifetimes)]
 #[allow(clippy::type_complexity)]
 mod bind;
+mod cache;
+mod code_actions;
 pub mod completion;
 mod definition;
 pub(crate) mod docs;
+mod document_symbols;
 pub mod error;
 mod exported;
+mod inlay_hints;
 pub(crate) mod inspect;
 pub(crate) mod loaded;
+mod references;
 pub mod server;
+mod signature_help;
 mod symbols;
+mod sync;
 #[cfg(all(test, not(windows)))]
 mod test;
@@ -0,0 +1,560 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Finding the references to a symbol, including in the modules that load it, and renaming it.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::iter;
+use std::path::Path;
+use std::path::PathBuf;
+
+use lsp_types::InitializeParams;
+use lsp_types::Location;
+use lsp_types::PrepareRenameResponse;
+use lsp_types::ReferenceParams;
+use lsp_types::RenameParams;
+use lsp_types::TextDocumentPositionParams;
+use lsp_types::TextEdit;
+use lsp_types::Url;
+use lsp_types::WorkspaceEdit;
+use starlark::codemap::Pos;
+use starlark::codemap::ResolvedSpan;
+use starlark::codemap::Span;
+use starlark_syntax::lexer::lex_exactly_one_identifier;
+use starlark_syntax::syntax::ast::Load;
+use starlark_syntax::syntax::ast::StmtP;
+use starlark_syntax::syntax::module::AstModuleFields;
+use starlark_syntax::syntax::top_level_stmts::top_level_stmts;
+
+use crate::bind::scope;
+use crate::bind::Bind;
+use crate::bind::Scope;
+use crate::definition::LspModule;
+use crate::server::Backend;
+use crate::server::LspContext;
+use crate::server::LspUrl;
+
+/// Errors when renaming a symbol.
+#[derive(thiserror::Error, Debug)]
+enum RenameError {
+    #[error("There is no symbol to rename at this position")]
+    NoSymbol,
+    #[error("`{}` is not a valid identifier", .0)]
+    InvalidName(String),
+    #[error("`{}` is not defined in a Starlark file, so cannot be renamed", .0)]
+    NotDefined(String),
+    #[error("Renaming `{}` to `{}` would make it private, but it is loaded by other files", .0, .1)]
+    WouldBecomePrivate(String, String),
+    #[error("`{}` may be loaded by files which are not open, and the workspace cannot be searched for them", .0)]
+    UnknownWorkspace(String),
+}
+
+/// The files to look for references to exported symbols in.
+pub(crate) struct WorkspaceFiles {
+    pub(crate) files: Vec<LspUrl>,
+    /// Whether `files` are all the files in the workspace, rather than only the open ones.
+    pub(crate) complete: bool,
+}
+
+/// An access or assignment of a variable, and where that variable is bound.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Variable {
+    pub(crate) name: String,
+    pub(crate) span: Span,
+    /// The first assignment to the variable in the scope that binds it. `None` if the
+    /// module does not bind it at all, e.g. for builtins.
+    pub(crate) binding: Option<Span>,
+}
+
+/// How a reference is spelled, which determines how renaming the symbol changes it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ReferenceKind {
+    /// An identifier, which is replaced by the new name.
+    Identifier,
+    /// The name of the symbol in another module in a `load()`, e.g. `"foo"` in
+    /// `load(":bar.bzl", "foo")`, which is replaced by the new name as a string.
+    LoadedName,
+    /// A `load()` argument without an alias, when only the local name is renamed. It gets an
+    /// alias, e.g. `load(":bar.bzl", "foo")` becomes `load(":bar.bzl", new = "foo")`.
+    LoadedLocalName,
+    /// A use of a loaded symbol under an alias, which is left alone when renaming.
+    Aliased,
+}
+
+/// A place that refers to a symbol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Reference {
+    pub(crate) uri: LspUrl,
+    pub(crate) span: ResolvedSpan,
+    pub(crate) kind: ReferenceKind,
+    /// Whether this is where the symbol is first assigned.
+    pub(crate) declaration: bool,
+}
+
+/// The symbol to find references to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Target {
+    /// A symbol that can only be referred to in one module.
+    Local {
+        uri: LspUrl,
+        name: String,
+        binding: Option<Span>,
+    },
+    /// A top level symbol that other modules may load.
+    Exported { uri: LspUrl, name: String },
+}
+
+impl LspModule {
+    /// Convert a zero based line and column to a position in the module.
+    pub(crate) fn pos_at(&self, line: u32, col: u32) -> Option<Pos> {
+        let line_span = self.ast.codemap().line_span_opt(line as usize)?;
+        Some(std::cmp::min(line_span.begin() + col, line_span.end()))
+    }
+
+    /// All the variable accesses and assignments in the module, with the scopes resolved.
+    pub(crate) fn variables(&self) -> Vec<Variable> {
+        fn binding(scope: &Scope, parents: &[&Scope], name: &str) -> Option<Span> {
+            iter::once(scope)
+                .chain(parents.iter().rev().copied())
+                .find_map(|scope| scope.bound.get(name))
+                .map(|(_, span)| *span)
+        }
+
+        fn walk<'a>(scope: &'a Scope, parents: &mut Vec<&'a Scope>, res: &mut Vec<Variable>) {
+            for bind in &scope.inner {
+                let (name, span) = match bind {
+                    Bind::Set(_, x) => (&x.ident, x.span),
+                    Bind::Get(x) => (&x.ident, x.span),
+                    Bind::GetDotted(x) => (&x.variable.ident, x.variable.span),
+                    Bind::Scope(inner) => {
+                        parents.push(scope);
+                        walk(inner, parents, res);
+                        parents.pop();
+                        continue;
+                    }
+                    Bind::Flow => continue,
+                };
+                res.push(Variable {
+                    name: name.clone(),
+                    span,
+                    binding: binding(scope, parents, name),
+                });
+            }
+        }
+
+        let mut res = Vec::new();
+        walk(&scope(&self.ast), &mut Vec::new(), &mut res);
+        res
+    }
+
+    /// The places in this module that refer to the variable `name` bound at `binding`.
+    pub(crate) fn find_references(&self, name: &str, binding: Option<Span>) -> Vec<Span> {
+        let mut spans: Vec<Span> = self
+            .variables()
+            .into_iter()
+            .filter(|x| x.name == name && x.binding == binding)
+            .map(|x| x.span)
+            .collect();
+        // `x += 1` both reads and assigns `x`.
+        spans.sort_by_key(|span| span.begin());
+        spans.dedup();
+        spans
+    }
+
+    /// Where a top level symbol is first assigned.
+    fn top_level_binding(&self, name: &str) -> Option<Span> {
+        scope(&self.ast).bound.get(name).map(|(_, span)| *span)
+    }
+
+    pub(crate) fn loads(&self) -> impl Iterator<Item = &Load> {
+        top_level_stmts(self.ast.statement())
+            .into_iter()
+            .filter_map(|x| match &x.node {
+                StmtP::Load(load) => Some(load),
+                _ => None,
+            })
+    }
+}
+
+impl<T: LspContext> Backend<T> {
+    /// The files to look for references in: the open ones, and the rest of the workspace
+    /// if the context knows about it.
+    pub(crate) fn workspace_files(
+        &self,
+        initialize_params: &InitializeParams,
+    ) -> anyhow::Result<WorkspaceFiles> {
+        let workspace_roots: Vec<PathBuf> = initialize_params
+            .workspace_folders
+            .iter()
+            .flatten()
+            .filter_map(|folder| folder.uri.to_file_path().ok())
+            .collect();
+        let open_files: Vec<LspUrl> = self
+            .last_valid_parse
+            .read()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect();
+        let workspace_files = self.context.get_workspace_files(&workspace_roots)?;
+        let complete = workspace_files.is_some();
+        let mut seen = HashSet::new();
+        let files = open_files
+            .into_iter()
+            .chain(workspace_files.into_iter().flatten())
+            .filter(|uri| seen.insert(uri.clone()))
+            .collect();
+        Ok(WorkspaceFiles { files, complete })
+    }
+
+    /// The symbol loaded by a `load()` argument, or the local binding if the module it is
+    /// loaded from cannot be found or parsed.
+    fn loaded_target(
+        &self,
+        uri: &LspUrl,
+        load: &Load,
+        index: usize,
+        workspace_root: Option<&Path>,
+    ) -> Target {
+        let arg = &load.args[index];
+        match self.resolve_load_path(&load.module, uri, workspace_root) {
+            Ok(loaded_uri)
+                if matches!(self.get_ast_or_load_from_disk(&loaded_uri), Ok(Some(_))) =>
+            {
+                Target::Exported {
+                    uri: loaded_uri,
+                    name: arg.their.node.clone(),
+                }
+            }
+            _ => Target::Local {
+                uri: uri.clone(),
+                name: arg.local.ident.clone(),
+                binding: Some(arg.local.span),
+            },
+        }
+    }
+
+    /// Find the symbol at a position, and the span of the name there.
+    fn target_at(
+        &self,
+        params: &TextDocumentPositionParams,
+        initialize_params: &InitializeParams,
+    ) -> anyhow::Result<Option<(Target, ResolvedSpan)>> {
+        let uri: LspUrl = params.text_document.uri.clone().try_into()?;
+        let workspace_root =
+            Self::get_workspace_root(initialize_params.workspace_folders.as_ref(), &uri);
+        let workspace_root = workspace_root.as_deref();
+        let Some(module) = self.get_ast(&uri) else {
+            return Ok(None);
+        };
+        let Some(pos) = module.pos_at(params.position.line, params.position.character) else {
+            return Ok(None);
+        };
+        let resolve = |span| module.ast.codemap().resolve_span(span);
+
+        // The name of the symbol in the other module, when it is aliased here.
+        for load in module.loads() {
+            for (i, arg) in load.args.iter().enumerate() {
+                if arg.their.span.contains(pos) && arg.local.span != arg.their.span {
+                    let target = self.loaded_target(&uri, load, i, workspace_root);
+                    return Ok(Some((target, resolve(arg.their.span))));
+                }
+            }
+        }
+
+        let Some(variable) = module
+            .variables()
+            .into_iter()
+            .find(|x| x.span.contains(pos))
+        else {
+            return Ok(None);
+        };
+        let source = resolve(variable.span);
+        if let Some(binding) = variable.binding {
+            for load in module.loads() {
+                if let Some(i) = load.args.iter().position(|x| x.local.span == binding) {
+                    let target = self.loaded_target(&uri, load, i, workspace_root);
+                    return Ok(Some((target, source)));
+                }
+            }
+            if module.top_level_binding(&variable.name) == Some(binding)
+                && module.find_exported_symbol(&variable.name).is_some()
+            {
+                let target = Target::Exported {
+                    uri,
+                    name: variable.name,
+                };
+                return Ok(Some((target, source)));
+            }
+        }
+        let target = Target::Local {
+            uri,
+            name: variable.name,
+            binding: variable.binding,
+        };
+        Ok(Some((target, source)))
+    }
+
+    /// All the references to a symbol. If `require_complete`, fail rather than only finding
+    /// the references in open files when the workspace cannot be searched.
+    fn references_to(
+        &self,
+        target: &Target,
+        initialize_params: &InitializeParams,
+        require_complete: bool,
+    ) -> anyhow::Result<Vec<Reference>> {
+        let mut references = Vec::new();
+        match target {
+            Target::Local { uri, name, binding } => {
+                let Some(module) = self.get_ast_or_load_from_disk(uri)? else {
+                    return Ok(Vec::new());
+                };
+                let unaliased_loads: HashSet<Span> = module
+                    .loads()
+                    .flat_map(|load| &load.args)
+                    .filter(|arg| arg.local.span == arg.their.span)
+                    .map(|arg| arg.local.span)
+                    .collect();
+                for span in module.find_references(name, *binding) {
+                    references.push(Reference {
+                        uri: uri.clone(),
+                        span: module.ast.codemap().resolve_span(span),
+                        kind: if unaliased_loads.contains(&span) {
+                            ReferenceKind::LoadedLocalName
+                        } else {
+                            ReferenceKind::Identifier
+                        },
+                        declaration: Some(span) == *binding,
+                    });
+                }
+            }
+            Target::Exported { uri, name } => {
+                let workspace = self.workspace_files(initialize_params)?;
+                if require_complete && !workspace.complete {
+                    return Err(RenameError::UnknownWorkspace(name.clone()).into());
+                }
+                if let Some(module) = self.get_ast_or_load_from_disk(uri)? {
+                    let binding = module.top_level_binding(name);
+                    for span in module.find_references(name, binding) {
+                        references.push(Reference {
+                            uri: uri.clone(),
+                            span: module.ast.codemap().resolve_span(span),
+                            kind: ReferenceKind::Identifier,
+                            declaration: Some(span) == binding,
+                        });
+                    }
+                }
+                for file in workspace.files {
+                    if &file == uri {
+                        continue;
+                    }
+                    // Files that don't parse, or that we can't read, just don't refer to it.
+                    let Ok(Some(module)) = self.get_ast_or_load_from_disk(&file) else {
+                        continue;
+                    };
+                    let workspace_root = Self::get_workspace_root(
+                        initialize_params.workspace_folders.as_ref(),
+                        &file,
+                    );
+                    for load in module.loads() {
+                        for arg in &load.args {
+                            if arg.their.node != *name
+                                || self
+                                    .resolve_load_path(
+                                        &load.module,
+                                        &file,
+                                        workspace_root.as_deref(),
+                                    )
+                                    .ok()
+                                    .as_ref()
+                                    != Some(uri)
+                            {
+                                continue;
+                            }
+                            references.push(Reference {
+                                uri: file.clone(),
+                                span: module.ast.codemap().resolve_span(arg.their.span),
+                                kind: ReferenceKind::LoadedName,
+                                declaration: false,
+                            });
+                            let aliased = arg.local.span != arg.their.span;
+                            for span in
+                                module.find_references(&arg.local.ident, Some(arg.local.span))
+                            {
+                                if span == arg.their.span {
+                                    continue;
+                                }
+                                references.push(Reference {
+                                    uri: file.clone(),
+                                    span: module.ast.codemap().resolve_span(span),
+                                    kind: if aliased {
+                                        ReferenceKind::Aliased
+                                    } else {
+                                        ReferenceKind::Identifier
+                                    },
+                                    declaration: false,
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(references)
+    }
+
+    pub(crate) fn find_references(
+        &self,
+        params: ReferenceParams,
+        initialize_params: &InitializeParams,
+    ) -> anyhow::Result<Vec<Location>> {
+        let Some((target, _)) =
+            self.target_at(&params.text_document_position, initialize_params)?
+        else {
+            return Ok(Vec::new());
+        };
+        self.references_to(&target, initialize_params, false)?
+            .into_iter()
+            .filter(|x| params.context.include_declaration || !x.declaration)
+            .map(|x| {
+                Ok(Location {
+                    uri: Url::try_from(&x.uri)?,
+                    range: x.span.into(),
+                })
+            })
+            .collect()
+    }
+
+    pub(crate) fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+        initialize_params: &InitializeParams,
+    ) -> anyhow::Result<Option<PrepareRenameResponse>> {
+        match self.target_at(&params, initialize_params)? {
+            Some((Target::Local { name, binding, .. }, _)) if binding.is_none() => {
+                Err(RenameError::NotDefined(name).into())
+            }
+            Some((_, source)) => Ok(Some(PrepareRenameResponse::Range(source.into()))),
+            None => Ok(None),
+        }
+    }
+
+    pub(crate) fn rename(
+        &self,
+        params: RenameParams,
+        initialize_params: &InitializeParams,
+    ) -> anyhow::Result<WorkspaceEdit> {
+        let new_name = params.new_name;
+        if lex_exactly_one_identifier(&new_name).as_deref() != Some(new_name.as_str()) {
+            return Err(RenameError::InvalidName(new_name).into());
+        }
+        let Some((target, _)) =
+            self.target_at(&params.text_document_position, initialize_params)?
+        else {
+            return Err(RenameError::NoSymbol.into());
+        };
+        match &target {
+            Target::Local {
+                name,
+                binding: None,
+                ..
+            } => return Err(RenameError::NotDefined(name.clone()).into()),
+            Target::Exported { name, .. } if new_name.starts_with('_') => {
+                return Err(RenameError::WouldBecomePrivate(name.clone(), new_name).into());
+            }
+            _ => {}
+        }
+        let name = match &target {
+            Target::Local { name, .. } | Target::Exported { name, .. } => name,
+        };
+
+        let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+        for reference in self.references_to(&target, initialize_params, true)? {
+            let new_text = match reference.kind {
+                ReferenceKind::Identifier => new_name.clone(),
+                ReferenceKind::LoadedName => format!("\"{}\"", new_name),
+                ReferenceKind::LoadedLocalName => format!("{} = \"{}\"", new_name, name),
+                ReferenceKind::Aliased => continue,
+            };
+            changes
+                .entry(Url::try_from(&reference.uri)?)
+                .or_default()
+                .push(TextEdit::new(reference.span.into(), new_text));
+        }
+        Ok(WorkspaceEdit {
+            changes: Some(changes),
+            ..WorkspaceEdit::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use starlark::codemap::ResolvedSpan;
+    use starlark_syntax::syntax::module::AstModuleFields;
+    use textwrap::dedent;
+
+    use crate::definition::helpers::FixtureWithRanges;
+
+    fn references(fixture: &FixtureWithRanges, name: &str) -> starlark::Result<Vec<ResolvedSpan>> {
+        let module = fixture.module()?;
+        let codemap = module.ast.codemap();
+        let variable = module
+            .variables()
+            .into_iter()
+            .find(|x| codemap.resolve_span(x.span) == fixture.resolved_span(name))
+            .expect("variable to be present");
+        Ok(module
+            .find_references(&variable.name, variable.binding)
+            .into_iter()
+            .map(|span| codemap.resolve_span(span))
+            .collect())
+    }
+
+    #[test]
+    fn find_references_respects_scopes() -> starlark::Result<()> {
+        let contents = dedent(
+            r#"
+            <x>x</x> = 1
+            def f(<p>x</p>):
+                return <p1>x</p1> + 1
+            def g():
+                <l>x</l> = 2
+                return [x for x in range(<l1>x</l1>)]
+            def h():
+                <x1>x</x1>.y += 1
+                return <x2>x</x2>
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let fixture = FixtureWithRanges::from_fixture("foo.star", &contents)?;
+        let spans = |names: &[&str]| {
+            names
+                .iter()
+                .map(|x| fixture.resolved_span(x))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(spans(&["x", "x1", "x2"]), references(&fixture, "x")?);
+        assert_eq!(spans(&["x", "x1", "x2"]), references(&fixture, "x2")?);
+        assert_eq!(spans(&["p", "p1"]), references(&fixture, "p1")?);
+        assert_eq!(spans(&["l", "l1"]), references(&fixture, "l")?);
+        Ok(())
+    }
+}
@@ -20,10 +20,12 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::fmt::Debug;
+use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::RwLock;
+use std::time::SystemTime;
 
 use derivative::Derivative;
 use derive_more::Display;
@@ -38,13 +40,24 @@ use lsp_server::RequestId;
 use lsp_server::Response;
 use lsp_server::ResponseError;
 use lsp_types::notification::DidChangeTextDocument;
+use lsp_types::notification::DidChangeWatchedFiles;
 use lsp_types::notification::DidCloseTextDocument;
 use lsp_types::notification::DidOpenTextDocument;
 use lsp_types::notification::LogMessage;
 use lsp_types::notification::PublishDiagnostics;
+use lsp_types::request::CodeActionRequest;
 use lsp_types::request::Completion;
+use lsp_types::request::DocumentSymbolRequest;
 use lsp_types::request::GotoDefinition;
 use lsp_types::request::HoverRequest;
+use lsp_types::request::InlayHintRequest;
+use lsp_types::request::PrepareRenameRequest;
+use lsp_types::request::References;
+use lsp_types::request::Rename;
+use lsp_types::request::SignatureHelpRequest;
+use lsp_types::request::WorkspaceSymbolRequest;
+use lsp_types::CodeActionParams;
+use lsp_types::CodeActionProviderCapability;
 use lsp_types::CompletionItem;
 use lsp_types::CompletionItemKind;
 use lsp_types::CompletionOptions;
@@ -53,8 +66,11 @@ use lsp_types::CompletionResponse;
 use lsp_types::DefinitionOptions;
 use lsp_types::Diagnostic;
 use lsp_types::DidChangeTextDocumentParams;
+use lsp_types::DidChangeWatchedFilesParams;
 use lsp_types::DidCloseTextDocumentParams;
 use lsp_types::DidOpenTextDocumentParams;
+use lsp_types::DocumentSymbolParams;
+use lsp_types::DocumentSymbolResponse;
 use lsp_types::Documentation;
 use lsp_types::GotoDefinitionParams;
 use lsp_types::GotoDefinitionResponse;
@@ -63,6 +79,7 @@ use lsp_types::HoverContents;
 use lsp_types::HoverParams;
 use lsp_types::HoverProviderCapability;
 use lsp_types::InitializeParams;
+use lsp_types::InlayHintParams;
 use lsp_types::LanguageString;
 use lsp_types::LocationLink;
 use lsp_types::LogMessageParams;
@@ -74,13 +91,21 @@ use lsp_types::OneOf;
 use lsp_types::Position;
 use lsp_types::PublishDiagnosticsParams;
 use lsp_types::Range;
+use lsp_types::ReferenceParams;
+use lsp_types::RenameOptions;
+use lsp_types::RenameParams;
 use lsp_types::ServerCapabilities;
+use lsp_types::SignatureHelpOptions;
+use lsp_types::SignatureHelpParams;
+use lsp_types::TextDocumentPositionParams;
 use lsp_types::TextDocumentSyncCapability;
 use lsp_types::TextDocumentSyncKind;
 use lsp_types::TextEdit;
 use lsp_types::Url;
 use lsp_types::WorkDoneProgressOptions;
 use lsp_types::WorkspaceFolder;
+use lsp_types::WorkspaceSymbolParams;
+use lsp_types::WorkspaceSymbolResponse;
 use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use serde::Deserializer;
@@ -93,21 +118,25 @@ use starlark::docs::markdown::render_doc_member;
 use starlark::docs::markdown::render_doc_param;
 use starlark::docs::DocMember;
 use starlark::docs::DocModule;
+use starlark::environment::Globals;
 use starlark::syntax::AstModule;
 use starlark_syntax::codemap::ResolvedPos;
 use starlark_syntax::syntax::ast::AstPayload;
 use starlark_syntax::syntax::ast::LoadArgP;
 use starlark_syntax::syntax::module::AstModuleFields;
 
+use crate::cache::ModuleCache;
 use crate::completion::StringCompletionResult;
 use crate::completion::StringCompletionType;
 use crate::definition::Definition;
 use crate::definition::DottedDefinition;
 use crate::definition::IdentifierDefinition;
 use crate::definition::LspModule;
+use crate::document_symbols::get_document_symbols;
 use crate::inspect::AstModuleInspect;
 use crate::inspect::AutocompleteType;
 use crate::symbols::find_symbols_at_location;
+use crate::sync::apply_change;
 
 /// The request to get the file contents for a starlark: URI
 struct StarlarkFileContentsRequest {}
@@ -271,6 +300,15 @@ pub struct LspEvalResult {
     pub ast: Option<AstModule>,
 }
 
+/// Identifies the version of a file on disk, changing whenever its contents do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStamp {
+    /// When the file was last modified, if the platform records it.
+    pub modified: Option<SystemTime>,
+    /// The size of the file in bytes.
+    pub len: u64,
+}
+
 /// Settings that the LspContext can provide to change what capabilities the server enables
 /// or disables.
 #[derive(Dupe, Clone, Debug, serde::Serialize, serde::Deserialize)]
@@ -367,6 +405,43 @@ pub trait LspContext {
         let _unused = (document_uri, kind, current_value, workspace_root);
         Ok(Vec::new())
     }
+
+    /// Get the starlark files in the workspace, which are searched for references to
+    /// symbols, e.g. when renaming them. Open files are always searched.
+    ///
+    /// `workspace_roots` are the folders the client has open. Returns `None` if the files
+    /// cannot be enumerated, in which case references are only found in open files, and
+    /// symbols other files may load are not renamed, as the edit would be incomplete.
+    fn get_workspace_files(
+        &self,
+        workspace_roots: &[PathBuf],
+    ) -> anyhow::Result<Option<Vec<LspUrl>>> {
+        let _unused = workspace_roots;
+        Ok(None)
+    }
+
+    /// Get the globals that a file is typechecked against, to infer the types shown as
+    /// inlay hints.
+    fn get_globals(&self, uri: &LspUrl) -> Globals {
+        let _unused = uri;
+        Globals::standard()
+    }
+
+    /// Get the current version of a file which is not open. Cached parses of the file, and
+    /// results derived from them, are dropped once this changes. Returning `None` keeps them
+    /// until the client reports a change to the file.
+    fn get_file_stamp(&self, uri: &LspUrl) -> Option<FileStamp> {
+        match uri {
+            LspUrl::File(path) => {
+                let metadata = fs::metadata(path).ok()?;
+                Some(FileStamp {
+                    modified: metadata.modified().ok(),
+                    len: metadata.len(),
+                })
+            }
+            _ => None,
+        }
+    }
 }
 
 /// Errors when [`LspContext::resolve_load()`] cannot resolve a given path.
@@ -391,6 +466,10 @@ pub(crate) struct Backend<T: LspContext> {
     /// The `AstModule` from the last time that a file was opened / changed and parsed successfully.
     /// Entries are evicted when the file is closed.
     pub(crate) last_valid_parse: RwLock<HashMap<LspUrl, Arc<LspModule>>>,
+    /// The text of the open files, which the changes the client sends are applied to.
+    documents: RwLock<HashMap<LspUrl, String>>,
+    /// Parses and typecheck results which are reused until the files change.
+    pub(crate) cache: RwLock<ModuleCache>,
 }
 
 /// The logic implementations of stuff
@@ -404,15 +483,35 @@ impl<T: LspContext> Backend<T> {
             })
         });
         ServerCapabilities {
-            text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+            text_document_sync: Some(TextDocumentSyncCapability::Kind(
+                TextDocumentSyncKind::INCREMENTAL,
+            )),
             definition_provider,
             completion_provider: Some(CompletionOptions::default()),
             hover_provider: Some(HoverProviderCapability::Simple(true)),
+            signature_help_provider: Some(SignatureHelpOptions {
+                trigger_characters: Some(vec!["(".to_owned(), ",".to_owned()]),
+                retrigger_characters: None,
+                work_done_progress_options: WorkDoneProgressOptions {
+                    work_done_progress: None,
+                },
+            }),
+            inlay_hint_provider: Some(OneOf::Left(true)),
+            code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+            references_provider: Some(OneOf::Left(true)),
+            rename_provider: Some(OneOf::Right(RenameOptions {
+                prepare_provider: Some(true),
+                work_done_progress_options: WorkDoneProgressOptions {
+                    work_done_progress: None,
+                },
+            })),
+            document_symbol_provider: Some(OneOf::Left(true)),
+            workspace_symbol_provider: Some(OneOf::Left(true)),
             ..ServerCapabilities::default()
         }
     }
 
-    fn get_ast(&self, uri: &LspUrl) -> Option<Arc<LspModule>> {
+    pub(crate) fn get_ast(&self, uri: &LspUrl) -> Option<Arc<LspModule>> {
         let last_valid_parse = self.last_valid_parse.read().unwrap();
         last_valid_parse.get(uri).duped()
     }
@@ -421,19 +520,15 @@ impl<T: LspContext> Backend<T> {
         &self,
         uri: &LspUrl,
     ) -> anyhow::Result<Option<Arc<LspModule>>> {
-        let module = match self.get_ast(uri) {
-            Some(result) => Some(result),
-            None => self
-                .context
-                .parse_file(uri)?
-                .and_then(|eval_result| eval_result.ast.map(|ast| Arc::new(LspModule::new(ast)))),
-        };
-        Ok(module)
+        match self.get_ast(uri) {
+            Some(result) => Ok(Some(result)),
+            None => self.get_cached_or_load_from_disk(uri),
+        }
     }
 
-    fn validate(&self, uri: Url, version: Option<i64>, text: String) -> anyhow::Result<()> {
-        let uri = uri.try_into()?;
+    fn validate(&self, uri: LspUrl, version: Option<i64>, text: String) -> anyhow::Result<()> {
         let eval_result = self.context.parse_file_with_contents(&uri, text);
+        self.cache.write().unwrap().invalidate(&uri);
         if let Some(ast) = eval_result.ast {
             let module = Arc::new(LspModule::new(ast));
             let mut last_valid_parse = self.last_valid_parse.write().unwrap();
@@ -444,32 +539,53 @@ impl<T: LspContext> Backend<T> {
     }
 
     fn did_open(&self, params: DidOpenTextDocumentParams) -> anyhow::Result<()> {
+        let uri: LspUrl = params.text_document.uri.try_into()?;
+        self.documents
+            .write()
+            .unwrap()
+            .insert(uri.clone(), params.text_document.text.clone());
         self.validate(
-            params.text_document.uri,
+            uri,
             Some(params.text_document.version as i64),
             params.text_document.text,
         )
     }
 
     fn did_change(&self, params: DidChangeTextDocumentParams) -> anyhow::Result<()> {
-        // We asked for Sync full, so can just grab all the text from params
-        let change = params.content_changes.into_iter().next().unwrap();
-        self.validate(
-            params.text_document.uri,
-            Some(params.text_document.version as i64),
-            change.text,
-        )
+        // We asked for incremental sync, so apply the changes to the text we have, in order.
+        let uri: LspUrl = params.text_document.uri.try_into()?;
+        let text = {
+            let mut documents = self.documents.write().unwrap();
+            let text = documents.entry(uri.clone()).or_default();
+            for change in params.content_changes {
+                apply_change(text, change);
+            }
+            text.clone()
+        };
+        self.validate(uri, Some(params.text_document.version as i64), text)
     }
 
     fn did_close(&self, params: DidCloseTextDocumentParams) -> anyhow::Result<()> {
         {
-            let mut last_valid_parse = self.last_valid_parse.write().unwrap();
-            last_valid_parse.remove(&params.text_document.uri.clone().try_into()?);
+            let uri = params.text_document.uri.clone().try_into()?;
+            self.last_valid_parse.write().unwrap().remove(&uri);
+            self.documents.write().unwrap().remove(&uri);
+            // The file on disk may differ from what was open.
+            self.cache.write().unwrap().invalidate(&uri);
         }
         self.publish_diagnostics(params.text_document.uri, Vec::new(), None);
         Ok(())
     }
 
+    fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        let mut cache = self.cache.write().unwrap();
+        for change in params.changes {
+            if let Ok(uri) = change.uri.try_into() {
+                cache.invalidate(&uri);
+            }
+        }
+    }
+
     /// Go to the definition of the symbol at the current cursor if that definition is in
     /// the same file.
     ///
@@ -506,6 +622,102 @@ impl<T: LspContext> Backend<T> {
         self.send_response(new_response(id, self.hover_info(params, initialize_params)));
     }
 
+    /// Offers the signature of the function being called at the current cursor.
+    fn signature_help_request(
+        &self,
+        id: RequestId,
+        params: SignatureHelpParams,
+        initialize_params: &InitializeParams,
+    ) {
+        self.send_response(new_response(
+            id,
+            self.signature_help(params, initialize_params),
+        ));
+    }
+
+    /// Shows the inferred types of unannotated locals.
+    fn inlay_hint_request(
+        &self,
+        id: RequestId,
+        params: InlayHintParams,
+        initialize_params: &InitializeParams,
+    ) {
+        self.send_response(new_response(
+            id,
+            self.inlay_hints(params, initialize_params),
+        ));
+    }
+
+    /// Offers quick fixes for the lints which can be fixed automatically.
+    fn code_action_request(&self, id: RequestId, params: CodeActionParams) {
+        self.send_response(new_response(id, self.code_actions(params)));
+    }
+
+    /// Finds the references to the symbol at the current cursor, including in the files
+    /// that load it.
+    fn references(
+        &self,
+        id: RequestId,
+        params: ReferenceParams,
+        initialize_params: &InitializeParams,
+    ) {
+        self.send_response(new_response(
+            id,
+            self.find_references(params, initialize_params),
+        ));
+    }
+
+    /// Checks that the symbol at the current cursor can be renamed.
+    fn prepare_rename_request(
+        &self,
+        id: RequestId,
+        params: TextDocumentPositionParams,
+        initialize_params: &InitializeParams,
+    ) {
+        self.send_response(new_response(
+            id,
+            self.prepare_rename(params, initialize_params),
+        ));
+    }
+
+    /// Renames the symbol at the current cursor, including in the files that load it.
+    fn rename_request(
+        &self,
+        id: RequestId,
+        params: RenameParams,
+        initialize_params: &InitializeParams,
+    ) {
+        self.send_response(new_response(id, self.rename(params, initialize_params)));
+    }
+
+    /// Lists the symbols in a document, for the outline.
+    fn document_symbols(&self, id: RequestId, params: DocumentSymbolParams) {
+        let response = LspUrl::try_from(params.text_document.uri)
+            .map_err(anyhow::Error::from)
+            .map(|uri| {
+                DocumentSymbolResponse::Nested(match self.get_ast(&uri) {
+                    Some(module) => {
+                        get_document_symbols(module.ast.codemap(), module.ast.statement())
+                    }
+                    None => Vec::new(),
+                })
+            });
+        self.send_response(new_response(id, response));
+    }
+
+    /// Searches the symbols exported by files in the workspace.
+    fn workspace_symbols(
+        &self,
+        id: RequestId,
+        params: WorkspaceSymbolParams,
+        initialize_params: &InitializeParams,
+    ) {
+        let response = self
+            .get_workspace_symbols(&params.query, initialize_params)
+            .map(WorkspaceSymbolResponse::Flat);
+        self.send_response(new_response(id, response));
+    }
+
     /// Get the file contents of a starlark: URI.
     fn get_starlark_file_contents(&self, id: RequestId, params: StarlarkFileContentsParams) {
         let response: anyhow::Result<_> = match params.uri {
@@ -1166,7 +1378,7 @@ impl<T: LspContext> Backend<T> {
         })
     }
 
-    fn get_workspace_root(
+    pub(crate) fn get_workspace_root(
         workspace_roots: Option<&Vec<WorkspaceFolder>>,
         target: &LspUrl,
     ) -> Option<PathBuf> {
@@ -1213,8 +1425,6 @@ impl<T: LspContext> Backend<T> {
         for msg in &self.connection.receiver {
             match msg {
                 Message::Request(req) => {
-                    // TODO(nmj): Also implement DocumentSymbols so that some logic can
-                    //            be handled client side.
                     if let Some(params) = as_request::<GotoDefinition>(&req) {
                         self.goto_definition(req.id, params, &initialize_params);
                     } else if let Some(params) = as_request::<StarlarkFileContentsRequest>(&req) {
@@ -1223,6 +1433,22 @@ impl<T: LspContext> Backend<T> {
                         self.completion(req.id, params, &initialize_params);
                     } else if let Some(params) = as_request::<HoverRequest>(&req) {
                         self.hover(req.id, params, &initialize_params);
+                    } else if let Some(params) = as_request::<SignatureHelpRequest>(&req) {
+                        self.signature_help_request(req.id, params, &initialize_params);
+                    } else if let Some(params) = as_request::<InlayHintRequest>(&req) {
+                        self.inlay_hint_request(req.id, params, &initialize_params);
+                    } else if let Some(params) = as_request::<CodeActionRequest>(&req) {
+                        self.code_action_request(req.id, params);
+                    } else if let Some(params) = as_request::<References>(&req) {
+                        self.references(req.id, params, &initialize_params);
+                    } else if let Some(params) = as_request::<PrepareRenameRequest>(&req) {
+                        self.prepare_rename_request(req.id, params, &initialize_params);
+                    } else if let Some(params) = as_request::<Rename>(&req) {
+                        self.rename_request(req.id, params, &initialize_params);
+                    } else if let Some(params) = as_request::<DocumentSymbolRequest>(&req) {
+                        self.document_symbols(req.id, params);
+                    } else if let Some(params) = as_request::<WorkspaceSymbolRequest>(&req) {
+                        self.workspace_symbols(req.id, params, &initialize_params);
                     } else if self.connection.handle_shutdown(&req)? {
                         return Ok(());
                     }
@@ -1235,6 +1461,8 @@ impl<T: LspContext> Backend<T> {
                         self.did_change(params)?;
                     } else if let Some(params) = as_notification::<DidCloseTextDocument>(&x) {
                         self.did_close(params)?;
+                    } else if let Some(params) = as_notification::<DidChangeWatchedFiles>(&x) {
+                        self.did_change_watched_files(params);
                     }
                 }
                 Message::Response(_) => {
@@ -1286,6 +1514,8 @@ pub fn server_with_connection<T: LspContext>(
         connection,
         context,
         last_valid_parse: RwLock::default(),
+        documents: RwLock::default(),
+        cache: RwLock::default(),
     }
     .main_loop(initialization_params)?;
 
@@ -1365,21 +1595,47 @@ where
 //            some paths. Revisit later.
 #[cfg(all(test, not(windows)))]
 mod tests {
+    use std::collections::HashMap;
     use std::path::Path;
     use std::path::PathBuf;
 
     use anyhow::Context;
     use lsp_server::Request;
     use lsp_server::RequestId;
+    use lsp_types::notification::PublishDiagnostics;
+    use lsp_types::request::CodeActionRequest;
     use lsp_types::request::GotoDefinition;
+    use lsp_types::request::InlayHintRequest;
+    use lsp_types::request::References;
+    use lsp_types::request::Rename;
+    use lsp_types::request::SignatureHelpRequest;
+    use lsp_types::request::WorkspaceSymbolRequest;
+    use lsp_types::CodeActionContext;
+    use lsp_types::CodeActionOrCommand;
+    use lsp_types::CodeActionParams;
+    use lsp_types::CodeActionResponse;
     use lsp_types::GotoDefinitionParams;
     use lsp_types::GotoDefinitionResponse;
+    use lsp_types::InlayHint;
+    use lsp_types::InlayHintLabel;
+    use lsp_types::InlayHintParams;
+    use lsp_types::Location;
     use lsp_types::LocationLink;
     use lsp_types::Position;
     use lsp_types::Range;
+    use lsp_types::ReferenceContext;
+    use lsp_types::ReferenceParams;
+    use lsp_types::RenameParams;
+    use lsp_types::SignatureHelp;
+    use lsp_types::SignatureHelpParams;
+    use lsp_types::TextDocumentContentChangeEvent;
     use lsp_types::TextDocumentIdentifier;
     use lsp_types::TextDocumentPositionParams;
+    use lsp_types::TextEdit;
     use lsp_types::Url;
+    use lsp_types::WorkspaceEdit;
+    use lsp_types::WorkspaceSymbolParams;
+    use lsp_types::WorkspaceSymbolResponse;
     use starlark::codemap::ResolvedSpan;
     use starlark::wasm::is_wasm;
     use textwrap::dedent;
@@ -2479,4 +2735,494 @@ mod tests {
         }
         Ok(())
     }
+
+    /// A macro defined in `bar.star`, loaded by the open `foo.star`, and under an alias by
+    /// `baz.star`, which is only on disk.
+    struct MacroFixtures {
+        bar_uri: Url,
+        foo_uri: Url,
+        baz_uri: Url,
+        bar: FixtureWithRanges,
+        foo: FixtureWithRanges,
+        baz: FixtureWithRanges,
+    }
+
+    impl MacroFixtures {
+        fn new() -> anyhow::Result<Self> {
+            let bar_uri = temp_file_uri("bar.star");
+            let foo_uri = temp_file_uri("foo.star");
+            let baz_uri = temp_file_uri("baz.star");
+            Ok(Self {
+                bar: FixtureWithRanges::from_fixture(
+                    bar_uri.path(),
+                    "def <def>my_macro</def>():\n    pass\n<use>my_macro</use>()\n",
+                )?,
+                foo: FixtureWithRanges::from_fixture(
+                    foo_uri.path(),
+                    "load(\"bar.star\", <load>\"my_macro\"</load>)\n<use>my_macro</use>()\n",
+                )?,
+                baz: FixtureWithRanges::from_fixture(
+                    baz_uri.path(),
+                    "load(\"bar.star\", <alias>m</alias> = <load>\"my_macro\"</load>)\n<use>m</use>()\n",
+                )?,
+                bar_uri,
+                foo_uri,
+                baz_uri,
+            })
+        }
+
+        fn server(&self) -> anyhow::Result<TestServer> {
+            let mut server = TestServer::new()?;
+            server.open_file(self.bar_uri.clone(), self.bar.program())?;
+            server.open_file(self.foo_uri.clone(), self.foo.program())?;
+            server.set_file_contents(PathBuf::from(self.baz_uri.path()), self.baz.program())?;
+            Ok(server)
+        }
+    }
+
+    fn text_document_position(
+        uri: Url,
+        fixture: &FixtureWithRanges,
+        id: &str,
+    ) -> TextDocumentPositionParams {
+        TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri },
+            position: Position::new(fixture.begin_line(id), fixture.begin_column(id)),
+        }
+    }
+
+    fn sorted_locations(mut locations: Vec<Location>) -> Vec<Location> {
+        locations.sort_by_key(|x| (x.uri.to_string(), x.range.start, x.range.end));
+        locations
+    }
+
+    #[test]
+    fn finds_references_in_loading_files() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let fixtures = MacroFixtures::new()?;
+        let mut server = fixtures.server()?;
+        let location = |uri: &Url, fixture: &FixtureWithRanges, id: &str| {
+            Location::new(uri.clone(), fixture.resolved_span(id).into())
+        };
+
+        for include_declaration in [true, false] {
+            let request = server.new_request::<References>(ReferenceParams {
+                text_document_position: text_document_position(
+                    fixtures.foo_uri.clone(),
+                    &fixtures.foo,
+                    "use",
+                ),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+                context: ReferenceContext {
+                    include_declaration,
+                },
+            });
+            let request_id = server.send_request(request)?;
+            let response = server.get_response::<Vec<Location>>(request_id)?;
+
+            let mut expected = vec![
+                location(&fixtures.bar_uri, &fixtures.bar, "use"),
+                location(&fixtures.foo_uri, &fixtures.foo, "load"),
+                location(&fixtures.foo_uri, &fixtures.foo, "use"),
+                location(&fixtures.baz_uri, &fixtures.baz, "load"),
+                location(&fixtures.baz_uri, &fixtures.baz, "alias"),
+                location(&fixtures.baz_uri, &fixtures.baz, "use"),
+            ];
+            if include_declaration {
+                expected.push(location(&fixtures.bar_uri, &fixtures.bar, "def"));
+            }
+            assert_eq!(sorted_locations(expected), sorted_locations(response));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn renames_in_loading_files() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let fixtures = MacroFixtures::new()?;
+        let mut server = fixtures.server()?;
+
+        let request = server.new_request::<Rename>(RenameParams {
+            text_document_position: text_document_position(
+                fixtures.bar_uri.clone(),
+                &fixtures.bar,
+                "def",
+            ),
+            new_name: "new_macro".to_owned(),
+            work_done_progress_params: Default::default(),
+        });
+        let request_id = server.send_request(request)?;
+        let mut changes = server
+            .get_response::<WorkspaceEdit>(request_id)?
+            .changes
+            .unwrap_or_default();
+        for edits in changes.values_mut() {
+            edits.sort_by_key(|x| x.range.start);
+        }
+
+        let edit = |fixture: &FixtureWithRanges, id: &str, new_text: &str| {
+            TextEdit::new(fixture.resolved_span(id).into(), new_text.to_owned())
+        };
+        let expected = HashMap::from([
+            (
+                fixtures.bar_uri.clone(),
+                vec![
+                    edit(&fixtures.bar, "def", "new_macro"),
+                    edit(&fixtures.bar, "use", "new_macro"),
+                ],
+            ),
+            (
+                fixtures.foo_uri.clone(),
+                vec![
+                    edit(&fixtures.foo, "load", "\"new_macro\""),
+                    edit(&fixtures.foo, "use", "new_macro"),
+                ],
+            ),
+            (
+                fixtures.baz_uri.clone(),
+                vec![edit(&fixtures.baz, "load", "\"new_macro\"")],
+            ),
+        ]);
+        assert_eq!(expected, changes);
+        Ok(())
+    }
+
+    #[test]
+    fn refuses_to_rename_exported_symbols_without_workspace_files() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let fixtures = MacroFixtures::new()?;
+        let mut server = fixtures.server()?;
+        server.disable_workspace_enumeration();
+
+        // `baz.star` is not open, so renaming `my_macro` would miss its load.
+        let request = server.new_request::<Rename>(RenameParams {
+            text_document_position: text_document_position(
+                fixtures.bar_uri.clone(),
+                &fixtures.bar,
+                "def",
+            ),
+            new_name: "new_macro".to_owned(),
+            work_done_progress_params: Default::default(),
+        });
+        let request_id = server.send_request(request)?;
+        assert!(server.get_response::<WorkspaceEdit>(request_id).is_err());
+
+        // References are still found in the open files.
+        let request = server.new_request::<References>(ReferenceParams {
+            text_document_position: text_document_position(
+                fixtures.bar_uri.clone(),
+                &fixtures.bar,
+                "def",
+            ),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: ReferenceContext {
+                include_declaration: false,
+            },
+        });
+        let request_id = server.send_request(request)?;
+        let response = server.get_response::<Vec<Location>>(request_id)?;
+        let location = |uri: &Url, fixture: &FixtureWithRanges, id: &str| {
+            Location::new(uri.clone(), fixture.resolved_span(id).into())
+        };
+        let expected = vec![
+            location(&fixtures.bar_uri, &fixtures.bar, "use"),
+            location(&fixtures.foo_uri, &fixtures.foo, "load"),
+            location(&fixtures.foo_uri, &fixtures.foo, "use"),
+        ];
+        assert_eq!(sorted_locations(expected), sorted_locations(response));
+        Ok(())
+    }
+
+    #[test]
+    fn renames_locals_and_rejects_builtins() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let uri = temp_file_uri("file.star");
+        let fixture = FixtureWithRanges::from_fixture(
+            uri.path(),
+            "load(\"bar.star\", <load>\"_m\"</load>)\ndef f(<x>x</x>):\n    return <x_use>x</x_use> + <m>_m</m>() + <builtin>len</builtin>([])\n",
+        )?;
+        let mut server = TestServer::new()?;
+        server.open_file(uri.clone(), fixture.program())?;
+
+        let mut rename = |id: &str, new_name: &str| {
+            let request = server.new_request::<Rename>(RenameParams {
+                text_document_position: text_document_position(uri.clone(), &fixture, id),
+                new_name: new_name.to_owned(),
+                work_done_progress_params: Default::default(),
+            });
+            server
+                .send_request(request)
+                .and_then(|request_id| server.get_response::<WorkspaceEdit>(request_id))
+                .map(|edit| {
+                    let mut edits = edit
+                        .changes
+                        .unwrap_or_default()
+                        .remove(&uri)
+                        .unwrap_or_default();
+                    edits.sort_by_key(|x| x.range.start);
+                    edits
+                })
+        };
+        let edit = |id: &str, new_text: &str| {
+            TextEdit::new(fixture.resolved_span(id).into(), new_text.to_owned())
+        };
+
+        assert_eq!(
+            vec![edit("x", "y"), edit("x_use", "y")],
+            rename("x_use", "y")?
+        );
+        // `bar.star` does not exist, so only the local name changes.
+        assert_eq!(
+            vec![edit("load", "m = \"_m\""), edit("m", "m")],
+            rename("m", "m")?
+        );
+        assert!(rename("builtin", "size").is_err());
+        assert!(rename("x", "not").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn finds_workspace_symbols() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let fixtures = MacroFixtures::new()?;
+        let mut server = fixtures.server()?;
+
+        let request = server.new_request::<WorkspaceSymbolRequest>(WorkspaceSymbolParams {
+            query: "MACRO".to_owned(),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        });
+        let request_id = server.send_request(request)?;
+        let symbols = match server.get_response::<WorkspaceSymbolResponse>(request_id)? {
+            WorkspaceSymbolResponse::Flat(symbols) => symbols,
+            response => return Err(anyhow::anyhow!("Unexpected response: {:?}", response)),
+        };
+        assert_eq!(
+            vec![Location::new(
+                fixtures.bar_uri.clone(),
+                fixtures.bar.resolved_span("def").into()
+            )],
+            symbols.into_iter().map(|x| x.location).collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn signature_help_for_loaded_and_native_functions() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let bar_uri = temp_file_uri("bar.star");
+        let foo_uri = temp_file_uri("foo.star");
+        let bar_contents = dedent(
+            r#"
+            def my_rule(name: str, srcs: list[str] = [], *, deps = None) -> list:
+                """Declares a rule.
+
+                Args:
+                    srcs: The sources.
+                """
+                return [name, srcs, deps]
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let foo = FixtureWithRanges::from_fixture(
+            foo_uri.path(),
+            "load(\"bar.star\", \"my_rule\")\nmy_rule(\"x\", <srcs>[]</srcs>, <deps>deps</deps> = [])\nnative_function1(<native>1</native>)\n",
+        )?;
+
+        let mut server = TestServer::new()?;
+        server.open_file(bar_uri, bar_contents)?;
+        server.open_file(foo_uri.clone(), foo.program())?;
+
+        let mut signature_help = |id: &str| -> anyhow::Result<Option<SignatureHelp>> {
+            let request = server.new_request::<SignatureHelpRequest>(SignatureHelpParams {
+                context: None,
+                text_document_position_params: text_document_position(foo_uri.clone(), &foo, id),
+                work_done_progress_params: Default::default(),
+            });
+            let request_id = server.send_request(request)?;
+            server.get_response::<Option<SignatureHelp>>(request_id)
+        };
+
+        let help = signature_help("srcs")?.context("no signature help")?;
+        let signature = &help.signatures[0];
+        assert_eq!(
+            "my_rule(name: str, srcs: list[str] = [], *, deps = None) -> list[typing.Any]",
+            signature.label
+        );
+        assert_eq!(Some(1), help.active_parameter);
+        let parameters = signature.parameters.as_ref().unwrap();
+        assert_eq!(3, parameters.len());
+        assert!(parameters[1].documentation.is_some());
+        assert!(signature.documentation.is_some());
+
+        let help = signature_help("deps")?.context("no signature help")?;
+        assert_eq!(Some(2), help.active_parameter);
+
+        let help = signature_help("native")?.context("no signature help")?;
+        assert_eq!("native_function1()", help.signatures[0].label);
+        assert_eq!(None, help.active_parameter);
+        Ok(())
+    }
+
+    #[test]
+    fn code_actions_fix_lints() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let foo_uri = temp_file_uri("foo.star");
+        let mut server = TestServer::new()?;
+        server.open_file(foo_uri.clone(), String::new())?;
+        server.change_file(
+            foo_uri.clone(),
+            "load(\"bar.star\", \"x\", \"y\")\nprint(y)\n".to_owned(),
+        )?;
+        let diagnostics = server.get_notification::<PublishDiagnostics>()?.diagnostics;
+        assert_eq!(1, diagnostics.len());
+
+        let request = server.new_request::<CodeActionRequest>(CodeActionParams {
+            text_document: TextDocumentIdentifier {
+                uri: foo_uri.clone(),
+            },
+            range: diagnostics[0].range,
+            context: CodeActionContext {
+                diagnostics: diagnostics.clone(),
+                only: None,
+                trigger_kind: None,
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        });
+        let request_id = server.send_request(request)?;
+        let actions = server.get_response::<Option<CodeActionResponse>>(request_id)?;
+        let Some([CodeActionOrCommand::CodeAction(action)]) = actions.as_deref() else {
+            return Err(anyhow::anyhow!("Unexpected code actions: {:?}", actions));
+        };
+        assert_eq!("Remove unused load", action.title);
+        assert_eq!(Some(&diagnostics), action.diagnostics.as_ref());
+        let expected = WorkspaceEdit {
+            changes: Some(HashMap::from([(
+                foo_uri,
+                vec![TextEdit::new(
+                    Range::new(Position::new(0, 15), Position::new(0, 20)),
+                    String::new(),
+                )],
+            )])),
+            ..WorkspaceEdit::default()
+        };
+        assert_eq!(Some(&expected), action.edit.as_ref());
+        Ok(())
+    }
+
+    /// The labels of the inlay hints in the first lines of `uri`.
+    fn hints(server: &mut TestServer, uri: &Url) -> anyhow::Result<Vec<String>> {
+        let request = server.new_request::<InlayHintRequest>(InlayHintParams {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+            range: Range::new(Position::new(0, 0), Position::new(10, 0)),
+            work_done_progress_params: Default::default(),
+        });
+        let request_id = server.send_request(request)?;
+        Ok(server
+            .get_response::<Vec<InlayHint>>(request_id)?
+            .into_iter()
+            .filter_map(|hint| match hint.label {
+                InlayHintLabel::String(label) => Some(label),
+                _ => None,
+            })
+            .collect())
+    }
+
+    #[test]
+    fn incremental_changes_invalidate_dependent_types() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let bar_uri = temp_file_uri("bar.star");
+        let foo_uri = temp_file_uri("foo.star");
+        let mut server = TestServer::new()?;
+        server.open_file(
+            bar_uri.clone(),
+            "def f() -> int:\n    return 1\n".to_owned(),
+        )?;
+        server.open_file(
+            foo_uri.clone(),
+            "load(\"bar.star\", \"f\")\ndef g():\n    x = f()\n    return x\n".to_owned(),
+        )?;
+
+        assert_eq!(vec![": int".to_owned()], hints(&mut server, &foo_uri)?);
+
+        // Replace the `1` and then the `int`, the rest of `bar.star` is unchanged.
+        server.edit_file(
+            bar_uri.clone(),
+            vec![
+                TextDocumentContentChangeEvent {
+                    range: Some(Range::new(Position::new(1, 11), Position::new(1, 12))),
+                    range_length: None,
+                    text: "\"one\"".to_owned(),
+                },
+                TextDocumentContentChangeEvent {
+                    range: Some(Range::new(Position::new(0, 11), Position::new(0, 14))),
+                    range_length: None,
+                    text: "str".to_owned(),
+                },
+            ],
+        )?;
+        let diagnostics = server.get_notification::<PublishDiagnostics>()?;
+        assert_eq!(bar_uri, diagnostics.uri);
+        assert!(diagnostics.diagnostics.is_empty());
+
+        assert_eq!(vec![": str".to_owned()], hints(&mut server, &foo_uri)?);
+        Ok(())
+    }
+
+    #[test]
+    fn files_changed_on_disk_invalidate_dependent_types() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let bar_uri = temp_file_uri("bar.star");
+        let foo_uri = temp_file_uri("foo.star");
+        let mut server = TestServer::new()?;
+        server.set_file_contents(
+            PathBuf::from(bar_uri.path()),
+            "def f() -> int:\n    return 1\n".to_owned(),
+        )?;
+        server.open_file(
+            foo_uri.clone(),
+            "load(\"bar.star\", \"f\")\ndef g():\n    x = f()\n    return x\n".to_owned(),
+        )?;
+
+        assert_eq!(vec![": int".to_owned()], hints(&mut server, &foo_uri)?);
+
+        // `bar.star` is not open and the client sends no notification about the change.
+        server.set_file_contents(
+            PathBuf::from(bar_uri.path()),
+            "def f() -> str:\n    return \"one\"\n".to_owned(),
+        )?;
+        assert_eq!(vec![": str".to_owned()], hints(&mut server, &foo_uri)?);
+        Ok(())
+    }
 }
@@ -0,0 +1,429 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Signature help for the call around the cursor.
+
+use std::fmt::Write;
+use std::path::Path;
+
+use lsp_types::Documentation;
+use lsp_types::InitializeParams;
+use lsp_types::MarkupContent;
+use lsp_types::MarkupKind;
+use lsp_types::ParameterInformation;
+use lsp_types::ParameterLabel;
+use lsp_types::SignatureHelp;
+use lsp_types::SignatureHelpParams;
+use lsp_types::SignatureInformation;
+use starlark::codemap::Pos;
+use starlark::docs::DocFunction;
+use starlark::docs::DocMember;
+use starlark::docs::DocParam;
+use starlark::docs::DocString;
+use starlark::typing::Ty;
+use starlark_syntax::syntax::ast::ArgumentP;
+use starlark_syntax::syntax::ast::AstArgument;
+use starlark_syntax::syntax::ast::AstExpr;
+use starlark_syntax::syntax::ast::AstNoPayload;
+use starlark_syntax::syntax::ast::AstStmt;
+use starlark_syntax::syntax::ast::DefP;
+use starlark_syntax::syntax::ast::Expr;
+use starlark_syntax::syntax::ast::Stmt;
+use starlark_syntax::syntax::module::AstModuleFields;
+use starlark_syntax::syntax::top_level_stmts::top_level_stmts;
+use starlark_syntax::syntax::uniplate::Visit;
+
+use crate::definition::Definition;
+use crate::definition::IdentifierDefinition;
+use crate::definition::LspModule;
+use crate::docs::get_signature_for_def;
+use crate::server::Backend;
+use crate::server::LspContext;
+use crate::server::LspUrl;
+
+/// The innermost call whose arguments contain `pos`, as the function and the arguments.
+fn find_call(node: Visit<'_, AstNoPayload>, pos: Pos) -> Option<(&AstExpr, &[AstArgument])> {
+    let span = match &node {
+        Visit::Stmt(x) => x.span,
+        Visit::Expr(x) => x.span,
+    };
+    if !span.contains(pos) {
+        return None;
+    }
+    let mut res = None;
+    node.visit_children(|x| {
+        if res.is_none() {
+            res = find_call(x, pos);
+        }
+    });
+    match node {
+        Visit::Expr(AstExpr {
+            node: Expr::Call(function, args),
+            span,
+        }) if res.is_none() && function.span.end() <= pos && pos < span.end() => {
+            Some((function, args))
+        }
+        _ => res,
+    }
+}
+
+/// The first `def` in `stmt` (including nested ones) that matches `pred`.
+fn find_def<'a>(
+    stmt: &'a AstStmt,
+    pred: &impl Fn(&DefP<AstNoPayload>) -> bool,
+) -> Option<&'a DefP<AstNoPayload>> {
+    match &stmt.node {
+        Stmt::Def(def) if pred(def) => Some(def),
+        _ => {
+            let mut res = None;
+            stmt.visit_stmt(|x| {
+                if res.is_none() {
+                    res = find_def(x, pred);
+                }
+            });
+            res
+        }
+    }
+}
+
+/// Which of the parameters with a [`ParameterInformation`] the argument at `pos` is passed to.
+fn active_parameter(params: &[DocParam], args: &[AstArgument], pos: Pos) -> Option<u32> {
+    let mut positional = Vec::new();
+    let mut named = Vec::new();
+    let mut args_index = None;
+    let mut kwargs_index = None;
+    let mut keyword_only = false;
+    let mut index = 0;
+    for param in params {
+        match param {
+            DocParam::NoArgs => keyword_only = true,
+            DocParam::OnlyPosBefore => named.clear(),
+            DocParam::Arg { name, .. } => {
+                if !keyword_only {
+                    positional.push(index);
+                }
+                named.push((name.as_str(), index));
+                index += 1;
+            }
+            DocParam::Args { .. } => {
+                args_index = Some(index);
+                keyword_only = true;
+                index += 1;
+            }
+            DocParam::Kwargs { .. } => {
+                kwargs_index = Some(index);
+                index += 1;
+            }
+        }
+    }
+
+    // The argument being typed is the first one that does not end before the cursor.
+    let current = args
+        .iter()
+        .position(|arg| arg.span.end() >= pos)
+        .unwrap_or(args.len());
+    match args.get(current).map(|arg| &arg.node) {
+        Some(ArgumentP::Named(arg_name, _)) => named
+            .iter()
+            .find(|(name, _)| *name == arg_name.node)
+            .map(|(_, index)| *index)
+            .or(kwargs_index),
+        Some(ArgumentP::Args(_)) => args_index,
+        Some(ArgumentP::KwArgs(_)) => kwargs_index,
+        Some(ArgumentP::Positional(_)) | None => {
+            let before = args[..current]
+                .iter()
+                .filter(|arg| matches!(arg.node, ArgumentP::Positional(_)))
+                .count();
+            positional.get(before).copied().or(args_index)
+        }
+    }
+}
+
+fn render_doc_string(docs: &DocString) -> Documentation {
+    let value = match &docs.details {
+        Some(details) => format!("{}\n\n{}", docs.summary, details),
+        None => docs.summary.clone(),
+    };
+    Documentation::MarkupContent(MarkupContent {
+        kind: MarkupKind::Markdown,
+        value,
+    })
+}
+
+fn render_param(param: &DocParam) -> String {
+    let typed = |name: &str, typ: &Ty| {
+        if *typ == Ty::any() {
+            name.to_owned()
+        } else {
+            format!("{}: {}", name, typ)
+        }
+    };
+    match param {
+        DocParam::Arg {
+            name,
+            typ,
+            default_value,
+            ..
+        } => match default_value {
+            Some(default) => format!("{} = {}", typed(name, typ), default),
+            None => typed(name, typ),
+        },
+        DocParam::NoArgs => "*".to_owned(),
+        DocParam::OnlyPosBefore => "/".to_owned(),
+        DocParam::Args { name, typ, .. } | DocParam::Kwargs { name, typ, .. } => typed(name, typ),
+    }
+}
+
+/// The signature of `function`, with the offsets of each parameter in the label.
+fn signature_information(name: &str, function: &DocFunction) -> SignatureInformation {
+    // Offsets are in UTF-16 code units, like positions.
+    fn offset(label: &str) -> u32 {
+        label.encode_utf16().count() as u32
+    }
+
+    let mut label = format!("{}(", name);
+    let mut parameters = Vec::new();
+    for (i, param) in function.params.iter().enumerate() {
+        if i != 0 {
+            label.push_str(", ");
+        }
+        let begin = offset(&label);
+        label.push_str(&render_param(param));
+        match param {
+            DocParam::NoArgs | DocParam::OnlyPosBefore => {}
+            DocParam::Arg { docs, .. }
+            | DocParam::Args { docs, .. }
+            | DocParam::Kwargs { docs, .. } => parameters.push(ParameterInformation {
+                label: ParameterLabel::LabelOffsets([begin, offset(&label)]),
+                documentation: docs.as_ref().map(render_doc_string),
+            }),
+        }
+    }
+    label.push(')');
+    if function.ret.typ != Ty::any() {
+        write!(label, " -> {}", function.ret.typ).unwrap();
+    }
+
+    SignatureInformation {
+        label,
+        documentation: function.docs.as_ref().map(render_doc_string),
+        parameters: Some(parameters),
+        active_parameter: None,
+    }
+}
+
+impl<T: LspContext> Backend<T> {
+    /// The type the typechecker inferred for the function defined by `def` in `uri`.
+    fn def_type(
+        &self,
+        uri: &LspUrl,
+        def: &DefP<AstNoPayload>,
+        workspace_root: Option<&Path>,
+    ) -> Option<Ty> {
+        let typechecked = self.typecheck(uri, workspace_root)?;
+        let ty = typechecked
+            .types
+            .bindings()
+            .find(|(name, span, _)| *name == def.name.ident && *span == def.name.span)
+            .map(|(_, _, ty)| ty.clone());
+        ty
+    }
+
+    /// The docs for the function that `function` refers to, if it is a plain identifier.
+    fn function_docs(
+        &self,
+        module: &LspModule,
+        uri: &LspUrl,
+        function: &AstExpr,
+        initialize_params: &InitializeParams,
+    ) -> anyhow::Result<Option<DocFunction>> {
+        if !matches!(function.node, Expr::Identifier(_)) {
+            return Ok(None);
+        }
+        let workspace_root =
+            Self::get_workspace_root(initialize_params.workspace_folders.as_ref(), uri);
+        let begin = module.ast.codemap().resolve_span(function.span).begin;
+        let Definition::Identifier(definition) =
+            module.find_definition_at_location(begin.line as u32, begin.column as u32)
+        else {
+            return Ok(None);
+        };
+        Ok(match definition {
+            IdentifierDefinition::Location {
+                destination, name, ..
+            } => {
+                let codemap = module.ast.codemap();
+                find_def(module.ast.statement(), &|def| {
+                    def.name.ident == name && codemap.resolve_span(def.name.span) == destination
+                })
+                .map(|def| {
+                    let ty = self.def_type(uri, def, workspace_root.as_deref());
+                    get_signature_for_def(def, ty.as_ref())
+                })
+            }
+            IdentifierDefinition::LoadedLocation { path, name, .. } => {
+                let load_uri = self.resolve_load_path(&path, uri, workspace_root.as_deref())?;
+                self.get_ast_or_load_from_disk(&load_uri)?
+                    .and_then(|loaded| {
+                        top_level_stmts(loaded.ast.statement())
+                            .into_iter()
+                            .find_map(|stmt| match &stmt.node {
+                                Stmt::Def(def) if def.name.ident == name => {
+                                    let ty =
+                                        self.def_type(&load_uri, def, workspace_root.as_deref());
+                                    Some(get_signature_for_def(def, ty.as_ref()))
+                                }
+                                _ => None,
+                            })
+                    })
+            }
+            IdentifierDefinition::Unresolved { name, .. } => self
+                .context
+                .get_environment(uri)
+                .members
+                .into_iter()
+                .find_map(|(member_name, member)| match member {
+                    DocMember::Function(function) if member_name == name => Some(function),
+                    _ => None,
+                }),
+            IdentifierDefinition::LoadPath { .. }
+            | IdentifierDefinition::StringLiteral { .. }
+            | IdentifierDefinition::NotFound => None,
+        })
+    }
+
+    /// The signature of the function being called around the cursor, and which parameter
+    /// the argument at the cursor is passed to.
+    pub(crate) fn signature_help(
+        &self,
+        params: SignatureHelpParams,
+        initialize_params: &InitializeParams,
+    ) -> anyhow::Result<Option<SignatureHelp>> {
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .try_into()?;
+        let position = params.text_document_position_params.position;
+        let Some(module) = self.get_ast(&uri) else {
+            return Ok(None);
+        };
+        let Some(pos) = module.pos_at(position.line, position.character) else {
+            return Ok(None);
+        };
+        let Some((function, args)) = find_call(Visit::Stmt(module.ast.statement()), pos) else {
+            return Ok(None);
+        };
+        let Some(docs) = self.function_docs(&module, &uri, function, initialize_params)? else {
+            return Ok(None);
+        };
+        Ok(Some(SignatureHelp {
+            signatures: vec![signature_information(&function.to_string(), &docs)],
+            active_signature: Some(0),
+            active_parameter: active_parameter(&docs.params, args, pos),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use starlark::codemap::Pos;
+    use starlark::environment::Globals;
+    use starlark::syntax::AstModule;
+    use starlark::syntax::Dialect;
+    use starlark_syntax::syntax::module::AstModuleFields;
+    use starlark_syntax::syntax::uniplate::Visit;
+
+    use super::active_parameter;
+    use super::find_call;
+    use super::find_def;
+    use super::signature_information;
+    use crate::cache::typecheck;
+    use crate::docs::get_signature_for_def;
+
+    #[test]
+    fn signature_and_active_parameter() -> starlark::Result<()> {
+        let program = r#"
+def f(a, b = 1, *args, c = 3, **kwargs):
+    """Does things.
+
+    Args:
+        a: The a.
+    """
+    pass
+f(x, g(y), c = 2, d = 3)
+"#;
+        let ast = AstModule::parse("foo.bzl", program.to_owned(), &Dialect::Extended)?;
+        let def = find_def(ast.statement(), &|def| def.name.ident == "f").unwrap();
+        let docs = get_signature_for_def(def, None);
+
+        let signature = signature_information("f", &docs);
+        assert_eq!("f(a, b = 1, *args, c = 3, **kwargs)", signature.label);
+        let labels: Vec<_> = signature
+            .parameters
+            .unwrap()
+            .into_iter()
+            .map(|param| match param.label {
+                lsp_types::ParameterLabel::LabelOffsets([begin, end]) => {
+                    signature.label[begin as usize..end as usize].to_owned()
+                }
+                lsp_types::ParameterLabel::Simple(label) => label,
+            })
+            .collect();
+        assert_eq!(vec!["a", "b = 1", "*args", "c = 3", "**kwargs"], labels);
+
+        let call = program.rfind("f(").unwrap();
+        let active = |needle: &str| {
+            let pos = Pos::new((call + program[call..].find(needle).unwrap()) as u32);
+            let (function, args) = find_call(Visit::Stmt(ast.statement()), pos).unwrap();
+            assert_eq!("f", function.to_string());
+            active_parameter(&docs.params, args, pos)
+        };
+        assert_eq!(Some(0), active("x"));
+        assert_eq!(Some(1), active("g("));
+        assert_eq!(Some(3), active("c ="));
+        assert_eq!(Some(4), active("d ="));
+        Ok(())
+    }
+
+    #[test]
+    fn signature_has_annotated_types() -> starlark::Result<()> {
+        let program = "def f(a: int, b, c: str = \"\", *args: bool, **kwargs: list[int]) -> str:\n    return c\n";
+        let ast = AstModule::parse("foo.bzl", program.to_owned(), &Dialect::Extended)?;
+        let (types, _interface) = typecheck(&ast, &Globals::standard(), &HashMap::new()).unwrap();
+        let ty = types
+            .bindings()
+            .find(|(name, _, _)| *name == "f")
+            .map(|(_, _, ty)| ty.clone());
+        let def = find_def(ast.statement(), &|def| def.name.ident == "f").unwrap();
+
+        let docs = get_signature_for_def(def, ty.as_ref());
+        assert_eq!(
+            "f(a: int, b, c: str = \"\", *args: bool, **kwargs: list[int]) -> str",
+            signature_information("f", &docs).label
+        );
+        let docs = get_signature_for_def(def, None);
+        assert_eq!(
+            "f(a, b, c = \"\", *args, **kwargs)",
+            signature_information("f", &docs).label
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Applying the incremental changes the client sends to the text of open documents.
+
+use lsp_types::Position;
+use lsp_types::TextDocumentContentChangeEvent;
+
+/// The byte offset of `position` in `text`. Positions past the end of a line or of the text
+/// are clamped to it, as the protocol requires.
+fn offset_at(text: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(i) => line_start += i + 1,
+            None => return text.len(),
+        }
+    }
+    let line = match text[line_start..].find('\n') {
+        Some(i) => &text[line_start..line_start + i],
+        None => &text[line_start..],
+    };
+    // Characters are counted in UTF-16 code units.
+    let mut character = 0;
+    for (i, c) in line.char_indices() {
+        if character >= position.character as usize {
+            return line_start + i;
+        }
+        character += c.len_utf16();
+    }
+    line_start + line.len()
+}
+
+/// Apply a change to the text of a document. A change without a range replaces all of it.
+pub(crate) fn apply_change(text: &mut String, change: TextDocumentContentChangeEvent) {
+    match change.range {
+        None => *text = change.text,
+        Some(range) => {
+            let start = offset_at(text, range.start);
+            let end = offset_at(text, range.end).max(start);
+            text.replace_range(start..end, &change.text);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::Position;
+    use lsp_types::Range;
+    use lsp_types::TextDocumentContentChangeEvent;
+
+    use super::apply_change;
+
+    fn change(
+        range: Option<((u32, u32), (u32, u32))>,
+        text: &str,
+    ) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range: range.map(|((l1, c1), (l2, c2))| {
+                Range::new(Position::new(l1, c1), Position::new(l2, c2))
+            }),
+            range_length: None,
+            text: text.to_owned(),
+        }
+    }
+
+    #[test]
+    fn applies_changes_in_order() {
+        let mut text = "x = 1\ny = 2\n".to_owned();
+        apply_change(&mut text, change(Some(((0, 4), (0, 5))), "10"));
+        assert_eq!("x = 10\ny = 2\n", text);
+        apply_change(&mut text, change(Some(((1, 0), (2, 0))), ""));
+        assert_eq!("x = 10\n", text);
+        apply_change(&mut text, change(Some(((1, 0), (1, 0))), "z = 3\n"));
+        assert_eq!("x = 10\nz = 3\n", text);
+        apply_change(&mut text, change(None, "w = 4"));
+        assert_eq!("w = 4", text);
+    }
+
+    #[test]
+    fn counts_utf16_code_units() {
+        // `😀` is two UTF-16 code units and four bytes.
+        let mut text = "s = \"😀é\"\n".to_owned();
+        apply_change(&mut text, change(Some(((0, 7), (0, 8))), "e"));
+        assert_eq!("s = \"😀e\"\n", text);
+    }
+
+    #[test]
+    fn clamps_positions() {
+        let mut text = "x = 1\n".to_owned();
+        apply_change(&mut text, change(Some(((0, 100), (5, 0))), " + 1"));
+        assert_eq!("x = 1 + 1", text);
+    }
+}
@@ -24,6 +24,7 @@ use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::RwLock;
 use std::time::Duration;
+use std::time::SystemTime;
 
 use dupe::Dupe;
 use lsp_server::Connection;
@@ -73,6 +74,7 @@ use starlark_syntax::slice_vec_ext::VecExt;
 use crate::error::eval_message_to_lsp_diagnostic;
 use crate::server::new_notification;
 use crate::server::server_with_connection;
+use crate::server::FileStamp;
 use crate::server::LspContext;
 use crate::server::LspEvalResult;
 use crate::server::LspServerSettings;
@@ -129,7 +131,9 @@ pub(crate) enum TestServerError {
 
 struct TestServerContext {
     file_contents: Arc<RwLock<HashMap<PathBuf, String>>>,
+    file_stamps: Arc<RwLock<HashMap<PathBuf, FileStamp>>>,
     dirs: Arc<RwLock<HashSet<PathBuf>>>,
+    enumerate_workspace: Arc<RwLock<bool>>,
     builtin_docs: Arc<HashMap<LspUrl, String>>,
     builtin_symbols: Arc<HashMap<String, LspUrl>>,
 }
@@ -290,6 +294,30 @@ impl LspContext for TestServerContext {
         Ok(self.builtin_symbols.get(symbol).cloned())
     }
 
+    fn get_workspace_files(
+        &self,
+        _workspace_roots: &[PathBuf],
+    ) -> anyhow::Result<Option<Vec<LspUrl>>> {
+        if !*self.enumerate_workspace.read().unwrap() {
+            return Ok(None);
+        }
+        Ok(Some(
+            self.file_contents
+                .read()
+                .unwrap()
+                .keys()
+                .map(|path| LspUrl::File(path.clone()))
+                .collect(),
+        ))
+    }
+
+    fn get_file_stamp(&self, uri: &LspUrl) -> Option<FileStamp> {
+        match uri {
+            LspUrl::File(path) => self.file_stamps.read().unwrap().get(path).copied(),
+            _ => None,
+        }
+    }
+
     fn get_environment(&self, _uri: &LspUrl) -> DocModule {
         DocModule {
             docs: None,
@@ -321,7 +349,11 @@ pub struct TestServer {
     /// How long to wait for messages to be received.
     recv_timeout: Duration,
     file_contents: Arc<RwLock<HashMap<PathBuf, String>>>,
+    /// When each file was last set, standing in for its modification time on disk.
+    file_stamps: Arc<RwLock<HashMap<PathBuf, FileStamp>>>,
     dirs: Arc<RwLock<HashSet<PathBuf>>>,
+    /// Whether the context can list the files in the workspace.
+    enumerate_workspace: Arc<RwLock<bool>>,
     /// If it's been received, the response payload for initialization.
     initialize_response: Option<InitializeResult>,
     /// Documentation for built in symbols.
@@ -434,10 +466,14 @@ impl TestServer {
             })
             .collect();
         let file_contents = Arc::new(RwLock::new(prelude_file_contents));
+        let file_stamps = Arc::new(RwLock::new(HashMap::new()));
         let dirs = Arc::new(RwLock::new(HashSet::new()));
+        let enumerate_workspace = Arc::new(RwLock::new(true));
         let ctx = TestServerContext {
             file_contents: file_contents.dupe(),
+            file_stamps: file_stamps.dupe(),
             dirs: dirs.dupe(),
+            enumerate_workspace: enumerate_workspace.dupe(),
             builtin_docs: builtin_docs.dupe(),
             builtin_symbols,
         };
@@ -457,7 +493,9 @@ impl TestServer {
             notifications: Default::default(),
             recv_timeout: Duration::from_secs(2),
             file_contents,
+            file_stamps,
             dirs,
+            enumerate_workspace,
             initialize_response: None,
             builtin_docs,
         };
@@ -644,16 +682,28 @@ impl TestServer {
 
     /// Send a notification saying that a file was changed with the given contents.
     pub fn change_file(&mut self, uri: Url, contents: String) -> anyhow::Result<()> {
+        self.edit_file(
+            uri,
+            vec![TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: contents,
+            }],
+        )
+    }
+
+    /// Send a notification saying that parts of a file were changed.
+    pub fn edit_file(
+        &mut self,
+        uri: Url,
+        content_changes: Vec<TextDocumentContentChangeEvent>,
+    ) -> anyhow::Result<()> {
         let change_params = DidChangeTextDocumentParams {
             text_document: VersionedTextDocumentIdentifier {
                 uri,
                 version: self.next_document_version(),
             },
-            content_changes: vec![TextDocumentContentChangeEvent {
-                range: None,
-                range_length: None,
-                text: contents,
-            }],
+            content_changes,
         };
         let change_notification = new_notification::<DidChangeTextDocument>(change_params);
         self.send_notification(change_notification)?;
@@ -666,11 +716,25 @@ impl TestServer {
         if !path.is_absolute() {
             Err(TestServerError::SetFileNotAbsolute(path).into())
         } else {
+            let stamp = FileStamp {
+                modified: Some(SystemTime::now()),
+                len: contents.len() as u64,
+            };
+            self.file_stamps
+                .write()
+                .unwrap()
+                .insert(path.clone(), stamp);
             self.file_contents.write().unwrap().insert(path, contents);
             Ok(())
         }
     }
 
+    /// Make the context unable to list the files in the workspace, like contexts which
+    /// only know about the files they are asked for.
+    pub fn disable_workspace_enumeration(&self) {
+        *self.enumerate_workspace.write().unwrap() = false;
+    }
+
     /// Configure a path to be "a directory". This will return IsADirectory as an
     /// error from get_load_contents
     pub fn mkdir(&self, uri: Url) {
@@ -0,0 +1,1214 @@
+/*
+ * Copyright 2018 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Canonical formatting of Starlark code, see [`AstModule::format`].
+//!
+//! The output is printed from the AST, so all the layout decisions are ours, except for:
+//!
+//! * Comments, which we get from the lexer and attach to the closest statement or collection item.
+//! * Literals, which are printed as written, because f-strings are desugared in the AST.
+//! * Blank lines, at most one of which is kept between statements and between collection items.
+//! * Trailing commas: a collection with a trailing comma stays one item per line.
+
+use dupe::Dupe;
+
+use crate::codemap::CodeMap;
+use crate::codemap::Pos;
+use crate::codemap::Span;
+use crate::lexer::Lexer;
+use crate::lexer::Token;
+use crate::syntax::ast::ArgumentP;
+use crate::syntax::ast::AssignTargetP;
+use crate::syntax::ast::AstAssignTarget;
+use crate::syntax::ast::AstExpr;
+use crate::syntax::ast::AstLiteral;
+use crate::syntax::ast::AstNoPayload;
+use crate::syntax::ast::AstParameter;
+use crate::syntax::ast::AstStmt;
+use crate::syntax::ast::BinOp;
+use crate::syntax::ast::ClauseP;
+use crate::syntax::ast::DefP;
+use crate::syntax::ast::Expr;
+use crate::syntax::ast::ForClauseP;
+use crate::syntax::ast::LoadP;
+use crate::syntax::ast::ParameterP;
+use crate::syntax::ast::Stmt;
+use crate::syntax::AstModule;
+use crate::syntax::Dialect;
+
+/// One level of indentation.
+const INDENT: &str = "    ";
+
+/// Collections, calls and parameter lists longer than this are split one item per line.
+const MAX_WIDTH: usize = 100;
+
+/// Precedence of lambda, the loosest binding expression.
+const PREC_TEST: u8 = 0;
+/// Precedence of `or`, required for comprehension clauses and conditional branches.
+const PREC_OR: u8 = 2;
+/// Precedence of `not`.
+const PREC_NOT: u8 = 4;
+/// Precedence of comparisons, whose operands must bind tighter.
+const PREC_COMPARISON: u8 = 5;
+/// Precedence of unary `-`, `+` and `~`.
+const PREC_UNARY: u8 = 12;
+/// Precedence of atoms, calls, attributes and indexing.
+const PREC_PRIMARY: u8 = 13;
+
+impl AstModule {
+    /// Format the module in the canonical style, keeping its comments.
+    ///
+    /// Formatting is idempotent and does not change the meaning of the code.
+    ///
+    /// ```
+    /// use starlark_syntax::syntax::AstModule;
+    /// use starlark_syntax::syntax::Dialect;
+    ///
+    /// let ast = AstModule::parse(
+    ///     "x.star",
+    ///     "def f(x,y = 1) :\n  return [x,y] # Both.\n".to_owned(),
+    ///     &Dialect::Extended,
+    /// )
+    /// .unwrap();
+    /// assert_eq!(
+    ///     ast.format(),
+    ///     "def f(x, y = 1):\n    return [x, y]  # Both.\n"
+    /// );
+    /// ```
+    pub fn format(&self) -> String {
+        let mut printer = Printer::new(&self.codemap, &self.dialect);
+        let mut out = String::new();
+        printer.block(&mut out, &self.statement, 0, printer.source.len());
+        // Anything we could not attach goes at the end rather than being lost.
+        printer.own_line_comments(&mut out, 0, usize::MAX);
+        match out.strip_prefix('\n') {
+            Some(out) => format!("{}\n", out),
+            None => out,
+        }
+    }
+}
+
+/// A comment, as found by the lexer.
+struct Comment {
+    begin: usize,
+    end: usize,
+    line: usize,
+    column: usize,
+    /// Nothing but whitespace precedes the comment on its line.
+    own_line: bool,
+}
+
+/// Where an expression is printed.
+#[derive(Clone, Copy)]
+struct Ctx {
+    /// Indentation level of the line the expression starts on.
+    indent: usize,
+    /// Column the expression starts at.
+    column: usize,
+    /// Print on one line, the enclosing collection has already decided that it fits.
+    flat: bool,
+    /// Inside brackets, where lines can be split anywhere.
+    nested: bool,
+}
+
+impl Ctx {
+    fn block(indent: usize) -> Ctx {
+        Ctx {
+            indent,
+            column: indent * INDENT.len(),
+            flat: false,
+            nested: false,
+        }
+    }
+
+    fn nested(self) -> Ctx {
+        Ctx {
+            nested: true,
+            ..self
+        }
+    }
+
+    /// The context right after printing `text` in this context.
+    fn after(self, text: &str) -> Ctx {
+        let column = match text.rfind('\n') {
+            Some(i) => width(&text[i + 1..]),
+            None => self.column + width(text),
+        };
+        Ctx { column, ..self }
+    }
+
+    fn flat(self) -> Ctx {
+        Ctx { flat: true, ..self }
+    }
+
+    fn fits(self, text: &str, reserve: usize) -> bool {
+        let first_line = text.split('\n').next().unwrap_or_default();
+        self.column + width(first_line) + reserve <= MAX_WIDTH
+    }
+}
+
+fn width(text: &str) -> usize {
+    text.chars().count()
+}
+
+fn indentation(indent: usize) -> String {
+    INDENT.repeat(indent)
+}
+
+/// A bracketed list of items, which is printed either on one line or one item per line.
+struct Bracketed<'s> {
+    open: &'s str,
+    close: &'s str,
+    /// Spans of the items, to place the comments between them.
+    items: &'s [Span],
+    /// Position of the closing bracket.
+    end: usize,
+    /// Items are separated by commas, rather than spaces like comprehension clauses.
+    commas: bool,
+    /// Print one item per line even if they fit on one.
+    explode: bool,
+    /// Width of the text following the closing bracket on the same line.
+    reserve: usize,
+}
+
+struct Printer<'a> {
+    codemap: &'a CodeMap,
+    source: &'a str,
+    comments: Vec<Comment>,
+    taken: Vec<bool>,
+    /// Index of the first comment which might not have been printed yet.
+    next_comment: usize,
+    /// End of the last statement, item or comment printed, to preserve blank lines after it.
+    /// `None` at the start of a block or a collection, where we never put blank lines.
+    last: Option<usize>,
+}
+
+impl<'a> Printer<'a> {
+    fn new(codemap: &'a CodeMap, dialect: &Dialect) -> Self {
+        let source = codemap.source();
+        let mut comments = Vec::new();
+        // The module parsed, so there are no lexer errors.
+        for (begin, token, end) in Lexer::new(source, dialect, codemap.dupe()).flatten() {
+            if let Token::Comment(_) = token {
+                let line = codemap.find_line(Pos::new(begin as u32));
+                let line_begin = codemap.line_span(line).begin().get() as usize;
+                let prefix = &source[line_begin..begin];
+                comments.push(Comment {
+                    begin,
+                    end: begin + source[begin..end].trim_end().len(),
+                    line,
+                    column: width(prefix),
+                    own_line: prefix.trim().is_empty(),
+                });
+            }
+        }
+        let taken = vec![false; comments.len()];
+        Printer {
+            codemap,
+            source,
+            comments,
+            taken,
+            next_comment: 0,
+            last: None,
+        }
+    }
+
+    fn text(&self, span: Span) -> &'a str {
+        &self.source[span.begin().get() as usize..span.end().get() as usize]
+    }
+
+    fn line(&self, pos: usize) -> usize {
+        self.codemap.find_line(Pos::new(pos as u32))
+    }
+
+    /// End of the line containing `pos`, including the line terminator.
+    fn line_end(&self, pos: usize) -> usize {
+        self.codemap.line_span(self.line(pos)).end().get() as usize
+    }
+
+    fn column(&self, pos: usize) -> usize {
+        let line_begin = self.codemap.line_span(self.line(pos)).begin().get() as usize;
+        width(&self.source[line_begin..pos])
+    }
+
+    /// Position of the first thing after `pos` which is not whitespace, a line continuation or a
+    /// comment.
+    fn skip_trivia(&self, mut pos: usize) -> usize {
+        let bytes = self.source.as_bytes();
+        while let Some(b) = bytes.get(pos) {
+            match b {
+                b' ' | b'\t' | b'\r' | b'\n' | b'\\' => pos += 1,
+                b'#' => {
+                    while bytes.get(pos).is_some_and(|b| *b != b'\n') {
+                        pos += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+        pos
+    }
+
+    /// Whether the item ending at `end` is followed by a comma, which keeps its collection one
+    /// item per line.
+    fn trailing_comma(&self, end: usize) -> bool {
+        self.source.as_bytes().get(self.skip_trivia(end)) == Some(&b',')
+    }
+
+    /// Whether the source has a blank line between `from` and `to`.
+    fn blank_line_between(&self, from: usize, to: usize) -> bool {
+        let from = self.source[..from].trim_end().len();
+        if from >= to {
+            return false;
+        }
+        let lines: Vec<&str> = self.source[from..to].split('\n').collect();
+        lines.len() > 2
+            && lines[1..lines.len() - 1]
+                .iter()
+                .any(|l| l.trim().is_empty())
+    }
+
+    fn blank_line_before(&self, begin: usize) -> bool {
+        self.last
+            .is_some_and(|last| self.blank_line_between(last, begin))
+    }
+
+    fn comments_in(&self, begin: usize, end: usize) -> bool {
+        let i = self.comments.partition_point(|c| c.begin < begin);
+        self.comments.get(i).is_some_and(|c| c.begin < end)
+    }
+
+    /// Take all the comments before `pos` which were not printed yet.
+    fn take_until(&mut self, pos: usize) -> Vec<usize> {
+        let mut res = Vec::new();
+        for i in self.next_comment..self.comments.len() {
+            if self.comments[i].begin >= pos {
+                break;
+            }
+            if !self.taken[i] {
+                self.taken[i] = true;
+                res.push(i);
+            }
+        }
+        while self.taken.get(self.next_comment) == Some(&true) {
+            self.next_comment += 1;
+        }
+        res
+    }
+
+    /// Take the comments up to the end of the line where something ending at `end` ends, but
+    /// not after `limit`. Returns the comments to put before it, and the one to put after it.
+    fn take_line_end(&mut self, end: usize, limit: usize) -> (Vec<usize>, Option<usize>) {
+        let line = self.line(end);
+        let mut comments = self.take_until(self.line_end(end).min(limit));
+        let trailing = match comments.last() {
+            Some(&c) if !self.comments[c].own_line && self.comments[c].line == line => {
+                comments.pop()
+            }
+            _ => None,
+        };
+        (comments, trailing)
+    }
+
+    /// Take the comment at the end of the line where something ending at `end` ends, if it is
+    /// before `limit`.
+    fn take_trailing(&mut self, end: usize, limit: usize) -> Option<usize> {
+        let line = self.line(end);
+        let i = (self.next_comment..self.comments.len()).find(|i| !self.taken[*i])?;
+        let comment = &self.comments[i];
+        if comment.own_line || comment.line != line || comment.begin >= limit {
+            return None;
+        }
+        self.take_until(comment.end).pop()
+    }
+
+    fn comment_text(&self, i: usize) -> &'a str {
+        &self.source[self.comments[i].begin..self.comments[i].end]
+    }
+
+    fn push_comment_line(&mut self, out: &mut String, indent: usize, i: usize) {
+        out.push('\n');
+        out.push_str(&indentation(indent));
+        out.push_str(self.comment_text(i));
+        self.last = Some(self.comments[i].end);
+    }
+
+    /// Print the comments before `pos` on their own lines.
+    fn own_line_comments(&mut self, out: &mut String, indent: usize, pos: usize) {
+        for i in self.take_until(pos) {
+            if self.blank_line_before(self.comments[i].begin) {
+                out.push('\n');
+            }
+            self.push_comment_line(out, indent, i);
+        }
+    }
+
+    /// Print a line of code which ends at `end` in the source, together with the comments at the
+    /// end of that line and any comments inside it we could not place.
+    fn push_line(
+        &mut self,
+        out: &mut String,
+        indent: usize,
+        blank_line: bool,
+        text: &str,
+        end: usize,
+        limit: usize,
+    ) {
+        let (orphans, trailing) = self.take_line_end(end, limit);
+        if blank_line {
+            out.push('\n');
+        }
+        for i in orphans {
+            self.push_comment_line(out, indent, i);
+        }
+        out.push('\n');
+        out.push_str(&indentation(indent));
+        out.push_str(text);
+        if let Some(i) = trailing {
+            out.push_str("  ");
+            out.push_str(self.comment_text(i));
+        }
+    }
+
+    fn block(&mut self, out: &mut String, stmt: &AstStmt, indent: usize, limit: usize) {
+        let mut stmts = Vec::new();
+        flatten(stmt, &mut stmts);
+        let column = match stmts.first() {
+            Some(stmt) => self.column(stmt.span.begin().get() as usize),
+            None => 0,
+        };
+        for (i, stmt) in stmts.iter().enumerate() {
+            let next = stmts
+                .get(i + 1)
+                .map_or(limit, |s| s.span.begin().get() as usize);
+            self.own_line_comments(out, indent, stmt.span.begin().get() as usize);
+            self.stmt(out, stmt, indent, next);
+        }
+        // Comments after the last statement belong to the block if they are indented like it.
+        while let Some(i) = (self.next_comment..self.comments.len()).find(|i| !self.taken[*i]) {
+            let comment = &self.comments[i];
+            if comment.begin >= limit || comment.column < column {
+                break;
+            }
+            if self.blank_line_before(comment.begin) {
+                out.push('\n');
+            }
+            self.taken[i] = true;
+            self.push_comment_line(out, indent, i);
+        }
+    }
+
+    /// Print the header of a compound statement, followed by its body.
+    fn header(
+        &mut self,
+        out: &mut String,
+        indent: usize,
+        blank_line: bool,
+        text: &str,
+        header_end: usize,
+        body: &AstStmt,
+    ) {
+        let body_begin = body.span.begin().get() as usize;
+        self.push_line(out, indent, blank_line, text, header_end, body_begin);
+        self.last = None;
+    }
+
+    fn stmt(&mut self, out: &mut String, stmt: &AstStmt, indent: usize, limit: usize) {
+        let begin = stmt.span.begin().get() as usize;
+        match &stmt.node {
+            Stmt::If(cond, then) => {
+                let blank_line = self.blank_line_before(begin);
+                self.if_stmt(out, "if", blank_line, cond, then, None, indent, limit)
+            }
+            Stmt::IfElse(cond, then_else) => {
+                let blank_line = self.blank_line_before(begin);
+                let (then, els) = &**then_else;
+                self.if_stmt(out, "if", blank_line, cond, then, Some(els), indent, limit)
+            }
+            Stmt::For(f) => {
+                let blank_line = self.blank_line_before(begin);
+                let ctx = Ctx::block(indent);
+                let var = self.target(&f.var, true, ctx.after("for "));
+                let mut text = format!("for {} in ", var);
+                text += &self.expr(&f.over, PREC_TEST, ctx.after(&text));
+                text.push(':');
+                let header_end = f.over.span.end().get() as usize;
+                self.header(out, indent, blank_line, &text, header_end, &f.body);
+                self.block(out, &f.body, indent + 1, limit);
+            }
+            Stmt::Def(def) => {
+                let blank_line = self.blank_line_before(begin);
+                let (text, header_end) = self.def_header(def, indent);
+                self.header(out, indent, blank_line, &text, header_end, &def.body);
+                self.block(out, &def.body, indent + 1, limit);
+            }
+            Stmt::Statements(_) => self.block(out, stmt, indent, limit),
+            _ => {
+                let blank_line = self.blank_line_before(begin);
+                let text = self.simple_stmt(stmt, Ctx::block(indent));
+                let end = stmt.span.end().get() as usize;
+                self.push_line(out, indent, blank_line, &text, end, limit);
+                self.last = Some(end);
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn if_stmt(
+        &mut self,
+        out: &mut String,
+        keyword: &str,
+        blank_line: bool,
+        cond: &AstExpr,
+        then: &AstStmt,
+        els: Option<&AstStmt>,
+        indent: usize,
+        limit: usize,
+    ) {
+        let ctx = Ctx::block(indent).after(keyword).after(" ");
+        let text = format!("{} {}:", keyword, self.expr(cond, PREC_TEST, ctx));
+        let header_end = cond.span.end().get() as usize;
+        self.header(out, indent, blank_line, &text, header_end, then);
+        let Some(els) = els else {
+            self.block(out, then, indent + 1, limit);
+            return;
+        };
+        let else_keyword = self.else_keyword(then, els);
+        self.block(out, then, indent + 1, else_keyword);
+        self.own_line_comments(out, indent, else_keyword);
+        match &els.node {
+            Stmt::If(cond, then) if self.is_elif(els) => {
+                self.if_stmt(out, "elif", false, cond, then, None, indent, limit)
+            }
+            Stmt::IfElse(cond, then_else) if self.is_elif(els) => {
+                let (then, els) = &**then_else;
+                self.if_stmt(out, "elif", false, cond, then, Some(els), indent, limit)
+            }
+            _ => {
+                self.header(out, indent, false, "else:", else_keyword, els);
+                self.block(out, els, indent + 1, limit);
+            }
+        }
+    }
+
+    /// An `elif` is parsed as an `if` statement in the `else` branch, but the span of the
+    /// latter starts at the `if` keyword.
+    fn is_elif(&self, els: &AstStmt) -> bool {
+        !starts_with_keyword(&self.source[els.span.begin().get() as usize..], "if")
+    }
+
+    /// Position of the `else` or `elif` keyword between the two branches.
+    fn else_keyword(&self, then: &AstStmt, els: &AstStmt) -> usize {
+        let els_begin = els.span.begin().get() as usize;
+        let then_end = (then.span.end().get() as usize).saturating_sub(1);
+        for line in self.line(then_end) + 1..=self.line(els_begin) {
+            let text = self.codemap.source_line(line);
+            let trimmed = text.trim_start();
+            if starts_with_keyword(trimmed, "else") || starts_with_keyword(trimmed, "elif") {
+                let line_begin = self.codemap.line_span(line).begin().get() as usize;
+                return line_begin + text.len() - trimmed.len();
+            }
+        }
+        els_begin
+    }
+
+    /// Returns the header text, and a position on its last line.
+    fn def_header(&mut self, def: &DefP<AstNoPayload>, indent: usize) -> (String, usize) {
+        let ctx = Ctx::block(indent);
+        let name_end = def.name.span.end().get() as usize;
+        let ret = match &def.return_type {
+            Some(ty) => format!(" -> {}", self.expr(&ty.node.expr, PREC_TEST, ctx.flat())),
+            None => String::new(),
+        };
+        let open = format!("def {}(", def.name.node.ident);
+        let items: Vec<Span> = def.params.iter().map(|p| p.span).collect();
+        let (end, explode) = match items.last() {
+            Some(last) => {
+                let last_end = last.end().get() as usize;
+                let mut end = self.skip_trivia(last_end);
+                let magic = self.source.as_bytes().get(end) == Some(&b',');
+                if magic {
+                    end = self.skip_trivia(end + 1);
+                }
+                let explode = magic
+                    || self.comments_in(name_end, end)
+                    || def.params.iter().any(|p| self.param_must_break(p));
+                (end, explode)
+            }
+            None => (name_end, false),
+        };
+        let mut text = self.bracketed(
+            Bracketed {
+                open: &open,
+                close: ")",
+                items: &items,
+                end,
+                commas: true,
+                explode,
+                reserve: ret.len() + 1,
+            },
+            ctx,
+            &mut |this, i, ctx| this.param(&def.params[i], ctx),
+        );
+        text += &ret;
+        text.push(':');
+        let header_end = match &def.return_type {
+            Some(ty) => ty.span.end().get() as usize,
+            None if items.is_empty() => name_end,
+            None => end,
+        };
+        (text, header_end)
+    }
+
+    fn simple_stmt(&mut self, stmt: &AstStmt, ctx: Ctx) -> String {
+        match &stmt.node {
+            Stmt::Break => "break".to_owned(),
+            Stmt::Continue => "continue".to_owned(),
+            Stmt::Pass => "pass".to_owned(),
+            Stmt::Return(None) => "return".to_owned(),
+            Stmt::Return(Some(e)) => {
+                format!("return {}", self.expr_list(e, ctx.after("return ")))
+            }
+            Stmt::Expression(e) => self.expr(e, PREC_TEST, ctx),
+            Stmt::Assign(assign) => {
+                let mut text = self.target(&assign.lhs, true, ctx);
+                if let Some(ty) = &assign.ty {
+                    text += ": ";
+                    text += &self.expr(&ty.node.expr, PREC_TEST, ctx.after(&text));
+                }
+                text += " = ";
+                text += &self.expr_list(&assign.rhs, ctx.after(&text));
+                text
+            }
+            Stmt::AssignModify(lhs, op, rhs) => {
+                let mut text = self.target(lhs, true, ctx);
+                text += &op.to_string();
+                text += &self.expr_list(rhs, ctx.after(&text));
+                text
+            }
+            Stmt::Load(load) => self.load(stmt, load, ctx),
+            Stmt::Statements(_) | Stmt::If(..) | Stmt::IfElse(..) | Stmt::For(_) | Stmt::Def(_) => {
+                unreachable!("not a simple statement")
+            }
+        }
+    }
+
+    fn load(&mut self, stmt: &AstStmt, load: &LoadP<AstNoPayload>, ctx: Ctx) -> String {
+        let mut items = vec![load.module.span];
+        items.extend(load.args.iter().map(|arg| arg.span()));
+        let end = stmt.span.end().get() as usize - 1;
+        let explode = load.args.last().is_some_and(|arg| arg.comma.is_some())
+            || self.comments_in(stmt.span.begin().get() as usize, end);
+        self.bracketed(
+            Bracketed {
+                open: "load(",
+                close: ")",
+                items: &items,
+                end,
+                commas: true,
+                explode,
+                reserve: 0,
+            },
+            ctx,
+            &mut |this, i, _| {
+                if i == 0 {
+                    return this.text(load.module.span).to_owned();
+                }
+                let arg = &load.args[i - 1];
+                if arg.local.span == arg.their.span {
+                    this.text(arg.their.span).to_owned()
+                } else {
+                    format!("{} = {}", arg.local.node.ident, this.text(arg.their.span))
+                }
+            },
+        )
+    }
+
+    fn bracketed(
+        &mut self,
+        b: Bracketed,
+        ctx: Ctx,
+        render: &mut dyn FnMut(&mut Self, usize, Ctx) -> String,
+    ) -> String {
+        if ctx.flat || !b.explode {
+            let mut text = b.open.to_owned();
+            for i in 0..b.items.len() {
+                if i != 0 {
+                    text += if b.commas { ", " } else { " " };
+                }
+                text += &render(self, i, ctx.flat().after(&text));
+            }
+            text += b.close;
+            if ctx.flat || ctx.fits(&text, b.reserve) {
+                return text;
+            }
+        }
+
+        let inner = ctx.indent + 1;
+        let mut text = b.open.to_owned();
+        let first = b.items.first().map_or(b.end, |s| s.begin().get() as usize);
+        let mut comments = self.take_until(first).into_iter();
+        match comments.next() {
+            Some(i) if !self.comments[i].own_line => {
+                text += "  ";
+                text += self.comment_text(i);
+            }
+            Some(i) => self.push_comment_line(&mut text, inner, i),
+            None => {}
+        }
+        for i in comments {
+            self.push_comment_line(&mut text, inner, i);
+        }
+        self.last = None;
+        for (i, span) in b.items.iter().enumerate() {
+            let begin = span.begin().get() as usize;
+            let end = span.end().get() as usize;
+            self.own_line_comments(&mut text, inner, begin);
+            let blank_line = self.blank_line_before(begin);
+            let mut item = render(self, i, Ctx::block(inner).nested());
+            if b.commas {
+                item.push(',');
+            }
+            let next = b
+                .items
+                .get(i + 1)
+                .map_or(b.end, |s| s.begin().get() as usize);
+            self.push_line(&mut text, inner, blank_line, &item, end, next);
+            self.last = Some(end);
+        }
+        self.own_line_comments(&mut text, inner, b.end);
+        text.push('\n');
+        text += &indentation(ctx.indent);
+        text += b.close;
+        text
+    }
+
+    /// Print an expression where a tuple does not need parentheses.
+    fn expr_list(&mut self, e: &AstExpr, ctx: Ctx) -> String {
+        match &e.node {
+            Expr::Tuple(xs) => self.tuple(e, xs, false, ctx),
+            _ => self.expr(e, PREC_TEST, ctx),
+        }
+    }
+
+    /// Print an expression, in parentheses if it binds looser than `prec`.
+    fn expr(&mut self, e: &AstExpr, prec: u8, ctx: Ctx) -> String {
+        let parens = precedence(e) < prec;
+        // Operators in brackets can be split one operand per line.
+        if !ctx.flat && matches!(e.node, Expr::Op(..)) {
+            let add_parens = parens || self.parenthesized(e);
+            if add_parens || ctx.nested {
+                let text = self.expr_inner(e, ctx.flat().after("("));
+                let text = if parens { format!("({})", text) } else { text };
+                if !self.must_break(e) && ctx.fits(&text, 0) {
+                    return text;
+                }
+                return self.operands(e, add_parens, ctx);
+            }
+        }
+        if parens {
+            format!("({})", self.expr_inner(e, ctx.after("(").nested()))
+        } else {
+            self.expr_inner(e, ctx)
+        }
+    }
+
+    /// Whether the source has parentheses around the expression, which are not those of a call.
+    fn parenthesized(&self, e: &AstExpr) -> bool {
+        let before = self.source[..e.span.begin().get() as usize].trim_end();
+        let Some(before) = before.strip_suffix('(') else {
+            return false;
+        };
+        let after = self.skip_trivia(e.span.end().get() as usize);
+        self.source.as_bytes().get(after) == Some(&b')')
+            && !before
+                .chars()
+                .next_back()
+                .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == ')' || c == ']')
+    }
+
+    /// Print a chain of operators of the same precedence one operand per line, in parentheses
+    /// unless we are inside brackets already.
+    fn operands(&mut self, e: &AstExpr, parens: bool, ctx: Ctx) -> String {
+        let prec = precedence(e);
+        let mut first = e;
+        let mut rest = Vec::new();
+        while let Expr::Op(l, op, r) = &first.node {
+            if binop_precedence(*op) != prec {
+                break;
+            }
+            rest.push((*op, &**r));
+            first = l;
+            if prec == PREC_COMPARISON {
+                break;
+            }
+        }
+        let r_prec = prec + 1;
+        let l_prec = if prec == PREC_COMPARISON {
+            r_prec
+        } else {
+            prec
+        };
+        let mut operands = vec![(first, l_prec, rest.last().map(|(op, _)| *op))];
+        for i in (0..rest.len()).rev() {
+            let op = i.checked_sub(1).map(|i| rest[i].0);
+            operands.push((rest[i].1, r_prec, op));
+        }
+
+        let inner = Ctx::block(ctx.indent + usize::from(parens)).nested();
+        let mut text = String::new();
+        if parens {
+            text.push('(');
+        }
+        self.last = None;
+        for (i, (x, prec, op)) in operands.iter().enumerate() {
+            let begin = x.span.begin().get() as usize;
+            let end = x.span.end().get() as usize;
+            let next = operands
+                .get(i + 1)
+                .map(|(x, ..)| x.span.begin().get() as usize);
+            if i == 0 && !parens {
+                // The first operand continues the line we are on.
+                text += &self.expr(x, *prec, ctx);
+                text += op.map(|op| op.to_string()).unwrap_or_default().trim_end();
+                if let Some(c) = next.and_then(|next| self.take_trailing(end, next)) {
+                    text += "  ";
+                    text += self.comment_text(c);
+                }
+            } else {
+                self.own_line_comments(&mut text, inner.indent, begin);
+                let mut item = self.expr(x, *prec, inner);
+                item += op.map(|op| op.to_string()).unwrap_or_default().trim_end();
+                match next {
+                    Some(next) => self.push_line(&mut text, inner.indent, false, &item, end, next),
+                    // The comments after the last operand belong to what follows.
+                    None if !parens => {
+                        text.push('\n');
+                        text += &indentation(inner.indent);
+                        text += &item;
+                    }
+                    None => self.push_line(&mut text, inner.indent, false, &item, end, usize::MAX),
+                }
+            }
+            self.last = Some(end);
+        }
+        if parens {
+            text.push('\n');
+            text += &indentation(ctx.indent);
+            text.push(')');
+        }
+        text
+    }
+
+    fn expr_inner(&mut self, e: &AstExpr, ctx: Ctx) -> String {
+        let end = e.span.end().get() as usize;
+        match &e.node {
+            Expr::Tuple(xs) => self.tuple(e, xs, true, ctx),
+            Expr::Dot(x, attr) => {
+                format!("{}.{}", self.expr(x, PREC_PRIMARY, ctx), attr.node)
+            }
+            Expr::Call(f, args) => {
+                let callee = self.expr(f, PREC_PRIMARY, ctx);
+                let items: Vec<Span> = args.iter().map(|a| a.span).collect();
+                let explode = !ctx.flat
+                    && (self.comments_in(f.span.end().get() as usize, end)
+                        || args
+                            .last()
+                            .is_some_and(|a| self.trailing_comma(a.span.end().get() as usize))
+                        || args.iter().any(|a| self.magic_comma(a.expr())));
+                let open = format!("{}(", callee);
+                self.bracketed(
+                    Bracketed {
+                        open: &open,
+                        close: ")",
+                        items: &items,
+                        end: end - 1,
+                        commas: true,
+                        explode,
+                        reserve: 0,
+                    },
+                    ctx,
+                    &mut |this, i, ctx| match &args[i].node {
+                        ArgumentP::Positional(x) => this.expr(x, PREC_TEST, ctx),
+                        ArgumentP::Named(name, x) => {
+                            let prefix = format!("{} = ", name.node);
+                            let x = this.expr(x, PREC_TEST, ctx.after(&prefix));
+                            prefix + &x
+                        }
+                        ArgumentP::Args(x) => {
+                            format!("*{}", this.expr(x, PREC_TEST, ctx.after("*")))
+                        }
+                        ArgumentP::KwArgs(x) => {
+                            format!("**{}", this.expr(x, PREC_TEST, ctx.after("**")))
+                        }
+                    },
+                )
+            }
+            Expr::Index(x_i) => {
+                let (x, i) = &**x_i;
+                let mut text = self.expr(x, PREC_PRIMARY, ctx);
+                text.push('[');
+                text += &self.expr(i, PREC_TEST, ctx.after(&text));
+                text.push(']');
+                text
+            }
+            Expr::Index2(x_i0_i1) => {
+                let (x, i0, i1) = &**x_i0_i1;
+                let mut text = self.expr(x, PREC_PRIMARY, ctx);
+                text.push('[');
+                text += &self.expr(i0, PREC_TEST, ctx.after(&text));
+                text += ", ";
+                text += &self.expr(i1, PREC_TEST, ctx.after(&text));
+                text.push(']');
+                text
+            }
+            Expr::Slice(x, i0, i1, i2) => {
+                let mut text = self.expr(x, PREC_PRIMARY, ctx);
+                text.push('[');
+                if let Some(i0) = i0 {
+                    text += &self.expr(i0, PREC_TEST, ctx.after(&text));
+                }
+                text.push(':');
+                if let Some(i1) = i1 {
+                    text += &self.expr(i1, PREC_TEST, ctx.after(&text));
+                }
+                if let Some(i2) = i2 {
+                    text.push(':');
+                    text += &self.expr(i2, PREC_TEST, ctx.after(&text));
+                }
+                text.push(']');
+                text
+            }
+            Expr::Identifier(x) => x.node.ident.clone(),
+            Expr::Lambda(lambda) => {
+                let mut text = "lambda".to_owned();
+                for (i, p) in lambda.params.iter().enumerate() {
+                    text += if i == 0 { " " } else { ", " };
+                    text += &self.param(p, ctx.flat().after(&text));
+                }
+                text += ": ";
+                text += &self.expr(&lambda.body, PREC_TEST, ctx.after(&text));
+                text
+            }
+            Expr::Literal(AstLiteral::Ellipsis) => "...".to_owned(),
+            // Print literals as written, we don't want to change escapes or number bases.
+            Expr::Literal(_) | Expr::FString(_) => self.text(e.span).to_owned(),
+            Expr::Not(x) => format!("not {}", self.expr(x, PREC_NOT, ctx.after("not "))),
+            Expr::Minus(x) => format!("-{}", self.expr(x, PREC_UNARY, ctx.after("-"))),
+            Expr::Plus(x) => format!("+{}", self.expr(x, PREC_UNARY, ctx.after("+"))),
+            Expr::BitNot(x) => format!("~{}", self.expr(x, PREC_UNARY, ctx.after("~"))),
+            Expr::Op(l, op, r) => {
+                let prec = binop_precedence(*op);
+                // Comparisons do not chain, both operands must bind tighter.
+                let (l_prec, r_prec) = if prec == PREC_COMPARISON {
+                    (prec + 1, prec + 1)
+                } else {
+                    (prec, prec + 1)
+                };
+                let mut text = self.expr(l, l_prec, ctx);
+                text += &op.to_string();
+                text += &self.expr(r, r_prec, ctx.after(&text));
+                text
+            }
+            Expr::If(c_t_f) => {
+                let (cond, then, els) = &**c_t_f;
+                let mut text = self.expr(then, PREC_OR, ctx);
+                text += " if ";
+                text += &self.expr(cond, PREC_OR, ctx.after(&text));
+                text += " else ";
+                text += &self.expr(els, PREC_TEST, ctx.after(&text));
+                text
+            }
+            Expr::List(xs) => {
+                let items: Vec<Span> = xs.iter().map(|x| x.span).collect();
+                let explode = !ctx.flat && self.must_break(e);
+                self.bracketed(
+                    Bracketed {
+                        open: "[",
+                        close: "]",
+                        items: &items,
+                        end: end - 1,
+                        commas: true,
+                        explode,
+                        reserve: 0,
+                    },
+                    ctx,
+                    &mut |this, i, ctx| this.expr(&xs[i], PREC_TEST, ctx),
+                )
+            }
+            Expr::Dict(xs) => {
+                let items: Vec<Span> = xs.iter().map(|(k, v)| k.span.merge(v.span)).collect();
+                let explode = !ctx.flat && self.must_break(e);
+                self.bracketed(
+                    Bracketed {
+                        open: "{",
+                        close: "}",
+                        items: &items,
+                        end: end - 1,
+                        commas: true,
+                        explode,
+                        reserve: 0,
+                    },
+                    ctx,
+                    &mut |this, i, ctx| this.entry(&xs[i].0, &xs[i].1, ctx),
+                )
+            }
+            Expr::ListComprehension(x, for_, clauses) => {
+                self.comprehension(e, "[", "]", x.span, for_, clauses, ctx, &mut |this, ctx| {
+                    this.expr(x, PREC_TEST, ctx)
+                })
+            }
+            Expr::DictComprehension(k_v, for_, clauses) => {
+                let (k, v) = &**k_v;
+                let span = k.span.merge(v.span);
+                self.comprehension(e, "{", "}", span, for_, clauses, ctx, &mut |this, ctx| {
+                    this.entry(k, v, ctx)
+                })
+            }
+        }
+    }
+
+    fn entry(&mut self, k: &AstExpr, v: &AstExpr, ctx: Ctx) -> String {
+        let mut text = self.expr(k, PREC_TEST, ctx);
+        text += ": ";
+        text += &self.expr(v, PREC_TEST, ctx.after(&text));
+        text
+    }
+
+    fn tuple(&mut self, e: &AstExpr, xs: &[AstExpr], parens: bool, ctx: Ctx) -> String {
+        let explode = !ctx.flat && self.must_break(e);
+        if let [x] = xs {
+            if !explode {
+                let text = format!("({},)", self.expr(x, PREC_TEST, ctx.flat().after("(")));
+                if ctx.flat || ctx.fits(&text, 0) {
+                    return text;
+                }
+            }
+        }
+        if !parens && !explode && xs.len() > 1 {
+            let mut text = String::new();
+            for (i, x) in xs.iter().enumerate() {
+                if i != 0 {
+                    text += ", ";
+                }
+                text += &self.expr(x, PREC_TEST, ctx.flat().after(&text));
+            }
+            if ctx.flat || ctx.fits(&text, 0) {
+                return text;
+            }
+        }
+        // The parentheses are not part of the span.
+        let mut end = e.span.end().get() as usize;
+        let close = self.skip_trivia(end);
+        if self.source.as_bytes().get(close) == Some(&b')') {
+            end = close;
+        }
+        let items: Vec<Span> = xs.iter().map(|x| x.span).collect();
+        self.bracketed(
+            Bracketed {
+                open: "(",
+                close: ")",
+                items: &items,
+                end,
+                commas: true,
+                // A tuple of one element needs the trailing comma.
+                explode: explode || xs.len() == 1,
+                reserve: 0,
+            },
+            ctx,
+            &mut |this, i, ctx| this.expr(&xs[i], PREC_TEST, ctx),
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn comprehension(
+        &mut self,
+        e: &AstExpr,
+        open: &str,
+        close: &str,
+        first: Span,
+        for_: &ForClauseP<AstNoPayload>,
+        clauses: &[ClauseP<AstNoPayload>],
+        ctx: Ctx,
+        render_first: &mut dyn FnMut(&mut Self, Ctx) -> String,
+    ) -> String {
+        let mut items = vec![first, for_.var.span.merge(for_.over.span)];
+        items.extend(clauses.iter().map(|c| match c {
+            ClauseP::For(f) => f.var.span.merge(f.over.span),
+            ClauseP::If(x) => x.span,
+        }));
+        let explode = !ctx.flat && self.must_break(e);
+        self.bracketed(
+            Bracketed {
+                open,
+                close,
+                items: &items,
+                end: e.span.end().get() as usize - 1,
+                commas: false,
+                explode,
+                reserve: 0,
+            },
+            ctx,
+            &mut |this, i, ctx| match i {
+                0 => render_first(this, ctx),
+                1 => this.for_clause(for_, ctx),
+                _ => match &clauses[i - 2] {
+                    ClauseP::For(f) => this.for_clause(f, ctx),
+                    ClauseP::If(x) => format!("if {}", this.expr(x, PREC_OR, ctx.after("if "))),
+                },
+            },
+        )
+    }
+
+    fn for_clause(&mut self, f: &ForClauseP<AstNoPayload>, ctx: Ctx) -> String {
+        let mut text = format!("for {} in ", self.target(&f.var, true, ctx.after("for ")));
+        text += &self.expr(&f.over, PREC_OR, ctx.after(&text));
+        text
+    }
+
+    fn param(&mut self, p: &AstParameter, ctx: Ctx) -> String {
+        let (prefix, name, ty, default) = match &p.node {
+            ParameterP::Normal(name, ty) => ("", name, ty, None),
+            ParameterP::WithDefaultValue(name, ty, default) => ("", name, ty, Some(default)),
+            ParameterP::NoArgs => return "*".to_owned(),
+            ParameterP::Args(name, ty) => ("*", name, ty, None),
+            ParameterP::KwArgs(name, ty) => ("**", name, ty, None),
+        };
+        let mut text = format!("{}{}", prefix, name.node.ident);
+        if let Some(ty) = ty {
+            text += ": ";
+            text += &self.expr(&ty.node.expr, PREC_TEST, ctx.after(&text));
+        }
+        if let Some(default) = default {
+            text += " = ";
+            text += &self.expr(default, PREC_TEST, ctx.after(&text));
+        }
+        text
+    }
+
+    fn param_must_break(&self, p: &AstParameter) -> bool {
+        let mut res = false;
+        p.node.visit_expr(|x| res |= self.magic_comma(x));
+        res
+    }
+
+    fn target(&mut self, t: &AstAssignTarget, top: bool, ctx: Ctx) -> String {
+        match &t.node {
+            AssignTargetP::Tuple(xs) => {
+                let mut text = String::new();
+                for (i, x) in xs.iter().enumerate() {
+                    if i != 0 {
+                        text += ", ";
+                    }
+                    text += &self.target(x, false, ctx.after(&text));
+                }
+                if xs.len() == 1 {
+                    format!("({},)", text)
+                } else if top {
+                    text
+                } else {
+                    format!("({})", text)
+                }
+            }
+            AssignTargetP::Index(x_i) => {
+                let (x, i) = &**x_i;
+                let mut text = self.expr(x, PREC_PRIMARY, ctx);
+                text.push('[');
+                text += &self.expr(i, PREC_TEST, ctx.after(&text));
+                text.push(']');
+                text
+            }
+            AssignTargetP::Dot(x, attr) => {
+                format!("{}.{}", self.expr(x, PREC_PRIMARY, ctx), attr.node)
+            }
+            AssignTargetP::Identifier(x) => x.node.ident.clone(),
+        }
+    }
+
+    /// Whether the expression cannot be printed on one line.
+    fn must_break(&self, e: &AstExpr) -> bool {
+        self.comments_in(e.span.begin().get() as usize, e.span.end().get() as usize)
+            || self.magic_comma(e)
+    }
+
+    /// Whether the expression contains a collection with a trailing comma.
+    fn magic_comma(&self, e: &AstExpr) -> bool {
+        let last_end = match &e.node {
+            Expr::List(xs) => xs.last().map(|x| x.span.end()),
+            Expr::Tuple(xs) if xs.len() > 1 => xs.last().map(|x| x.span.end()),
+            Expr::Dict(xs) => xs.last().map(|(_, v)| v.span.end()),
+            Expr::Call(_, args) => args.last().map(|a| a.span.end()),
+            // The expressions of f-strings are inside the string.
+            Expr::FString(_) => return false,
+            _ => None,
+        };
+        if last_end.is_some_and(|end| self.trailing_comma(end.get() as usize)) {
+            return true;
+        }
+        let mut res = false;
+        e.node.visit_expr(|x| res = res || self.magic_comma(x));
+        res
+    }
+}
+
+fn flatten<'s>(stmt: &'s AstStmt, res: &mut Vec<&'s AstStmt>) {
+    match &stmt.node {
+        Stmt::Statements(stmts) => stmts.iter().for_each(|s| flatten(s, res)),
+        _ => res.push(stmt),
+    }
+}
+
+fn starts_with_keyword(text: &str, keyword: &str) -> bool {
+    text.strip_prefix(keyword).is_some_and(|rest| {
+        !rest
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_')
+    })
+}
+
+fn precedence(e: &AstExpr) -> u8 {
+    match &e.node {
+        Expr::Lambda(_) => PREC_TEST,
+        Expr::If(_) => PREC_TEST + 1,
+        Expr::Op(_, op, _) => binop_precedence(*op),
+        Expr::Not(_) => PREC_NOT,
+        Expr::Minus(_) | Expr::Plus(_) | Expr::BitNot(_) => PREC_UNARY,
+        _ => PREC_PRIMARY,
+    }
+}
+
+fn binop_precedence(op: BinOp) -> u8 {
+    match op {
+        BinOp::Or => PREC_OR,
+        BinOp::And => PREC_OR + 1,
+        BinOp::Equal
+        | BinOp::NotEqual
+        | BinOp::Less
+        | BinOp::Greater
+        | BinOp::LessOrEqual
+        | BinOp::GreaterOrEqual
+        | BinOp::In
+        | BinOp::NotIn => PREC_COMPARISON,
+        BinOp::BitOr => 6,
+        BinOp::BitXor => 7,
+        BinOp::BitAnd => 8,
+        BinOp::LeftShift | BinOp::RightShift => 9,
+        BinOp::Add | BinOp::Subtract => 10,
+        BinOp::Multiply | BinOp::Percent | BinOp::Divide | BinOp::FloorDivide => 11,
+    }
+}
@@ -0,0 +1,195 @@
+/*
+ * Copyright 2018 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::golden_test_template::golden_test_template;
+use crate::syntax::AstModule;
+use crate::syntax::Dialect;
+
+const DIALECT: Dialect = Dialect {
+    enable_f_strings: true,
+    ..Dialect::Extended
+};
+
+/// Format the program, checking that the result means the same and is formatted already.
+fn format(program: &str) -> String {
+    let ast = AstModule::parse("test.star", program.to_owned(), &DIALECT).unwrap();
+    let formatted = ast.format();
+    let reparsed = AstModule::parse("test.star", formatted.clone(), &DIALECT)
+        .unwrap_or_else(|e| panic!("Formatted code does not parse: {}\n{}", e, formatted));
+    assert_eq!(
+        ast.statement.to_string(),
+        reparsed.statement.to_string(),
+        "Formatting changed the code:\n{}",
+        formatted
+    );
+    assert_eq!(formatted, reparsed.format(), "Formatting is not idempotent");
+    formatted
+}
+
+fn format_golden(name: &str, program: &str) {
+    let program = program.trim();
+    let out = format!("Program:\n{}\n\nFormatted:\n{}", program, format(program));
+    golden_test_template(&format!("src/syntax/format_tests/{}.golden", name), &out);
+}
+
+#[test]
+fn test_format_simple() {
+    assert_eq!(format(""), "");
+    assert_eq!(format("x=1"), "x = 1\n");
+    assert_eq!(
+        format("x  =  [1,2 ,3]\n\n\n\ny =x"),
+        "x = [1, 2, 3]\n\ny = x\n"
+    );
+    assert_eq!(format("a,b=b,a"), "a, b = b, a\n");
+    assert_eq!(format("x = 1,"), "x = (1,)\n");
+    assert_eq!(format("x += 1;y -= 2"), "x += 1\ny -= 2\n");
+}
+
+#[test]
+fn test_format_parentheses() {
+    assert_eq!(format("x = (a + b) * c"), "x = (a + b) * c\n");
+    assert_eq!(format("x = a + (b * c)"), "x = a + b * c\n");
+    assert_eq!(format("x = a - (b - c)"), "x = a - (b - c)\n");
+    assert_eq!(format("x = (a < b) == c"), "x = (a < b) == c\n");
+    assert_eq!(format("x = -(a + b)"), "x = -(a + b)\n");
+    assert_eq!(format("x = (not a) and b"), "x = not a and b\n");
+    assert_eq!(format("x = not (a and b)"), "x = not (a and b)\n");
+    assert_eq!(
+        format("x = (a if b else c) if d else e"),
+        "x = (a if b else c) if d else e\n"
+    );
+    assert_eq!(format("x = (lambda: 1)()"), "x = (lambda: 1)()\n");
+    assert_eq!(format("f((a, b))"), "f((a, b))\n");
+    assert_eq!(
+        format("for (a, b) in [(1, 2)]: pass"),
+        "for a, b in [(1, 2)]:\n    pass\n"
+    );
+}
+
+#[test]
+fn test_format_statements() {
+    format_golden(
+        "statements",
+        r#"
+load("//foo:bar.bzl","a",b="c")
+def f(x,y:int=1,*,z=2,**kwargs)->str:
+  if x: return "a"
+  elif y:
+    return 'b'
+  else:
+    if z:
+      pass
+  for a,b in kwargs.items(): print(a,b)
+  return f"{x}"
+x: list[int] = [y*2 for y in range(10) if y%2]
+"#,
+    );
+}
+
+#[test]
+fn test_format_comments() {
+    format_golden(
+        "comments",
+        r#"
+# Header comment.
+
+load("//foo:bar.bzl", "a")  # Trailing comment.
+
+def f(
+    x,  # The x.
+    # Before y.
+    y,
+):  # After the header.
+    # First statement.
+    return x + y
+    # End of f.
+
+# Before rule.
+rule(
+    name = "foo",  # The name.
+    srcs = [
+        # Sources.
+        "a.c",
+
+        "b.c",
+    ],
+)
+# End of file.
+"#,
+    );
+}
+
+#[test]
+fn test_format_line_breaking() {
+    format_golden(
+        "line_breaking",
+        r#"
+rule(name = "a_rather_long_name", srcs = ["first_file.c", "second_file.c", "third_file.c"], deps = [":dep"])
+short = [1, 2, 3,]
+keep_on_one_line = {"a": 1, "b": 2}
+comprehension = [some_long_function_name(element) for element in some_long_iterable_name if element.enabled]
+def function_with_a_long_name(first_parameter, second_parameter, third_parameter, fourth_parameter = None):
+    pass
+"#,
+    );
+}
+
+#[test]
+fn test_format_idempotent() {
+    let program = r#"
+# Header comment.
+load("//foo:bar.bzl", "a", b = "c")  # Trailing comment.
+
+def f(x,   # The x.
+      y = {"k": [1,2,3]}, *args, **kwargs):  # After the header.
+    # First statement.
+    if x:  # Why.
+        return [e for e in y if e]   # Comprehension.
+    elif y: pass
+    else:
+        # Nothing to do.
+        return None
+    # End of f.
+
+
+
+# Before rule.
+rule(name = "a_rather_long_name", srcs = ["first_file.c", "second_file.c", "third_file.c"],  # Sources.
+     deps = [
+         # Deps.
+         ":dep",
+
+         ":other",
+     ])
+x = (a + b) * c  # Arithmetic.
+# End of file.
+"#;
+    let once = format(program);
+    let ast = AstModule::parse("test.star", once.clone(), &DIALECT).unwrap();
+    assert_eq!(once, ast.format());
+    for comment in [
+        "# Header comment.",
+        "# The x.",
+        "# Why.",
+        "# Nothing to do.",
+        "# Sources.",
+        "# Deps.",
+        "# End of file.",
+    ] {
+        assert!(once.contains(comment), "Lost `{}`:\n{}", comment, once);
+    }
+}
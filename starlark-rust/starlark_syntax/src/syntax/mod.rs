@@ -25,6 +25,9 @@ pub use crate::dialect::DialectTypes;
 
 pub mod ast;
 pub mod def;
+mod format;
+#[cfg(test)]
+mod format_tests;
 #[cfg(test)]
 mod grammar_tests;
 pub mod grammar_util;